@@ -0,0 +1,107 @@
+//! Criterion benchmarks of the ranking steps, to compare optimizations
+//! against a saved baseline
+//!
+//! Each step runs on generated tables of several sizes, and counting also
+//! on tables of several cardinalities and on one of a million rows:
+//!
+//! ```text
+//! cargo bench --bench criterion -- --save-baseline before
+//! cargo bench --bench criterion -- --baseline before
+//! ```
+
+mod fixture;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use fixture::{fixture, uniform};
+use rsf::ranking::{
+    compute_cardinality, rank_columns, reorder_data, sort_rows_canonical, RankingOptions,
+};
+
+const COLUMNS: usize = 20;
+const ROWS: [usize; 3] = [1_000, 10_000, 100_000];
+const CARDINALITIES: [u64; 4] = [2, 100, 10_000, 100_000];
+
+/// Rows of the large counting case, with columns from a flag up to an id
+/// per row
+const LARGE_ROWS: usize = 1_000_000;
+const LARGE_COLUMNS: usize = 7;
+
+fn cardinality(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compute_cardinality");
+    for rows in ROWS {
+        let (headers, data) = fixture(rows, COLUMNS);
+        group.throughput(Throughput::Elements((rows * COLUMNS) as u64));
+        group.bench_with_input(BenchmarkId::new("rows", rows), &data, |b, data| {
+            b.iter(|| compute_cardinality(&headers, data, RankingOptions::default()).unwrap())
+        });
+    }
+
+    let rows = 100_000;
+    group.throughput(Throughput::Elements((rows * COLUMNS) as u64));
+    for cardinality in CARDINALITIES {
+        let (headers, data) = uniform(rows, COLUMNS, cardinality);
+        group.bench_with_input(
+            BenchmarkId::new("cardinality", cardinality),
+            &data,
+            |b, data| {
+                b.iter(|| compute_cardinality(&headers, data, RankingOptions::default()).unwrap())
+            },
+        );
+    }
+
+    let (headers, data) = fixture(LARGE_ROWS, LARGE_COLUMNS);
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((LARGE_ROWS * LARGE_COLUMNS) as u64));
+    group.bench_with_input(BenchmarkId::new("rows", LARGE_ROWS), &data, |b, data| {
+        b.iter(|| compute_cardinality(&headers, data, RankingOptions::default()).unwrap())
+    });
+    group.finish();
+}
+
+fn rank(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rank_columns");
+    for rows in ROWS {
+        let (headers, data) = fixture(rows, COLUMNS);
+        group.throughput(Throughput::Elements((rows * COLUMNS) as u64));
+        group.bench_with_input(BenchmarkId::new("rows", rows), &data, |b, data| {
+            b.iter(|| rank_columns(&headers, data, RankingOptions::default()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn sort(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort_rows_canonical");
+    for rows in ROWS {
+        let (headers, data) = fixture(rows, COLUMNS);
+        // Sorting ranked rows, as rank does
+        let ranked = rank_columns(&headers, &data, RankingOptions::default()).unwrap();
+        let (_, data) = reorder_data(&headers, data, &ranked).unwrap();
+        group.throughput(Throughput::Elements(rows as u64));
+        group.bench_with_input(BenchmarkId::new("rows", rows), &data, |b, data| {
+            b.iter(|| sort_rows_canonical(data))
+        });
+    }
+    group.finish();
+}
+
+fn reorder(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reorder_data");
+    for rows in ROWS {
+        let (headers, data) = fixture(rows, COLUMNS);
+        let ranked = rank_columns(&headers, &data, RankingOptions::default()).unwrap();
+        group.throughput(Throughput::Elements(rows as u64));
+        // reorder_data takes the rows, so each run gets a fresh copy
+        group.bench_with_input(BenchmarkId::new("rows", rows), &data, |b, data| {
+            b.iter_batched(
+                || data.clone(),
+                |data| reorder_data(&headers, data, &ranked).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cardinality, rank, sort, reorder);
+criterion_main!(benches);
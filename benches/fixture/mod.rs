@@ -0,0 +1,65 @@
+//! Generated tables for the benchmarks, the same on every run
+
+/// Deterministic xorshift, so every run sees the same table
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Self(0x9E37_79B9_7F4A_7C15)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn headers(columns: usize) -> Vec<String> {
+    (0..columns).map(|c| format!("col_{:02}", c)).collect()
+}
+
+/// A table whose columns range from unique ids to a handful of categories,
+/// with some empty cells, in shuffled order
+pub fn fixture(rows: usize, columns: usize) -> (Vec<String>, Vec<Vec<String>>) {
+    // Distinct values per column: 2, 10, 100, ... up to one per row
+    let cardinalities: Vec<u64> = (0..columns)
+        .map(|c| {
+            10u64
+                .saturating_pow((c % 7) as u32)
+                .clamp(2, rows.max(2) as u64)
+        })
+        .collect();
+
+    let mut rng = Rng::new();
+    let data = (0..rows)
+        .map(|row| {
+            cardinalities
+                .iter()
+                .enumerate()
+                .map(|(c, &cardinality)| match rng.next() % 50 {
+                    0 => String::new(),
+                    _ if cardinality >= rows as u64 => format!("id-{}", row),
+                    n => format!("v{}-{}", c, (rng.next() ^ n) % cardinality),
+                })
+                .collect()
+        })
+        .collect();
+    (headers(columns), data)
+}
+
+/// A table whose columns all draw from `cardinality` values, so the cost of
+/// counting can be compared across cardinalities at one size
+#[allow(dead_code)]
+pub fn uniform(rows: usize, columns: usize, cardinality: u64) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut rng = Rng::new();
+    let data = (0..rows)
+        .map(|_| {
+            (0..columns)
+                .map(|c| format!("v{}-{}", c, rng.next() % cardinality.max(1)))
+                .collect()
+        })
+        .collect();
+    (headers(columns), data)
+}
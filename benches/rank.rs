@@ -0,0 +1,149 @@
+//! Times counting, ranking and sorting a generated table, counting heap
+//! allocations, the most heap memory each phase has allocated at once and
+//! the heap memory it leaves allocated
+//!
+//! `RSF_BENCH_ROWS` and `RSF_BENCH_COLUMNS` set its size (default 200,000 by
+//! 40). With `RSF_BENCH_FIXTURE=path` the table is also written there as
+//! CSV, to time or measure the CLI on the same data, and read back through
+//! a buffer and from a memory map. The fixture takes about 300 MB per
+//! million rows, so 4,000,000 make one of 1.2 GB:
+//!
+//! ```text
+//! RSF_BENCH_ROWS=4000000 RSF_BENCH_FIXTURE=big.csv cargo bench --bench rank
+//! ```
+
+mod fixture;
+
+use fixture::fixture;
+use rsf::io::{read_csv_file, write_csv_to, ReadOptions};
+use rsf::ranking::{compute_cardinality, rank_columns, reorder_data, RankingOptions};
+use rsf::RsfDocument;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// The system allocator, counting allocations and reallocations and the
+/// bytes currently allocated, and keeping the most allocated at once
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Count `size` more bytes allocated
+fn grow(size: usize) {
+    let live = LIVE_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        grow(layout.size());
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        grow(new_size);
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Run `f`, printing its time, the allocations it made, the heap memory
+/// allocated at its peak, inputs included, and how much more heap memory
+/// is allocated after it than before, e.g. for its result
+fn measure<T>(phase: &str, f: impl FnOnce() -> T) -> T {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let live = LIVE_BYTES.load(Ordering::Relaxed);
+    PEAK_BYTES.store(live, Ordering::Relaxed);
+    let start = Instant::now();
+    let result = f();
+    report(
+        phase,
+        start.elapsed(),
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        PEAK_BYTES.load(Ordering::Relaxed),
+        LIVE_BYTES.load(Ordering::Relaxed) as isize - live as isize,
+    );
+    result
+}
+
+fn report(phase: &str, elapsed: Duration, allocations: usize, peak: usize, retained: isize) {
+    const MIB: f64 = 1024.0 * 1024.0;
+    println!(
+        "{:<11} {:>8.2?}  {:>10} allocations  {:>8.1} MiB peak  {:>+8.1} MiB retained",
+        phase,
+        elapsed,
+        allocations,
+        peak as f64 / MIB,
+        retained as f64 / MIB
+    );
+}
+
+fn env_usize(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+fn main() {
+    let rows = env_usize("RSF_BENCH_ROWS", 200_000);
+    let columns = env_usize("RSF_BENCH_COLUMNS", 40);
+
+    let start = Instant::now();
+    let (headers, data) = fixture(rows, columns);
+    println!(
+        "generate    {:>8.2?}  ({} rows x {} columns)",
+        start.elapsed(),
+        rows,
+        columns
+    );
+
+    if let Ok(path) = env::var("RSF_BENCH_FIXTURE") {
+        let file = File::create(&path).expect("create fixture");
+        write_csv_to(BufWriter::new(file), &headers, &data).expect("write fixture");
+        println!("fixture written to {}", path);
+
+        // The page cache is warm for both, so only parsing differs
+        for (phase, options) in [
+            ("read", ReadOptions::default()),
+            ("read-mmap", ReadOptions::default().mmap(true)),
+        ] {
+            let input = measure(phase, || read_csv_file(&path, options).expect("read"));
+            assert_eq!(input.rows.len(), rows);
+        }
+    }
+
+    // Counting alone, the bulk of ranking on wide tables
+    let stats = measure("cardinality", || {
+        compute_cardinality(&headers, &data, RankingOptions::default()).expect("count")
+    });
+    assert_eq!(stats.len(), columns);
+
+    // Moving the columns alone, on a copy so ranking below starts afresh
+    let ranked = rank_columns(&headers, &data, RankingOptions::default()).expect("rank");
+    let copy = data.clone();
+    measure("reorder", || {
+        reorder_data(&headers, copy, &ranked).expect("reorder")
+    });
+
+    let mut doc = RsfDocument::new(headers, data);
+    measure("rank", || {
+        doc.rank(RankingOptions::default()).expect("rank")
+    });
+    measure("sort", || doc.sort());
+}
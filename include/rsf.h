@@ -0,0 +1,129 @@
+#ifndef RSF_H
+#define RSF_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Success
+ */
+#define RSF_OK 0
+
+/**
+ * The input could not be read or ranked
+ */
+#define RSF_ERROR 1
+
+/**
+ * Validation ran and found problems
+ */
+#define RSF_INVALID 2
+
+/**
+ * A required pointer was null or an option was out of range
+ */
+#define RSF_BAD_ARGUMENT 3
+
+/**
+ * rsf panicked; the message is available from [`rsf_last_error`]
+ */
+#define RSF_PANIC 4
+
+/**
+ * Rank by number of distinct values
+ */
+#define RSF_METRIC_CARDINALITY 0
+
+/**
+ * Rank by Shannon entropy of the value distribution
+ */
+#define RSF_METRIC_ENTROPY 1
+
+/**
+ * Ranking options; pass null for the defaults
+ */
+typedef struct RsfOptions {
+  /**
+   * Count every empty cell as a distinct value
+   */
+  bool nulls_distinct;
+  /**
+   * One of the `RSF_METRIC_*` constants
+   */
+  uint32_t metric;
+} RsfOptions;
+
+/**
+ * Bytes owned by rsf until passed to [`rsf_buffer_free`]
+ */
+typedef struct RsfBuffer {
+  uint8_t *data;
+  size_t len;
+} RsfBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Rank the columns of a CSV document and sort its rows canonically
+ *
+ * On success `out` receives the RSF document and, unless it is null,
+ * `schema_out` receives its schema as YAML. On failure both are set empty.
+ *
+ * # Safety
+ *
+ * `data` must point to `len` readable bytes; `options` and `schema_out`
+ * must be null or valid; `out` must be valid for writes.
+ */
+int32_t rsf_rank_csv(const char *data,
+                     size_t len,
+                     const struct RsfOptions *options,
+                     struct RsfBuffer *out,
+                     struct RsfBuffer *schema_out);
+
+/**
+ * Validate a CSV document against a YAML schema
+ *
+ * Returns [`RSF_OK`] if the document is valid RSF and [`RSF_INVALID`] if a
+ * check failed; unless it is null, `report_out` then receives one line per
+ * failed check.
+ *
+ * # Safety
+ *
+ * `data` and `schema` must point to `len` and `schema_len` readable bytes;
+ * `report_out` must be null or valid for writes.
+ */
+int32_t rsf_validate_csv(const char *data,
+                         size_t len,
+                         const char *schema,
+                         size_t schema_len,
+                         struct RsfBuffer *report_out);
+
+/**
+ * Message for the last failure on this thread, or null if there was none
+ *
+ * The string stays valid until the next rsf call on the same thread.
+ */
+const char *rsf_last_error(void);
+
+/**
+ * Release a buffer filled by rsf and set it empty; freeing an empty buffer
+ * does nothing
+ *
+ * # Safety
+ *
+ * `buffer` must be null or hold data allocated by rsf and not yet freed.
+ */
+void rsf_buffer_free(struct RsfBuffer *buffer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RSF_H */
@@ -0,0 +1,166 @@
+use crate::ranker::RsfRanker;
+use crate::ranking::{ColumnMeta, ColumnStats, RankingOptions};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std-fs")]
+use {
+    crate::errors::{RsfError, RsfResult},
+    std::io,
+    std::path::Path,
+};
+
+/// Per-column value counts saved between runs, so that re-ranking a file
+/// that has only grown counts just the new rows
+///
+/// The cache assumes rows are appended: it is used only when the headers and
+/// options are unchanged, the input has at least as many rows as were
+/// counted, and the last counted row still reads the same.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RankCache {
+    pub options: RankingOptions,
+    /// Number of rows counted
+    pub records: usize,
+    /// The last row counted, to notice an input that was rewritten
+    pub last_record: Vec<String>,
+    /// Statistics of each column, in header order
+    pub columns: Vec<ColumnStats>,
+}
+
+impl RankCache {
+    /// Whether the cache counts a prefix of `rows` under `options`
+    pub fn covers(
+        &self,
+        headers: &[impl AsRef<str>],
+        rows: &[Vec<String>],
+        options: &RankingOptions,
+    ) -> bool {
+        self.options == *options
+            && self.columns.len() == headers.len()
+            && self
+                .columns
+                .iter()
+                .zip(headers)
+                .all(|(col, header)| col.name == header.as_ref())
+            && self.records <= rows.len()
+            && match self.records.checked_sub(1) {
+                Some(last) => rows[last] == self.last_record,
+                None => true,
+            }
+    }
+}
+
+/// Rank the columns, counting only the rows beyond those in `cache`
+///
+/// A cache that does not [cover](RankCache::covers) the rows is ignored and
+/// every row is counted. Returns the columns in rank order and a cache of
+/// all the rows for the next run.
+pub fn rank_columns_cached(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    options: RankingOptions,
+    cache: Option<RankCache>,
+) -> (Vec<ColumnMeta>, RankCache) {
+    let mut ranker = match cache.filter(|cache| cache.covers(headers, rows, &options)) {
+        Some(cache) => RsfRanker::resume(options.clone(), cache.columns, cache.records),
+        None => RsfRanker::new(options.clone()).with_headers(headers),
+    };
+    for row in &rows[ranker.records()..] {
+        ranker.push_record(row);
+    }
+
+    let cache = RankCache {
+        options,
+        records: ranker.records(),
+        last_record: rows.last().cloned().unwrap_or_default(),
+        columns: ranker.stats().to_vec(),
+    };
+    let (columns, _) = ranker.finish();
+    (columns, cache)
+}
+
+/// Write a cache file
+#[cfg(feature = "std-fs")]
+pub fn write_cache(cache: &RankCache, path: impl AsRef<Path>) -> RsfResult<()> {
+    let path = path.as_ref();
+    let file = std::fs::File::create(path).map_err(|e| RsfError::io_error(path, e))?;
+
+    serde_yaml::to_writer(file, cache)
+        .map_err(|e| RsfError::io_error(path, io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+/// Read a cache file
+#[cfg(feature = "std-fs")]
+pub fn read_cache(path: impl AsRef<Path>) -> RsfResult<RankCache> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path).map_err(|e| RsfError::io_error(path, e))?;
+
+    serde_yaml::from_reader(file)
+        .map_err(|e| RsfError::io_error(path, io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranking::{rank_columns, RankMetric};
+
+    fn rows(cells: &[[&str; 3]]) -> Vec<Vec<String>> {
+        cells
+            .iter()
+            .map(|row| row.map(String::from).to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_cached_plus_new_rows_matches_full_rerank() {
+        let headers = ["Region", "Id", "Note"];
+        let mut data = rows(&[
+            ["north", "1", ""],
+            ["south", "2", "late"],
+            ["north", "3", ""],
+        ]);
+
+        for metric in [RankMetric::Cardinality, RankMetric::Entropy] {
+            let options = RankingOptions::builder().metric(metric).build().unwrap();
+            let (_, cache) = rank_columns_cached(&headers, &data, options.clone(), None);
+            assert_eq!(cache.records, 3);
+
+            // Round-trip through YAML as the CLI does between runs
+            let cache: RankCache =
+                serde_yaml::from_str(&serde_yaml::to_string(&cache).unwrap()).unwrap();
+
+            let mut grown = data.clone();
+            grown.extend(rows(&[["east", "4", "late"], ["east", "5", "early"]]));
+            assert!(cache.covers(&headers, &grown, &options));
+
+            let (columns, cache) =
+                rank_columns_cached(&headers, &grown, options.clone(), Some(cache));
+            assert_eq!(columns, rank_columns(&headers, &grown, options).unwrap());
+            assert_eq!(cache.records, 5);
+        }
+
+        // A rewritten row invalidates the cache instead of skewing the counts
+        let options = RankingOptions::default();
+        let (_, cache) = rank_columns_cached(&headers, &data, options.clone(), None);
+        data[2][1] = "1".to_string();
+        assert!(!cache.covers(&headers, &data, &options));
+
+        let (columns, _) = rank_columns_cached(&headers, &data, options.clone(), Some(cache));
+        assert_eq!(columns, rank_columns(&headers, &data, options).unwrap());
+    }
+
+    #[test]
+    fn test_cache_ignored_for_other_headers_or_options() {
+        let data = rows(&[["a", "1", "x"], ["b", "2", "x"]]);
+        let (_, cache) = rank_columns_cached(&["A", "B", "C"], &data, Default::default(), None);
+
+        assert!(cache.covers(&["A", "B", "C"], &data, &Default::default()));
+        assert!(!cache.covers(&["A", "C", "B"], &data, &Default::default()));
+        assert!(!cache.covers(&["A", "B", "C"], &data[..1], &Default::default()));
+
+        let nulls_distinct = RankingOptions::builder()
+            .treat_empty_as_null(false)
+            .build()
+            .unwrap();
+        assert!(!cache.covers(&["A", "B", "C"], &data, &nulls_distinct));
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One line of the rank change log
+#[derive(Debug, Serialize)]
+pub struct ChangeLogEntry {
+    /// UTC time of the run, RFC 3339
+    pub timestamp: String,
+    pub input_file: String,
+    /// `None` when the output went to stdout
+    pub output_file: Option<String>,
+    pub rows_processed: usize,
+    pub columns_ranked: usize,
+    pub schema_version: String,
+    /// Commit checked out where the input lives, if it is in a git work tree
+    pub git_sha: Option<String>,
+}
+
+impl ChangeLogEntry {
+    pub fn new(
+        input: &str,
+        output: Option<&Path>,
+        rows_processed: usize,
+        columns_ranked: usize,
+        schema_version: &str,
+    ) -> Self {
+        Self {
+            timestamp: now_utc(),
+            input_file: input.to_string(),
+            output_file: output.map(|p| p.display().to_string()),
+            rows_processed,
+            columns_ranked,
+            schema_version: schema_version.to_string(),
+            git_sha: git_sha(input),
+        }
+    }
+
+    /// Append the entry as a single JSON line, creating the log if needed
+    pub fn append(&self, path: &Path) -> Result<()> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("Failed to append to change log: {:?}", path))
+    }
+}
+
+/// `HEAD` of the git work tree containing `input` (or the current directory
+/// for stdin)
+fn git_sha(input: &str) -> Option<String> {
+    let dir = Path::new(input)
+        .parent()
+        .filter(|dir| input != "-" && !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let sha = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!sha.is_empty()).then_some(sha)
+}
+
+/// The current time, as [`format_utc`] writes it
+pub fn now_utc() -> String {
+    format_utc(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    )
+}
+
+/// Format seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_utc(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_append_writes_one_line_per_entry() {
+        let path = std::env::temp_dir().join(format!("rsf-changelog-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let entry = ChangeLogEntry::new("-", None, 3, 2, "0.1");
+        entry.append(&path).unwrap();
+        entry.append(&path).unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["rows_processed"], 3);
+        assert_eq!(lines[0]["output_file"], serde_json::Value::Null);
+        assert!(lines[0].get("git_sha").is_some());
+    }
+}
@@ -0,0 +1,907 @@
+use clap::builder::BoolishValueParser;
+use clap::{ArgAction, ArgGroup, Parser, Subcommand, ValueEnum};
+use rsf::ranking::{CardinalityBand, CardinalityLimit, RankMetric, TieBreak};
+use rsf::unicode::NormalizationForm;
+use std::ffi::OsString;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+/// RSF - Ranked Spreadsheet Format
+///
+/// Deterministic column ordering based on cardinality.
+/// Columns are ranked from most unique (highest cardinality) to least unique.
+#[derive(Parser)]
+#[command(name = "rsf")]
+#[command(about = "Ranked Spreadsheet Format - Stable scaffolding for tabular data", long_about = None)]
+#[command(
+    after_long_help = "Options marked [env: RSF_...] can also be set by that environment variable. \
+    A flag on the command line wins over the variable, and the variable over the config file. \
+    Switches take true or false, 1 or 0, yes or no, on or off."
+)]
+pub struct Cli {
+    /// Read flag defaults from this TOML file (default: nearest rsf.toml or .rsf.toml)
+    #[arg(long, global = true, value_name = "FILE", env = "RSF_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Don't look for a config file
+    #[arg(
+        long,
+        global = true,
+        conflicts_with = "config",
+        env = "RSF_NO_CONFIG",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub no_config: bool,
+
+    /// How to print an error that stops the command
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "human",
+        env = "RSF_ERROR_FORMAT"
+    )]
+    pub error_format: ErrorFormat,
+
+    /// Exit non-zero if the command printed any warning, even if it succeeded
+    #[arg(
+        long,
+        global = true,
+        env = "RSF_FAIL_ON_WARNING",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub fail_on_warning: bool,
+
+    /// Print nothing on stderr but errors; can't be combined with --verbose, except
+    /// that a flag on the command line overrides the other set in the environment
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "RSF_QUIET",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub quiet: bool,
+
+    /// Also print phase timings, counts, derived paths and effective options
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "RSF_VERBOSE",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub verbose: bool,
+
+    /// Color the output: on a terminal unless NO_COLOR is set, always or never
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "auto",
+        value_name = "WHEN"
+    )]
+    pub color: ColorWhen,
+
+    /// Overwrite output files that already exist
+    #[arg(
+        long,
+        global = true,
+        env = "RSF_FORCE",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub force: bool,
+
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+// Parsed once per run, so the size of the rank variant doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Rank a CSV file by column cardinality
+    #[command(group = ArgGroup::new("preview").args(["dry_run", "estimate"]))]
+    Rank {
+        /// Input CSV file (use - for stdin)
+        #[arg(default_value = "-")]
+        input: String,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Replace the input with its ranked version, through a temporary file, keeping
+        /// the original as INPUT.bak; an input that is already canonical is left untouched
+        #[arg(
+            long,
+            conflicts_with_all = ["output", "chunk_rows", "output_delta", "check", "dry_run", "estimate", "watch"]
+        )]
+        in_place: bool,
+
+        /// With --in-place, don't keep a backup of the input
+        #[arg(long, requires = "in_place")]
+        no_backup: bool,
+
+        /// Split the output into part-0001.csv, part-0002.csv, ... of at most N rows
+        /// each, in the directory given by --output; one schema describes all of them
+        #[arg(
+            long,
+            visible_alias = "rows-per-chunk",
+            value_name = "N",
+            requires = "output"
+        )]
+        chunk_rows: Option<NonZeroUsize>,
+
+        /// Format of the ranked data; `auto` picks it from the --output extension
+        /// (.csv, .tsv, .jsonl, and .parquet with the `parquet` feature) and writes
+        /// CSV to stdout
+        #[arg(
+            long,
+            value_enum,
+            default_value = "auto",
+            conflicts_with = "chunk_rows"
+        )]
+        output_format: OutputFormat,
+
+        /// Write only the rows that are new or changed since PREVIOUS, an earlier
+        /// ranked version of the same data
+        #[arg(long, value_name = "PREVIOUS", conflicts_with_all = ["check", "dry_run"])]
+        output_delta: Option<PathBuf>,
+
+        /// With --output-delta, write the rows of PREVIOUS that are gone or changed to FILE
+        #[arg(long, value_name = "FILE", requires = "output_delta")]
+        deleted_output: Option<PathBuf>,
+
+        /// Also write the ranked data as TSV to PATH, in the same pass as the CSV
+        #[arg(long, value_name = "PATH", conflicts_with = "chunk_rows")]
+        emit_tsv_alongside: Option<PathBuf>,
+
+        /// Generate schema.yaml file
+        #[arg(short, long)]
+        schema: bool,
+
+        /// Write the schema as a `# ---` front-matter block at the top of the output,
+        /// which validate uses when there is no schema file
+        #[arg(long, conflicts_with = "chunk_rows")]
+        embed_schema: bool,
+
+        /// Write `#rsf-version`, `#ranked-at`, `#column-count` and `#top-key` comment
+        /// lines before the CSV header, which readers given `--comment '#'` skip
+        #[arg(long, conflicts_with = "chunk_rows")]
+        emit_metadata_header: bool,
+
+        /// Count nulls as distinct values (`--nulls-distinct=false` folds empty cells together)
+        #[arg(
+            long,
+            default_value_t = true,
+            num_args = 0..=1,
+            default_missing_value = "true",
+            action = ArgAction::Set,
+            env = "RSF_NULLS_DISTINCT"
+        )]
+        nulls_distinct: bool,
+
+        /// Fail before ranking if the input lacks this column (repeatable)
+        #[arg(long, value_name = "COLUMN")]
+        require_column: Vec<String>,
+
+        /// Fail before ranking if the input lacks any column of this schema
+        #[arg(long, value_name = "SCHEMA")]
+        require_columns_from_schema: Option<PathBuf>,
+
+        /// Warn if COLUMN has more than MAX distinct values (repeatable)
+        #[arg(long, value_name = "COLUMN:MAX")]
+        warn_if_cardinality_exceeds: Vec<CardinalityLimit>,
+
+        /// Fail instead of warning when a --warn-if-cardinality-exceeds limit is passed
+        #[arg(long, requires = "warn_if_cardinality_exceeds")]
+        fail_if_cardinality_exceeds: bool,
+
+        /// Leave empty cells out of the distinct count, so cardinality counts only real values
+        #[arg(long, conflicts_with = "nulls_distinct")]
+        exclude_nulls_from_cardinality: bool,
+
+        /// Count these comma-separated tokens (e.g. `N/A,-,\\N,null`) as null, folded
+        /// together with empty cells; implies --nulls-distinct=false
+        #[arg(
+            long,
+            value_name = "TOKENS",
+            value_delimiter = ',',
+            conflicts_with = "nulls_distinct",
+            env = "RSF_NULL_VALUES"
+        )]
+        null_values: Vec<String>,
+
+        /// Match --null-values regardless of case
+        #[arg(
+            long,
+            requires = "null_values",
+            env = "RSF_NULL_VALUES_IGNORE_CASE",
+            value_parser = BoolishValueParser::new()
+        )]
+        null_values_ignore_case: bool,
+
+        /// Rank these columns (e.g. col1,col2) last, in this order, whatever their cardinality
+        #[arg(long, value_name = "COLS", value_delimiter = ',')]
+        pin_last: Vec<String>,
+
+        /// Rank the columns in this YAML map (`COLUMN: CARDINALITY`) by the given
+        /// cardinality instead of the counted one, e.g. one known from the full population
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["cache_dir", "check", "dry_run"])]
+        cardinalities: Option<PathBuf>,
+
+        /// Read the row after the header as column types (int, float, date, str), not data
+        #[arg(long, conflicts_with_all = ["check", "dry_run"])]
+        type_row: bool,
+
+        /// Collapse rows that are equal once each cell is trimmed, keeping the first
+        /// as written
+        #[arg(long)]
+        dedup_normalized: bool,
+
+        /// Rename headers that differ from an earlier one only by case (e.g. `ID`
+        /// and `id`) to `id_2`, `id_3`, ... instead of warning about them
+        #[arg(long)]
+        rename_case_collisions: bool,
+
+        /// Record the input header of each column renamed by --rename-case-collisions
+        /// as its `original_name` in the schema
+        #[arg(long, requires = "rename_case_collisions")]
+        preserve_original_header_case: bool,
+
+        /// Fill cells missing from rows shorter than the header with VALUE
+        #[arg(long, value_name = "VALUE")]
+        fill_missing: Option<String>,
+
+        /// Fill cells missing from short rows with an empty value, as --fill-missing ""
+        #[arg(long, conflicts_with = "fill_missing")]
+        fill_missing_with_null: bool,
+
+        /// Record `sort_stable: true` in the schema: columns that tie keep their input
+        /// order and equal rows theirs, so two runs over the same data match byte for byte
+        #[arg(long)]
+        sort_stability_guarantee: bool,
+
+        /// Metric used to order columns
+        #[arg(long, value_enum, default_value = "cardinality", env = "RSF_RANK_BY")]
+        rank_by: RankBy,
+
+        /// How to order columns that tie on the metric
+        #[arg(long, value_enum, default_value = "position", env = "RSF_TIE_BREAK")]
+        tiebreak: Tiebreak,
+
+        /// Read numbers as `1.234,56` (`.` thousands, `,` decimal) and sort them by value
+        #[arg(long)]
+        decimal_comma: bool,
+
+        /// With --decimal-comma, rewrite numeric cells as `.`-decimal (1234.56)
+        #[arg(long, requires = "decimal_comma")]
+        canonical_decimal: bool,
+
+        /// Rewrite every value in this Unicode normalization form before ranking, so
+        /// `café` spelled precomposed and with a combining accent count as one value
+        #[arg(long, value_enum, value_name = "FORM")]
+        normalize_unicode: Option<UnicodeForm>,
+
+        /// Warn about values in a column that differ only in Unicode normalization
+        /// (checked on the input, before --normalize-unicode)
+        #[arg(long)]
+        verify_utf8_normalization: bool,
+
+        /// Prepend a key column joining these columns (e.g. col1,col2), then rank it with the rest
+        #[arg(long, value_name = "COLS")]
+        concat_key: Option<String>,
+
+        /// Name of the --concat-key column
+        #[arg(
+            long,
+            default_value = "key",
+            value_name = "NAME",
+            requires = "concat_key"
+        )]
+        key_name: String,
+
+        /// Separator between the joined cells of --concat-key
+        #[arg(long, default_value = "|", value_name = "SEP", requires = "concat_key")]
+        key_separator: String,
+
+        /// Remove the columns joined by --concat-key
+        #[arg(long, requires = "concat_key")]
+        drop_key_columns: bool,
+
+        /// Append a column of this name holding the SHA-256 of each row's key cells
+        /// joined with `|`, a stable row identifier for change tracking
+        #[arg(long, value_name = "COLUMN_NAME")]
+        emit_row_hashes: Option<String>,
+
+        /// Key columns hashed by --emit-row-hashes (e.g. col1,col2); without it, the
+        /// columns the schema types as keys
+        #[arg(long, value_name = "COLS", requires = "emit_row_hashes")]
+        row_hash_key: Option<String>,
+
+        /// Write a JSON map from ranked column positions to original positions
+        #[arg(long, value_name = "FILE")]
+        emit_inverse_mapping: Option<PathBuf>,
+
+        /// Write `new,old` 1-based column positions, one pair per line, sorted by new position
+        #[arg(long, value_name = "FILE")]
+        output_index_map: Option<PathBuf>,
+
+        /// Write `old,new` 1-based column positions, one pair per line, sorted by old position
+        #[arg(long, value_name = "FILE")]
+        output_inverse_index_map: Option<PathBuf>,
+
+        /// Append an NDJSON audit record of this run to FILE
+        #[arg(long, value_name = "FILE")]
+        emit_change_log: Option<PathBuf>,
+
+        /// Write the ranked columns' stats as JSON, as `rsf stats --format json` on the output
+        #[arg(long, value_name = "FILE")]
+        stats_out: Option<PathBuf>,
+
+        /// Write `{column: sha256 of its sorted distinct values}` as JSON, to spot
+        /// columns whose values changed by comparing with an earlier run
+        #[arg(long, value_name = "FILE")]
+        output_column_fingerprint: Option<PathBuf>,
+
+        /// Write each column's null count and fraction as JSON, from the ranking pass
+        #[arg(long, value_name = "FILE")]
+        nulls_report: Option<PathBuf>,
+
+        /// Write phase timings, counts and options as JSON
+        #[arg(long, value_name = "FILE")]
+        profile: Option<PathBuf>,
+
+        /// Remove NUL bytes from fields (otherwise they are only reported)
+        #[arg(long)]
+        strip_nul: bool,
+
+        /// Skip input lines starting with CHAR (e.g. '#'), before and after the header
+        #[arg(long, visible_alias = "comment-char", value_name = "CHAR", value_parser = parse_comment)]
+        comment: Option<u8>,
+
+        /// Separate input fields with CHAR, `tab`, or `auto` to sniff it from the start of the file
+        #[arg(long, default_value = ",", value_name = "CHAR", value_parser = parse_delimiter)]
+        delimiter: Delimiter,
+
+        /// Print the delimiters `--delimiter auto` tries on the input, how evenly each
+        /// splits its records and the one it picks, to stderr
+        #[arg(long)]
+        delimiter_detect_report: bool,
+
+        /// Parse input files of 16 MiB or more on this many threads
+        #[arg(long, default_value_t = 1, value_name = "N", env = "RSF_THREADS")]
+        threads: usize,
+
+        /// Parse input files from a memory map (Unix only; on Windows, and for
+        /// stdin, input is read through a buffer as without it)
+        #[arg(long)]
+        mmap: bool,
+
+        /// Show a progress bar on stderr for each phase (the default when stderr is a terminal)
+        #[arg(long, conflicts_with = "no_progress")]
+        progress: bool,
+
+        /// Never show the progress bar, e.g. when stderr is captured to a log
+        #[arg(long)]
+        no_progress: bool,
+
+        /// Keep per-column value counts in DIR so re-ranking an appended-to
+        /// input file only counts the new rows; ignored for stdin
+        #[arg(long, value_name = "DIR")]
+        cache_dir: Option<PathBuf>,
+
+        /// Write the ranking as a GraphViz DOT graph
+        #[arg(long, value_name = "FILE")]
+        dot: Option<PathBuf>,
+
+        /// Write the ranking as a Mermaid erDiagram block titled with the file name
+        #[arg(long, value_name = "FILE")]
+        emit_mermaid_er: Option<PathBuf>,
+
+        /// Write the distinct values of the key columns (the first --key-count) as a
+        /// sorted CSV lookup file
+        #[arg(long, value_name = "FILE")]
+        output_sorted_keys_only: Option<PathBuf>,
+
+        /// Number of leading ranked columns that form the key
+        #[arg(
+            long,
+            default_value = "1",
+            value_name = "N",
+            requires = "output_sorted_keys_only"
+        )]
+        key_count: NonZeroUsize,
+
+        /// Exit non-zero if ranking with the other options given would change the
+        /// input (writes nothing)
+        #[arg(long, conflicts_with_all = ["output", "in_place", "output_delta"])]
+        check: bool,
+
+        /// Print the planned column moves and row sortedness without writing anything
+        #[arg(long, conflicts_with = "check")]
+        dry_run: bool,
+
+        /// Estimate rows, distinct values per column and peak memory in one pass
+        /// that keeps no rows, and recommend ranking in memory or streaming
+        #[arg(long, conflicts_with_all = ["check", "dry_run"])]
+        estimate: bool,
+
+        /// Format of the --dry-run plan or the --estimate
+        #[arg(long, value_enum, default_value = "text", requires = "preview")]
+        format: ReportFormat,
+
+        /// Read and rank the input a second time on a different number of threads, through
+        /// every transform, and fail unless both runs write byte-identical data and
+        /// schemas (holds the input twice in memory)
+        #[arg(long, conflicts_with_all = ["check", "dry_run"])]
+        determinism_check: bool,
+
+        /// Rank again, rewriting the output and schema, whenever the input file changes
+        #[arg(long, conflicts_with_all = ["check", "dry_run"])]
+        watch: bool,
+    },
+
+    /// Trim and sort a CSV canonically, keeping its column order
+    Normalize {
+        /// Input CSV file (use - for stdin)
+        #[arg(default_value = "-")]
+        input: String,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Generate schema.yaml file
+        #[arg(short, long)]
+        schema: bool,
+
+        /// Read numbers as `1.234,56` (`.` thousands, `,` decimal) and sort them by value
+        #[arg(long)]
+        decimal_comma: bool,
+
+        /// Remove NUL bytes from fields (otherwise they are only reported)
+        #[arg(long)]
+        strip_nul: bool,
+    },
+
+    /// Validate an RSF file
+    Validate {
+        /// RSF CSV files to validate
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Schema file (defaults to input.schema.yaml)
+        #[arg(short, long)]
+        schema: Option<PathBuf>,
+
+        /// Accept cardinalities between MIN and MAX times the schema value (e.g. 0.5:2)
+        #[arg(long, value_name = "MIN:MAX")]
+        cardinality_band: Option<CardinalityBand>,
+
+        /// Accept the schema's `key` columns in any order, as long as they come
+        /// before the other columns, which must still follow the schema's order
+        #[arg(long)]
+        allow_reordered_key_columns: bool,
+
+        /// Write every check and its outcome to FILE as JSON
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+
+        /// Fail on the first byte sequence that is invalid in ENCODING, before parsing
+        #[arg(long, value_enum, value_name = "ENCODING")]
+        check_encoding: Option<Encoding>,
+
+        /// Replace invalid sequences with U+FFFD and validate the result instead of failing
+        #[arg(long, requires = "check_encoding")]
+        fix_encoding: bool,
+
+        /// Skip input lines starting with CHAR, such as the `#` lines of
+        /// `rank --emit-metadata-header`
+        #[arg(long, visible_alias = "comment-char", value_name = "CHAR", value_parser = parse_comment)]
+        comment: Option<u8>,
+
+        /// Columns to show either side of the first difference when the header does
+        /// not match the schema
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        context: usize,
+
+        /// Fail unless the data has the number of rows written in FILE, e.g. by the
+        /// upstream system that produced it
+        #[arg(long, value_name = "FILE")]
+        row_count_file: Option<PathBuf>,
+
+        /// Accept up to N rows more or fewer than --row-count-file
+        #[arg(
+            long,
+            value_name = "N",
+            default_value_t = 0,
+            requires = "row_count_file"
+        )]
+        row_count_tolerance: usize,
+
+        /// How to print each input's result on stdout
+        #[arg(long, value_enum, default_value = "text")]
+        format: ValidateFormat,
+
+        /// When a check fails, rank and sort the input as `rsf rank` would and write it
+        /// to INPUT.fixed.csv, or to FILE with `--fix=FILE` (`-` for stdout)
+        #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true)]
+        fix: Option<Option<PathBuf>>,
+    },
+
+    /// Check a schema file for common authoring mistakes
+    SchemaLint {
+        /// Schema file to check
+        schema: PathBuf,
+
+        /// Treat warnings as errors
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// Show cardinality statistics for a CSV
+    Stats {
+        /// Input CSV file
+        input: PathBuf,
+
+        /// Also report the cardinality of a combined key (e.g. col1,col2); repeatable
+        #[arg(long, value_name = "COLS")]
+        combine: Vec<String>,
+
+        /// Check whether one column determines the other (e.g. col1,col2); repeatable
+        #[arg(long, value_name = "COL1,COL2")]
+        functional_dep: Vec<String>,
+
+        /// Flag columns whose values look like emails, phone numbers, card numbers,
+        /// IP addresses or names
+        #[arg(long)]
+        detect_pii: bool,
+
+        /// Record --detect-pii results (pii_risk and a PII tag) in this schema file
+        #[arg(long, value_name = "SCHEMA", requires = "detect_pii")]
+        schema: Option<PathBuf>,
+
+        /// Remove NUL bytes from fields (otherwise they are only reported)
+        #[arg(long)]
+        strip_nul: bool,
+
+        /// Parse input files of 16 MiB or more on this many threads
+        #[arg(long, default_value_t = 1, value_name = "N", env = "RSF_THREADS")]
+        threads: usize,
+
+        /// Parse input files from a memory map (Unix only; on Windows, and for
+        /// stdin, input is read through a buffer as without it)
+        #[arg(long)]
+        mmap: bool,
+
+        /// Compare cardinalities against a second snapshot (exits 1 on any change)
+        #[arg(long, value_name = "FILE")]
+        compare: Option<PathBuf>,
+
+        /// List columns whose rank depends on whether empty cells count as distinct values
+        #[arg(long, conflicts_with = "compare")]
+        compare_null_modes: bool,
+
+        /// Only count empty cells per column, most first, in one pass that keeps no values
+        #[arg(
+            long,
+            conflicts_with_all = ["compare", "compare_null_modes", "combine", "functional_dep", "detect_pii", "mmap"]
+        )]
+        nulls: bool,
+
+        /// Count these comma-separated tokens (e.g. `N/A,-,\\N,null`) as null, folded
+        /// together with empty cells, in the cardinality table and --nulls
+        #[arg(
+            long,
+            value_name = "TOKENS",
+            value_delimiter = ',',
+            conflicts_with_all = ["compare_null_modes", "top_values_per_column"],
+            env = "RSF_NULL_VALUES"
+        )]
+        null_values: Vec<String>,
+
+        /// Match --null-values regardless of case
+        #[arg(
+            long,
+            requires = "null_values",
+            env = "RSF_NULL_VALUES_IGNORE_CASE",
+            value_parser = BoolishValueParser::new()
+        )]
+        null_values_ignore_case: bool,
+
+        /// Stop collecting a column's distinct values at N, bounding memory on
+        /// untrusted data; such columns are reported as `>N (capped)`
+        #[arg(
+            long,
+            value_name = "N",
+            alias = "max-distinct",
+            conflicts_with_all = ["compare", "compare_null_modes", "nulls", "top_values_per_column"]
+        )]
+        max_distinct_per_column: Option<NonZeroUsize>,
+
+        /// Profile each column for a data catalog as JSON: cardinality, missing
+        /// rate, unique ratio, and this many top and sample values
+        #[arg(
+            long,
+            value_name = "N",
+            conflicts_with_all = ["compare", "compare_null_modes", "combine", "functional_dep", "detect_pii", "nulls"]
+        )]
+        top_values_per_column: Option<usize>,
+
+        /// Write the --top-values-per-column profile to this file instead of stdout
+        #[arg(long, value_name = "FILE", requires = "top_values_per_column")]
+        output_json: Option<PathBuf>,
+
+        /// Output format; json reports cardinality, nulls, type and lengths per column
+        #[arg(
+            long,
+            value_enum,
+            default_value = "text",
+            conflicts_with_all = ["compare", "compare_null_modes", "combine", "functional_dep", "detect_pii"]
+        )]
+        format: ReportFormat,
+    },
+
+    /// Show the distribution of one column: bins if numeric, top values otherwise
+    Hist {
+        /// Input CSV file (use - for stdin)
+        #[arg(default_value = "-")]
+        input: String,
+
+        /// Column to inspect
+        #[arg(short, long)]
+        column: String,
+
+        /// Number of equal-width bins for a numeric column
+        #[arg(long, default_value = "10")]
+        bins: NonZeroUsize,
+
+        /// Number of values to list for a non-numeric column
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+
+        /// Read numbers as `1.234,56` (`.` thousands, `,` decimal)
+        #[arg(long)]
+        decimal_comma: bool,
+    },
+
+    /// Browse column rankings interactively
+    #[cfg(feature = "tui")]
+    Explore {
+        /// Input CSV file
+        input: PathBuf,
+
+        /// Count nulls as distinct values at startup
+        #[arg(
+            long,
+            default_value_t = true,
+            num_args = 0..=1,
+            default_missing_value = "true",
+            action = ArgAction::Set
+        )]
+        nulls_distinct: bool,
+
+        /// Metric used to order columns at startup
+        #[arg(long, value_enum, default_value = "cardinality")]
+        rank_by: RankBy,
+    },
+
+    /// Turn columns into rows (column,value_1,value_2,...)
+    Transpose {
+        /// Input CSV file (use - for stdin)
+        #[arg(default_value = "-")]
+        input: String,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Invert a previous transpose
+        #[arg(long)]
+        back: bool,
+
+        /// Rank the transposed data before writing
+        #[arg(long)]
+        rank: bool,
+
+        /// Refuse inputs with more cells than this
+        #[arg(long, default_value_t = 10_000_000)]
+        max_cells: usize,
+    },
+
+    /// Reorder a CSV's columns to match another file's schema, leaving rows as they are
+    Align {
+        /// Input CSV file (use - for stdin)
+        #[arg(default_value = "-")]
+        input: String,
+
+        /// Schema whose column order to follow
+        #[arg(short, long)]
+        reference: PathBuf,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Inspect the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+/// Config file commands
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print every option of a command with its value and where it comes from:
+    /// the command line, the environment, the config file or the default
+    Show {
+        /// The command to show, with any of its flags (e.g. `rank in.csv --threads 2`)
+        #[arg(
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "COMMAND"
+        )]
+        args: Vec<OsString>,
+    },
+}
+
+/// Metric used to order columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RankBy {
+    /// Number of distinct values
+    Cardinality,
+    /// Shannon entropy of the value distribution
+    Entropy,
+}
+
+impl From<RankBy> for RankMetric {
+    fn from(rank_by: RankBy) -> Self {
+        match rank_by {
+            RankBy::Cardinality => RankMetric::Cardinality,
+            RankBy::Entropy => RankMetric::Entropy,
+        }
+    }
+}
+
+/// Unicode normalization form for --normalize-unicode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum UnicodeForm {
+    /// Canonical composition (precomposed `é`)
+    Nfc,
+    /// Canonical decomposition (`e` and a combining accent)
+    Nfd,
+    /// Compatibility composition; also folds e.g. `ﬁ` into `fi`
+    Nfkc,
+    /// Compatibility decomposition
+    Nfkd,
+}
+
+impl From<UnicodeForm> for NormalizationForm {
+    fn from(form: UnicodeForm) -> Self {
+        match form {
+            UnicodeForm::Nfc => NormalizationForm::Nfc,
+            UnicodeForm::Nfd => NormalizationForm::Nfd,
+            UnicodeForm::Nfkc => NormalizationForm::Nfkc,
+            UnicodeForm::Nfkd => NormalizationForm::Nfkd,
+        }
+    }
+}
+
+/// How to order columns that tie on the metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Tiebreak {
+    /// Keep the input column order
+    Position,
+    /// By a stable hash of each column's distinct values, independent of input order
+    Hash,
+}
+
+impl From<Tiebreak> for TieBreak {
+    fn from(tiebreak: Tiebreak) -> Self {
+        match tiebreak {
+            Tiebreak::Position => TieBreak::Position,
+            Tiebreak::Hash => TieBreak::Hash,
+        }
+    }
+}
+
+/// Output format for fatal errors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// The message and its causes, as text
+    #[default]
+    Human,
+    /// A JSON object with the error kind, its fields and the message
+    Json,
+}
+
+/// When to color terminal output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorWhen {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<ColorWhen> for anstream::ColorChoice {
+    fn from(when: ColorWhen) -> Self {
+        match when {
+            ColorWhen::Auto => Self::Auto,
+            ColorWhen::Always => Self::Always,
+            ColorWhen::Never => Self::Never,
+        }
+    }
+}
+
+/// Text encoding an input is expected to be in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    #[value(name = "utf-8")]
+    Utf8,
+}
+
+/// Format of the data `rsf rank` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Auto,
+    Csv,
+    Tsv,
+    Jsonl,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// Output format for reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+/// Output format for `rsf validate` results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ValidateFormat {
+    Text,
+    /// Test Anything Protocol: a `1..N` plan, then `ok N - FILE` or
+    /// `not ok N - FILE` per input
+    Tap,
+}
+
+/// Input field delimiter given to `rank --delimiter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    /// Sniff it from the start of the input
+    Auto,
+    Byte(u8),
+}
+
+/// Parse a `--delimiter`: `auto`, `tab` or one ASCII character
+fn parse_delimiter(value: &str) -> Result<Delimiter, String> {
+    match value {
+        "auto" => Ok(Delimiter::Auto),
+        "tab" | "\\t" => Ok(Delimiter::Byte(b'\t')),
+        _ => parse_comment(value).map(Delimiter::Byte).map_err(|_| {
+            format!(
+                "expected `auto`, `tab` or a single ASCII character, found {:?}",
+                value
+            )
+        }),
+    }
+}
+
+/// Parse a `--comment` character, which the CSV reader matches as one byte
+fn parse_comment(value: &str) -> Result<u8, String> {
+    match value.as_bytes() {
+        [byte] if byte.is_ascii() => Ok(*byte),
+        _ => Err(format!(
+            "expected a single ASCII character, found {:?}",
+            value
+        )),
+    }
+}
@@ -0,0 +1,83 @@
+//! Output files the CLI won't overwrite unless `--force` is given
+//!
+//! Files that are updated on purpose, such as the change log, the cache,
+//! the schema given to `stats --detect-pii --schema` and the input under
+//! `--in-place`, are written directly.
+
+use anyhow::{bail, Context, Result};
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static FORCE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_force(force: bool) {
+    FORCE.store(force, Ordering::Relaxed);
+}
+
+/// Fail if something exists at `path`, even a dangling symlink, unless
+/// `--force`
+pub fn check(path: &Path) -> Result<()> {
+    if !FORCE.load(Ordering::Relaxed) && fs::symlink_metadata(path).is_ok() {
+        bail!(
+            "Refusing to overwrite {:?}, which already exists; pass --force to replace it",
+            path
+        );
+    }
+    Ok(())
+}
+
+/// Fail if `output` is `input` under another name, such as a symlink or a
+/// relative path, which `--force` does not allow
+///
+/// Either path not existing yet means they differ.
+pub fn check_not_input(input: &Path, output: &Path) -> Result<()> {
+    if let (Ok(input_path), Ok(output_path)) = (fs::canonicalize(input), fs::canonicalize(output)) {
+        if input_path == output_path {
+            bail!(
+                "Output {:?} is the input file {:?}; use --in-place to replace the input",
+                output,
+                input
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Create the file at `path` after [`check`]ing it
+pub fn create(path: &Path) -> Result<File> {
+    check(path)?;
+    File::create(path).with_context(|| format!("Failed to create file: {:?}", path))
+}
+
+/// Write `contents` to `path` after [`check`]ing it
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    check(path)?;
+    fs::write(path, contents).with_context(|| format!("Failed to write file: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_to_input_is_the_input() {
+        let dir = std::env::temp_dir().join(format!("rsf-clobber-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let input = dir.join("input.csv");
+        fs::write(&input, "a\n1\n").unwrap();
+        std::os::unix::fs::symlink(&input, dir.join("link.csv")).unwrap();
+
+        assert!(check_not_input(&input, &dir.join("link.csv")).is_err());
+        assert!(check_not_input(&input, &dir.join("sub").join("..").join("input.csv")).is_err());
+        assert!(check_not_input(&input, &dir.join("other.csv")).is_ok());
+
+        // A dangling symlink is still something to overwrite
+        std::os::unix::fs::symlink(dir.join("missing.csv"), dir.join("dangling.csv")).unwrap();
+        assert!(check(&dir.join("dangling.csv")).is_err());
+        assert!(check(&dir.join("other.csv")).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
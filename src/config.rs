@@ -0,0 +1,407 @@
+use anyhow::{bail, Context, Result};
+use clap::builder::StyledStr;
+use clap::error::{ContextKind, ContextValue};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Config file looked up in the current directory and its parents
+pub const CONFIG_FILE: &str = "rsf.toml";
+
+/// Config file names tried in each directory, the first found winning
+pub const CONFIG_FILES: [&str; 2] = [CONFIG_FILE, ".rsf.toml"];
+
+/// Find the nearest `rsf.toml` or `.rsf.toml` in `dir` or one of its ancestors
+pub fn discover(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|dir| CONFIG_FILES.map(|name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Read and parse a config file
+pub fn load(path: &Path) -> Result<toml::Table> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to open config: {:?}", path))?;
+    text.parse()
+        .with_context(|| format!("Invalid config file: {:?}", path))
+}
+
+/// Command-line arguments that apply config defaults to a parsed invocation
+///
+/// Keys are long flag names. Top-level keys apply to every subcommand that
+/// has the flag; a `[rank]`-style table applies to one subcommand and wins
+/// over top-level keys. Flags given on the command line are left alone.
+pub fn config_args(
+    command: &Command,
+    matches: &ArgMatches,
+    config: &toml::Table,
+) -> Result<Vec<String>> {
+    let Some((name, sub_matches)) = matches.subcommand() else {
+        return Ok(Vec::new());
+    };
+    let Some(sub_command) = command.find_subcommand(name) else {
+        return Ok(Vec::new());
+    };
+
+    let mut values: BTreeMap<&str, &toml::Value> = BTreeMap::new();
+    for (key, value) in config {
+        if value.is_table() && command.find_subcommand(key).is_some() {
+            continue;
+        }
+        if !command
+            .get_subcommands()
+            .any(|sub| find_flag(sub, key).is_some())
+        {
+            bail!("Unknown key '{}' in config file", key);
+        }
+        values.insert(key, value);
+    }
+    if let Some(section) = config.get(name).and_then(toml::Value::as_table) {
+        for (key, value) in section {
+            if find_flag(sub_command, key).is_none() {
+                bail!("Unknown key '{}' in [{}] of config file", key, name);
+            }
+            values.insert(key, value);
+        }
+    }
+
+    let mut args = Vec::new();
+    for (key, value) in values {
+        // Top-level keys may belong to other subcommands
+        let Some(arg) = find_flag(sub_command, key) else {
+            continue;
+        };
+        if matches!(
+            sub_matches.value_source(arg.get_id().as_str()),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+
+        match arg.get_action() {
+            ArgAction::SetTrue => {
+                let enabled = value
+                    .as_bool()
+                    .with_context(|| format!("Config key '{}' must be true or false", key))?;
+                if enabled {
+                    args.push(format!("--{}", key));
+                }
+            }
+            _ => match value {
+                toml::Value::Array(items) => {
+                    for item in items {
+                        args.push(format!("--{}={}", key, scalar(key, item)?));
+                    }
+                }
+                other => args.push(format!("--{}={}", key, scalar(key, other)?)),
+            },
+        }
+    }
+
+    Ok(args)
+}
+
+/// Where an option's value comes from, lowest precedence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Default,
+    File,
+    Env,
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::Default => "default",
+            Source::File => "config file",
+            Source::Env => "environment",
+            Source::CommandLine => "command line",
+        })
+    }
+}
+
+/// An option's value in a run, printed as a config line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    pub key: String,
+    pub value: toml::Value,
+    pub source: Source,
+}
+
+impl fmt::Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}  # {}", self.key, self.value, self.source)
+    }
+}
+
+/// The flags of `command` that have a value in `matches`, which were parsed
+/// with the config args `from_file` added
+///
+/// Flags without a default that were not given are left out.
+pub fn effective(command: &Command, matches: &ArgMatches, from_file: &[String]) -> Vec<Setting> {
+    let file_keys: BTreeSet<&str> = from_file
+        .iter()
+        .filter_map(|arg| arg.strip_prefix("--")?.split('=').next())
+        .collect();
+
+    command
+        .get_arguments()
+        .filter_map(|arg| {
+            let key = arg.get_long()?;
+            let id = arg.get_id().as_str();
+            let source = if file_keys.contains(key) {
+                Source::File
+            } else {
+                match matches.value_source(id)? {
+                    ValueSource::CommandLine => Source::CommandLine,
+                    ValueSource::EnvVariable => Source::Env,
+                    ValueSource::DefaultValue => Source::Default,
+                    _ => return None,
+                }
+            };
+
+            let value = match arg.get_action() {
+                ArgAction::SetTrue | ArgAction::SetFalse => {
+                    toml::Value::Boolean(matches.get_flag(id))
+                }
+                action => {
+                    let mut values: Vec<toml::Value> = matches
+                        .get_raw(id)?
+                        .map(|value| toml::Value::String(value.to_string_lossy().into_owned()))
+                        .collect();
+                    if values.len() == 1 && !matches!(action, ArgAction::Append) {
+                        values.remove(0)
+                    } else {
+                        toml::Value::Array(values)
+                    }
+                }
+            };
+
+            Some(Setting {
+                key: key.to_string(),
+                value,
+                source,
+            })
+        })
+        .collect()
+}
+
+/// Add a tip naming the environment variable behind an argument that `err`
+/// complains about, which clap names only by its flag
+///
+/// `args` is the command line; a flag given there did not come from the
+/// environment.
+pub fn name_env_vars(command: &Command, args: &[OsString], mut err: clap::Error) -> clap::Error {
+    let mentioned: Vec<&str> = [ContextKind::InvalidArg, ContextKind::PriorArg]
+        .into_iter()
+        .flat_map(|kind| match err.get(kind) {
+            Some(ContextValue::String(arg)) => vec![arg.as_str()],
+            Some(ContextValue::Strings(args)) => args.iter().map(String::as_str).collect(),
+            _ => Vec::new(),
+        })
+        .collect();
+
+    let mut tips = Vec::new();
+    for long in mentioned
+        .iter()
+        .filter_map(|arg| arg.strip_prefix("--")?.split([' ', '=']).next())
+    {
+        let flag = format!("--{}", long);
+        let on_command_line = args.iter().any(|arg| {
+            let arg = arg.to_string_lossy();
+            arg == flag || arg.starts_with(&format!("{}=", flag))
+        });
+        let env = std::iter::once(command)
+            .chain(command.get_subcommands())
+            .find_map(|cmd| find_flag(cmd, long)?.get_env())
+            .filter(|name| !on_command_line && std::env::var_os(name).is_some());
+        if let Some(name) = env {
+            tips.push(StyledStr::from(format!(
+                "{} was set by the environment variable {}",
+                flag,
+                name.to_string_lossy()
+            )));
+        }
+    }
+
+    if !tips.is_empty() {
+        err.insert(ContextKind::Suggested, ContextValue::StyledStrs(tips));
+    }
+    err
+}
+
+fn find_flag<'a>(command: &'a Command, long: &str) -> Option<&'a Arg> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long))
+}
+
+fn scalar(key: &str, value: &toml::Value) -> Result<String> {
+    Ok(match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Float(f) => f.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        _ => bail!("Config key '{}' must be a string, number or boolean", key),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::CommandFactory;
+
+    fn args_for(argv: &[&str], config: &str) -> Result<Vec<String>> {
+        let matches = Cli::command().try_get_matches_from(argv).unwrap();
+        config_args(&Cli::command(), &matches, &config.parse().unwrap())
+    }
+
+    #[test]
+    fn test_config_fills_flags_not_given_on_command_line() {
+        let config = r#"
+            strip-nul = true
+            max-cells = 5
+            threads = 2
+
+            [rank]
+            threads = 8
+            decimal-comma = true
+
+            [stats]
+            combine = ["a,b", "c,d"]
+        "#;
+
+        let args = args_for(&["rsf", "rank", "in.csv", "--threads", "3"], config).unwrap();
+        assert_eq!(args, vec!["--decimal-comma", "--strip-nul"]);
+
+        let args = args_for(&["rsf", "rank", "in.csv"], config).unwrap();
+        assert_eq!(args, vec!["--decimal-comma", "--strip-nul", "--threads=8"]);
+
+        let args = args_for(&["rsf", "stats", "in.csv"], config).unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "--combine=a,b",
+                "--combine=c,d",
+                "--strip-nul",
+                "--threads=2"
+            ]
+        );
+
+        assert!(args_for(&["rsf", "rank"], "no-such-flag = 1").is_err());
+        assert!(args_for(&["rsf", "rank"], "[rank]\nmax-cells = 1").is_err());
+    }
+
+    #[test]
+    fn test_precedence_is_command_line_env_file_default() {
+        let threads = |argv: &[&str], config: &str| {
+            // Arg::env reads the variable when the command is built
+            let command = Command::new("rsf").subcommand(
+                Command::new("rank").arg(
+                    Arg::new("threads")
+                        .long("threads")
+                        .env("RSF_CONFIG_TEST_THREADS")
+                        .default_value("1"),
+                ),
+            );
+            let matches = command.clone().try_get_matches_from(argv).unwrap();
+            let from_file = config_args(&command, &matches, &config.parse().unwrap()).unwrap();
+            let argv = argv
+                .iter()
+                .map(|arg| arg.to_string())
+                .chain(from_file.clone());
+            let matches = command.clone().try_get_matches_from(argv).unwrap();
+            let rank = command.find_subcommand("rank").unwrap();
+            let settings = effective(
+                rank,
+                matches.subcommand_matches("rank").unwrap(),
+                &from_file,
+            );
+            (settings[0].value.to_string(), settings[0].source)
+        };
+        let file = "threads = 4";
+
+        assert_eq!(
+            threads(&["rsf", "rank"], ""),
+            ("\"1\"".into(), Source::Default)
+        );
+        assert_eq!(
+            threads(&["rsf", "rank"], file),
+            ("\"4\"".into(), Source::File)
+        );
+
+        std::env::set_var("RSF_CONFIG_TEST_THREADS", "6");
+        assert_eq!(
+            threads(&["rsf", "rank"], file),
+            ("\"6\"".into(), Source::Env)
+        );
+        assert_eq!(
+            threads(&["rsf", "rank", "--threads", "8"], file),
+            ("\"8\"".into(), Source::CommandLine)
+        );
+        std::env::remove_var("RSF_CONFIG_TEST_THREADS");
+    }
+
+    #[test]
+    fn test_effective_settings_print_as_config() {
+        let argv = ["rsf", "rank", "in.csv", "--tiebreak", "hash"];
+        let config = "[rank]\nnull-values = [\"NA\", \"-\"]\nstrip-nul = true";
+        let matches = Cli::command().try_get_matches_from(argv).unwrap();
+        let from_file = config_args(&Cli::command(), &matches, &config.parse().unwrap()).unwrap();
+        let argv = argv
+            .iter()
+            .map(|arg| arg.to_string())
+            .chain(from_file.clone());
+        let matches = Cli::command().try_get_matches_from(argv).unwrap();
+
+        let command = Cli::command();
+        let settings = effective(
+            command.find_subcommand("rank").unwrap(),
+            matches.subcommand_matches("rank").unwrap(),
+            &from_file,
+        );
+        let line = |key: &str| {
+            settings
+                .iter()
+                .find(|setting| setting.key == key)
+                .map(ToString::to_string)
+        };
+        assert_eq!(
+            line("tiebreak").unwrap(),
+            "tiebreak = \"hash\"  # command line"
+        );
+        assert_eq!(
+            line("null-values").unwrap(),
+            "null-values = [\"NA\", \"-\"]  # config file"
+        );
+        assert_eq!(
+            line("strip-nul").unwrap(),
+            "strip-nul = true  # config file"
+        );
+        assert_eq!(
+            line("decimal-comma").unwrap(),
+            "decimal-comma = false  # default"
+        );
+        assert_eq!(line("output"), None);
+    }
+
+    #[test]
+    fn test_discover_finds_dotfile_in_parent() {
+        let root = std::env::temp_dir().join(format!("rsf-config-{}", std::process::id()));
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(".rsf.toml"), "").unwrap();
+
+        assert_eq!(discover(&nested), Some(root.join(".rsf.toml")));
+        fs::write(root.join("a").join(CONFIG_FILE), "").unwrap();
+        assert_eq!(discover(&nested), Some(root.join("a").join(CONFIG_FILE)));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
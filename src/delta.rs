@@ -0,0 +1,89 @@
+use crate::ranking::{compare_rows, RankingOptions};
+use std::cmp::Ordering;
+
+/// Rows that differ between two versions of a table with the same columns
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RowDelta {
+    /// Rows of the new version that the previous one lacks, in canonical order
+    pub added: Vec<Vec<String>>,
+    /// Rows of the previous version that the new one lacks, in canonical order
+    pub removed: Vec<Vec<String>>,
+}
+
+/// Merge-join two versions of a table, keeping the rows found in only one
+///
+/// Rows are compared whole, so a row whose cells changed is both removed
+/// (as it was) and added (as it is now). Repeated rows are matched one for
+/// one. Both tables must have the same columns in the same order; rows need
+/// not be sorted.
+pub fn row_delta(
+    new: &[Vec<String>],
+    previous: &[Vec<String>],
+    options: RankingOptions,
+) -> RowDelta {
+    // Canonical order, with ties such as `1,5` and `1,50` under
+    // decimal_comma broken by the text so equal means identical
+    let order =
+        |a: &&Vec<String>, b: &&Vec<String>| compare_rows(a, b, &options).then_with(|| a.cmp(b));
+    let mut new: Vec<&Vec<String>> = new.iter().collect();
+    let mut previous: Vec<&Vec<String>> = previous.iter().collect();
+    new.sort_by(order);
+    previous.sort_by(order);
+
+    let mut delta = RowDelta::default();
+    let (mut new, mut previous) = (new.into_iter().peekable(), previous.into_iter().peekable());
+    loop {
+        match (new.peek(), previous.peek()) {
+            (Some(a), Some(b)) => match order(a, b) {
+                Ordering::Less => delta.added.extend(new.next().cloned()),
+                Ordering::Greater => delta.removed.extend(previous.next().cloned()),
+                Ordering::Equal => {
+                    new.next();
+                    previous.next();
+                }
+            },
+            (Some(_), None) => delta.added.extend(new.by_ref().cloned()),
+            (None, Some(_)) => delta.removed.extend(previous.by_ref().cloned()),
+            (None, None) => return delta,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|c| c.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_row_delta() {
+        let previous = rows(&[&["1", "a"], &["2", "b"], &["3", "c"], &["3", "c"]]);
+        let new = rows(&[&["4", "d"], &["3", "c"], &["2", "B"], &["1", "a"]]);
+
+        let delta = row_delta(&new, &previous, RankingOptions::default());
+        assert_eq!(delta.added, rows(&[&["2", "B"], &["4", "d"]]));
+        // One of the two copies of (3, c) is gone
+        assert_eq!(delta.removed, rows(&[&["2", "b"], &["3", "c"]]));
+
+        assert_eq!(
+            row_delta(&new, &new, Default::default()),
+            RowDelta::default()
+        );
+    }
+
+    #[test]
+    fn test_equal_numbers_written_differently_differ() {
+        let options = RankingOptions::builder()
+            .decimal_comma(true)
+            .build()
+            .unwrap();
+        let delta = row_delta(&rows(&[&["1,50"]]), &rows(&[&["1,5"]]), options);
+        assert_eq!(delta.added, rows(&[&["1,50"]]));
+        assert_eq!(delta.removed, rows(&[&["1,5"]]));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::ranking::{ColumnMeta, ColumnType};
+use std::fmt::Write;
+
+/// Render ranked columns as a GraphViz DOT graph
+///
+/// Columns become nodes in rank order, labelled with their cardinality, and
+/// consecutive ranks are chained with edges. Output depends only on the
+/// ranking, so the same schema always renders identically.
+pub fn render_dot(columns: &[ColumnMeta]) -> String {
+    let mut dot = String::from("digraph rsf {\n    rankdir=LR;\n    node [shape=box];\n");
+
+    for (idx, col) in columns.iter().enumerate() {
+        let _ = writeln!(
+            dot,
+            "    c{} [label=\"{}. {}\\n(cardinality: {})\"];",
+            idx,
+            idx + 1,
+            escape_dot(&col.name),
+            col.cardinality
+        );
+    }
+
+    for idx in 1..columns.len() {
+        let _ = writeln!(dot, "    c{} -> c{};", idx - 1, idx);
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// Render ranked columns as a Mermaid `erDiagram` block titled `title`
+///
+/// The table is a single entity whose attributes are the columns in rank
+/// order. `Key` columns are marked `PK`, other unique columns `UK`, and each
+/// attribute's comment gives its rank and cardinality.
+pub fn render_mermaid_er(title: &str, columns: &[ColumnMeta]) -> String {
+    let mut er = format!(
+        "---\ntitle: {}\n---\nerDiagram\n    {} {{\n",
+        title.replace('\n', " "),
+        mermaid_name(title)
+    );
+
+    for (idx, col) in columns.iter().enumerate() {
+        let marker = if col.col_type == Some(ColumnType::Key) {
+            " PK"
+        } else if col.is_unique {
+            " UK"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            er,
+            "        string {}{} \"{}. {}, cardinality: {}\"",
+            mermaid_name(&col.name),
+            marker,
+            idx + 1,
+            col.name.replace('"', "'"),
+            col.cardinality
+        );
+    }
+
+    er.push_str("    }\n");
+    er
+}
+
+/// Reduce a name to the characters Mermaid accepts in entity and attribute
+/// names
+fn mermaid_name(value: &str) -> String {
+    let name: String = value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name
+    } else {
+        format!("_{}", name)
+    }
+}
+
+/// Escape a string for use inside a quoted DOT label
+fn escape_dot(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_dot() {
+        let columns = vec![
+            ColumnMeta::new("Id", 1, 10),
+            ColumnMeta::new("Say \"hi\"", 2, 3),
+        ];
+
+        let dot = render_dot(&columns);
+        assert_eq!(
+            dot,
+            "digraph rsf {\n    rankdir=LR;\n    node [shape=box];\n    \
+             c0 [label=\"1. Id\\n(cardinality: 10)\"];\n    \
+             c1 [label=\"2. Say \\\"hi\\\"\\n(cardinality: 3)\"];\n    \
+             c0 -> c1;\n}\n"
+        );
+        assert_eq!(dot, render_dot(&columns));
+    }
+
+    #[test]
+    fn test_render_mermaid_er() {
+        let mut id = ColumnMeta::new("Id", 1, 10);
+        id.col_type = Some(ColumnType::Key);
+        let mut email = ColumnMeta::new("E-mail address", 2, 10);
+        email.is_unique = true;
+        let columns = vec![id, email, ColumnMeta::new("2nd \"tier\"", 3, 3)];
+
+        assert_eq!(
+            render_mermaid_er("sales.csv", &columns),
+            "---\ntitle: sales.csv\n---\nerDiagram\n    sales_csv {\n        \
+             string Id PK \"1. Id, cardinality: 10\"\n        \
+             string E-mail_address UK \"2. E-mail address, cardinality: 10\"\n        \
+             string _2nd__tier_ \"3. 2nd 'tier', cardinality: 3\"\n    }\n"
+        );
+    }
+}
@@ -0,0 +1,768 @@
+use crate::cache::{rank_columns_cached, RankCache};
+use crate::errors::{RsfError, RsfResult};
+use crate::io::{read_csv_reader_with_progress, write_csv_to_with_progress, CsvInput, ReadOptions};
+use crate::progress::{NoProgress, Phase, ProgressSink, RowCounter};
+use crate::ranker::RsfRanker;
+use crate::ranking::{
+    append_row_hashes, check_pinned_columns, read_schema_reader, reorder_data, sort_rows_in_place,
+    trim_cells, validate_cardinalities, validate_cardinality_order, validate_column_order,
+    validate_column_order_any_key_order, validate_rank_sequence, validate_sorted, validate_types,
+    CardinalityBand, ColumnMeta, ColumnStats, DataType, RankingOptions, Schema,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{Read, Write};
+
+/// A table together with the schema describing its ranking
+///
+/// Wraps the free functions in [`crate::ranking`] so that columns are
+/// reordered, rows sorted and the schema recorded in the right order:
+///
+/// ```
+/// use rsf::{ranking::RankingOptions, RsfDocument};
+///
+/// let mut doc = RsfDocument::from_reader("Category,Id\nfood,2\ntravel,1\nfood,3\n".as_bytes())?;
+/// doc.rank(RankingOptions::default())?;
+/// doc.sort();
+///
+/// assert_eq!(doc.headers(), ["Id", "Category"]);
+/// assert_eq!(doc.schema().columns[0].cardinality, 3);
+/// assert!(doc.validate().is_empty());
+/// # Ok::<(), rsf::RsfError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct RsfDocument {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    schema: Schema,
+    options: RankingOptions,
+    any_key_order: bool,
+}
+
+impl RsfDocument {
+    /// Create a document from headers and rows, with an empty schema
+    pub fn new(headers: Vec<String>, rows: Vec<Vec<String>>) -> Self {
+        Self {
+            headers,
+            rows,
+            schema: Schema::new(Vec::new()),
+            options: RankingOptions::default(),
+            any_key_order: false,
+        }
+    }
+
+    /// Read a document from CSV; the first record is the header
+    pub fn from_reader<R: Read>(reader: R) -> RsfResult<Self> {
+        Self::from_reader_with_progress(reader, &NoProgress)
+    }
+
+    /// Like [`from_reader`](Self::from_reader), reporting to `progress`
+    pub fn from_reader_with_progress<R: Read>(
+        reader: R,
+        progress: &dyn ProgressSink,
+    ) -> RsfResult<Self> {
+        read_csv_reader_with_progress(reader, ReadOptions::default(), progress).map(Self::from)
+    }
+
+    /// Build a document from serializable records, such as structs deriving
+    /// `Serialize`; the header comes from the first record's field names
+    pub fn from_serialize<T: Serialize>(records: impl IntoIterator<Item = T>) -> RsfResult<Self> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for record in records {
+            writer.serialize(record)?;
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|err| RsfError::from(err.into_error()))?;
+        Self::from_reader(bytes.as_slice())
+    }
+
+    /// Attach a schema, e.g. one read with [`crate::ranking::read_schema`],
+    /// for [`validate`](Self::validate) and [`sort`](Self::sort)
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.options = schema.ranking_options();
+        self.schema = schema;
+        self
+    }
+
+    /// Record the declared type of each named column in the schema, as read
+    /// from a type row; call after [`rank`](Self::rank), which replaces the
+    /// schema
+    pub fn declare_types(&mut self, declared: &[(String, DataType)]) {
+        for col in &mut self.schema.columns {
+            col.data_type = declared
+                .iter()
+                .find(|(name, _)| name == &col.name)
+                .map(|(_, data_type)| *data_type);
+        }
+    }
+
+    /// Accept cardinalities within `band` of the schema when validating
+    pub fn with_cardinality_band(mut self, band: CardinalityBand) -> Self {
+        self.options.cardinality_band = Some(band);
+        self
+    }
+
+    /// Accept the schema's `key` columns in any order when validating, as
+    /// long as they come before all other columns
+    pub fn with_any_key_order(mut self) -> Self {
+        self.any_key_order = true;
+        self
+    }
+
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    pub fn rows(&self) -> &[Vec<String>] {
+        &self.rows
+    }
+
+    /// The schema recorded by the last [`rank`](Self::rank) or attached with
+    /// [`with_schema`](Self::with_schema); it has no columns before either
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// The rows as typed records, with fields matched to columns by header
+    /// name so the struct's field order need not follow the ranking
+    ///
+    /// A row that fails to parse yields an error naming its 1-based index and
+    /// the offending column.
+    pub fn deserialize<'a, T: DeserializeOwned + 'a>(
+        &'a self,
+    ) -> impl Iterator<Item = RsfResult<T>> + 'a {
+        let headers: csv::StringRecord = self.headers.iter().collect();
+
+        self.rows.iter().enumerate().map(move |(idx, row)| {
+            row.iter()
+                .collect::<csv::StringRecord>()
+                .deserialize(Some(&headers))
+                .map_err(|err| self.deserialize_error(err, idx + 1))
+        })
+    }
+
+    fn deserialize_error(&self, err: csv::Error, row: usize) -> RsfError {
+        match err.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => {
+                let column = err.field().and_then(|idx| self.headers.get(idx as usize));
+                RsfError::csv_error(match column {
+                    Some(name) => format!("row {}, column '{}': {}", row, name, err.kind()),
+                    None => format!("row {}: {}", row, err.kind()),
+                })
+            }
+            _ => err.into(),
+        }
+    }
+
+    /// Append a column holding the hash of each row's `key_columns` cells,
+    /// as [`append_row_hashes`](crate::ranking::append_row_hashes) does,
+    /// returning its schema entry
+    ///
+    /// The schema is left as it is: it describes the ranked columns, which
+    /// the hash column is not one of.
+    pub fn append_row_hashes(
+        &mut self,
+        name: &str,
+        key_columns: &[impl AsRef<str>],
+    ) -> RsfResult<ColumnMeta> {
+        append_row_hashes(&mut self.headers, &mut self.rows, name, key_columns)
+    }
+
+    /// Give up the document, returning its headers and rows
+    pub fn into_parts(self) -> (Vec<String>, Vec<Vec<String>>) {
+        (self.headers, self.rows)
+    }
+
+    /// Rank the columns and move them into rank order, recording the schema
+    ///
+    /// Rows keep their order; call [`sort`](Self::sort) to sort them.
+    pub fn rank(&mut self, options: RankingOptions) -> RsfResult<()> {
+        self.rank_with_progress(options, &NoProgress)
+    }
+
+    /// Like [`rank`](Self::rank), reporting the cardinality phase to
+    /// `progress`
+    pub fn rank_with_progress(
+        &mut self,
+        options: RankingOptions,
+        progress: &dyn ProgressSink,
+    ) -> RsfResult<()> {
+        self.rank_with_stats(options, progress).map(drop)
+    }
+
+    /// Like [`rank_with_progress`](Self::rank_with_progress), returning the
+    /// value counts of each column, in header order of the input
+    pub fn rank_with_stats(
+        &mut self,
+        options: RankingOptions,
+        progress: &dyn ProgressSink,
+    ) -> RsfResult<Vec<ColumnStats>> {
+        self.rank_with_overrides(options, BTreeMap::new(), progress)
+    }
+
+    /// Like [`rank_with_stats`](Self::rank_with_stats), ranking the columns
+    /// named in `overrides` by the given cardinality instead of the counted
+    /// one; the schema marks them as overridden
+    ///
+    /// Fails if a name in `overrides` or `options.pin_last` is not a column.
+    pub fn rank_with_overrides(
+        &mut self,
+        options: RankingOptions,
+        overrides: BTreeMap<String, usize>,
+        progress: &dyn ProgressSink,
+    ) -> RsfResult<Vec<ColumnStats>> {
+        if let Some(name) = overrides.keys().find(|name| !self.headers.contains(name)) {
+            return Err(RsfError::schema_error(format!(
+                "Column '{}' has a cardinality override but is not in the data",
+                name
+            )));
+        }
+        check_pinned_columns(&self.headers, &options)?;
+
+        let mut counter = RowCounter::start(progress, Phase::Cardinality, Some(self.rows.len()));
+        let mut ranker = RsfRanker::new(options.clone())
+            .with_headers(&self.headers)
+            .with_cardinality_overrides(overrides);
+        for row in &self.rows {
+            ranker.push_record(row);
+            counter.tick();
+        }
+        counter.finish();
+
+        let (ranked_columns, _) = ranker.ranking();
+        self.apply_ranking(ranked_columns, options)?;
+        Ok(ranker.into_stats())
+    }
+
+    /// Like [`rank`](Self::rank), but count only the rows not already in
+    /// `cache`, returning the cache to save for the next run
+    pub fn rank_cached(
+        &mut self,
+        options: RankingOptions,
+        cache: Option<RankCache>,
+    ) -> RsfResult<RankCache> {
+        check_pinned_columns(&self.headers, &options)?;
+        let (ranked_columns, cache) =
+            rank_columns_cached(&self.headers, &self.rows, options.clone(), cache);
+        self.apply_ranking(ranked_columns, options)?;
+        Ok(cache)
+    }
+
+    fn apply_ranking(
+        &mut self,
+        ranked_columns: Vec<ColumnMeta>,
+        options: RankingOptions,
+    ) -> RsfResult<()> {
+        let row_count = self.rows.len();
+        let (headers, rows) = reorder_data(
+            &self.headers,
+            std::mem::take(&mut self.rows),
+            &ranked_columns,
+        )?;
+
+        let mut schema = Schema::new(ranked_columns);
+        schema.row_count = row_count;
+        schema.rank_by = options.metric;
+        schema.decimal_comma = options.decimal_comma;
+        schema.nulls_excluded = options.treat_empty_as_null && !options.include_nulls;
+        schema.options = Some(options.clone());
+
+        self.headers = headers;
+        self.rows = rows;
+        self.schema = schema;
+        self.options = options;
+        Ok(())
+    }
+
+    /// Trim whitespace from every cell and record a schema that keeps the
+    /// columns in their source order
+    ///
+    /// For files whose column order is fixed elsewhere; like
+    /// [`rank`](Self::rank), rows keep their order until [`sort`](Self::sort).
+    pub fn normalize(&mut self, options: RankingOptions) -> RsfResult<()> {
+        trim_cells(&mut self.rows);
+
+        let mut ranker = RsfRanker::new(options.clone()).with_headers(&self.headers);
+        for row in &self.rows {
+            ranker.push_record(row);
+        }
+
+        let mut schema = Schema::new(ranker.finish_in_source_order());
+        schema.row_count = self.rows.len();
+        schema.rank_by = options.metric;
+        schema.decimal_comma = options.decimal_comma;
+        schema.nulls_excluded = options.treat_empty_as_null && !options.include_nulls;
+        schema.options = Some(options.clone());
+        schema.source_order = true;
+
+        self.schema = schema;
+        self.options = options;
+        Ok(())
+    }
+
+    /// Sort rows canonically with the options of the last ranking or schema
+    pub fn sort(&mut self) {
+        self.sort_with_progress(&NoProgress);
+    }
+
+    /// Like [`sort`](Self::sort), reporting to `progress`; rows are counted
+    /// only once the sort is done
+    pub fn sort_with_progress(&mut self, progress: &dyn ProgressSink) {
+        let counter = RowCounter::start(progress, Phase::Sort, Some(self.rows.len()));
+        sort_rows_in_place(&mut self.rows, self.options.clone());
+        counter.finish_with(self.rows.len());
+    }
+
+    /// Check the document against its schema, reporting every failed check
+    ///
+    /// An empty result means the document is valid RSF.
+    pub fn validate(&self) -> Vec<Finding> {
+        self.run_checks()
+            .into_iter()
+            .filter_map(|(check, result)| result.err().map(|error| Finding { check, error }))
+            .collect()
+    }
+
+    /// Check the document against its schema, recording the outcome of
+    /// every check, passed or not
+    pub fn validation_report(&self) -> ValidationReport {
+        let checks: Vec<CheckOutcome> = self
+            .run_checks()
+            .into_iter()
+            .map(|(check, result)| CheckOutcome {
+                check,
+                description: check.description(),
+                passed: result.is_ok(),
+                error: result.err().map(|error| error.to_string()),
+            })
+            .collect();
+
+        ValidationReport {
+            valid: checks.iter().all(|outcome| outcome.passed),
+            checks,
+        }
+    }
+
+    fn run_checks(&self) -> [(Check, RsfResult<()>); 5] {
+        let columns = &self.schema.columns;
+        let options = &self.options;
+        // Synthetic columns follow the ranked ones without being ranked
+        let ranked: Vec<ColumnMeta> = columns
+            .iter()
+            .filter(|col| !col.synthetic)
+            .cloned()
+            .collect();
+
+        [
+            (
+                Check::ColumnOrder,
+                if self.any_key_order {
+                    validate_column_order_any_key_order(&self.headers, columns)
+                } else {
+                    validate_column_order(&self.headers, columns)
+                },
+            ),
+            (Check::RankSequence, validate_rank_sequence(columns)),
+            (
+                Check::Cardinality,
+                if self.schema.source_order {
+                    validate_cardinalities(&self.headers, &self.rows, &ranked, options.clone())
+                } else {
+                    validate_cardinality_order(&self.headers, &self.rows, &ranked, options.clone())
+                },
+            ),
+            (Check::Sorted, validate_sorted(&self.rows, options.clone())),
+            (
+                Check::Types,
+                validate_types(
+                    &self.headers,
+                    &self.rows,
+                    columns,
+                    self.schema.decimal_comma,
+                ),
+            ),
+        ]
+    }
+
+    /// Write the document as CSV
+    pub fn write_to<W: Write>(&self, writer: W) -> RsfResult<()> {
+        self.write_to_with_progress(writer, &NoProgress)
+    }
+
+    /// Like [`write_to`](Self::write_to), reporting to `progress`
+    pub fn write_to_with_progress<W: Write>(
+        &self,
+        writer: W,
+        progress: &dyn ProgressSink,
+    ) -> RsfResult<()> {
+        write_csv_to_with_progress(writer, &self.headers, &self.rows, progress)
+    }
+}
+
+/// Validate CSV against a schema, both given as file contents
+///
+/// Needs no filesystem, so it suits hosts such as the browser. Fails only if
+/// either input cannot be parsed; an empty result means the CSV is valid RSF.
+pub fn validate_bytes(csv: &[u8], schema: &[u8]) -> RsfResult<Vec<Finding>> {
+    let schema = read_schema_reader(schema)?;
+    Ok(RsfDocument::from_reader(csv)?
+        .with_schema(schema)
+        .validate())
+}
+
+impl From<CsvInput> for RsfDocument {
+    fn from(input: CsvInput) -> Self {
+        Self::new(input.headers, input.rows)
+    }
+}
+
+/// Validation check that produced a [`Finding`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Check {
+    /// Headers match the schema's column order
+    ColumnOrder,
+    /// Schema ranks run 1, 2, 3, ...
+    RankSequence,
+    /// Columns are ordered by the schema's metric and cardinalities match
+    Cardinality,
+    /// Rows are in canonical order
+    Sorted,
+    /// Cells hold values of their column's declared type
+    Types,
+}
+
+impl Check {
+    /// What the check verifies, in a few words
+    pub fn description(self) -> &'static str {
+        match self {
+            Check::ColumnOrder => "Headers match the schema's column order",
+            Check::RankSequence => "Schema ranks run 1, 2, 3, ...",
+            Check::Cardinality => {
+                "Columns are ordered by the schema's metric and cardinalities match"
+            }
+            Check::Sorted => "Rows are in canonical order",
+            Check::Types => "Cells hold values of their column's declared type",
+        }
+    }
+}
+
+/// A failed validation check
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Finding {
+    pub check: Check,
+    pub error: RsfError,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+/// Outcome of every validation check, for archiving
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct ValidationReport {
+    /// Every check passed
+    pub valid: bool,
+    pub checks: Vec<CheckOutcome>,
+}
+
+/// Outcome of one validation check
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct CheckOutcome {
+    pub check: Check,
+    pub description: &'static str,
+    pub passed: bool,
+    /// Why the check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranking::ColumnType;
+
+    #[test]
+    fn test_rank_sort_write() {
+        let mut doc = RsfDocument::from_reader("B,A\nx,2\nx,1\n".as_bytes()).unwrap();
+        doc.rank(RankingOptions::default()).unwrap();
+        doc.sort();
+
+        let mut out = Vec::new();
+        doc.write_to(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "A,B\n1,x\n2,x\n");
+        assert_eq!(doc.schema().columns.len(), 2);
+    }
+
+    #[test]
+    fn test_validate_reports_every_failed_check() {
+        let doc = RsfDocument::from_reader("A,B\n2,x\n1,x\n".as_bytes()).unwrap();
+        // Rank 3 is out of sequence and the rows are not sorted
+        let schema = Schema::new(vec![ColumnMeta::new("A", 1, 2), ColumnMeta::new("B", 3, 1)]);
+        let doc = doc.with_schema(schema);
+
+        let checks: Vec<Check> = doc.validate().iter().map(|f| f.check).collect();
+        assert_eq!(checks, vec![Check::RankSequence, Check::Sorted]);
+    }
+
+    #[test]
+    fn test_rank_with_stats_counts_the_ranked_data() {
+        let mut doc =
+            RsfDocument::from_reader("Cat,Id,Note\nfood,2,\ntravel,1,x\nfood,3,\n".as_bytes())
+                .unwrap();
+        let stats = doc
+            .rank_with_stats(RankingOptions::default(), &NoProgress)
+            .unwrap();
+        doc.sort();
+
+        let names: Vec<&str> = stats.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Cat", "Id", "Note"]);
+        let recounted =
+            crate::ranking::compute_cardinality(doc.headers(), doc.rows(), Default::default())
+                .unwrap();
+        for stat in &stats {
+            let same = recounted.iter().find(|r| r.name == stat.name).unwrap();
+            assert_eq!(same.value_counts, stat.value_counts);
+        }
+    }
+
+    #[test]
+    fn test_declared_types_are_checked() {
+        let mut doc = RsfDocument::from_reader("Id,Amount\n1,9.5\n2,n/a\n".as_bytes()).unwrap();
+        doc.rank(RankingOptions::default()).unwrap();
+        doc.declare_types(&[("Amount".to_string(), DataType::Float)]);
+        doc.sort();
+
+        let types: Vec<(&str, Option<DataType>)> = doc
+            .schema()
+            .columns
+            .iter()
+            .map(|col| (col.name.as_str(), col.data_type))
+            .collect();
+        assert_eq!(types, [("Id", None), ("Amount", Some(DataType::Float))]);
+
+        let findings = doc.validate();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, Check::Types);
+        assert_eq!(
+            findings[0].to_string(),
+            "Column 'Amount' is declared float but row 2 holds 'n/a'"
+        );
+    }
+
+    #[test]
+    fn test_typed_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Expense {
+            category: String,
+            id: u32,
+            amount: f64,
+        }
+
+        let expenses = vec![
+            Expense {
+                category: "food".into(),
+                id: 2,
+                amount: 4.5,
+            },
+            Expense {
+                category: "travel".into(),
+                id: 1,
+                amount: 120.0,
+            },
+            Expense {
+                category: "food".into(),
+                id: 3,
+                amount: 4.5,
+            },
+        ];
+
+        let mut doc = RsfDocument::from_serialize(&expenses).unwrap();
+        doc.rank(RankingOptions::default()).unwrap();
+        doc.sort();
+        assert_eq!(doc.headers(), ["id", "category", "amount"]);
+
+        let typed: Vec<Expense> = doc.deserialize().collect::<RsfResult<_>>().unwrap();
+        let expected: Vec<&Expense> = [1, 0, 2].iter().map(|&idx| &expenses[idx]).collect();
+        assert_eq!(typed.iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_deserialize_error_names_row_and_column() {
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Row {
+            name: String,
+            count: u32,
+        }
+
+        let doc = RsfDocument::from_reader("count,name\n1,a\nmany,b\n".as_bytes()).unwrap();
+        let results: Vec<RsfResult<Row>> = doc.deserialize().collect();
+        assert!(results[0].is_ok());
+        let err = results[1].as_ref().unwrap_err().to_string();
+        assert!(
+            err.starts_with("CSV error: row 2, column 'count': "),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_validation_uses_recorded_options() {
+        // Folded nulls make Note's "" and " " one value
+        let options = RankingOptions::builder()
+            .treat_empty_as_null(true)
+            .include_nulls(true)
+            .build()
+            .unwrap();
+        let mut doc = RsfDocument::from_reader("Id,Note\n1,\n2, \n3,x\n".as_bytes()).unwrap();
+        doc.rank(options.clone()).unwrap();
+        doc.sort();
+
+        let yaml = serde_yaml::to_string(doc.schema()).unwrap();
+        let schema: Schema = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(schema.options, Some(options));
+        let (headers, rows) = doc.into_parts();
+        let reread = RsfDocument::new(headers.clone(), rows.clone()).with_schema(schema.clone());
+        assert!(reread.validate().is_empty());
+
+        // Without them, validation guesses nulls are distinct and counts three
+        let mut legacy = schema;
+        legacy.options = None;
+        let findings = RsfDocument::new(headers, rows)
+            .with_schema(legacy)
+            .validate();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, Check::Cardinality);
+    }
+
+    #[test]
+    fn test_cardinality_overrides() {
+        let mut doc = RsfDocument::from_reader("Id,Region\n1,eu\n2,us\n".as_bytes()).unwrap();
+        let overrides = BTreeMap::from([("Region".to_string(), 40)]);
+        doc.rank_with_overrides(RankingOptions::default(), overrides, &NoProgress)
+            .unwrap();
+        doc.sort();
+
+        assert_eq!(doc.headers(), ["Region", "Id"]);
+        let region = &doc.schema().columns[0];
+        assert_eq!(region.cardinality, 40);
+        assert!(region.cardinality_overridden);
+        assert!(!doc.schema().columns[1].cardinality_overridden);
+        // Validation orders by the recorded override instead of recounting
+        assert!(doc.validate().is_empty());
+
+        let unknown = BTreeMap::from([("Country".to_string(), 40)]);
+        let err = doc
+            .rank_with_overrides(RankingOptions::default(), unknown, &NoProgress)
+            .unwrap_err();
+        assert!(err.to_string().contains("'Country'"));
+    }
+
+    #[test]
+    fn test_any_key_order() {
+        let mut a = ColumnMeta::new("A", 1, 2);
+        let mut b = ColumnMeta::new("B", 2, 2);
+        a.col_type = Some(ColumnType::Key);
+        b.col_type = Some(ColumnType::Key);
+        let schema = Schema::new(vec![a, b, ColumnMeta::new("C", 3, 1)]);
+        let doc = |csv: &str| {
+            RsfDocument::from_reader(csv.as_bytes())
+                .unwrap()
+                .with_schema(schema.clone())
+        };
+
+        let swapped = "B,A,C\n1,1,x\n2,2,x\n";
+        let checks: Vec<Check> = doc(swapped).validate().iter().map(|f| f.check).collect();
+        assert_eq!(checks, [Check::ColumnOrder]);
+        assert!(doc(swapped).with_any_key_order().validate().is_empty());
+
+        // A value column among the keys is still out of order
+        let findings = doc("A,C,B\n1,x,1\n2,x,2\n").with_any_key_order().validate();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, Check::ColumnOrder);
+    }
+
+    #[test]
+    fn test_row_hash_column_is_not_ranked() {
+        let mut doc = RsfDocument::from_reader("Id,Kind\n2,x\n1,x\n3,y\n".as_bytes()).unwrap();
+        doc.rank(RankingOptions::default()).unwrap();
+        doc.sort();
+        let mut schema = doc.schema().clone();
+        let (mut headers, mut rows) = doc.into_parts();
+        schema.columns.push(
+            crate::ranking::append_row_hashes(&mut headers, &mut rows, "row_hash", &["Id"])
+                .unwrap(),
+        );
+
+        // The hash column has the highest cardinality but stays last
+        let doc = RsfDocument::new(headers, rows).with_schema(schema);
+        assert!(doc.validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate_bytes() {
+        let schema = b"version: '0.1'\ncolumns:\n- name: Id\n  rank: 1\n  cardinality: 2\n- name: Kind\n  rank: 2\n  cardinality: 1\n";
+
+        assert!(validate_bytes(b"Id,Kind\n1,x\n2,x\n", schema)
+            .unwrap()
+            .is_empty());
+
+        let findings = validate_bytes(b"Id,Kind\n2,x\n1,x\n", schema).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check, Check::Sorted);
+
+        assert!(validate_bytes(b"Id,Kind\n", b"columns: 3").is_err());
+    }
+
+    #[test]
+    fn test_normalize_keeps_column_order() {
+        let mut doc = RsfDocument::from_reader("B,A\n x ,2\nx,1\n".as_bytes()).unwrap();
+        doc.normalize(RankingOptions::default()).unwrap();
+        doc.sort();
+
+        assert_eq!(doc.headers(), ["B", "A"]);
+        assert_eq!(doc.rows(), [vec!["x", "1"], vec!["x", "2"]]);
+        assert!(doc.schema().source_order);
+        assert_eq!(doc.schema().columns[0].cardinality, 1);
+        assert!(doc.validate().is_empty());
+    }
+
+    #[test]
+    fn test_report_lists_every_check() {
+        let doc = RsfDocument::from_reader("A,B\n2,x\n1,x\n".as_bytes()).unwrap();
+        let schema = Schema::new(vec![ColumnMeta::new("A", 1, 2), ColumnMeta::new("B", 2, 1)]);
+        let report = doc.with_schema(schema).validation_report();
+
+        assert!(!report.valid);
+        let outcomes: Vec<(Check, bool)> = report
+            .checks
+            .iter()
+            .map(|outcome| (outcome.check, outcome.passed))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (Check::ColumnOrder, true),
+                (Check::RankSequence, true),
+                (Check::Cardinality, true),
+                (Check::Sorted, false),
+                (Check::Types, true),
+            ]
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["checks"][3]["check"], "sorted");
+        assert!(json["checks"][3]["error"].is_string());
+        assert!(json["checks"][0].get("error").is_none());
+    }
+}
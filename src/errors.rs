@@ -1,46 +1,281 @@
-use anyhow::Error;
+use crate::ranking::DataType;
+use serde::{Serialize, Serializer};
 use std::path::PathBuf;
+use thiserror::Error;
 
 /// Custom error type for RSF operations
-#[derive(Debug)]
+///
+/// Serializes as `{"kind": "schema_error", "details": {...}}` for tools that
+/// consume errors as data.
+#[derive(Debug, Error, Serialize)]
+#[serde(tag = "kind", content = "details", rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum RsfError {
-    /// File I/O error
+    /// File I/O error; `path` is `None` for streams such as stdin
+    ///
+    /// The message names the file; the underlying error is the source.
+    #[error("{}", io_message(.path))]
     IoError {
-        path: PathBuf,
+        path: Option<PathBuf>,
+        #[source]
+        #[serde(serialize_with = "serialize_display")]
         cause: std::io::Error,
     },
     /// CSV parsing error
-    CsvError { message: String },
+    ///
+    /// With a path the message starts `data.csv:LINE:COLUMN:`, like a
+    /// compiler's, and a snippet of the line follows it.
+    #[error("{}{message}{}", csv_location(.path, .position), snippet_suffix(.snippet))]
+    CsvError {
+        message: String,
+        /// Where in the input the error was found, when known
+        position: Option<CsvPosition>,
+        /// File the input was read from, when known
+        #[serde(skip_serializing_if = "Option::is_none")]
+        path: Option<PathBuf>,
+        /// Up to [`SNIPPET_CHARS`] characters of the offending line
+        #[serde(skip_serializing_if = "Option::is_none")]
+        snippet: Option<String>,
+    },
+    /// Input bytes that are not valid in the expected encoding
+    #[error("Invalid {expected} at byte {byte_offset}: found {found}")]
+    EncodingError {
+        /// Offset of the first invalid byte from the start of the input
+        byte_offset: usize,
+        expected: String,
+        /// The offending bytes, in hex
+        found: String,
+    },
     /// Schema validation error
-    SchemaError { message: String },
+    #[error("Schema error{}: {message}", path_suffix(.path))]
+    SchemaError {
+        message: String,
+        /// Schema file being read or written, when known
+        path: Option<PathBuf>,
+    },
     /// Invalid column ordering
+    #[error(
+        "Column order mismatch at position {position}: expected '{expected}', found '{found}'"
+    )]
     ColumnOrderError {
         position: usize,
         expected: String,
         found: String,
     },
+    /// Column the caller requires is not in the input
+    #[error("Required column '{column}' is missing from the input")]
+    MissingColumn { column: String },
     /// Invalid cardinality ranking
+    #[error(
+        "Column '{column}' has invalid cardinality: expected {expected}, found {found}{}",
+        ratio_suffix(*.expected, *.found)
+    )]
     CardinalityError {
         column: String,
         expected: usize,
         found: usize,
     },
+    /// Column marked unique in the schema has repeated values
+    #[error(
+        "Column '{column}' is marked unique but has {actual_cardinality} distinct values for {expected_cardinality} rows"
+    )]
+    UniquenessViolation {
+        column: String,
+        expected_cardinality: usize,
+        actual_cardinality: usize,
+    },
+    /// Cell that does not hold a value of its column's declared type
+    #[error("Column '{column}' is declared {expected} but row {row} holds '{value}'")]
+    TypeError {
+        column: String,
+        expected: String,
+        /// 1-based data row
+        row: usize,
+        value: String,
+    },
     /// Row sorting error
-    SortError,
+    #[error("Rows are not in canonical sorted order{}", row_suffix(.row))]
+    SortError {
+        /// 1-based data row that belongs before the row above it
+        row: Option<usize>,
+    },
+    /// Ranking options that contradict each other or are out of range
+    #[error("Invalid ranking options: {message}")]
+    InvalidOptions { message: String },
     /// Unknown error type
+    #[error("Unknown error: {0}")]
     Unknown(String),
 }
 
+/// Location of a CSV error, as reported by the parser
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CsvPosition {
+    /// 0-based record index, counting the header
+    pub record: u64,
+    /// 1-based line number
+    pub line: u64,
+    /// Byte offset from the start of the input
+    pub byte: u64,
+    /// 1-based byte offset of the error within its line, when known; the
+    /// parser itself reports only where the record starts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u64>,
+}
+
+impl From<&csv::Position> for CsvPosition {
+    fn from(position: &csv::Position) -> Self {
+        Self {
+            record: position.record(),
+            line: position.line(),
+            byte: position.byte(),
+            column: None,
+        }
+    }
+}
+
+/// Characters of the offending line a [`RsfError::CsvError`] quotes
+pub const SNIPPET_CHARS: usize = 80;
+
+fn serialize_display<S: Serializer>(
+    value: &std::io::Error,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+fn io_message(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => format!("Failed to open file '{}'", path.display()),
+        None => "I/O error".to_string(),
+    }
+}
+
+fn csv_location(path: &Option<PathBuf>, position: &Option<CsvPosition>) -> String {
+    match (path, position) {
+        (Some(path), Some(p)) => match p.column {
+            Some(column) => format!("{}:{}:{}: ", path.display(), p.line, column),
+            None => format!("{}:{}: ", path.display(), p.line),
+        },
+        (Some(path), None) => format!("CSV error in '{}': ", path.display()),
+        (None, Some(p)) => {
+            let column = p
+                .column
+                .map_or(String::new(), |c| format!(", column {}", c));
+            format!(
+                "CSV error at line {}{} (record {}, byte {}): ",
+                p.line, column, p.record, p.byte
+            )
+        }
+        (None, None) => "CSV error: ".to_string(),
+    }
+}
+
+fn snippet_suffix(snippet: &Option<String>) -> String {
+    snippet
+        .as_ref()
+        .map_or(String::new(), |snippet| format!("\n  | {}", snippet))
+}
+
+fn path_suffix(path: &Option<PathBuf>) -> String {
+    path.as_ref()
+        .map_or(String::new(), |path| format!(" in '{}'", path.display()))
+}
+
+fn ratio_suffix(expected: usize, found: usize) -> String {
+    if expected > 0 {
+        format!(" (ratio {:.2})", found as f64 / expected as f64)
+    } else {
+        String::new()
+    }
+}
+
+fn row_suffix(row: &Option<usize>) -> String {
+    row.map_or(String::new(), |row| {
+        format!(": row {} belongs before the row above it", row)
+    })
+}
+
 impl RsfError {
     /// Create an I/O error with context
-    pub fn io_error(path: PathBuf, cause: std::io::Error) -> Self {
-        RsfError::IoError { path, cause }
+    pub fn io_error(path: impl Into<PathBuf>, cause: std::io::Error) -> Self {
+        RsfError::IoError {
+            path: Some(path.into()),
+            cause,
+        }
     }
 
     /// Create a CSV parsing error
     pub fn csv_error(message: impl Into<String>) -> Self {
         RsfError::CsvError {
             message: message.into(),
+            position: None,
+            path: None,
+            snippet: None,
+        }
+    }
+
+    /// Create an error for a quoted field opened at `position` and never
+    /// closed, quoting `line`, the line it opened on
+    pub fn unterminated_quote(position: CsvPosition, line: &[u8]) -> Self {
+        RsfError::CsvError {
+            message: format!(
+                "unterminated quoted field starting at byte {}",
+                position.byte
+            ),
+            position: Some(position),
+            path: None,
+            snippet: Some(snippet(line)),
+        }
+    }
+
+    /// Quote `line` in a [`RsfError::CsvError`] that has no snippet yet;
+    /// other errors are returned unchanged
+    pub fn with_csv_snippet(self, line: &[u8]) -> Self {
+        match self {
+            RsfError::CsvError {
+                message,
+                position,
+                path,
+                snippet: None,
+            } => RsfError::CsvError {
+                message,
+                position,
+                path,
+                snippet: Some(snippet(line)),
+            },
+            other => other,
+        }
+    }
+
+    /// Record the file a [`RsfError::CsvError`] came from; other errors are
+    /// returned unchanged
+    pub fn with_csv_path(self, csv_path: impl Into<PathBuf>) -> Self {
+        match self {
+            RsfError::CsvError {
+                message,
+                position,
+                snippet,
+                ..
+            } => RsfError::CsvError {
+                message,
+                position,
+                path: Some(csv_path.into()),
+                snippet,
+            },
+            other => other,
+        }
+    }
+
+    /// Create an encoding error for the bytes `found` at `byte_offset`
+    pub fn encoding_error(byte_offset: usize, expected: impl Into<String>, found: &[u8]) -> Self {
+        RsfError::EncodingError {
+            byte_offset,
+            expected: expected.into(),
+            found: found
+                .iter()
+                .map(|byte| format!("0x{:02X}", byte))
+                .collect::<Vec<_>>()
+                .join(" "),
         }
     }
 
@@ -48,6 +283,19 @@ impl RsfError {
     pub fn schema_error(message: impl Into<String>) -> Self {
         RsfError::SchemaError {
             message: message.into(),
+            path: None,
+        }
+    }
+
+    /// Record the schema file a [`RsfError::SchemaError`] came from; other
+    /// errors are returned unchanged
+    pub fn with_schema_path(self, schema_path: impl Into<PathBuf>) -> Self {
+        match self {
+            RsfError::SchemaError { message, .. } => RsfError::SchemaError {
+                message,
+                path: Some(schema_path.into()),
+            },
+            other => other,
         }
     }
 
@@ -60,6 +308,13 @@ impl RsfError {
         }
     }
 
+    /// Create a missing column error
+    pub fn missing_column(column: impl Into<String>) -> Self {
+        RsfError::MissingColumn {
+            column: column.into(),
+        }
+    }
+
     /// Create a cardinality error
     pub fn cardinality_error(column: String, expected: usize, found: usize) -> Self {
         RsfError::CardinalityError {
@@ -69,9 +324,49 @@ impl RsfError {
         }
     }
 
+    /// Create a uniqueness violation error
+    pub fn uniqueness_violation(
+        column: String,
+        expected_cardinality: usize,
+        actual_cardinality: usize,
+    ) -> Self {
+        RsfError::UniquenessViolation {
+            column,
+            expected_cardinality,
+            actual_cardinality,
+        }
+    }
+
+    /// Create a type error for the cell `value` in 1-based data `row`
+    pub fn type_error(
+        column: impl Into<String>,
+        expected: DataType,
+        row: usize,
+        value: impl Into<String>,
+    ) -> Self {
+        RsfError::TypeError {
+            column: column.into(),
+            expected: expected.to_string(),
+            row,
+            value: value.into(),
+        }
+    }
+
     /// Create a sort error
     pub fn sort_error() -> Self {
-        RsfError::SortError
+        RsfError::SortError { row: None }
+    }
+
+    /// Create a sort error naming the first out-of-order row (1-based)
+    pub fn sort_error_at(row: usize) -> Self {
+        RsfError::SortError { row: Some(row) }
+    }
+
+    /// Create an invalid options error
+    pub fn invalid_options(message: impl Into<String>) -> Self {
+        RsfError::InvalidOptions {
+            message: message.into(),
+        }
     }
 
     /// Create an unknown error
@@ -80,60 +375,51 @@ impl RsfError {
     }
 }
 
-impl std::fmt::Display for RsfError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            RsfError::IoError { path, cause } => {
-                write!(f, "Failed to open file '{}': {}", path.display(), cause)
-            }
-            RsfError::CsvError { message } => write!(f, "CSV error: {}", message),
-            RsfError::SchemaError { message } => write!(f, "Schema error: {}", message),
-            RsfError::ColumnOrderError {
-                position,
-                expected,
-                found,
-            } => {
-                write!(
-                    f,
-                    "Column order mismatch at position {}: expected '{}', found '{}'",
-                    position, expected, found
-                )
-            }
-            RsfError::CardinalityError {
-                column,
-                expected,
-                found,
-            } => {
-                write!(
-                    f,
-                    "Column '{}' has invalid cardinality: expected {}, found {}",
-                    column, expected, found
-                )
-            }
-            RsfError::SortError => write!(f, "Rows are not in canonical sorted order"),
-            RsfError::Unknown(message) => write!(f, "Unknown error: {}", message),
-        }
+impl From<std::io::Error> for RsfError {
+    fn from(cause: std::io::Error) -> Self {
+        RsfError::IoError { path: None, cause }
     }
 }
 
-impl std::error::Error for RsfError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            RsfError::IoError { cause, .. } => Some(cause),
-            _ => None,
+impl From<csv::Error> for RsfError {
+    fn from(err: csv::Error) -> Self {
+        let position = err.position().map(CsvPosition::from);
+        // The parser's own message repeats the position, so describe the kind
+        let message = match err.kind() {
+            csv::ErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => format!(
+                "found record with {} fields, but the previous record has {} fields",
+                len, expected_len
+            ),
+            csv::ErrorKind::Utf8 { err, .. } => err.to_string(),
+            csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+            _ => err.to_string(),
+        };
+
+        match err.into_kind() {
+            csv::ErrorKind::Io(cause) => RsfError::IoError { path: None, cause },
+            _ => RsfError::CsvError {
+                message,
+                position,
+                path: None,
+                snippet: None,
+            },
         }
     }
 }
 
-impl From<std::io::Error> for RsfError {
-    fn from(err: std::io::Error) -> Self {
-        RsfError::io_error(PathBuf::from("<unknown>"), err)
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for RsfError {
+    fn from(err: parquet::errors::ParquetError) -> Self {
+        std::io::Error::other(err).into()
     }
 }
 
-impl From<csv::Error> for RsfError {
-    fn from(err: csv::Error) -> Self {
-        RsfError::csv_error(err.to_string())
+#[cfg(feature = "parquet")]
+impl From<arrow_schema::ArrowError> for RsfError {
+    fn from(err: arrow_schema::ArrowError) -> Self {
+        std::io::Error::other(err).into()
     }
 }
 
@@ -143,16 +429,170 @@ impl From<serde_yaml::Error> for RsfError {
     }
 }
 
-/// Convert RsfError to anyhow::Error with context
-pub trait IntoAnyhow {
-    fn into_anyhow(self) -> Error;
-}
-
-impl IntoAnyhow for RsfError {
-    fn into_anyhow(self) -> Error {
-        Error::new(self).context("RSF operation failed")
+/// The first [`SNIPPET_CHARS`] characters of `line` without its line
+/// break, with control characters such as NUL escaped
+fn snippet(line: &[u8]) -> String {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_end_matches(['\r', '\n']);
+    let mut snippet = String::new();
+    for (count, c) in line.chars().enumerate() {
+        if count == SNIPPET_CHARS {
+            snippet.push('…');
+            break;
+        }
+        if c.is_control() {
+            snippet.extend(c.escape_default());
+        } else {
+            snippet.push(c);
+        }
     }
+    snippet
 }
 
 /// Result type alias for RSF operations
 pub type RsfResult<T> = Result<T, RsfError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use std::io;
+
+    #[test]
+    fn test_io_error_messages() {
+        let err = RsfError::io_error("data.csv", io::Error::other("denied"));
+        assert_eq!(err.to_string(), "Failed to open file 'data.csv'");
+        assert_eq!(err.source().unwrap().to_string(), "denied");
+
+        let err = RsfError::from(io::Error::other("broken pipe"));
+        assert_eq!(err.to_string(), "I/O error");
+    }
+
+    #[test]
+    fn test_csv_error_messages() {
+        assert_eq!(
+            RsfError::csv_error("bad quote").to_string(),
+            "CSV error: bad quote"
+        );
+
+        let err: RsfError = csv::Reader::from_reader("a,b\n1,2\n3\n".as_bytes())
+            .records()
+            .find_map(Result::err)
+            .unwrap()
+            .into();
+        assert_eq!(
+            err.to_string(),
+            "CSV error at line 3 (record 2, byte 8): \
+             found record with 1 fields, but the previous record has 2 fields"
+        );
+        assert!(matches!(
+            err,
+            RsfError::CsvError {
+                position: Some(CsvPosition { line: 3, .. }),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_csv_error_path_and_snippet() {
+        let position = CsvPosition {
+            record: 2,
+            line: 3,
+            byte: 10,
+            column: Some(3),
+        };
+        let err = RsfError::unterminated_quote(position, b"3,\"x\r\n").with_csv_path("data.csv");
+        assert_eq!(
+            err.to_string(),
+            "data.csv:3:3: unterminated quoted field starting at byte 10\n  | 3,\"x"
+        );
+
+        let err = RsfError::csv_error("bad quote").with_csv_path("data.csv");
+        assert_eq!(err.to_string(), "CSV error in 'data.csv': bad quote");
+
+        // Long lines are cut and control characters escaped
+        let line = format!("\0{}", "x".repeat(100));
+        let err = RsfError::csv_error("bad").with_csv_snippet(line.as_bytes());
+        let snippet = format!("\\u{{0}}{}…", "x".repeat(SNIPPET_CHARS - 1));
+        assert_eq!(err.to_string(), format!("CSV error: bad\n  | {}", snippet));
+    }
+
+    #[test]
+    fn test_encoding_error_messages() {
+        assert_eq!(
+            RsfError::encoding_error(12, "UTF-8", &[0xE9, 0x20]).to_string(),
+            "Invalid UTF-8 at byte 12: found 0xE9 0x20"
+        );
+    }
+
+    #[test]
+    fn test_schema_error_messages() {
+        let err = RsfError::schema_error("missing field `rank`");
+        assert_eq!(err.to_string(), "Schema error: missing field `rank`");
+        assert_eq!(
+            err.with_schema_path("out.schema.yaml").to_string(),
+            "Schema error in 'out.schema.yaml': missing field `rank`"
+        );
+    }
+
+    #[test]
+    fn test_validation_error_messages() {
+        assert_eq!(
+            RsfError::column_order_error(1, "Id".into(), "Name".into()).to_string(),
+            "Column order mismatch at position 1: expected 'Id', found 'Name'"
+        );
+        assert_eq!(
+            RsfError::missing_column("Id").to_string(),
+            "Required column 'Id' is missing from the input"
+        );
+        assert_eq!(
+            RsfError::cardinality_error("Id".into(), 4, 6).to_string(),
+            "Column 'Id' has invalid cardinality: expected 4, found 6 (ratio 1.50)"
+        );
+        assert_eq!(
+            RsfError::cardinality_error("Id".into(), 0, 6).to_string(),
+            "Column 'Id' has invalid cardinality: expected 0, found 6"
+        );
+        assert_eq!(
+            RsfError::uniqueness_violation("Id".into(), 10, 9).to_string(),
+            "Column 'Id' is marked unique but has 9 distinct values for 10 rows"
+        );
+        assert_eq!(
+            RsfError::type_error("Amount", DataType::Int, 2, "12.5").to_string(),
+            "Column 'Amount' is declared int but row 2 holds '12.5'"
+        );
+        assert_eq!(
+            RsfError::sort_error().to_string(),
+            "Rows are not in canonical sorted order"
+        );
+        assert_eq!(
+            RsfError::sort_error_at(3).to_string(),
+            "Rows are not in canonical sorted order: row 3 belongs before the row above it"
+        );
+    }
+
+    #[test]
+    fn test_serialized_form() {
+        let err = RsfError::schema_error("bad rank").with_schema_path("s.yaml");
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"kind":"schema_error","details":{"message":"bad rank","path":"s.yaml"}}"#
+        );
+
+        let err = RsfError::from(io::Error::other("denied"));
+        assert_eq!(
+            serde_json::to_string(&err).unwrap(),
+            r#"{"kind":"io_error","details":{"path":null,"cause":"denied"}}"#
+        );
+    }
+
+    #[test]
+    fn test_other_error_messages() {
+        assert_eq!(
+            RsfError::invalid_options("band minimum 2 exceeds maximum 1").to_string(),
+            "Invalid ranking options: band minimum 2 exceeds maximum 1"
+        );
+        assert_eq!(RsfError::unknown("oops").to_string(), "Unknown error: oops");
+    }
+}
@@ -0,0 +1,292 @@
+//! Size estimates for a rank, from one pass that keeps no rows
+//!
+//! [`SizeEstimator`] counts every record but only sketches the distinct
+//! values of the first ones, so it runs in bounded memory however large the
+//! input is.
+
+use serde::Serialize;
+
+/// Records whose values are sketched unless [`SizeEstimator::with_sample_rows`]
+/// says otherwise
+pub const DEFAULT_SAMPLE_ROWS: usize = 100_000;
+
+/// Bits of the hash that pick a register; 2^12 registers give about 1.6%
+/// standard error
+const PRECISION: u32 = 12;
+
+/// Bytes of a `String` or `Vec` without its heap buffer
+const HANDLE_BYTES: u64 = 24;
+
+/// Approximate distinct count in fixed memory (HyperLogLog)
+#[derive(Debug, Clone)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HyperLogLog {
+    pub fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << PRECISION],
+        }
+    }
+
+    pub fn insert(&mut self, value: &str) {
+        let hash = hash(value);
+        let idx = (hash >> (64 - PRECISION)) as usize;
+        // Leading zeros of the remaining bits, plus one; a sentinel bit
+        // bounds the run when they are all zero
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[idx] = self.registers[idx].max(rank);
+    }
+
+    /// Estimated number of distinct values inserted
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-i32::from(rank)))
+            .sum();
+        let raw = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate while registers are unused
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            raw.round() as u64
+        }
+    }
+}
+
+/// 64-bit FNV-1a with a final mix, so the high bits that pick a register
+/// depend on every byte
+fn hash(value: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in value.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// Estimated size of one column
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct ColumnEstimate {
+    pub name: String,
+    /// Approximate distinct values in the whole input
+    pub distinct: u64,
+    /// `distinct` was scaled up from a sample in which nearly every value
+    /// was distinct, assuming the column stays a key
+    pub extrapolated: bool,
+    /// Mean bytes per value
+    pub mean_bytes: f64,
+}
+
+/// How to rank an input, from its [`SizeEstimate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum Recommendation {
+    /// Read every row, as `rsf rank` does
+    InMemory,
+    /// Only count values as the records pass, as [`crate::RsfRanker`] does
+    Streaming,
+}
+
+/// What ranking an input would take, from [`SizeEstimator::finish`]
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct SizeEstimate {
+    /// Data rows in the input, counted exactly
+    pub rows: usize,
+    /// Bytes of cell text in the input, counted exactly
+    pub cell_bytes: u64,
+    /// Leading rows whose values were sketched
+    pub sampled_rows: usize,
+    pub columns: Vec<ColumnEstimate>,
+    /// Predicted peak memory of ranking with every row held
+    pub in_memory_bytes: u64,
+    /// Predicted peak memory of only counting the values
+    pub streaming_bytes: u64,
+    /// Memory free for the run, when known
+    pub available_bytes: Option<u64>,
+    pub recommendation: Recommendation,
+}
+
+/// Estimates the size of a rank one record at a time
+///
+/// ```
+/// use rsf::estimate::{Recommendation, SizeEstimator};
+///
+/// let mut estimator = SizeEstimator::new(&["Id", "Kind"]);
+/// for id in 0..1000 {
+///     estimator.push_record(&[id.to_string(), (id % 2).to_string()]);
+/// }
+///
+/// let estimate = estimator.finish(None);
+/// assert_eq!(estimate.rows, 1000);
+/// assert_eq!(estimate.columns[1].distinct, 2);
+/// assert_eq!(estimate.recommendation, Recommendation::InMemory);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SizeEstimator {
+    names: Vec<String>,
+    sketches: Vec<HyperLogLog>,
+    sampled_bytes: Vec<u64>,
+    sample_rows: usize,
+    rows: usize,
+    cell_bytes: u64,
+}
+
+impl SizeEstimator {
+    pub fn new(headers: &[impl AsRef<str>]) -> Self {
+        Self {
+            names: headers.iter().map(|h| h.as_ref().to_string()).collect(),
+            sketches: vec![HyperLogLog::new(); headers.len()],
+            sampled_bytes: vec![0; headers.len()],
+            sample_rows: DEFAULT_SAMPLE_ROWS,
+            rows: 0,
+            cell_bytes: 0,
+        }
+    }
+
+    /// Sketch the values of the first `rows` records instead of
+    /// [`DEFAULT_SAMPLE_ROWS`]
+    pub fn with_sample_rows(mut self, rows: usize) -> Self {
+        self.sample_rows = rows;
+        self
+    }
+
+    /// Count one record, sketching its values while still in the sample;
+    /// cells beyond the last header are ignored
+    pub fn push_record(&mut self, record: &[impl AsRef<str>]) {
+        let sampled = self.rows < self.sample_rows;
+        for (idx, cell) in record.iter().take(self.names.len()).enumerate() {
+            let cell = cell.as_ref();
+            self.cell_bytes += cell.len() as u64;
+            if sampled {
+                self.sketches[idx].insert(cell);
+                self.sampled_bytes[idx] += cell.len() as u64;
+            }
+        }
+        self.rows += 1;
+    }
+
+    /// Predict peak memory and recommend a mode; in-memory is recommended
+    /// while it needs at most half of `available_bytes`, or always when
+    /// that is unknown
+    pub fn finish(self, available_bytes: Option<u64>) -> SizeEstimate {
+        let rows = self.rows;
+        let sampled_rows = rows.min(self.sample_rows);
+
+        let columns: Vec<ColumnEstimate> = self
+            .names
+            .into_iter()
+            .zip(self.sketches)
+            .zip(self.sampled_bytes)
+            .map(|((name, sketch), bytes)| {
+                let mut distinct = sketch.estimate().min(sampled_rows as u64);
+                let extrapolated = sampled_rows < rows && distinct * 10 >= sampled_rows as u64 * 9;
+                if extrapolated {
+                    distinct = distinct * rows as u64 / sampled_rows as u64;
+                }
+                ColumnEstimate {
+                    name,
+                    distinct,
+                    extrapolated,
+                    mean_bytes: if sampled_rows == 0 {
+                        0.0
+                    } else {
+                        bytes as f64 / sampled_rows as f64
+                    },
+                }
+            })
+            .collect();
+
+        // Each counted value is a map entry: its String, its count and a
+        // control byte, in a table at most 7/8 full
+        let streaming_bytes: u64 = columns
+            .iter()
+            .map(|col| {
+                let entry = HANDLE_BYTES as f64 + 9.0 + col.mean_bytes;
+                (col.distinct as f64 * entry * 8.0 / 7.0) as u64
+            })
+            .sum();
+        // Each row is a Vec of Strings holding the cell text
+        let rows_bytes = rows as u64 * HANDLE_BYTES * (1 + columns.len() as u64) + self.cell_bytes;
+        let in_memory_bytes = rows_bytes + streaming_bytes;
+
+        let recommendation = match available_bytes {
+            Some(available) if in_memory_bytes > available / 2 => Recommendation::Streaming,
+            _ => Recommendation::InMemory,
+        };
+
+        SizeEstimate {
+            rows,
+            cell_bytes: self.cell_bytes,
+            sampled_rows,
+            columns,
+            in_memory_bytes,
+            streaming_bytes,
+            available_bytes,
+            recommendation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyperloglog_is_close() {
+        for n in [0u64, 1, 100, 10_000, 200_000] {
+            let mut sketch = HyperLogLog::new();
+            for value in 0..n {
+                sketch.insert(&value.to_string());
+                sketch.insert(&value.to_string());
+            }
+            let error = sketch.estimate().abs_diff(n) as f64 / n.max(1) as f64;
+            assert!(error < 0.05, "{} estimated as {}", n, sketch.estimate());
+        }
+    }
+
+    #[test]
+    fn test_sample_extrapolates_keys_only() {
+        let mut estimator = SizeEstimator::new(&["Id", "Kind"]).with_sample_rows(1000);
+        for id in 0..10_000 {
+            estimator.push_record(&[format!("{:05}", id), (id % 3).to_string()]);
+        }
+
+        let estimate = estimator.finish(Some(1 << 40));
+        assert_eq!((estimate.rows, estimate.sampled_rows), (10_000, 1000));
+        assert_eq!(estimate.cell_bytes, 10_000 * 6);
+        let id = &estimate.columns[0];
+        assert!(id.extrapolated);
+        assert!(id.distinct.abs_diff(10_000) < 500, "{}", id.distinct);
+        assert_eq!(id.mean_bytes, 5.0);
+        assert!(!estimate.columns[1].extrapolated);
+        assert_eq!(estimate.columns[1].distinct, 3);
+        assert!(estimate.in_memory_bytes > estimate.streaming_bytes);
+        assert_eq!(estimate.recommendation, Recommendation::InMemory);
+
+        let mut estimator = SizeEstimator::new(&["Id"]);
+        estimator.push_record(&["1"]);
+        let estimate = estimator.finish(Some(64));
+        assert_eq!(estimate.recommendation, Recommendation::Streaming);
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::{Context, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::fs;
+use std::path::Path;
+
+use rsf::ranking::{compute_cardinality, rank_columns, ColumnMeta, ColumnStats, RankMetric};
+
+use crate::log::info;
+use crate::{config, ranking_options};
+
+/// Number of most frequent values listed for the selected column
+const TOP_VALUES: usize = 10;
+
+/// How an explore session ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exit {
+    Quit,
+    SaveConfig,
+}
+
+/// State of an explore session: the data, the chosen options and the
+/// ranking they produce
+struct Explorer {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    nulls_distinct: bool,
+    metric: RankMetric,
+    ranked: Vec<ColumnMeta>,
+    /// Per-column statistics in header order
+    stats: Vec<ColumnStats>,
+    table: TableState,
+}
+
+impl Explorer {
+    fn new(
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+        nulls_distinct: bool,
+        metric: RankMetric,
+    ) -> Result<Self> {
+        let mut explorer = Self {
+            headers,
+            rows,
+            nulls_distinct,
+            metric,
+            ranked: Vec::new(),
+            stats: Vec::new(),
+            table: TableState::default().with_selected(Some(0)),
+        };
+        explorer.rerank()?;
+        Ok(explorer)
+    }
+
+    /// Recompute the ranking for the current options
+    fn rerank(&mut self) -> Result<()> {
+        let options = ranking_options(self.nulls_distinct)
+            .metric(self.metric)
+            .build()?;
+        self.ranked = rank_columns(&self.headers, &self.rows, options.clone())?;
+        self.stats = compute_cardinality(&self.headers, &self.rows, options)?;
+        Ok(())
+    }
+
+    fn toggle_metric(&mut self) -> Result<()> {
+        self.metric = match self.metric {
+            RankMetric::Cardinality => RankMetric::Entropy,
+            _ => RankMetric::Cardinality,
+        };
+        self.rerank()
+    }
+
+    fn toggle_nulls(&mut self) -> Result<()> {
+        self.nulls_distinct = !self.nulls_distinct;
+        self.rerank()
+    }
+
+    fn stats_for(&self, name: &str) -> Option<&ColumnStats> {
+        self.stats.iter().find(|stat| stat.name == name)
+    }
+
+    /// Handle one key press, returning how the session ends if it does
+    fn handle_key(&mut self, code: KeyCode) -> Result<Option<Exit>> {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(Some(Exit::Quit)),
+            KeyCode::Char('s') => return Ok(Some(Exit::SaveConfig)),
+            KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+            KeyCode::Home | KeyCode::Char('g') => self.table.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.table.select_last(),
+            KeyCode::Char('m') => self.toggle_metric()?,
+            KeyCode::Char('n') => self.toggle_nulls()?,
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<Exit> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                if let Some(exit) = self.handle_key(key.code)? {
+                    return Ok(exit);
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [ranking, details] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(main);
+
+        let rows = self.ranked.iter().map(|col| {
+            let entropy = self
+                .stats_for(&col.name)
+                .map_or("-".to_string(), |stat| format!("{:.3}", stat.entropy()));
+            Row::new(vec![
+                col.rank.to_string(),
+                col.name.clone(),
+                col.cardinality.to_string(),
+                entropy,
+                if col.is_unique { "✓" } else { "" }.to_string(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(4),
+                Constraint::Min(12),
+                Constraint::Length(12),
+                Constraint::Length(8),
+                Constraint::Length(6),
+            ],
+        )
+        .header(
+            Row::new(vec!["Rank", "Column", "Cardinality", "Entropy", "Unique"])
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            " Ranked by {}, nulls {} ",
+            self.metric,
+            if self.nulls_distinct {
+                "distinct"
+            } else {
+                "as one value"
+            }
+        )));
+        frame.render_stateful_widget(table, ranking, &mut self.table);
+
+        frame.render_widget(
+            Paragraph::new(self.detail_lines())
+                .block(Block::default().borders(Borders::ALL).title(" Column ")),
+            details,
+        );
+
+        frame.render_widget(
+            Paragraph::new("↑/↓ select  m metric  n nulls  s save config and quit  q quit"),
+            footer,
+        );
+    }
+
+    /// Statistics and top values of the selected column
+    fn detail_lines(&self) -> Vec<Line<'_>> {
+        let Some(col) = self.table.selected().and_then(|idx| self.ranked.get(idx)) else {
+            return Vec::new();
+        };
+        let Some(stat) = self.stats_for(&col.name) else {
+            return Vec::new();
+        };
+
+        let mut lines = vec![
+            Line::from(col.name.clone()).style(Style::default().add_modifier(Modifier::BOLD)),
+            Line::from(format!("Rank:        {}", col.rank)),
+            Line::from(format!("Cardinality: {}", col.cardinality)),
+            Line::from(format!("Entropy:     {:.3} bits", stat.entropy())),
+            Line::from(format!(
+                "Unique:      {}",
+                if col.is_unique { "yes" } else { "no" }
+            )),
+            Line::from(""),
+            Line::from("Top values:"),
+        ];
+        for (value, count) in stat.top_values(TOP_VALUES) {
+            let value = if value.is_empty() { "(empty)" } else { value };
+            lines.push(Line::from(format!("{:>10}  {}", count, value)));
+        }
+        lines
+    }
+}
+
+/// Run the explorer on a table until the user quits
+///
+/// Saving writes the chosen `rank-by` and `nulls-distinct` into the `[rank]`
+/// table of `config_path`, keeping any other settings already there.
+pub fn run(
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    nulls_distinct: bool,
+    metric: RankMetric,
+    config_path: &Path,
+) -> Result<()> {
+    let mut explorer = Explorer::new(headers, rows, nulls_distinct, metric)?;
+
+    let mut terminal = ratatui::init();
+    let exit = explorer.event_loop(&mut terminal);
+    ratatui::restore();
+
+    if exit? == Exit::SaveConfig {
+        save_config(config_path, explorer.nulls_distinct, explorer.metric)?;
+        info!("Configuration written to: {}", config_path.display());
+    }
+    Ok(())
+}
+
+fn save_config(path: &Path, nulls_distinct: bool, metric: RankMetric) -> Result<()> {
+    let mut config = if path.exists() {
+        config::load(path)?
+    } else {
+        toml::Table::new()
+    };
+
+    let rank = config
+        .entry("rank")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .with_context(|| format!("'rank' in {:?} is not a table", path))?;
+    rank.insert("rank-by".to_string(), metric.to_string().into());
+    rank.insert("nulls-distinct".to_string(), nulls_distinct.into());
+
+    fs::write(path, toml::to_string(&config)?)
+        .with_context(|| format!("Failed to write file: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_explorer_toggles_rerank_and_render() {
+        // Skewed has more distinct values, Even a flatter distribution
+        let headers = strings(&["Skewed", "Even"]);
+        let rows = (0..10)
+            .map(|i| {
+                let skewed = match i {
+                    8 => "b",
+                    9 => "c",
+                    _ => "a",
+                };
+                let even = if i % 2 == 0 { "x" } else { "y" };
+                strings(&[skewed, even])
+            })
+            .collect();
+
+        let mut explorer = Explorer::new(headers, rows, true, RankMetric::Cardinality).unwrap();
+        assert_eq!(explorer.ranked[0].name, "Skewed");
+
+        assert_eq!(explorer.handle_key(KeyCode::Char('m')).unwrap(), None);
+        assert_eq!(explorer.metric, RankMetric::Entropy);
+        assert_eq!(explorer.ranked[0].name, "Even");
+
+        explorer.handle_key(KeyCode::Down).unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(100, 20)).unwrap();
+        terminal.draw(|frame| explorer.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Ranked by entropy"));
+        assert!(screen.contains("Top values:"));
+
+        assert_eq!(
+            explorer.handle_key(KeyCode::Char('s')).unwrap(),
+            Some(Exit::SaveConfig)
+        );
+    }
+}
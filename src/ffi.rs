@@ -0,0 +1,356 @@
+//! C API for embedding rsf in other languages
+//!
+//! Every function returns one of the `RSF_*` status codes and never unwinds
+//! into the caller: a panic is caught at the boundary and reported as
+//! [`RSF_PANIC`]. After a failure, [`rsf_last_error`] describes it.
+//!
+//! Buffers handed out through [`RsfBuffer`] belong to the caller, who
+//! releases them with [`rsf_buffer_free`]. The header `include/rsf.h` is
+//! generated from this module with `cbindgen --config cbindgen.toml`.
+
+use crate::document::{validate_bytes, RsfDocument};
+use crate::errors::{RsfError, RsfResult};
+use crate::ranking::{RankMetric, RankingOptions};
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+/// Success
+pub const RSF_OK: i32 = 0;
+/// The input could not be read or ranked
+pub const RSF_ERROR: i32 = 1;
+/// Validation ran and found problems
+pub const RSF_INVALID: i32 = 2;
+/// A required pointer was null or an option was out of range
+pub const RSF_BAD_ARGUMENT: i32 = 3;
+/// rsf panicked; the message is available from [`rsf_last_error`]
+pub const RSF_PANIC: i32 = 4;
+
+/// Rank by number of distinct values
+pub const RSF_METRIC_CARDINALITY: u32 = 0;
+/// Rank by Shannon entropy of the value distribution
+pub const RSF_METRIC_ENTROPY: u32 = 1;
+
+/// Ranking options; pass null for the defaults
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RsfOptions {
+    /// Count every empty cell as a distinct value
+    pub nulls_distinct: bool,
+    /// One of the `RSF_METRIC_*` constants
+    pub metric: u32,
+}
+
+/// Bytes owned by rsf until passed to [`rsf_buffer_free`]
+#[repr(C)]
+#[derive(Debug)]
+pub struct RsfBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl RsfBuffer {
+    const EMPTY: Self = Self {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior NULs would truncate the message in C, so drop them
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Error raised for a bad argument, as opposed to bad input
+struct BadArgument(&'static str);
+
+enum Failure {
+    Argument(BadArgument),
+    Rsf(RsfError),
+}
+
+impl From<BadArgument> for Failure {
+    fn from(err: BadArgument) -> Self {
+        Failure::Argument(err)
+    }
+}
+
+impl From<RsfError> for Failure {
+    fn from(err: RsfError) -> Self {
+        Failure::Rsf(err)
+    }
+}
+
+/// Run `f`, turning its error or panic into a status code and last error
+fn guard(f: impl FnOnce() -> Result<i32, Failure>) -> i32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(status)) => status,
+        Ok(Err(Failure::Argument(BadArgument(message)))) => {
+            set_last_error(message.to_string());
+            RSF_BAD_ARGUMENT
+        }
+        Ok(Err(Failure::Rsf(err))) => {
+            set_last_error(err.to_string());
+            RSF_ERROR
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(format!("rsf panicked: {}", message));
+            RSF_PANIC
+        }
+    }
+}
+
+/// # Safety
+///
+/// `data` must be null or point to `len` readable bytes.
+unsafe fn input<'a>(
+    data: *const c_char,
+    len: usize,
+    name: &'static str,
+) -> Result<&'a [u8], BadArgument> {
+    match (data.is_null(), len) {
+        (_, 0) => Ok(&[]),
+        (true, _) => Err(BadArgument(name)),
+        (false, len) => Ok(unsafe { slice::from_raw_parts(data.cast::<u8>(), len) }),
+    }
+}
+
+fn ranking_options(options: Option<&RsfOptions>) -> Result<RankingOptions, Failure> {
+    let Some(options) = options else {
+        return Ok(RankingOptions::default());
+    };
+    let metric = match options.metric {
+        RSF_METRIC_CARDINALITY => RankMetric::Cardinality,
+        RSF_METRIC_ENTROPY => RankMetric::Entropy,
+        _ => return Err(BadArgument("unknown metric").into()),
+    };
+    Ok(RankingOptions::builder()
+        .treat_empty_as_null(!options.nulls_distinct)
+        .metric(metric)
+        .build()?)
+}
+
+fn rank_csv(csv: &[u8], options: RankingOptions) -> RsfResult<(Vec<u8>, Vec<u8>)> {
+    let mut doc = RsfDocument::from_reader(csv)?;
+    doc.rank(options)?;
+    doc.sort();
+
+    let mut ranked = Vec::new();
+    doc.write_to(&mut ranked)?;
+    let schema = serde_yaml::to_string(doc.schema())?;
+    Ok((ranked, schema.into_bytes()))
+}
+
+/// Rank the columns of a CSV document and sort its rows canonically
+///
+/// On success `out` receives the RSF document and, unless it is null,
+/// `schema_out` receives its schema as YAML. On failure both are set empty.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes; `options` and `schema_out`
+/// must be null or valid; `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rsf_rank_csv(
+    data: *const c_char,
+    len: usize,
+    options: *const RsfOptions,
+    out: *mut RsfBuffer,
+    schema_out: *mut RsfBuffer,
+) -> i32 {
+    guard(|| {
+        let out = unsafe { out.as_mut() }.ok_or(BadArgument("out is null"))?;
+        let mut schema_out = unsafe { schema_out.as_mut() };
+        *out = RsfBuffer::EMPTY;
+        if let Some(schema_out) = schema_out.as_deref_mut() {
+            *schema_out = RsfBuffer::EMPTY;
+        }
+
+        let csv = unsafe { input(data, len, "data is null") }?;
+        let options = ranking_options(unsafe { options.as_ref() })?;
+        let (ranked, schema) = rank_csv(csv, options)?;
+
+        *out = RsfBuffer::from_vec(ranked);
+        if let Some(schema_out) = schema_out {
+            *schema_out = RsfBuffer::from_vec(schema);
+        }
+        Ok(RSF_OK)
+    })
+}
+
+/// Validate a CSV document against a YAML schema
+///
+/// Returns [`RSF_OK`] if the document is valid RSF and [`RSF_INVALID`] if a
+/// check failed; unless it is null, `report_out` then receives one line per
+/// failed check.
+///
+/// # Safety
+///
+/// `data` and `schema` must point to `len` and `schema_len` readable bytes;
+/// `report_out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rsf_validate_csv(
+    data: *const c_char,
+    len: usize,
+    schema: *const c_char,
+    schema_len: usize,
+    report_out: *mut RsfBuffer,
+) -> i32 {
+    guard(|| {
+        let report_out = unsafe { report_out.as_mut() };
+        let csv = unsafe { input(data, len, "data is null") }?;
+        let schema = unsafe { input(schema, schema_len, "schema is null") }?;
+
+        let findings = validate_bytes(csv, schema)?;
+        let report: String = findings.iter().map(|f| format!("{}\n", f)).collect();
+        if let Some(report_out) = report_out {
+            *report_out = RsfBuffer::from_vec(report.into_bytes());
+        }
+        Ok(if findings.is_empty() {
+            RSF_OK
+        } else {
+            RSF_INVALID
+        })
+    })
+}
+
+/// Message for the last failure on this thread, or null if there was none
+///
+/// The string stays valid until the next rsf call on the same thread.
+#[no_mangle]
+pub extern "C" fn rsf_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Release a buffer filled by rsf and set it empty; freeing an empty buffer
+/// does nothing
+///
+/// # Safety
+///
+/// `buffer` must be null or hold data allocated by rsf and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn rsf_buffer_free(buffer: *mut RsfBuffer) {
+    let Some(buffer) = (unsafe { buffer.as_mut() }) else {
+        return;
+    };
+    if !buffer.data.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)) });
+    }
+    *buffer = RsfBuffer::EMPTY;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn bytes(buffer: &RsfBuffer) -> &[u8] {
+        unsafe { slice::from_raw_parts(buffer.data, buffer.len) }
+    }
+
+    #[test]
+    fn test_rank_then_validate() {
+        let csv = b"Category,Id\nfood,2\ntravel,1\nfood,3\n";
+        let (mut out, mut schema) = (RsfBuffer::EMPTY, RsfBuffer::EMPTY);
+        let status = unsafe {
+            rsf_rank_csv(
+                csv.as_ptr().cast(),
+                csv.len(),
+                ptr::null(),
+                &mut out,
+                &mut schema,
+            )
+        };
+        assert_eq!(status, RSF_OK);
+        assert_eq!(bytes(&out), b"Id,Category\n1,travel\n2,food\n3,food\n");
+
+        let mut report = RsfBuffer::EMPTY;
+        let status = unsafe {
+            rsf_validate_csv(
+                out.data.cast(),
+                out.len,
+                schema.data.cast(),
+                schema.len,
+                &mut report,
+            )
+        };
+        assert_eq!(status, RSF_OK);
+        assert_eq!(report.len, 0);
+
+        let status = unsafe {
+            rsf_validate_csv(
+                csv.as_ptr().cast(),
+                csv.len(),
+                schema.data.cast(),
+                schema.len,
+                &mut report,
+            )
+        };
+        assert_eq!(status, RSF_INVALID);
+        assert!(!bytes(&report).is_empty());
+
+        for buffer in [&mut out, &mut schema, &mut report] {
+            unsafe { rsf_buffer_free(buffer) };
+            assert!(buffer.data.is_null());
+        }
+    }
+
+    #[test]
+    fn test_errors_are_reported_not_raised() {
+        let mut out = RsfBuffer::EMPTY;
+        let options = RsfOptions {
+            nulls_distinct: false,
+            metric: 7,
+        };
+        let status = unsafe {
+            rsf_rank_csv(
+                b"A\n".as_ptr().cast(),
+                2,
+                &options,
+                &mut out,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(status, RSF_BAD_ARGUMENT);
+        let message = unsafe { CStr::from_ptr(rsf_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "unknown metric");
+
+        let jagged = b"A,B\n1\n";
+        let status = unsafe {
+            rsf_rank_csv(
+                jagged.as_ptr().cast(),
+                jagged.len(),
+                ptr::null(),
+                &mut out,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(status, RSF_ERROR);
+        assert!(out.data.is_null());
+
+        assert_eq!(guard(|| panic!("boom")), RSF_PANIC);
+        let message = unsafe { CStr::from_ptr(rsf_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "rsf panicked: boom");
+    }
+}
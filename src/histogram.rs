@@ -0,0 +1,170 @@
+use crate::numeric::parse_number;
+use crate::ranking::ColumnStats;
+
+/// Value distribution of a single column
+#[derive(Debug, Clone, PartialEq)]
+pub enum Histogram {
+    /// Counts in equal-width bins, for a mostly numeric column
+    Numeric(NumericHistogram),
+    /// The most frequent values, for any other column
+    Categorical(Categories),
+}
+
+/// Equal-width bins between the smallest and largest number
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct NumericHistogram {
+    pub bins: Vec<Bin>,
+    /// Non-empty cells that are not finite numbers
+    pub other: usize,
+    /// Empty or whitespace-only cells
+    pub empty: usize,
+}
+
+/// Cells from `lower` up to `upper`, inclusive only for the last bin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+}
+
+/// Most frequent values of a column
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Categories {
+    /// Values with their counts, most frequent first, ties by value
+    pub top: Vec<(String, usize)>,
+    /// Cells holding any value not in `top`
+    pub rest: usize,
+    /// Number of distinct values
+    pub distinct: usize,
+}
+
+/// Bin the values counted in `stats` if at least half of its non-empty cells
+/// are numbers, or list its `top` most frequent values otherwise
+///
+/// `bins` is at least 1; all values fall in one bin when they are equal.
+pub fn histogram(stats: &ColumnStats, bins: usize, top: usize, decimal_comma: bool) -> Histogram {
+    let bins = bins.max(1);
+    let mut numbers: Vec<(f64, usize)> = Vec::new();
+    let (mut other, mut empty) = (0, 0);
+    for (value, &count) in &stats.value_counts {
+        if value.trim().is_empty() {
+            empty += count;
+            continue;
+        }
+        match parse_number(value, decimal_comma).filter(|n| n.is_finite()) {
+            Some(number) => numbers.push((number, count)),
+            None => other += count,
+        }
+    }
+
+    let numeric: usize = numbers.iter().map(|(_, count)| count).sum();
+    if numeric == 0 || numeric < other {
+        let total: usize = stats.value_counts.values().sum();
+        let top: Vec<(String, usize)> = stats
+            .top_values(top)
+            .into_iter()
+            .map(|(value, count)| (value.to_string(), count))
+            .collect();
+        let shown: usize = top.iter().map(|(_, count)| count).sum();
+        return Histogram::Categorical(Categories {
+            top,
+            rest: total - shown,
+            distinct: stats.cardinality(),
+        });
+    }
+
+    let min = numbers
+        .iter()
+        .map(|(n, _)| *n)
+        .fold(f64::INFINITY, f64::min);
+    let max = numbers
+        .iter()
+        .map(|(n, _)| *n)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let bins = if min == max { 1 } else { bins };
+    let width = (max - min) / bins as f64;
+
+    let mut counts = vec![0; bins];
+    for (number, count) in numbers {
+        // The maximum belongs to the last bin, which is closed
+        let idx = (((number - min) / width) as usize).min(bins - 1);
+        counts[idx] += count;
+    }
+
+    Histogram::Numeric(NumericHistogram {
+        bins: counts
+            .into_iter()
+            .enumerate()
+            .map(|(idx, count)| Bin {
+                lower: min + width * idx as f64,
+                upper: if idx + 1 == bins {
+                    max
+                } else {
+                    min + width * (idx + 1) as f64
+                },
+                count,
+            })
+            .collect(),
+        other,
+        empty,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(values: &[&str]) -> ColumnStats {
+        let mut stats = ColumnStats::new("col");
+        for value in values {
+            stats.add_value(value);
+        }
+        stats
+    }
+
+    #[test]
+    fn test_numeric_bins() {
+        let values = ["1", "2", "2", "3", "5", "n/a", "", "4.5"];
+        let Histogram::Numeric(hist) = histogram(&stats(&values), 2, 10, false) else {
+            panic!("expected a numeric histogram");
+        };
+
+        let bins: Vec<(f64, f64, usize)> = hist
+            .bins
+            .iter()
+            .map(|bin| (bin.lower, bin.upper, bin.count))
+            .collect();
+        assert_eq!(bins, [(1.0, 3.0, 3), (3.0, 5.0, 3)]);
+        assert_eq!((hist.other, hist.empty), (1, 1));
+
+        let Histogram::Numeric(hist) = histogram(&stats(&["7", "7"]), 4, 10, false) else {
+            panic!("expected a numeric histogram");
+        };
+        assert_eq!(
+            hist.bins,
+            [Bin {
+                lower: 7.0,
+                upper: 7.0,
+                count: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_mostly_text_is_categorical() {
+        let values = ["food", "travel", "food", "12", "food", "NaN"];
+        let Histogram::Categorical(categories) = histogram(&stats(&values), 5, 2, false) else {
+            panic!("expected categories");
+        };
+
+        assert_eq!(
+            categories.top,
+            [("food".to_string(), 3), ("12".to_string(), 1)]
+        );
+        assert_eq!(categories.rest, 2);
+        assert_eq!(categories.distinct, 4);
+    }
+}
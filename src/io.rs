@@ -0,0 +1,1571 @@
+use crate::errors::{CsvPosition, RsfError, RsfResult};
+use crate::progress::{NoProgress, Phase, ProgressSink, RowCounter};
+use csv::{ReaderBuilder, Writer, WriterBuilder};
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(feature = "std-fs")]
+use {
+    std::fs::File,
+    std::io::BufWriter,
+    std::path::{Path, PathBuf},
+    std::sync::{mpsc, Mutex},
+    std::thread,
+};
+
+/// Files smaller than this are parsed on one thread whatever `threads` says
+#[cfg(feature = "std-fs")]
+pub const PARALLEL_MIN_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Size of the blocks handed to parse workers
+#[cfg(feature = "std-fs")]
+const CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Line opening and closing a schema embedded at the top of a CSV, whose
+/// YAML lines are each prefixed with `# `
+pub const FRONT_MATTER_DELIMITER: &str = "# ---";
+
+/// Options controlling how CSV input is read
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct ReadOptions {
+    /// Byte separating fields, `,` by default
+    pub delimiter: u8,
+    /// Remove NUL bytes from fields instead of only reporting them
+    pub strip_nul: bool,
+    /// Parse files of at least [`PARALLEL_MIN_BYTES`] on this many threads
+    pub threads: usize,
+    /// Accept rows with fewer or more fields than the header
+    pub flexible: bool,
+    /// Skip lines starting with this byte, including before the header
+    pub comment: Option<u8>,
+    /// Parse files from a memory map instead of through a buffer
+    pub mmap: bool,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            strip_nul: false,
+            threads: 0,
+            flexible: false,
+            comment: None,
+            mmap: false,
+        }
+    }
+}
+
+impl ReadOptions {
+    /// Separate fields with `delimiter` instead of `,`, e.g. one picked by
+    /// [`sniff_delimiter`]
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Remove NUL bytes from fields
+    pub fn strip_nul(mut self, strip_nul: bool) -> Self {
+        self.strip_nul = strip_nul;
+        self
+    }
+
+    /// Number of parse threads for large files (0 or 1 reads sequentially)
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Accept jagged rows instead of failing on them
+    pub fn flexible(mut self, flexible: bool) -> Self {
+        self.flexible = flexible;
+        self
+    }
+
+    /// Skip lines that start with `comment`, such as `#` metadata lines
+    ///
+    /// Only the first byte of a record counts, so the byte may still appear
+    /// in fields, and a quoted first field is never a comment.
+    pub fn comment(mut self, comment: Option<u8>) -> Self {
+        self.comment = comment;
+        self
+    }
+
+    /// Parse local files from a read-only memory map, which saves copying
+    /// them through a buffer and allocating a record per row
+    ///
+    /// Memory maps are Unix only: on other platforms, Windows included, every
+    /// file is read normally, as are stdin and files that cannot be mapped,
+    /// such as pipes. The file must not be truncated while it is read.
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+}
+
+/// Parsed CSV input
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct CsvInput {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// `(row, column)` indices of fields that contained NUL bytes
+    pub nul_fields: Vec<(usize, usize)>,
+    /// YAML of the front-matter block the input started with, if any
+    pub front_matter: Option<String>,
+}
+
+/// Read a CSV file
+#[cfg(feature = "std-fs")]
+pub fn read_csv_file(path: impl AsRef<Path>, options: ReadOptions) -> RsfResult<CsvInput> {
+    read_csv_file_with_progress(path, options, &NoProgress)
+}
+
+/// Like [`read_csv_file`], reporting to `progress`
+///
+/// Files read on several threads report only their total. Parse errors
+/// name the file.
+#[cfg(feature = "std-fs")]
+pub fn read_csv_file_with_progress(
+    path: impl AsRef<Path>,
+    options: ReadOptions,
+    progress: &dyn ProgressSink,
+) -> RsfResult<CsvInput> {
+    let path = path.as_ref();
+    read_file(path, options, progress).map_err(|err| err.with_csv_path(path))
+}
+
+#[cfg(feature = "std-fs")]
+fn read_file(
+    path: &Path,
+    options: ReadOptions,
+    progress: &dyn ProgressSink,
+) -> RsfResult<CsvInput> {
+    let file = File::open(path).map_err(|e| RsfError::io_error(path, e))?;
+
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    #[cfg(unix)]
+    if let Some(map) = options
+        .mmap
+        .then(|| crate::mmap::Mmap::map(&file))
+        .flatten()
+    {
+        if options.threads > 1 && size >= PARALLEL_MIN_BYTES {
+            let counter = RowCounter::start(progress, Phase::Read, None);
+            let input = read_csv_parallel(&map[..], options, CHUNK_BYTES)?;
+            counter.finish_with(input.rows.len());
+            return Ok(input);
+        }
+        return read_csv_bytes(&map, options, progress);
+    }
+
+    if options.threads > 1 && size >= PARALLEL_MIN_BYTES {
+        let counter = RowCounter::start(progress, Phase::Read, None);
+        let input = read_csv_parallel(file, options, CHUNK_BYTES)?;
+        counter.finish_with(input.rows.len());
+        return Ok(input);
+    }
+
+    read_csv_sized(file, options, progress, Some(size))
+}
+
+/// Read CSV from any reader; the first record is the header
+pub fn read_csv_reader<R: Read>(reader: R, options: ReadOptions) -> RsfResult<CsvInput> {
+    read_csv_reader_with_progress(reader, options, &NoProgress)
+}
+
+/// Like [`read_csv_reader`], reporting to `progress`
+pub fn read_csv_reader_with_progress<R: Read>(
+    reader: R,
+    options: ReadOptions,
+    progress: &dyn ProgressSink,
+) -> RsfResult<CsvInput> {
+    read_csv_sized(reader, options, progress, None)
+}
+
+/// Like [`read_csv_reader_with_progress`], also reporting bytes read out of
+/// `size` when the input's length is known
+fn read_csv_sized<R: Read>(
+    reader: R,
+    options: ReadOptions,
+    progress: &dyn ProgressSink,
+    size: Option<u64>,
+) -> RsfResult<CsvInput> {
+    let mut counter = RowCounter::start(progress, Phase::Read, None).with_bytes(size);
+    let mut reader = BufReader::new(reader);
+    let (front_matter, replay) = take_front_matter(&mut reader)?;
+    let mut csv_reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(options.flexible)
+        .comment(options.comment)
+        .from_reader(QuoteScan::new(replay.as_slice().chain(reader), options));
+
+    let headers: Vec<String> = csv_reader
+        .headers()?
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    let rows = read_rows(&mut csv_reader, &mut counter)?;
+    counter.finish();
+
+    Ok(finish_input(headers, rows, front_matter, options))
+}
+
+/// Like [`read_csv_reader_with_progress`] over bytes already in memory
+#[cfg(all(feature = "std-fs", unix))]
+fn read_csv_bytes(
+    bytes: &[u8],
+    options: ReadOptions,
+    progress: &dyn ProgressSink,
+) -> RsfResult<CsvInput> {
+    let mut counter =
+        RowCounter::start(progress, Phase::Read, None).with_bytes(Some(bytes.len() as u64));
+    let mut rest = bytes;
+    let (front_matter, _) = take_front_matter(&mut rest)?;
+    let data = if front_matter.is_some() { rest } else { bytes };
+    let mut csv_reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(options.flexible)
+        .comment(options.comment)
+        .from_reader(QuoteScan::new(data, options));
+
+    let headers: Vec<String> = csv_reader
+        .headers()?
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+    let rows = read_rows(&mut csv_reader, &mut counter)?;
+    counter.finish();
+
+    Ok(finish_input(headers, rows, front_matter, options))
+}
+
+/// Read the records after the header, reusing one record buffer and
+/// converting each field to UTF-8 only as it is stored
+fn read_rows<R: Read>(
+    csv_reader: &mut csv::Reader<QuoteScan<R>>,
+    counter: &mut RowCounter,
+) -> RsfResult<Vec<Vec<String>>> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut record = csv::ByteRecord::new();
+    loop {
+        match csv_reader.read_byte_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => return Err(csv_reader.get_ref().record_error(err.into(), &record)),
+        }
+        let row = record_to_row(&record)
+            .map_err(|err| csv_reader.get_ref().record_error(err, &record))?;
+        rows.push(row);
+        counter.tick_at(csv_reader.position().byte());
+    }
+    csv_reader.get_ref().check_closed()?;
+    Ok(rows)
+}
+
+/// Follows the parser's quoting over the bytes passing through, to catch a
+/// quoted field that is never closed; the parser takes it as a field
+/// running to the end of the input
+///
+/// As in the parser, a quote opens a field only at its start and `""`
+/// inside one is an escaped quote. Positions count from where the parser
+/// starts, so they match the ones it reports.
+#[derive(Debug)]
+struct QuoteScan<R> {
+    inner: R,
+    delimiter: u8,
+    comment: Option<u8>,
+    state: ScanState,
+    /// The record being scanned has a field
+    in_record: bool,
+    record: u64,
+    line: u64,
+    byte: u64,
+    line_start: u64,
+    /// Leading bytes of the current line, for a snippet
+    line_bytes: Vec<u8>,
+    /// Where the open quoted field starts, and its line once complete
+    open: Option<(CsvPosition, Option<Vec<u8>>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScanState {
+    StartRecord,
+    StartField,
+    Unquoted,
+    Quoted,
+    /// A quote inside a quoted field: the end, or the first of `""`
+    QuoteInQuoted,
+    Comment,
+}
+
+/// Bytes of a line kept for a snippet, enough for
+/// [`SNIPPET_CHARS`](crate::errors::SNIPPET_CHARS) characters of UTF-8
+const SNIPPET_BYTES: usize = 4 * crate::errors::SNIPPET_CHARS;
+
+impl<R: Read> QuoteScan<R> {
+    fn new(inner: R, options: ReadOptions) -> Self {
+        Self {
+            inner,
+            delimiter: options.delimiter,
+            comment: options.comment,
+            state: ScanState::StartRecord,
+            in_record: false,
+            record: 0,
+            line: 1,
+            byte: 0,
+            line_start: 0,
+            line_bytes: Vec::new(),
+            open: None,
+        }
+    }
+
+    fn scan(&mut self, byte: u8) {
+        use ScanState::*;
+        self.state = match self.state {
+            Quoted if byte == b'"' => QuoteInQuoted,
+            Quoted => Quoted,
+            Comment if byte == b'\n' => StartRecord,
+            Comment => Comment,
+            StartRecord if Some(byte) == self.comment => Comment,
+            StartRecord | StartField if byte == b'"' => {
+                self.in_record = true;
+                let position = CsvPosition {
+                    record: self.record,
+                    line: self.line,
+                    byte: self.byte,
+                    column: Some(self.byte - self.line_start + 1),
+                };
+                self.open = Some((position, None));
+                Quoted
+            }
+            QuoteInQuoted if byte == b'"' => Quoted,
+            // Anything else ends a field that was quoted
+            _ if byte == b'\n' || byte == b'\r' => {
+                self.record += u64::from(self.in_record);
+                self.in_record = false;
+                self.open = None;
+                StartRecord
+            }
+            _ if byte == self.delimiter => {
+                self.in_record = true;
+                self.open = None;
+                StartField
+            }
+            _ => {
+                self.in_record = true;
+                self.open = None;
+                Unquoted
+            }
+        };
+
+        self.byte += 1;
+        if byte == b'\n' {
+            if let Some((_, line @ None)) = &mut self.open {
+                *line = Some(std::mem::take(&mut self.line_bytes));
+            }
+            self.line += 1;
+            self.line_start = self.byte;
+            self.line_bytes.clear();
+        } else if self.line_bytes.len() < SNIPPET_BYTES {
+            self.line_bytes.push(byte);
+        }
+    }
+
+    /// Pass over `bytes`, which hold no line break and leave the state as it is
+    fn skip(&mut self, bytes: &[u8]) {
+        self.byte += bytes.len() as u64;
+        let room = SNIPPET_BYTES.saturating_sub(self.line_bytes.len());
+        self.line_bytes
+            .extend_from_slice(&bytes[..room.min(bytes.len())]);
+    }
+
+    /// Pass over `bytes` outside any quoted field, which hold no quote and
+    /// so open none; only without comments, which could start on any line
+    fn skip_unquoted(&mut self, bytes: &[u8]) {
+        let Some(&last) = bytes.last() else {
+            return;
+        };
+        let mut field_from = 0;
+        let mut line_from = None;
+        for end in memchr::memchr2_iter(b'\n', b'\r', bytes) {
+            self.in_record |= end > field_from;
+            self.record += u64::from(self.in_record);
+            self.in_record = false;
+            if bytes[end] == b'\n' {
+                self.line += 1;
+                line_from = Some(end + 1);
+            }
+            field_from = end + 1;
+        }
+        self.in_record |= bytes.len() > field_from;
+        let tail = match line_from {
+            Some(from) => {
+                self.line_start = self.byte + from as u64;
+                self.line_bytes.clear();
+                &bytes[from..]
+            }
+            None => bytes,
+        };
+        let room = SNIPPET_BYTES.saturating_sub(self.line_bytes.len());
+        self.line_bytes
+            .extend_from_slice(&tail[..room.min(tail.len())]);
+        self.byte += bytes.len() as u64;
+        self.state = match last {
+            b'\n' | b'\r' => ScanState::StartRecord,
+            byte if byte == self.delimiter => ScanState::StartField,
+            _ => ScanState::Unquoted,
+        };
+    }
+
+    /// The error for a quoted field left open at the end of the input
+    fn unterminated(&self) -> Option<RsfError> {
+        let (position, line) = self.open.as_ref()?;
+        Some(RsfError::unterminated_quote(
+            *position,
+            line.as_deref().unwrap_or(&self.line_bytes),
+        ))
+    }
+
+    /// Fail if the input ended inside a quoted field
+    fn check_closed(&self) -> RsfResult<()> {
+        match self.state {
+            ScanState::Quoted => Err(self.unterminated().expect("quoted field is open")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Describe `err`, found reading `record`: a record that swallowed the
+    /// rest of the input in an unclosed quote is reported as that, and
+    /// other errors quote the record
+    fn record_error(&self, err: RsfError, record: &csv::ByteRecord) -> RsfError {
+        let swallowed = match (&err, &self.open) {
+            (
+                RsfError::CsvError {
+                    position: Some(at), ..
+                },
+                Some((open, _)),
+            ) => self.state == ScanState::Quoted && at.record == open.record,
+            _ => false,
+        };
+        if swallowed {
+            return self.unterminated().unwrap_or(err);
+        }
+
+        let mut line = Vec::new();
+        for (idx, field) in record.iter().enumerate() {
+            if idx > 0 {
+                line.push(self.delimiter);
+            }
+            line.extend_from_slice(field);
+            if line.len() >= SNIPPET_BYTES {
+                break;
+            }
+        }
+        err.with_csv_snippet(&line)
+    }
+}
+
+impl<R: Read> Read for QuoteScan<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let mut rest = &buf[..read];
+        while let Some(&byte) = rest.first() {
+            if self.comment.is_none()
+                && matches!(
+                    self.state,
+                    ScanState::StartRecord | ScanState::StartField | ScanState::Unquoted
+                )
+            {
+                let run = memchr::memchr(b'"', rest).unwrap_or(rest.len());
+                if run > 0 {
+                    self.skip_unquoted(&rest[..run]);
+                    rest = &rest[run..];
+                    continue;
+                }
+            }
+            // Inside a field or comment most bytes change nothing, so runs of
+            // them are skipped whole
+            let run = match self.state {
+                ScanState::Quoted => memchr::memchr2(b'"', b'\n', rest),
+                ScanState::Unquoted => memchr::memchr3(self.delimiter, b'\n', b'\r', rest),
+                ScanState::Comment => memchr::memchr(b'\n', rest),
+                _ => Some(0),
+            };
+            let run = run.unwrap_or(rest.len());
+            if run > 0 {
+                self.skip(&rest[..run]);
+                rest = &rest[run..];
+            } else {
+                self.scan(byte);
+                rest = &rest[1..];
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// The fields of a record as strings, failing on the first that is not UTF-8
+fn record_to_row(record: &csv::ByteRecord) -> RsfResult<Vec<String>> {
+    let mut row = Vec::with_capacity(record.len());
+    for field in record {
+        match std::str::from_utf8(field) {
+            Ok(value) => row.push(value.to_string()),
+            // Let the parser describe the field, as it would reading strings
+            Err(_) => {
+                let position = record.position().map(Into::into);
+                let err = csv::StringRecord::from_byte_record(record.clone()).unwrap_err();
+                return Err(RsfError::CsvError {
+                    message: err.utf8_error().to_string(),
+                    position,
+                    path: None,
+                    snippet: None,
+                });
+            }
+        }
+    }
+    Ok(row)
+}
+
+/// A reader with the bytes sniffed for front matter put back in front
+type Peeked<R> = std::io::Chain<std::io::Cursor<Vec<u8>>, BufReader<R>>;
+
+/// CSV records read one at a time, for passes that need not hold every row
+///
+/// Created by [`csv_records`]. Front matter is skipped and NUL bytes are
+/// left in place.
+#[derive(Debug)]
+pub struct CsvRecords<R: Read> {
+    headers: Vec<String>,
+    reader: csv::Reader<QuoteScan<Peeked<R>>>,
+    record: csv::ByteRecord,
+    done: bool,
+}
+
+impl<R: Read> CsvRecords<R> {
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+}
+
+impl<R: Read> Iterator for CsvRecords<R> {
+    type Item = RsfResult<Vec<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let read = self.reader.read_byte_record(&mut self.record);
+        let scan = self.reader.get_ref();
+        let result = match read {
+            Ok(true) => {
+                let row = record_to_row(&self.record);
+                return Some(row.map_err(|err| scan.record_error(err, &self.record)));
+            }
+            Ok(false) => scan.check_closed().err().map(Err),
+            Err(err) => Some(Err(scan.record_error(err.into(), &self.record))),
+        };
+        // The input is used up or broken either way
+        self.done = true;
+        result
+    }
+}
+
+/// Read the header of CSV from any reader, leaving the records to be
+/// iterated
+pub fn csv_records<R: Read>(reader: R, options: ReadOptions) -> RsfResult<CsvRecords<R>> {
+    let mut reader = BufReader::new(reader);
+    let (_, replay) = take_front_matter(&mut reader)?;
+    let mut reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(options.flexible)
+        .comment(options.comment)
+        .from_reader(QuoteScan::new(
+            std::io::Cursor::new(replay).chain(reader),
+            options,
+        ));
+
+    let headers = reader.headers()?.iter().map(|s| s.to_string()).collect();
+    Ok(CsvRecords {
+        headers,
+        reader,
+        record: csv::ByteRecord::new(),
+        done: false,
+    })
+}
+
+/// Delimiters [`sniff_delimiter`] tries, in the order ties are broken
+pub const DELIMITER_CANDIDATES: [u8; 4] = [b',', b'\t', b';', b'|'];
+
+/// Bytes from the start of an input that are enough to sniff its delimiter
+pub const SNIFF_BYTES: usize = 64 * 1024;
+
+/// How one candidate delimiter splits a sample
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct DelimiterCandidate {
+    pub delimiter: u8,
+    /// Mean fields per record
+    pub mean_fields: f64,
+    /// Variance of the fields per record; 0 when every record splits alike
+    pub variance: f64,
+}
+
+/// What [`sniff_delimiter`] tried and picked
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct DelimiterReport {
+    /// Complete records in the sample, the header included
+    pub records: usize,
+    /// Every candidate, in [`DELIMITER_CANDIDATES`] order
+    pub candidates: Vec<DelimiterCandidate>,
+    pub chosen: u8,
+}
+
+/// Pick the delimiter of the CSV starting with `sample`
+///
+/// Each of [`DELIMITER_CANDIDATES`] parses the sample's complete lines, and
+/// the one splitting records into the most consistent number of fields
+/// wins, then the one giving the most fields. Candidates that never split a
+/// record only win if none does, which leaves `,`. Front matter, lines
+/// starting with `options.comment` and any text after the last newline,
+/// which may be a line cut short, are skipped.
+///
+/// ```
+/// use rsf::io::{sniff_delimiter, ReadOptions};
+///
+/// let report = sniff_delimiter(b"Id;Name\n1;Ann\n2;Bo, Jr\n", ReadOptions::default());
+/// assert_eq!(report.chosen, b';');
+/// ```
+pub fn sniff_delimiter(sample: &[u8], options: ReadOptions) -> DelimiterReport {
+    let mut rest = sample;
+    let data = match take_front_matter(&mut rest) {
+        Ok((Some(_), _)) => rest,
+        _ => sample,
+    };
+    // A line cut off by the end of the sample would skew its field count
+    let data = match data.iter().rposition(|&byte| byte == b'\n') {
+        Some(end) => &data[..=end],
+        None => data,
+    };
+
+    let mut records = 0;
+    let candidates: Vec<DelimiterCandidate> = DELIMITER_CANDIDATES
+        .iter()
+        .map(|&delimiter| {
+            let counts: Vec<f64> = ReaderBuilder::new()
+                .has_headers(false)
+                .delimiter(delimiter)
+                .flexible(true)
+                .comment(options.comment)
+                .from_reader(data)
+                .byte_records()
+                .map_while(Result::ok)
+                .map(|record| record.len() as f64)
+                .collect();
+            records = records.max(counts.len());
+            let mean = counts.iter().sum::<f64>() / counts.len().max(1) as f64;
+            let variance = counts.iter().map(|n| (n - mean) * (n - mean)).sum::<f64>()
+                / counts.len().max(1) as f64;
+            DelimiterCandidate {
+                delimiter,
+                mean_fields: mean,
+                variance,
+            }
+        })
+        .collect();
+
+    let chosen = candidates
+        .iter()
+        .filter(|candidate| candidate.mean_fields > 1.0)
+        .min_by(|a, b| {
+            a.variance
+                .total_cmp(&b.variance)
+                .then(b.mean_fields.total_cmp(&a.mean_fields))
+        })
+        .map_or(b',', |candidate| candidate.delimiter);
+
+    DelimiterReport {
+        records,
+        candidates,
+        chosen,
+    }
+}
+
+/// Read a front-matter block from the start of `reader`
+///
+/// The block runs from a [`FRONT_MATTER_DELIMITER`] first line to the next
+/// one, and every line in between must start with `#`. Anything else, such
+/// as data that merely starts with `---` or a comment that is never closed,
+/// is not front matter; the bytes read are then returned to be parsed again.
+fn take_front_matter<R: BufRead>(reader: &mut R) -> RsfResult<(Option<String>, Vec<u8>)> {
+    let is_delimiter = |line: &[u8]| {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        line == FRONT_MATTER_DELIMITER.as_bytes()
+    };
+
+    let mut consumed = Vec::new();
+    if reader.read_until(b'\n', &mut consumed)? == 0 || !is_delimiter(&consumed) {
+        return Ok((None, consumed));
+    }
+
+    let mut yaml = Vec::new();
+    loop {
+        let start = consumed.len();
+        if reader.read_until(b'\n', &mut consumed)? == 0 || consumed[start] != b'#' {
+            return Ok((None, consumed));
+        }
+        let line = &consumed[start..];
+        if is_delimiter(line) {
+            break;
+        }
+        let line = line.strip_prefix(b"# ").unwrap_or(&line[1..]);
+        yaml.extend_from_slice(line);
+    }
+
+    String::from_utf8(yaml)
+        .map(|yaml| (Some(yaml), Vec::new()))
+        .map_err(|_| RsfError::schema_error("front matter is not valid UTF-8"))
+}
+
+/// Write `yaml` as a front-matter block for [`read_csv_reader`] to pick up
+pub fn write_front_matter<W: Write>(mut writer: W, yaml: &str) -> RsfResult<()> {
+    writeln!(writer, "{}", FRONT_MATTER_DELIMITER)?;
+    for line in yaml.lines() {
+        if line.is_empty() {
+            writeln!(writer, "#")?;
+        } else {
+            writeln!(writer, "# {}", line)?;
+        }
+    }
+    writeln!(writer, "{}", FRONT_MATTER_DELIMITER)?;
+    Ok(())
+}
+
+/// Check that `bytes` are valid UTF-8, reporting the first invalid sequence
+pub fn check_utf8(bytes: &[u8]) -> RsfResult<()> {
+    std::str::from_utf8(bytes).map(|_| ()).map_err(|err| {
+        let start = err.valid_up_to();
+        // A sequence cut off by the end of the input has no length
+        let end = err.error_len().map_or(bytes.len(), |len| start + len);
+        RsfError::encoding_error(start, "UTF-8", &bytes[start..end])
+    })
+}
+
+/// Replace each invalid UTF-8 sequence in `bytes` with U+FFFD, returning
+/// the text and the number of sequences replaced
+pub fn repair_utf8(bytes: &[u8]) -> (String, usize) {
+    let replaced = bytes
+        .utf8_chunks()
+        .filter(|chunk| !chunk.invalid().is_empty())
+        .count();
+    (String::from_utf8_lossy(bytes).into_owned(), replaced)
+}
+
+/// Read CSV with one thread splitting the input into blocks of whole records
+/// and `options.threads` workers parsing them
+///
+/// The block queue is bounded so reading cannot run far ahead of parsing;
+/// parsed blocks are reassembled in input order, so the result is identical
+/// to [`read_csv_reader`].
+#[cfg(feature = "std-fs")]
+fn read_csv_parallel<R: Read>(
+    reader: R,
+    options: ReadOptions,
+    chunk_bytes: usize,
+) -> RsfResult<CsvInput> {
+    let mut reader = BufReader::new(reader);
+    let (front_matter, replay) = take_front_matter(&mut reader)?;
+    let mut reader = QuoteScan::new(replay.as_slice().chain(reader), options);
+    let workers = options.threads.max(1);
+    let (chunk_tx, chunk_rx) = mpsc::sync_channel::<(usize, Vec<u8>)>(workers * 2);
+    let chunk_rx = Mutex::new(chunk_rx);
+    let (parsed_tx, parsed_rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let chunk_rx = &chunk_rx;
+            let parsed_tx = parsed_tx.clone();
+            scope.spawn(move || loop {
+                // The lock is released as soon as a block has been taken
+                let next = match chunk_rx.lock() {
+                    Ok(rx) => rx.recv(),
+                    Err(_) => break,
+                };
+                let Ok((seq, chunk)) = next else {
+                    break;
+                };
+                if parsed_tx
+                    .send((seq, parse_records(&chunk, options)))
+                    .is_err()
+                {
+                    break;
+                }
+            });
+        }
+        drop(parsed_tx);
+
+        let result = split_records(&mut reader, chunk_bytes, options.comment, |seq, chunk| {
+            chunk_tx.send((seq, chunk)).is_ok()
+        });
+        drop(chunk_tx);
+        result
+    })?;
+    reader.check_closed()?;
+
+    let mut parsed: Vec<(usize, RsfResult<Vec<Vec<String>>>)> = parsed_rx.into_iter().collect();
+    parsed.sort_by_key(|(seq, _)| *seq);
+
+    let mut records = Vec::new();
+    for (_, chunk) in parsed {
+        records.extend(chunk?);
+    }
+
+    let mut records = records.into_iter();
+    let headers = records.next().unwrap_or_default();
+    let rows: Vec<Vec<String>> = records.collect();
+
+    // Blocks are parsed independently, so field counts are checked here
+    if let Some((idx, row)) = rows
+        .iter()
+        .enumerate()
+        .find(|(_, row)| row.len() != headers.len())
+    {
+        return Err(RsfError::csv_error(format!(
+            "record {} has {} fields, but the header has {}",
+            idx + 1,
+            row.len(),
+            headers.len()
+        )));
+    }
+
+    Ok(finish_input(headers, rows, front_matter, options))
+}
+
+/// Read `reader` in blocks and pass on runs of complete records
+///
+/// A record ends at a newline outside double quotes; an escaped `""` inside a
+/// quoted field toggles the quote state twice and leaves it unchanged. Quotes
+/// on a `comment` line are ignored, as the parser skips the whole line.
+/// Stops early when `emit` returns false.
+#[cfg(feature = "std-fs")]
+fn split_records<R: Read>(
+    reader: &mut R,
+    chunk_bytes: usize,
+    comment: Option<u8>,
+    mut emit: impl FnMut(usize, Vec<u8>) -> bool,
+) -> RsfResult<()> {
+    let mut pending: Vec<u8> = Vec::with_capacity(chunk_bytes * 2);
+    let mut scanned = 0;
+    let mut boundary = 0;
+    let mut in_quotes = false;
+    let mut in_comment = false;
+    let mut line_start = true;
+    let mut seq = 0;
+
+    loop {
+        let start = pending.len();
+        pending.resize(start + chunk_bytes, 0);
+        let read = reader.read(&mut pending[start..])?;
+        pending.truncate(start + read);
+
+        for (offset, &byte) in pending[scanned..].iter().enumerate() {
+            let at_line_start = std::mem::replace(&mut line_start, false);
+            match byte {
+                b'\n' if in_comment || !in_quotes => {
+                    in_comment = false;
+                    line_start = true;
+                    boundary = scanned + offset + 1;
+                }
+                _ if in_comment => {}
+                _ if at_line_start && Some(byte) == comment => in_comment = true,
+                b'"' => in_quotes = !in_quotes,
+                _ => {}
+            }
+        }
+        scanned = pending.len();
+
+        let eof = read == 0;
+        if eof {
+            boundary = pending.len();
+        }
+        if boundary > 0 && (eof || pending.len() >= chunk_bytes) {
+            let rest = pending.split_off(boundary);
+            let chunk = std::mem::replace(&mut pending, rest);
+            if !emit(seq, chunk) {
+                return Ok(());
+            }
+            seq += 1;
+            scanned -= boundary;
+            boundary = 0;
+        }
+
+        if eof {
+            return Ok(());
+        }
+    }
+}
+
+/// Parse a block of whole records without treating any as a header
+#[cfg(feature = "std-fs")]
+fn parse_records(chunk: &[u8], options: ReadOptions) -> RsfResult<Vec<Vec<String>>> {
+    ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .comment(options.comment)
+        .from_reader(chunk)
+        .records()
+        .map(|result| {
+            result
+                .map(|record| record.iter().map(|s| s.to_string()).collect())
+                // Positions are relative to the block, so they are dropped
+                .map_err(|err| match RsfError::from(err) {
+                    RsfError::CsvError { message, .. } => RsfError::csv_error(message),
+                    other => other,
+                })
+        })
+        .collect()
+}
+
+fn finish_input(
+    headers: Vec<String>,
+    mut rows: Vec<Vec<String>>,
+    front_matter: Option<String>,
+    options: ReadOptions,
+) -> CsvInput {
+    let nul_fields = find_nul_bytes(&mut rows, options.strip_nul);
+
+    CsvInput {
+        headers,
+        rows,
+        nul_fields,
+        front_matter,
+    }
+}
+
+/// Locate fields containing NUL bytes, removing the bytes when `strip` is set
+fn find_nul_bytes(rows: &mut [Vec<String>], strip: bool) -> Vec<(usize, usize)> {
+    let mut found = Vec::new();
+
+    for (row_idx, row) in rows.iter_mut().enumerate() {
+        for (col_idx, value) in row.iter_mut().enumerate() {
+            if !value.contains('\0') {
+                continue;
+            }
+
+            found.push((row_idx, col_idx));
+            if strip {
+                value.retain(|c| c != '\0');
+            }
+        }
+    }
+
+    found
+}
+
+/// Write rows as CSV files of at most `chunk_rows` rows each, named
+/// `part-0001.csv`, `part-0002.csv`, ... in `dir`
+///
+/// Every part starts with the header, and the parts in name order hold the
+/// rows in their original order. Input without rows still gets one part.
+/// Returns the paths written.
+#[cfg(feature = "std-fs")]
+pub fn write_csv_chunks(
+    dir: impl AsRef<Path>,
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    chunk_rows: usize,
+) -> RsfResult<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(|e| RsfError::io_error(dir, e))?;
+
+    let chunks: Vec<&[Vec<String>]> = if rows.is_empty() {
+        vec![rows]
+    } else {
+        rows.chunks(chunk_rows.max(1)).collect()
+    };
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let path = dir.join(format!("part-{:04}.csv", idx + 1));
+            let file = File::create(&path).map_err(|e| RsfError::io_error(&path, e))?;
+            write_csv_to(BufWriter::new(file), headers, chunk)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Write a header and rows as CSV
+pub fn write_csv_to<W: Write>(
+    writer: W,
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+) -> RsfResult<()> {
+    write_csv_to_with_progress(writer, headers, rows, &NoProgress)
+}
+
+/// Like [`write_csv_to`], reporting to `progress`
+pub fn write_csv_to_with_progress<W: Write>(
+    writer: W,
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    progress: &dyn ProgressSink,
+) -> RsfResult<()> {
+    let mut counter = RowCounter::start(progress, Phase::Write, Some(rows.len()));
+    let mut csv_writer = Writer::from_writer(writer);
+
+    csv_writer.write_record(headers.iter().map(AsRef::as_ref))?;
+
+    for row in rows {
+        csv_writer.write_record(row)?;
+        counter.tick();
+    }
+
+    csv_writer.flush()?;
+    counter.finish();
+    Ok(())
+}
+
+/// Write a header and rows as CSV to `csv` and as TSV to `tsv` in one pass
+pub fn write_csv_and_tsv_to<W: Write, T: Write>(
+    csv: W,
+    tsv: T,
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+) -> RsfResult<()> {
+    write_csv_and_tsv_to_with_progress(csv, tsv, headers, rows, &NoProgress)
+}
+
+/// Like [`write_csv_and_tsv_to`], reporting to `progress`
+pub fn write_csv_and_tsv_to_with_progress<W: Write, T: Write>(
+    csv: W,
+    tsv: T,
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    progress: &dyn ProgressSink,
+) -> RsfResult<()> {
+    let mut counter = RowCounter::start(progress, Phase::Write, Some(rows.len()));
+    let mut csv_writer = Writer::from_writer(csv);
+    let mut tsv_writer = WriterBuilder::new().delimiter(b'\t').from_writer(tsv);
+
+    csv_writer.write_record(headers.iter().map(AsRef::as_ref))?;
+    tsv_writer.write_record(headers.iter().map(AsRef::as_ref))?;
+
+    for row in rows {
+        csv_writer.write_record(row)?;
+        tsv_writer.write_record(row)?;
+        counter.tick();
+    }
+
+    csv_writer.flush()?;
+    tsv_writer.flush()?;
+    counter.finish();
+    Ok(())
+}
+
+/// Format of written rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DataFormat {
+    Csv,
+    /// Tab-separated, quoted like CSV where needed
+    Tsv,
+    /// One JSON object per row, keyed by header in column order
+    Jsonl,
+    /// Apache Parquet with every column a UTF-8 string, Snappy-compressed
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl DataFormat {
+    /// Format written for a file extension, matched case-insensitively;
+    /// `None` for extensions without a writer
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "csv" | "rsf" => Some(DataFormat::Csv),
+            "tsv" | "tab" => Some(DataFormat::Tsv),
+            "jsonl" | "ndjson" => Some(DataFormat::Jsonl),
+            #[cfg(feature = "parquet")]
+            "parquet" => Some(DataFormat::Parquet),
+            _ => None,
+        }
+    }
+}
+
+/// Write a header and rows in `format`
+pub fn write_rows_to<W: Write>(
+    writer: W,
+    format: DataFormat,
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+) -> RsfResult<()> {
+    write_rows_to_with_progress(writer, format, headers, rows, &NoProgress)
+}
+
+/// Like [`write_rows_to`], reporting to `progress`
+pub fn write_rows_to_with_progress<W: Write>(
+    writer: W,
+    format: DataFormat,
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    progress: &dyn ProgressSink,
+) -> RsfResult<()> {
+    match format {
+        DataFormat::Csv => write_csv_to_with_progress(writer, headers, rows, progress),
+        DataFormat::Tsv => {
+            let mut counter = RowCounter::start(progress, Phase::Write, Some(rows.len()));
+            let mut tsv_writer = WriterBuilder::new().delimiter(b'\t').from_writer(writer);
+            tsv_writer.write_record(headers.iter().map(AsRef::as_ref))?;
+            for row in rows {
+                tsv_writer.write_record(row)?;
+                counter.tick();
+            }
+            tsv_writer.flush()?;
+            counter.finish();
+            Ok(())
+        }
+        DataFormat::Jsonl => write_jsonl_to(writer, headers, rows, progress),
+        #[cfg(feature = "parquet")]
+        DataFormat::Parquet => write_parquet_to(writer, headers, rows, progress),
+    }
+}
+
+/// Rows per Parquet row group
+#[cfg(feature = "parquet")]
+const PARQUET_BATCH_ROWS: usize = 64 * 1024;
+
+/// Write the rows as Parquet, every column a non-null UTF-8 string; cells
+/// missing from short rows are written empty
+///
+/// The file is built in memory first, as the Parquet writer needs a `Send`
+/// writer.
+#[cfg(feature = "parquet")]
+fn write_parquet_to<W: Write>(
+    mut writer: W,
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    progress: &dyn ProgressSink,
+) -> RsfResult<()> {
+    use arrow_array::{ArrayRef, RecordBatch, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+    use std::sync::Arc;
+
+    let fields: Vec<Field> = headers
+        .iter()
+        .map(|header| Field::new(header.as_ref(), DataType::Utf8, false))
+        .collect();
+    let schema = Arc::new(Schema::new(fields));
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+
+    let mut counter = RowCounter::start(progress, Phase::Write, Some(rows.len()));
+    let mut bytes = Vec::new();
+    let mut parquet = ArrowWriter::try_new(&mut bytes, schema.clone(), Some(properties))?;
+    for batch in rows.chunks(PARQUET_BATCH_ROWS) {
+        let columns: Vec<ArrayRef> = (0..headers.len())
+            .map(|idx| {
+                let values = batch
+                    .iter()
+                    .map(|row| row.get(idx).map_or("", String::as_str));
+                Arc::new(StringArray::from_iter_values(values)) as ArrayRef
+            })
+            .collect();
+        parquet.write(&RecordBatch::try_new(schema.clone(), columns)?)?;
+        for _ in batch {
+            counter.tick();
+        }
+    }
+    parquet.close()?;
+
+    writer.write_all(&bytes)?;
+    writer.flush()?;
+    counter.finish();
+    Ok(())
+}
+
+/// Write each row as a JSON object on its own line; cells missing from
+/// short rows are left out of their object
+fn write_jsonl_to<W: Write>(
+    mut writer: W,
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    progress: &dyn ProgressSink,
+) -> RsfResult<()> {
+    let mut counter = RowCounter::start(progress, Phase::Write, Some(rows.len()));
+    let mut line = String::new();
+    for row in rows {
+        line.clear();
+        line.push('{');
+        for (idx, (header, value)) in headers.iter().zip(row).enumerate() {
+            if idx > 0 {
+                line.push(',');
+            }
+            push_json_string(&mut line, header.as_ref());
+            line.push(':');
+            push_json_string(&mut line, value);
+        }
+        line.push_str("}\n");
+        writer.write_all(line.as_bytes())?;
+        counter.tick();
+    }
+    writer.flush()?;
+    counter.finish();
+    Ok(())
+}
+
+/// Append `value` to `out` as a quoted JSON string
+fn push_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => out.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_csv_and_tsv() {
+        let rows = vec![
+            vec!["1".to_string(), "a,b".to_string()],
+            vec!["2".to_string(), "tab\there".to_string()],
+        ];
+        let (mut csv, mut tsv) = (Vec::new(), Vec::new());
+        write_csv_and_tsv_to(&mut csv, &mut tsv, &["Id", "Note"], &rows).unwrap();
+
+        let mut csv_only = Vec::new();
+        write_csv_to(&mut csv_only, &["Id", "Note"], &rows).unwrap();
+        assert_eq!(csv, csv_only);
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "Id\tNote\n1\ta,b\n2\t\"tab\there\"\n"
+        );
+    }
+
+    #[test]
+    fn test_jsonl_inferred_from_extension() {
+        let path = std::path::Path::new("out.jsonl");
+        let format = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(DataFormat::from_extension);
+        assert_eq!(format, Some(DataFormat::Jsonl));
+        assert_eq!(DataFormat::from_extension("TSV"), Some(DataFormat::Tsv));
+        assert_eq!(DataFormat::from_extension("xlsx"), None);
+
+        let rows = vec![
+            vec!["1".to_string(), "say \"hi\"\n".to_string()],
+            vec!["2".to_string()],
+        ];
+        let mut out = Vec::new();
+        write_rows_to(&mut out, format.unwrap(), &["Id", "Note"], &rows).unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                serde_json::json!({"Id": "1", "Note": "say \"hi\"\n"}),
+                serde_json::json!({"Id": "2"}),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "parquet", feature = "std-fs"))]
+    fn test_parquet_round_trip() {
+        use arrow_array::{Array, StringArray};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        assert_eq!(
+            DataFormat::from_extension("Parquet"),
+            Some(DataFormat::Parquet)
+        );
+        let rows = vec![
+            vec!["1".to_string(), "a,b".to_string()],
+            vec!["2".to_string()],
+        ];
+        let path = std::env::temp_dir().join(format!("rsf-{}.parquet", std::process::id()));
+        let file = File::create(&path).unwrap();
+        write_rows_to(file, DataFormat::Parquet, &["Id", "Note"], &rows).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        let names: Vec<_> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(names, ["Id", "Note"]);
+        let note = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            (note.value(0), note.value(1), note.null_count()),
+            ("a,b", "", 0)
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_front_matter() {
+        let mut bytes = Vec::new();
+        write_front_matter(&mut bytes, "version: '0.1'\ncolumns:\n- name: Id\n\n").unwrap();
+        bytes.extend_from_slice(b"Id\n1\n");
+        let input = read_csv_reader(bytes.as_slice(), ReadOptions::default()).unwrap();
+        assert_eq!(
+            input.front_matter.as_deref(),
+            Some("version: '0.1'\ncolumns:\n- name: Id\n\n")
+        );
+        assert_eq!(input.headers, ["Id"]);
+        assert_eq!(input.rows, [["1"]]);
+
+        #[cfg(feature = "std-fs")]
+        {
+            let parallel =
+                read_csv_parallel(bytes.as_slice(), ReadOptions::default().threads(2), 4).unwrap();
+            assert_eq!(parallel.front_matter, input.front_matter);
+            assert_eq!(parallel.rows, input.rows);
+        }
+
+        // Data that looks like a delimiter, or a block that is never closed, is data
+        for csv in ["---\n1\n", "# ---\nId\n1\n", "# ---\n# a: 1\n"] {
+            let input = read_csv_reader(csv.as_bytes(), ReadOptions::default()).unwrap();
+            assert_eq!(input.front_matter, None, "{:?}", csv);
+        }
+        let input = read_csv_reader("# ---\nId\n1\n".as_bytes(), ReadOptions::default()).unwrap();
+        assert_eq!(input.headers, ["# ---"]);
+        assert_eq!(input.rows.len(), 2);
+    }
+
+    #[test]
+    fn test_check_and_repair_utf8() {
+        assert!(check_utf8("Café,1\n".as_bytes()).is_ok());
+
+        // "Caf\xE9" is Latin-1 for "Café"
+        let latin1 = b"Name\nCaf\xE9 noir\nCaf\xE9";
+        let err = check_utf8(latin1).unwrap_err();
+        assert!(matches!(
+            &err,
+            RsfError::EncodingError { byte_offset: 8, found, .. } if found == "0xE9"
+        ));
+
+        // A multi-byte sequence cut off at the end
+        let err = check_utf8(b"caf\xC3").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid UTF-8 at byte 3: found 0xC3");
+
+        let (text, replaced) = repair_utf8(latin1);
+        assert_eq!(text, "Name\nCaf\u{FFFD} noir\nCaf\u{FFFD}");
+        assert_eq!(replaced, 2);
+        assert_eq!(repair_utf8(b"ok"), ("ok".to_string(), 0));
+
+        // Parsing still rejects the field, at the record holding it
+        let err = read_csv_reader(&latin1[..], ReadOptions::default()).unwrap_err();
+        assert!(matches!(
+            &err,
+            RsfError::CsvError { message, position: Some(position), .. }
+                if message.contains("field 0") && position.line == 2
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "std-fs")]
+    fn test_write_csv_chunks() {
+        let dir = std::env::temp_dir().join(format!("rsf-chunks-{}", std::process::id()));
+        let headers = ["id"];
+        let rows: Vec<Vec<String>> = (1..=7).map(|id| vec![id.to_string()]).collect();
+
+        let parts = write_csv_chunks(&dir, &headers, &rows, 3).unwrap();
+        let contents: Vec<String> = parts
+            .iter()
+            .map(|part| std::fs::read_to_string(part).unwrap())
+            .collect();
+        let names: Vec<_> = parts.iter().map(|part| part.file_name().unwrap()).collect();
+        assert_eq!(names, ["part-0001.csv", "part-0002.csv", "part-0003.csv"]);
+        assert_eq!(contents, ["id\n1\n2\n3\n", "id\n4\n5\n6\n", "id\n7\n"]);
+
+        let parts = write_csv_chunks(&dir, &headers, &[], 3).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(std::fs::read_to_string(&parts[0]).unwrap(), "id\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std-fs")]
+    fn test_mmap_read_matches_buffered() {
+        let path = std::env::temp_dir().join(format!("rsf-mmap-{}.csv", std::process::id()));
+        let read = |contents: &[u8], options: ReadOptions| {
+            std::fs::write(&path, contents).unwrap();
+            let result = read_csv_file(&path, options);
+            std::fs::remove_file(&path).unwrap();
+            result
+        };
+
+        let mut bytes = Vec::new();
+        write_front_matter(&mut bytes, "version: '0.1'\n").unwrap();
+        bytes.extend_from_slice(b"id,note\n1,\"a\nb\"\n# 2,x\n3,caf\xC3\xA9\n");
+        for options in [
+            ReadOptions::default(),
+            ReadOptions::default().comment(Some(b'#')),
+        ] {
+            let buffered = read(&bytes, options).unwrap();
+            let mapped = read(&bytes, options.mmap(true)).unwrap();
+            assert_eq!(mapped.headers, buffered.headers);
+            assert_eq!(mapped.rows, buffered.rows);
+            assert_eq!(mapped.front_matter, buffered.front_matter);
+        }
+
+        let empty = read(b"", ReadOptions::default().mmap(true)).unwrap();
+        assert!(empty.headers.is_empty() && empty.rows.is_empty());
+
+        let latin1 = b"id,note\n1,ok\n2,caf\xE9\n";
+        let buffered = read(latin1, ReadOptions::default()).unwrap_err();
+        let mapped = read(latin1, ReadOptions::default().mmap(true)).unwrap_err();
+        assert_eq!(mapped.to_string(), buffered.to_string());
+    }
+
+    #[test]
+    fn test_read_csv_reports_and_strips_nul_bytes() {
+        let data = "A,B\nx\0,y\nz,w\n";
+
+        let input = read_csv_reader(data.as_bytes(), ReadOptions::default()).unwrap();
+        assert_eq!(input.headers, vec!["A", "B"]);
+        assert_eq!(input.nul_fields, vec![(0, 0)]);
+        assert_eq!(input.rows[0][0], "x\0");
+
+        let input =
+            read_csv_reader(data.as_bytes(), ReadOptions::default().strip_nul(true)).unwrap();
+        assert_eq!(input.nul_fields, vec![(0, 0)]);
+        assert_eq!(input.rows[0][0], "x");
+
+        let mut out = Vec::new();
+        write_csv_to(&mut out, &input.headers, &input.rows).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "A,B\nx,y\nz,w\n");
+    }
+
+    #[test]
+    fn test_unterminated_quote_is_an_error() {
+        let data = "A,B\n1,\"p\nq\"\n3,\"x\n4,5\n";
+        let message = "CSV error at line 4, column 3 (record 2, byte 14): \
+                       unterminated quoted field starting at byte 14\n  | 3,\"x";
+
+        let err = read_csv_reader(data.as_bytes(), ReadOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), message);
+        let err = csv_records(data.as_bytes(), ReadOptions::default())
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap();
+        assert_eq!(err.to_string(), message);
+        #[cfg(feature = "std-fs")]
+        for chunk_bytes in [1, 5, 1024] {
+            let options = ReadOptions::default().threads(2);
+            let err = read_csv_parallel(data.as_bytes(), options, chunk_bytes).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+
+        // A closed quote is fine even with an escaped quote at the end
+        let input = read_csv_reader("A\n\"x\"\"\"\n".as_bytes(), ReadOptions::default());
+        assert_eq!(input.unwrap().rows, [["x\""]]);
+    }
+
+    #[test]
+    #[cfg(feature = "std-fs")]
+    fn test_parallel_read_matches_sequential() {
+        let data =
+            "\u{feff}id,note\n1,\"multi\nline, \"\"quoted\"\"\"\n2,plain\r\n\n3,\"a\"\"b\"\n4,last";
+        let options = ReadOptions::default().threads(3);
+
+        let sequential = read_csv_reader(data.as_bytes(), options).unwrap();
+        // Tiny blocks force splits inside quoted fields
+        for chunk_bytes in [1, 2, 5, 16, 1024] {
+            let parallel = read_csv_parallel(data.as_bytes(), options, chunk_bytes).unwrap();
+            assert_eq!(parallel.headers, sequential.headers);
+            assert_eq!(parallel.rows, sequential.rows);
+        }
+
+        let ragged = "a,b\n1,2\n3\n";
+        assert!(read_csv_parallel(ragged.as_bytes(), options, 4).is_err());
+    }
+
+    #[test]
+    fn test_comment_lines_are_skipped() {
+        // The quote on the second comment line must not open a field
+        let data = "# exported 2024-05-01\n# owner: \"ops\nid,note\n1,\"# not a comment\"\n#2,skipped\n3,a#b\n";
+        let options = ReadOptions::default().comment(Some(b'#'));
+
+        let input = read_csv_reader(data.as_bytes(), options).unwrap();
+        assert_eq!(input.headers, ["id", "note"]);
+        assert_eq!(input.rows, [["1", "# not a comment"], ["3", "a#b"]]);
+
+        #[cfg(feature = "std-fs")]
+        for chunk_bytes in [1, 3, 7, 1024] {
+            let parallel =
+                read_csv_parallel(data.as_bytes(), options.threads(2), chunk_bytes).unwrap();
+            assert_eq!(parallel.headers, input.headers);
+            assert_eq!(parallel.rows, input.rows);
+        }
+
+        // Without the option the metadata line is read as the header
+        let input = read_csv_reader(data.as_bytes(), ReadOptions::default().flexible(true));
+        assert_eq!(input.unwrap().headers, ["# exported 2024-05-01"]);
+    }
+
+    #[test]
+    fn test_sniff_delimiter() {
+        let sniff = |data: &str| sniff_delimiter(data.as_bytes(), ReadOptions::default());
+
+        // Commas inside fields split records unevenly
+        let report = sniff("id\tnote\n1\ta, b\n2\tc\n3\td, e, f\n");
+        assert_eq!(report.chosen, b'\t');
+        assert_eq!(report.records, 4);
+        assert_eq!(report.candidates[1].variance, 0.0);
+        assert!(report.candidates[0].variance > 0.0);
+
+        assert_eq!(sniff("a|b|c\n1|2|3\n4|5|6").chosen, b'|');
+        assert_eq!(sniff("\"a;b\",c\n\"1;2\",3\n").chosen, b',');
+        // Nothing splits, so the default stays
+        assert_eq!(sniff("name\nAnn\n").chosen, b',');
+
+        let data = "# ---\n# columns: []\n# ---\n# x,y,z\na;b\n1;2\n";
+        let report = sniff_delimiter(data.as_bytes(), ReadOptions::default().comment(Some(b'#')));
+        assert_eq!((report.chosen, report.records), (b';', 2));
+
+        let input = read_csv_reader(
+            "a;b\n1;2\n".as_bytes(),
+            ReadOptions::default().delimiter(b';'),
+        )
+        .unwrap();
+        assert_eq!(input.rows, [["1", "2"]]);
+    }
+}
@@ -0,0 +1,56 @@
+//! RSF - Ranked Spreadsheet Format
+//!
+//! Deterministic column ordering based on cardinality. Columns are ranked
+//! from most unique (highest cardinality) to least unique, and rows are then
+//! sorted canonically so the same data always produces the same file.
+//!
+//! The `rsf` command-line tool is built on this library and is enabled by the
+//! default `cli` feature; depend on the crate with `default-features = false`
+//! to use the library alone.
+//!
+//! ```
+//! use rsf::{ranking::RankingOptions, RsfDocument};
+//!
+//! let mut doc = RsfDocument::from_reader("Category,Id\nfood,2\ntravel,1\nfood,3\n".as_bytes())?;
+//! doc.rank(RankingOptions::default())?;
+//! doc.sort();
+//!
+//! let mut out = Vec::new();
+//! doc.write_to(&mut out)?;
+//! assert_eq!(out, b"Id,Category\n1,travel\n2,food\n3,food\n");
+//! # Ok::<(), rsf::RsfError>(())
+//! ```
+//!
+//! The steps are also available as free functions in [`ranking`] and [`io`]
+//! for callers that manage headers and rows themselves, and [`RsfRanker`]
+//! ranks records as they stream in without holding them in memory.
+
+pub mod cache;
+pub mod delta;
+pub mod diagram;
+pub mod document;
+pub mod errors;
+pub mod estimate;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod histogram;
+pub mod io;
+pub mod lint;
+#[cfg(all(feature = "std-fs", unix))]
+mod mmap;
+pub mod numeric;
+pub mod pii;
+pub mod pipeline;
+pub mod plan;
+pub mod progress;
+pub mod ranker;
+pub mod ranking;
+pub mod summary;
+pub mod transpose;
+pub mod unicode;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use document::RsfDocument;
+pub use errors::{RsfError, RsfResult};
+pub use ranker::RsfRanker;
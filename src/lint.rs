@@ -0,0 +1,179 @@
+use crate::errors::RsfError;
+use crate::ranking::{
+    case_collisions, duplicate_names, validate_rank_sequence, ColumnType, Schema,
+};
+use std::fmt;
+
+/// How serious a [`Lint`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Probably a mistake, but the schema is still usable
+    Warning,
+    /// The schema is inconsistent and validation against it will fail
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in a schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Lint {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Lint {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Check a hand-written schema for common mistakes
+///
+/// Reports everything [`Schema::validate_self`] rejects as errors, plus
+/// warnings for columns with empty names, zero cardinalities next to
+/// non-zero ones, and schemas where every column is a key.
+pub fn lint_schema(schema: &Schema) -> Vec<Lint> {
+    let mut lints: Vec<Lint> = duplicate_names(&schema.columns)
+        .into_iter()
+        .map(|name| Lint::error(format!("Column '{}' appears more than once", name)))
+        .collect();
+
+    if let Err(err) = validate_rank_sequence(&schema.columns) {
+        lints.push(Lint::error(match err {
+            RsfError::SchemaError { message, .. } => message,
+            other => other.to_string(),
+        }));
+    }
+
+    for (idx, col) in schema.columns.iter().enumerate() {
+        if col.name.trim().is_empty() {
+            lints.push(Lint::warning(format!(
+                "Column {} has an empty name",
+                idx + 1
+            )));
+        }
+    }
+
+    // Collide in databases and tools that ignore case
+    let names: Vec<&str> = schema.columns.iter().map(|col| col.name.as_str()).collect();
+    for group in case_collisions(&names) {
+        lints.push(Lint::warning(format!(
+            "Columns '{}' differ only by case",
+            group.join("', '")
+        )));
+    }
+
+    // A column with no values only makes sense if the file had no rows
+    if schema.columns.iter().any(|col| col.cardinality > 0) {
+        for col in schema.columns.iter().filter(|col| col.cardinality == 0) {
+            lints.push(Lint::warning(format!(
+                "Column '{}' has cardinality 0 but other columns have values",
+                col.name
+            )));
+        }
+    }
+
+    if schema.columns.len() > 1
+        && schema
+            .columns
+            .iter()
+            .all(|col| col.col_type == Some(ColumnType::Key))
+    {
+        lints.push(Lint::warning(
+            "Every column is typed 'key'; was the key threshold set?",
+        ));
+    }
+
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranking::ColumnMeta;
+
+    fn key(mut col: ColumnMeta) -> ColumnMeta {
+        col.col_type = Some(ColumnType::Key);
+        col
+    }
+
+    #[test]
+    fn test_clean_schema() {
+        let schema = Schema::new(vec![
+            ColumnMeta::new("Id", 1, 3),
+            ColumnMeta::new("Kind", 2, 2),
+        ]);
+        assert!(schema.validate_self().is_ok());
+        assert!(lint_schema(&schema).is_empty());
+    }
+
+    #[test]
+    fn test_reports_errors_and_warnings() {
+        let schema = Schema::new(vec![
+            key(ColumnMeta::new("Id", 1, 3)),
+            key(ColumnMeta::new("Id", 2, 2)),
+            key(ColumnMeta::new(" ", 4, 0)),
+        ]);
+        assert!(schema.validate_self().is_err());
+
+        let lints = lint_schema(&schema);
+        let severities: Vec<Severity> = lints.iter().map(|lint| lint.severity).collect();
+        assert_eq!(
+            severities,
+            vec![
+                Severity::Error,
+                Severity::Error,
+                Severity::Warning,
+                Severity::Warning,
+                Severity::Warning
+            ]
+        );
+        assert_eq!(
+            lints[0].to_string(),
+            "error: Column 'Id' appears more than once"
+        );
+        assert!(lints[1].message.contains("invalid rank"));
+        assert_eq!(lints[2].message, "Column 3 has an empty name");
+    }
+
+    #[test]
+    fn test_case_collision_is_a_warning() {
+        let schema = Schema::new(vec![
+            ColumnMeta::new("ID", 1, 3),
+            ColumnMeta::new("id", 2, 2),
+        ]);
+        assert!(schema.validate_self().is_ok());
+
+        let lints = lint_schema(&schema);
+        assert_eq!(lints.len(), 1);
+        assert_eq!(
+            lints[0].to_string(),
+            "warning: Columns 'ID', 'id' differ only by case"
+        );
+    }
+}
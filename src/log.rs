@@ -0,0 +1,60 @@
+//! How much the CLI prints on stderr, set once by `--quiet` or `--verbose`
+//!
+//! Errors are printed with `anstream::eprintln!` directly and never suppressed;
+//! everything else goes through [`info!`] or [`verbose!`].
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Amount of stderr output, from least to most
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Whether messages at `level` are printed
+pub fn enabled(level: Level) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Print a progress or status message on stderr unless `--quiet`
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Normal) {
+            anstream::eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Print a diagnostic message on stderr only with `--verbose`
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Verbose) {
+            anstream::eprintln!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {info, verbose};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_are_ordered() {
+        set_level(Level::Quiet);
+        assert!(!enabled(Level::Normal));
+        set_level(Level::Verbose);
+        assert!(enabled(Level::Normal) && enabled(Level::Verbose));
+        set_level(Level::Normal);
+        assert!(enabled(Level::Normal) && !enabled(Level::Verbose));
+    }
+}
@@ -1,243 +1,2541 @@
-mod errors;
-mod ranking;
+mod changelog;
+mod cli;
+mod clobber;
+mod config;
+#[cfg(feature = "tui")]
+mod explore;
+mod log;
+mod profile;
+mod progress_bar;
+mod style;
+mod warnings;
+mod watch;
 
+use anstream::{eprintln, println};
+use anstyle::Style;
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use csv::{Reader, Writer};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
 use std::fs::File;
-use std::io::{self, BufReader};
+use std::io::{self, BufWriter, IsTerminal, Read};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-use crate::errors::IntoAnyhow;
-use crate::ranking::{
-    rank_columns, reorder_data, sort_rows_canonical, validate_cardinality_order,
-    validate_column_order, validate_sorted, write_schema, RankingOptions, Schema,
+use rsf::cache::{read_cache, write_cache};
+use rsf::delta::row_delta;
+use rsf::diagram::{render_dot, render_mermaid_er};
+use rsf::document::Check;
+use rsf::estimate::{Recommendation, SizeEstimate, SizeEstimator};
+use rsf::histogram::{histogram, Categories, Histogram, NumericHistogram};
+use rsf::io::{
+    check_utf8, csv_records, read_csv_file, read_csv_file_with_progress, read_csv_reader,
+    read_csv_reader_with_progress, repair_utf8, sniff_delimiter,
+    write_csv_and_tsv_to_with_progress, write_csv_chunks, write_front_matter,
+    write_rows_to_with_progress, CsvInput, DataFormat, DelimiterReport, ReadOptions, SNIFF_BYTES,
 };
+use rsf::lint::{lint_schema, Severity};
+use rsf::pii::{detect_pii, PiiFinding, PII_SAMPLE, PII_TAG};
+use rsf::pipeline::{ConcatKey, Prepared, RankPipeline, RowHashes};
+use rsf::plan::{plan_rank, RankPlan};
+use rsf::progress::{NoProgress, ProgressSink};
+use rsf::ranking::{
+    align_columns, check_cardinality_limits, column_permutation, compare_cardinality,
+    compare_null_modes, compute_cardinality, compute_combined_cardinality, distinct_keys,
+    functional_dependency, header_divergence, key_column_names, rank_columns,
+    read_cardinality_overrides, read_schema, read_schema_reader, reorder_data, require_columns,
+    write_schema, CardinalityBand, ColumnMeta, ColumnStats, HeaderDivergence, RankingOptions,
+    RankingOptionsBuilder, Schema,
+};
+use rsf::summary::{CatalogColumn, NullCounter, NullReport, StatsReport};
+use rsf::transpose::{transpose, untranspose};
+use rsf::unicode::NormalizationForm;
+use rsf::{RsfDocument, RsfError, RsfRanker};
+
+use crate::changelog::ChangeLogEntry;
+use crate::cli::{
+    Cli, ColorWhen, Commands, ConfigCommand, Delimiter, Encoding, ErrorFormat, OutputFormat,
+    RankBy, ReportFormat, ValidateFormat,
+};
+use crate::config::{Setting, Source};
+use crate::log::{info, verbose};
+use crate::profile::Profile;
+use crate::progress_bar::StderrProgress;
+use crate::style::{ERROR, EXPECTED, FOUND, HEADING, OK};
+
+fn main() -> ExitCode {
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let matches = Cli::command()
+        .try_get_matches_from(&args)
+        .unwrap_or_else(|err| config::name_env_vars(&Cli::command(), &args, err).exit());
+    let error_format = matches
+        .get_one::<ErrorFormat>("error_format")
+        .copied()
+        .unwrap_or_default();
+    let fail_on_warning = matches.get_flag("fail_on_warning");
+    anstream::ColorChoice::write_global(
+        matches
+            .get_one::<ColorWhen>("color")
+            .copied()
+            .unwrap_or_default()
+            .into(),
+    );
+    log::set_level(log_level(&matches));
+    clobber::set_force(matches.get_flag("force"));
+
+    let watched = matches
+        .subcommand_matches("rank")
+        .filter(|rank| rank.get_flag("watch"))
+        .and_then(|rank| rank.get_one::<String>("input"))
+        .filter(|input| *input != "-")
+        .map(PathBuf::from);
+    if let Some(input) = watched {
+        // A failed run is reported and the next change tried again
+        let watched = watch::watch(&input, || {
+            let result = run(matches.clone());
+            // Later runs replace what the first one wrote
+            if result.is_ok() {
+                clobber::set_force(true);
+            }
+            report(result, error_format, fail_on_warning);
+        });
+        return report(
+            watched.map(|()| ExitCode::SUCCESS),
+            error_format,
+            fail_on_warning,
+        );
+    }
 
-/// RSF - Ranked Spreadsheet Format
+    report(run(matches), error_format, fail_on_warning)
+}
+
+/// How much to print on stderr, from `--quiet` and `--verbose`
 ///
-/// Deterministic column ordering based on cardinality.
-/// Columns are ranked from most unique (highest cardinality) to least unique.
-#[derive(Parser)]
-#[command(name = "rsf")]
-#[command(about = "Ranked Spreadsheet Format - Stable scaffolding for tabular data", long_about = None)]
-struct Cli {
-    #[command(subcommand)]
-    command: Commands,
-}
-
-#[derive(Subcommand)]
-enum Commands {
-    /// Rank a CSV file by column cardinality
-    Rank {
-        /// Input CSV file (use - for stdin)
-        #[arg(default_value = "-")]
-        input: String,
-
-        /// Output file (defaults to stdout)
-        #[arg(short, long)]
-        output: Option<PathBuf>,
-
-        /// Generate schema.yaml file
-        #[arg(short, long)]
-        schema: bool,
-
-        /// Count nulls as distinct values
-        #[arg(long, default_value = "true")]
-        nulls_distinct: bool,
-    },
-
-    /// Validate an RSF file
-    Validate {
-        /// RSF CSV file to validate
-        input: PathBuf,
-
-        /// Schema file (defaults to input.schema.yaml)
-        #[arg(short, long)]
-        schema: Option<PathBuf>,
-    },
-
-    /// Show cardinality statistics for a CSV
-    Stats {
-        /// Input CSV file
-        input: PathBuf,
-    },
-}
-
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+/// Both may be on when one comes from the environment; the one given on the
+/// command line wins.
+fn log_level(matches: &ArgMatches) -> log::Level {
+    let on_command_line = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+    match (matches.get_flag("quiet"), matches.get_flag("verbose")) {
+        (true, true) => match (on_command_line("quiet"), on_command_line("verbose")) {
+            (true, false) => log::Level::Quiet,
+            (false, true) => log::Level::Verbose,
+            (true, true) => Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "the argument '--quiet' cannot be used with '--verbose'",
+                )
+                .exit(),
+            (false, false) => Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "RSF_QUIET and RSF_VERBOSE cannot both be set",
+                )
+                .exit(),
+        },
+        (true, false) => log::Level::Quiet,
+        (false, true) => log::Level::Verbose,
+        (false, false) => log::Level::Normal,
+    }
+}
+
+/// Print a fatal error in the requested format, treating a run that printed
+/// warnings as failed under `--fail-on-warning`
+fn report(result: Result<ExitCode>, error_format: ErrorFormat, fail_on_warning: bool) -> ExitCode {
+    let warnings = warnings::take();
+    match result {
+        Ok(_) if fail_on_warning && warnings > 0 => report(
+            Err(anyhow::anyhow!(
+                "{} warning(s) printed with --fail-on-warning",
+                warnings
+            )),
+            error_format,
+            false,
+        ),
+        Ok(code) => code,
+        // The reader went away (`rsf rank big.csv | head`), which is not a failure
+        Err(err) if is_broken_pipe(&err) => ExitCode::SUCCESS,
+        Err(err) => {
+            match error_format {
+                ErrorFormat::Human => eprintln!("{ERROR}Error:{ERROR:#} {:?}", err),
+                ErrorFormat::Json => eprintln!("{}", error_json(&err)),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// JSON form of a fatal error: the kind and fields of the underlying
+/// `RsfError` if there is one, and the full message chain
+fn error_json(err: &anyhow::Error) -> serde_json::Value {
+    let mut json = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<RsfError>())
+        .and_then(|rsf_err| serde_json::to_value(rsf_err).ok())
+        .unwrap_or_else(|| serde_json::json!({ "kind": "other" }));
+    json["message"] = format!("{:#}", err).into();
+    json
+}
+
+fn run(matches: ArgMatches) -> Result<ExitCode> {
+    let cli = parse_cli(matches)?;
 
     match cli.command {
+        #[cfg(feature = "tui")]
+        Commands::Explore {
+            input,
+            nulls_distinct,
+            rank_by,
+        } => {
+            let CsvInput { headers, rows, .. } = read_csv(&input, ReadOptions::default())?;
+            let config_path = match cli.config {
+                Some(path) => path,
+                None => config::discover(&std::env::current_dir()?)
+                    .unwrap_or_else(|| PathBuf::from(config::CONFIG_FILE)),
+            };
+            explore::run(headers, rows, nulls_distinct, rank_by.into(), &config_path)?;
+        }
+
         Commands::Rank {
             input,
             output,
+            in_place,
+            no_backup,
+            chunk_rows,
+            output_format,
+            emit_tsv_alongside,
             schema,
+            embed_schema,
+            emit_metadata_header,
             nulls_distinct,
+            require_column,
+            require_columns_from_schema,
+            warn_if_cardinality_exceeds,
+            fail_if_cardinality_exceeds,
+            exclude_nulls_from_cardinality,
+            null_values,
+            null_values_ignore_case,
+            pin_last,
+            cardinalities,
+            determinism_check,
+            dedup_normalized: dedup_trimmed,
+            rename_case_collisions: rename_by_case,
+            preserve_original_header_case,
+            output_delta,
+            deleted_output,
+            type_row,
+            fill_missing: fill_value,
+            fill_missing_with_null,
+            sort_stability_guarantee,
+            rank_by,
+            tiebreak,
+            decimal_comma,
+            canonical_decimal,
+            normalize_unicode,
+            verify_utf8_normalization,
+            concat_key,
+            key_name,
+            key_separator,
+            drop_key_columns,
+            emit_row_hashes,
+            row_hash_key,
+            emit_inverse_mapping,
+            output_index_map,
+            output_inverse_index_map,
+            emit_change_log,
+            stats_out,
+            output_column_fingerprint,
+            nulls_report,
+            profile: profile_path,
+            strip_nul,
+            comment,
+            delimiter,
+            delimiter_detect_report,
+            threads,
+            mmap,
+            progress: show_progress,
+            no_progress,
+            cache_dir,
+            dot,
+            emit_mermaid_er,
+            output_sorted_keys_only,
+            key_count,
+            check,
+            dry_run,
+            estimate,
+            format,
+            watch,
         } => {
-            let (headers, rows) = read_csv(&input)?;
-            let options = ranking_options(nulls_distinct);
-            let ranked_columns =
-                rank_columns(&headers, &rows, options).map_err(IntoAnyhow::into_anyhow)?;
+            // main re-runs this for each change to a watched file
+            if watch && input == "-" {
+                anyhow::bail!("--watch needs an input file, not stdin");
+            }
+            if in_place && input == "-" {
+                anyhow::bail!("--in-place needs an input file, not stdin");
+            }
+            // --in-place writes next to the input and moves the file over it
+            // at the end; sidecars are named after the input
+            let target = if in_place {
+                Some(PathBuf::from(&input))
+            } else {
+                output.clone()
+            };
+            let temp_output = in_place.then(|| in_place_temp_path(Path::new(&input)));
+            if let Some(temp) = &temp_output {
+                // Left behind by an interrupted run
+                let _ = std::fs::remove_file(temp);
+            }
+            let schema_path = schema.then(|| schema_path_for(target.as_deref()));
+            if !in_place {
+                // Chunked output goes to numbered files, the first of which
+                // stands for the rest
+                let first_output = match (chunk_rows, &output) {
+                    (Some(_), Some(dir)) => Some(dir.join("part-0001.csv")),
+                    _ => output.clone(),
+                };
+                check_output_paths(&input, first_output.as_deref())?;
+                if let Some(schema_path) = &schema_path {
+                    clobber::check(schema_path)?;
+                }
+            }
+            let output = temp_output.clone().or(output);
+            let data_format = data_format(output_format, target.as_deref())?;
+            if emit_tsv_alongside.is_some() && data_format != DataFormat::Csv {
+                anyhow::bail!("--emit-tsv-alongside needs CSV output");
+            }
+            let text_output = matches!(data_format, DataFormat::Csv | DataFormat::Tsv);
+            if embed_schema && !text_output {
+                anyhow::bail!("--embed-schema needs CSV or TSV output");
+            }
+            if emit_metadata_header && !text_output {
+                anyhow::bail!("--emit-metadata-header needs CSV or TSV output");
+            }
+
+            let nulls_distinct =
+                nulls_distinct && !exclude_nulls_from_cardinality && null_values.is_empty();
+            let options = ranking_options(nulls_distinct)
+                .include_nulls(!exclude_nulls_from_cardinality)
+                .null_values(null_values)
+                .null_values_ignore_case(null_values_ignore_case)
+                .pin_last(pin_last.iter().map(|name| name.trim()))
+                .metric(rank_by.into())
+                .tiebreak(tiebreak.into())
+                .decimal_comma(decimal_comma)
+                .build()?;
+            let fill_value = fill_value.or(fill_missing_with_null.then(String::new));
+            let read_options = ReadOptions::default()
+                .strip_nul(strip_nul)
+                .threads(threads)
+                .mmap(mmap)
+                .flexible(fill_value.is_some())
+                .comment(comment);
+            let read_options = read_options.delimiter(input_delimiter(
+                &input,
+                delimiter,
+                read_options,
+                delimiter_detect_report,
+            )?);
+
+            let unicode_form = normalize_unicode.map(NormalizationForm::from);
+            let pipeline = RankPipeline::new()
+                .strip_nul(strip_nul)
+                .rename_case_collisions(rename_by_case)
+                .preserve_original_header_case(preserve_original_header_case)
+                .type_row(type_row)
+                .fill_missing(fill_value.clone())
+                .dedup_normalized(dedup_trimmed)
+                .decimal_comma(decimal_comma)
+                .canonical_decimal(canonical_decimal)
+                .verify_unicode_normalization(verify_utf8_normalization)
+                .normalize_unicode(unicode_form)
+                .concat_key(concat_key.map(|columns| {
+                    ConcatKey::new(columns.split(',').map(str::trim), &key_name)
+                        .separator(&key_separator)
+                        .drop_columns(drop_key_columns)
+                }))
+                .row_hashes(emit_row_hashes.as_deref().map(|name| {
+                    let row_hashes = RowHashes::new(name);
+                    match &row_hash_key {
+                        Some(columns) => row_hashes.key(columns.split(',').map(str::trim)),
+                        None => row_hashes,
+                    }
+                }))
+                .sort_stable(sort_stability_guarantee);
+
+            let mut required = require_column;
+            if let Some(schema_path) = require_columns_from_schema {
+                let required_schema = read_schema(&schema_path)?;
+                required.extend(required_schema.columns.into_iter().map(|col| col.name));
+            }
+
+            if dry_run {
+                let CsvInput { headers, rows, .. } = read_csv(&input, read_options)?;
+                require_columns(&headers, &required)?;
+                let ranked_columns = rank_columns(&headers, &rows, options.clone())?;
+                let plan = plan_rank(&headers, &rows, &ranked_columns, options)?;
+                print_plan(&plan, format)?;
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            if estimate {
+                let reader: Box<dyn Read> = if input == "-" {
+                    Box::new(io::stdin())
+                } else {
+                    Box::new(
+                        File::open(&input)
+                            .with_context(|| format!("Failed to open file: {:?}", input))?,
+                    )
+                };
+                let named = csv_error_in(&input);
+                let mut records = csv_records(reader, read_options).map_err(&named)?;
+                let mut estimator = SizeEstimator::new(records.headers());
+                for record in records.by_ref() {
+                    estimator.push_record(&record.map_err(&named)?);
+                }
+                print_estimate(&estimator.finish(available_memory()), format)?;
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let overrides = match &cardinalities {
+                Some(_) if rank_by == RankBy::Entropy => {
+                    anyhow::bail!("--cardinalities needs --rank-by cardinality")
+                }
+                Some(path) => read_cardinality_overrides(path)?,
+                None => BTreeMap::new(),
+            };
 
-            // Reorder data
-            let (new_headers, new_rows) =
-                reorder_data(&headers, &rows, &ranked_columns).map_err(IntoAnyhow::into_anyhow)?;
+            let mut profile = Profile::new(options.clone());
+            verbose!("Ranking options: {}", serde_json::to_string(&options)?);
 
-            // Sort rows canonically
-            let sorted_rows = sort_rows_canonical(&new_rows);
+            let show_progress = log::enabled(log::Level::Normal)
+                && (show_progress || (!no_progress && io::stderr().is_terminal()));
+            let progress: Box<dyn ProgressSink> = if show_progress {
+                Box::new(StderrProgress::new())
+            } else {
+                Box::new(NoProgress)
+            };
+            // --check runs the whole pipeline below and compares the output
+            // with these bytes instead of writing it; --determinism-check
+            // reads stdin from them a second time
+            let input_bytes = (check || (determinism_check && input == "-"))
+                .then(|| read_input_bytes(&input))
+                .transpose()?;
+            let check_bytes = input_bytes.as_deref().filter(|_| check);
+            let csv = profile.time("read", || match &input_bytes {
+                Some(bytes) => Ok(read_csv_reader(bytes.as_slice(), read_options)
+                    .map_err(csv_error_in(&input))?),
+                None => read_csv_with_progress(&input, read_options, progress.as_ref()),
+            })?;
+            require_columns(&csv.headers, &required)?;
+            let original = check.then(|| (csv.headers.clone(), csv.rows.clone()));
+            // A canonical file keeps the front matter it was checked with
+            let kept_front_matter = csv.front_matter.clone().filter(|_| check && !embed_schema);
+            verbose!(
+                "Read {} row(s) x {} column(s) from {}",
+                csv.rows.len(),
+                csv.headers.len(),
+                input
+            );
+            let (mut doc, prepared) = pipeline.prepare(csv)?;
+            report_prepared(&prepared, fill_value.as_deref(), unicode_form);
+            if !warn_if_cardinality_exceeds.is_empty() {
+                let exceeded = check_cardinality_limits(
+                    doc.headers(),
+                    doc.rows(),
+                    &warn_if_cardinality_exceeds,
+                    options.clone(),
+                )?;
+                for limit in &exceeded {
+                    if fail_if_cardinality_exceeds {
+                        eprintln!("{ERROR}Error:{ERROR:#} {}", limit);
+                    } else {
+                        warnings::warn(limit);
+                    }
+                }
+                if fail_if_cardinality_exceeds && !exceeded.is_empty() {
+                    anyhow::bail!("{} column(s) above their cardinality limit", exceeded.len());
+                }
+            }
+            let headers = doc.headers().to_vec();
+
+            let rerun = determinism_check.then(|| DeterminismCheck {
+                input: &input,
+                bytes: input_bytes.as_deref(),
+                read_options,
+                pipeline: &pipeline,
+                options: options.clone(),
+                overrides: overrides.clone(),
+                previous: output_delta.as_deref(),
+                data_format,
+            });
+            let reports = CountReports {
+                stats: stats_out.is_some(),
+                fingerprint: output_column_fingerprint.is_some(),
+                nulls: nulls_report.is_some(),
+            };
+            if cache_dir.is_some() && input == "-" {
+                warnings::warn("ignoring --cache-dir: stdin has no path to key the cache on");
+            }
+            let counted = match cache_dir.as_ref().filter(|_| input != "-") {
+                Some(cache_dir) => rank_document_cached(
+                    &mut doc,
+                    options.clone(),
+                    &mut profile,
+                    progress.as_ref(),
+                    cache_dir,
+                    &input,
+                    reports,
+                )?,
+                None => rank_document(
+                    &mut doc,
+                    options.clone(),
+                    overrides,
+                    &mut profile,
+                    progress.as_ref(),
+                    reports,
+                )?,
+            };
+            if let Some(previous_path) = &output_delta {
+                let removed;
+                (doc, removed) = delta_since(doc, previous_path, read_options, &options)?;
+                info!(
+                    "Delta: {} new or changed row(s), {} removed or changed",
+                    doc.rows().len(),
+                    removed.len()
+                );
+                if let Some(path) = &deleted_output {
+                    write_csv(doc.headers(), &removed, Some(path))?;
+                    info!("Removed rows written to: {}", path.display());
+                }
+            }
+            if emit_row_hashes.is_some()
+                && row_hash_key.is_none()
+                && key_column_names(&doc.schema().columns).is_empty()
+            {
+                anyhow::bail!("--emit-row-hashes needs key columns: name them with --row-hash-key");
+            }
+            let mut rsf_schema = pipeline.finish(&mut doc, &prepared)?;
+            if let Some(rerun) = rerun {
+                profile.time("determinism-check", || rerun.run(&doc, &rsf_schema))?;
+            }
+            let ranked_columns = &doc.schema().columns;
+
+            if output_delta.is_some() {
+                // The delta keeps the full data's ranking, so its columns are
+                // checked against the full data's cardinalities
+                rsf_schema.row_count = doc.rows().len();
+                for col in &mut rsf_schema.columns {
+                    col.cardinality_overridden = true;
+                }
+            }
+            rsf_schema.also_available_as_tsv = emit_tsv_alongside.clone();
+            // Front matter must come first for readers to find it
+            let mut preamble = Vec::new();
+            if embed_schema {
+                write_front_matter(&mut preamble, &serde_yaml::to_string(&rsf_schema)?)?;
+            }
+            if let Some(yaml) = &kept_front_matter {
+                write_front_matter(&mut preamble, yaml)?;
+            }
+            if emit_metadata_header {
+                // --check regenerates the lines but keeps the time they were
+                // written
+                let ranked_at = check_bytes
+                    .and_then(metadata_ranked_at)
+                    .unwrap_or_else(changelog::now_utc);
+                let header = metadata_header(&rsf_schema.version, &ranked_at, doc.headers());
+                preamble.extend(header.bytes());
+            }
+
+            if let (Some(bytes), Some(original)) = (check_bytes, original) {
+                let mut ranked_bytes = preamble;
+                write_rows_to_with_progress(
+                    &mut ranked_bytes,
+                    data_format,
+                    doc.headers(),
+                    doc.rows(),
+                    &NoProgress,
+                )?;
+                return check_canonical(&input, bytes, &ranked_bytes, original, &doc, options);
+            }
 
             // Write output
-            write_csv(&new_headers, &sorted_rows, output.as_deref())?;
+            let written = profile.time("write", || {
+                match (chunk_rows, &output, &emit_tsv_alongside) {
+                    (Some(chunk_rows), Some(dir), _) => {
+                        rsf_schema.chunks =
+                            write_csv_chunks(dir, doc.headers(), doc.rows(), chunk_rows.get())?;
+                        info!(
+                            "{} part(s) written to: {}",
+                            rsf_schema.chunks.len(),
+                            dir.display()
+                        );
+                        Ok(())
+                    }
+                    (_, _, Some(tsv_path)) => {
+                        write_csv_and_tsv(
+                            doc.headers(),
+                            doc.rows(),
+                            output.as_deref(),
+                            &preamble,
+                            tsv_path,
+                            progress.as_ref(),
+                        )?;
+                        info!("TSV written to: {}", tsv_path.display());
+                        Ok(())
+                    }
+                    _ => write_rows_with_progress(
+                        doc.headers(),
+                        doc.rows(),
+                        output.as_deref(),
+                        data_format,
+                        &preamble,
+                        progress.as_ref(),
+                    ),
+                }
+            });
+            // Ends the bar's line before anything else is printed
+            drop(progress);
+            if let Some(temp) = &temp_output {
+                if written.is_err() {
+                    let _ = std::fs::remove_file(temp);
+                }
+            }
+            written?;
+            let replaced = match &temp_output {
+                Some(temp) => replace_in_place(temp, Path::new(&input), !no_backup)?,
+                None => true,
+            };
 
             // Generate schema if requested
-            if schema {
-                let schema_path = output
-                    .as_ref()
-                    .map(|p| PathBuf::from(format!("{}.schema.yaml", p.display())))
-                    .unwrap_or_else(|| PathBuf::from("output.schema.yaml"));
-
-                write_schema(&ranked_columns, &schema_path).map_err(IntoAnyhow::into_anyhow)?;
-                eprintln!("Schema written to: {}", schema_path.display());
-            }
-
-            // Print stats to stderr
-            eprintln!("\n=== RSF Ranking Complete ===");
-            eprintln!("Columns ranked by cardinality (highest → lowest):\n");
-            for (rank, col) in ranked_columns.iter().enumerate() {
-                eprintln!(
-                    "  {}. {} (cardinality: {})",
-                    rank + 1,
-                    col.name,
-                    col.cardinality
+            if let Some(schema_path) = schema_path {
+                let unchanged = !replaced
+                    && std::fs::read_to_string(&schema_path)
+                        .is_ok_and(|old| serde_yaml::to_string(&rsf_schema).ok() == Some(old));
+                if !unchanged {
+                    write_schema(&rsf_schema, &schema_path)?;
+                    info!("Schema written to: {}", schema_path.display());
+                }
+            }
+
+            let permutation = column_permutation(&headers, ranked_columns)?;
+
+            if let Some(mapping_path) = emit_inverse_mapping {
+                write_inverse_mapping(&permutation, &mapping_path)?;
+                info!("Inverse mapping written to: {}", mapping_path.display());
+            }
+
+            if let Some(map_path) = output_index_map {
+                write_index_map(&permutation, &map_path, false)?;
+                info!("Index map written to: {}", map_path.display());
+            }
+
+            if let Some(map_path) = output_inverse_index_map {
+                write_index_map(&permutation, &map_path, true)?;
+                info!("Inverse index map written to: {}", map_path.display());
+            }
+
+            if let Some(log_path) = emit_change_log {
+                ChangeLogEntry::new(
+                    &input,
+                    target.as_deref(),
+                    doc.rows().len(),
+                    ranked_columns.len(),
+                    &doc.schema().version,
+                )
+                .append(&log_path)?;
+                info!("Change log appended to: {}", log_path.display());
+            }
+
+            if let Some(keys_path) = output_sorted_keys_only {
+                let key_count = key_count.get().min(doc.headers().len());
+                let keys = distinct_keys(doc.rows(), key_count);
+                write_csv(&doc.headers()[..key_count], &keys, Some(&keys_path))?;
+                info!(
+                    "{} distinct key(s) written to: {}",
+                    keys.len(),
+                    keys_path.display()
                 );
             }
-            eprintln!("\nRows sorted canonically by key columns.");
+
+            if let Some(dot_path) = dot {
+                clobber::write(&dot_path, render_dot(ranked_columns))?;
+                info!("DOT graph written to: {}", dot_path.display());
+            }
+
+            if let Some(er_path) = emit_mermaid_er {
+                let title = target
+                    .as_deref()
+                    .or((input != "-").then(|| Path::new(&input)))
+                    .and_then(Path::file_name)
+                    .map_or("stdin".into(), |name| name.to_string_lossy().into_owned());
+                clobber::write(&er_path, render_mermaid_er(&title, ranked_columns))?;
+                info!("Mermaid ER diagram written to: {}", er_path.display());
+            }
+
+            if let (Some(stats_path), Some(report)) = (stats_out, counted.stats) {
+                clobber::write(&stats_path, stats_json(&report)?)?;
+                info!("Stats written to: {}", stats_path.display());
+            }
+
+            if let (Some(path), Some(fingerprint)) =
+                (output_column_fingerprint, counted.fingerprint)
+            {
+                clobber::write(&path, serde_json::to_string_pretty(&fingerprint)? + "\n")?;
+                info!("Column fingerprint written to: {}", path.display());
+            }
+
+            if let (Some(path), Some(report)) = (nulls_report, counted.nulls) {
+                clobber::write(&path, serde_json::to_string_pretty(&report)? + "\n")?;
+                info!("Nulls report written to: {}", path.display());
+            }
+
+            profile.record_counts(doc.rows().len(), ranked_columns);
+            profile.print_timings();
+            if let Some(profile_path) = profile_path {
+                profile.write(&profile_path)?;
+                info!("Profile written to: {}", profile_path.display());
+            }
+
+            print_ranking_summary(ranked_columns);
         }
 
-        Commands::Validate { input, schema } => {
-            let schema_path = schema.unwrap_or_else(|| {
-                let mut p = input.clone();
-                p.set_extension("schema.yaml");
-                p
-            });
+        Commands::Normalize {
+            input,
+            output,
+            schema,
+            decimal_comma,
+            strip_nul,
+        } => {
+            let schema_path = schema.then(|| schema_path_for(output.as_deref()));
+            check_output_paths(&input, output.as_deref())?;
+            if let Some(schema_path) = &schema_path {
+                clobber::check(schema_path)?;
+            }
+            let options = ranking_options(true).decimal_comma(decimal_comma).build()?;
+            let csv = read_csv(&input, ReadOptions::default().strip_nul(strip_nul))?;
+            let nul_stripped = strip_nul && !csv.nul_fields.is_empty();
 
-            validate_rsf(&input, &schema_path)?;
-            println!("✓ Valid RSF file");
+            let mut doc = RsfDocument::from(csv);
+            doc.normalize(options)?;
+            doc.sort();
+            write_csv(doc.headers(), doc.rows(), output.as_deref())?;
+
+            if let Some(schema_path) = schema_path {
+                let mut rsf_schema = doc.schema().clone();
+                rsf_schema.nul_stripped = nul_stripped;
+                write_schema(&rsf_schema, &schema_path)?;
+                info!("Schema written to: {}", schema_path.display());
+            }
         }
 
-        Commands::Stats { input } => {
-            let (headers, rows) = read_csv_file(&input)?;
-            let options = ranking_options(true);
-            let stats = rank_columns(&headers, &rows, options).map_err(IntoAnyhow::into_anyhow)?;
+        Commands::Transpose {
+            input,
+            output,
+            back,
+            rank,
+            max_cells,
+        } => {
+            check_output_paths(&input, output.as_deref())?;
+            let CsvInput { headers, rows, .. } = read_csv(&input, ReadOptions::default())?;
+
+            let cells = headers.len().saturating_mul(rows.len());
+            if cells > max_cells {
+                anyhow::bail!(
+                    "Refusing to transpose {} cells (limit {}); raise --max-cells to proceed",
+                    cells,
+                    max_cells
+                );
+            }
+
+            let (new_headers, new_rows) = if back {
+                untranspose(&headers, &rows)?
+            } else {
+                transpose(&headers, &rows)
+            };
+
+            let mut doc = RsfDocument::new(new_headers, new_rows);
+            if rank {
+                let options = ranking_options(true).build()?;
+                rank_document(
+                    &mut doc,
+                    options.clone(),
+                    BTreeMap::new(),
+                    &mut Profile::new(options),
+                    &NoProgress,
+                    CountReports::default(),
+                )?;
+                print_ranking_summary(&doc.schema().columns);
+            }
+
+            write_csv(doc.headers(), doc.rows(), output.as_deref())?;
+        }
+
+        Commands::Align {
+            input,
+            reference,
+            output,
+        } => {
+            check_output_paths(&input, output.as_deref())?;
+            let reference = read_schema(&reference)?;
+            let CsvInput { headers, rows, .. } = read_csv(&input, ReadOptions::default())?;
+            let (headers, rows) = align_columns(&headers, rows, &reference.columns)?;
+            write_csv(&headers, &rows, output.as_deref())?;
+        }
+
+        Commands::Config {
+            command: ConfigCommand::Show { args },
+        } => {
+            let args: Vec<OsString> = std::iter::once(OsString::from("rsf")).chain(args).collect();
+            let matches = Cli::command()
+                .try_get_matches_from(&args)
+                .unwrap_or_else(|err| config::name_env_vars(&Cli::command(), &args, err).exit());
+            // --config and --no-config apply before or after `show`
+            let config_path = matches.get_one::<PathBuf>("config").cloned().or(cli.config);
+            let no_config = matches.get_flag("no_config") || cli.no_config;
+            let (matches, from_file) = apply_config(matches, args, config_path, no_config)?;
+            for setting in effective_settings(&matches, &from_file) {
+                println!("{}", setting);
+            }
+        }
+
+        Commands::Validate {
+            inputs,
+            schema,
+            cardinality_band,
+            allow_reordered_key_columns,
+            report,
+            check_encoding,
+            fix_encoding,
+            comment,
+            row_count_file,
+            row_count_tolerance,
+            context,
+            format,
+            fix,
+        } => {
+            if inputs.len() > 1 && (report.is_some() || row_count_file.is_some()) {
+                anyhow::bail!("--report and --row-count-file take a single input");
+            }
+            let fix_path = |input: &Path| match &fix {
+                Some(Some(path)) => Some(path.clone()),
+                Some(None) => Some(input.with_extension("fixed.csv")),
+                None => None,
+            };
+            if inputs.len() > 1 && matches!(fix, Some(Some(_))) {
+                anyhow::bail!("--fix=FILE takes a single input");
+            }
+            if format == ValidateFormat::Tap
+                && matches!(&fix, Some(Some(path)) if path == Path::new("-"))
+            {
+                anyhow::bail!("--fix=- would mix the fixed file into the TAP output");
+            }
+
+            let read_options = ReadOptions::default().comment(comment);
+            let validate = |input: &Path| -> Result<bool> {
+                let csv = match check_encoding {
+                    Some(Encoding::Utf8) => {
+                        let bytes = std::fs::read(input)
+                            .with_context(|| format!("Failed to open file: {:?}", input))?;
+                        let bytes = if fix_encoding {
+                            let (text, replaced) = repair_utf8(&bytes);
+                            if replaced > 0 {
+                                info!("Replaced {} invalid UTF-8 sequence(s)", replaced);
+                            }
+                            text.into_bytes()
+                        } else {
+                            check_utf8(&bytes)?;
+                            bytes
+                        };
+                        read_csv_reader(bytes.as_slice(), read_options)
+                            .map_err(csv_error_in(input))?
+                    }
+                    None => read_csv(input, read_options)?,
+                };
+
+                // A schema embedded in the input stands in for the default file
+                let schema_path = match &schema {
+                    Some(path) => Some(path.clone()),
+                    None if csv.front_matter.is_some() => None,
+                    None => {
+                        let mut p = input.to_path_buf();
+                        p.set_extension("schema.yaml");
+                        Some(p)
+                    }
+                };
+                match &schema_path {
+                    Some(path) => verbose!("Schema file: {}", path.display()),
+                    None => verbose!("Schema: embedded front matter"),
+                }
+
+                let row_count = row_count_file
+                    .as_ref()
+                    .map(|path| -> Result<RowCountCheck> {
+                        Ok(RowCountCheck {
+                            expected: read_row_count(path)?,
+                            tolerance: row_count_tolerance,
+                            actual: csv.rows.len(),
+                        })
+                    })
+                    .transpose()?;
+
+                let fixed_path = fix_path(input);
+                let fix_input = fixed_path.as_ref().map(|_| csv.clone());
+                let validation = validate_rsf(
+                    csv,
+                    input,
+                    schema_path.as_deref(),
+                    Leniency {
+                        cardinality_band,
+                        any_key_order: allow_reordered_key_columns,
+                    },
+                    row_count,
+                    context,
+                    report.as_deref(),
+                )?;
+                if let (Some(path), Some(csv)) = (&fixed_path, fix_input) {
+                    if !validation.structure {
+                        write_fixed(csv, validation.options.clone(), path)?;
+                    }
+                }
+                Ok(validation.valid())
+            };
+
+            let mut all_valid = true;
+            match format {
+                ValidateFormat::Text => {
+                    for input in &inputs {
+                        if !validate(input)? {
+                            all_valid = false;
+                        } else if inputs.len() == 1 {
+                            println!("{OK}✓ Valid RSF file{OK:#}");
+                        } else {
+                            println!("{OK}✓{OK:#} {}", input.display());
+                        }
+                    }
+                }
+                ValidateFormat::Tap => {
+                    // A file that cannot be read fails its own test point
+                    // instead of stopping the run
+                    println!("1..{}", inputs.len());
+                    for (number, input) in (1..).zip(&inputs) {
+                        let outcome = validate(input);
+                        let valid = matches!(outcome, Ok(true));
+                        all_valid &= valid;
+                        let status = if valid { "ok" } else { "not ok" };
+                        println!("{} {} - {}", status, number, input.display());
+                        if let Err(err) = outcome {
+                            for line in format!("{:#}", err).lines() {
+                                println!("# {}", line);
+                            }
+                        }
+                    }
+                }
+            }
+            if !all_valid {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+
+        Commands::SchemaLint { schema, strict } => {
+            let schema = read_schema(&schema)?;
+            let lints = lint_schema(&schema);
+            for lint in &lints {
+                if lint.severity == Severity::Warning {
+                    warnings::count();
+                    info!("{}", lint);
+                } else {
+                    eprintln!("{}", lint);
+                }
+            }
+
+            let errors = lints
+                .iter()
+                .filter(|lint| strict || lint.severity == Severity::Error)
+                .count();
+            if errors > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+            match lints.len() {
+                0 => println!("{OK}✓ No problems found{OK:#}"),
+                1 => println!("{OK}✓ No errors (1 warning){OK:#}"),
+                warnings => println!("{OK}✓ No errors ({} warnings){OK:#}", warnings),
+            }
+        }
+
+        Commands::Hist {
+            input,
+            column,
+            bins,
+            top,
+            decimal_comma,
+        } => {
+            let CsvInput { headers, rows, .. } = read_csv(&input, ReadOptions::default())?;
+            let idx = headers
+                .iter()
+                .position(|h| *h == column)
+                .ok_or_else(|| RsfError::missing_column(&column))?;
+
+            let mut stats = ColumnStats::new(&column);
+            for row in &rows {
+                stats.add_value(row.get(idx).map_or("", String::as_str));
+            }
+
+            match histogram(&stats, bins.get(), top, decimal_comma) {
+                Histogram::Numeric(hist) => {
+                    if hist.other > 0 {
+                        warnings::warn(format_args!(
+                            "{} non-numeric value(s) in '{}' counted as other",
+                            hist.other, column
+                        ));
+                    }
+                    print_numeric_histogram(&column, &hist);
+                }
+                Histogram::Categorical(categories) => {
+                    print_categories(&column, &categories);
+                }
+            }
+        }
+
+        Commands::Stats {
+            input,
+            combine,
+            functional_dep,
+            detect_pii: pii,
+            schema: pii_schema,
+            strip_nul,
+            threads,
+            mmap,
+            compare,
+            compare_null_modes: null_modes,
+            nulls,
+            null_values,
+            null_values_ignore_case,
+            max_distinct_per_column,
+            top_values_per_column,
+            output_json,
+            format,
+        } => {
+            let read_options = ReadOptions::default()
+                .strip_nul(strip_nul)
+                .threads(threads)
+                .mmap(mmap);
+            let options = stats_options(null_values, null_values_ignore_case)?;
+            if nulls {
+                let file = File::open(&input)
+                    .with_context(|| format!("Failed to open file: {:?}", input))?;
+                let named = csv_error_in(&input);
+                let mut records = csv_records(file, read_options).map_err(&named)?;
+                let mut counter = NullCounter::new(records.headers()).with_options(options);
+                for record in records.by_ref() {
+                    counter.push_record(&record.map_err(&named)?);
+                }
+                print_null_report(&counter.finish(), format)?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            let CsvInput { headers, rows, .. } = read_csv(&input, read_options)?;
+
+            if let Some(top) = top_values_per_column {
+                let stats = compute_cardinality(&headers, &rows, options)?;
+                let catalog: Vec<CatalogColumn> = stats
+                    .iter()
+                    .map(|stats| CatalogColumn::from_stats(stats, rows.len(), top))
+                    .collect();
+                let json = serde_json::to_string_pretty(&catalog)? + "\n";
+                match output_json {
+                    Some(path) => {
+                        clobber::write(&path, json)?;
+                        info!("Catalog profile written to: {}", path.display());
+                    }
+                    None => print!("{}", json),
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            if format == ReportFormat::Json {
+                if max_distinct_per_column.is_some() {
+                    anyhow::bail!("--max-distinct-per-column applies to the text report");
+                }
+                let stats = compute_cardinality(&headers, &rows, options.clone())?;
+                print!(
+                    "{}",
+                    stats_json(&StatsReport::from_stats(&stats, rows.len(), &options))?
+                );
+                return Ok(ExitCode::SUCCESS);
+            }
+            // Names of the columns whose cardinality stopped at the cap
+            let (stats, capped) = match max_distinct_per_column {
+                Some(max) => {
+                    let mut ranker = RsfRanker::new(options.clone())
+                        .with_max_distinct(max.get())
+                        .with_headers(&headers);
+                    for row in &rows {
+                        ranker.push_record(row);
+                    }
+                    let capped: HashSet<String> = ranker
+                        .stats()
+                        .iter()
+                        .filter(|stat| stat.is_capped())
+                        .map(|stat| stat.name.clone())
+                        .collect();
+                    (ranker.finish().0, capped)
+                }
+                None => (
+                    rank_columns(&headers, &rows, options.clone())?,
+                    HashSet::new(),
+                ),
+            };
+
+            if let Some(compare_path) = compare {
+                let other = read_csv(&compare_path, read_options)?;
+                let other_stats = rank_columns(&other.headers, &other.rows, options.clone())?;
+
+                let drift = compare_cardinality(&stats, &other_stats);
+
+                println!("\n=== Cardinality Drift ===\n");
+                println!(
+                    "{:<20} {:>12} {:>12} {:>8}",
+                    "Column", "Before", "After", "Delta"
+                );
+                println!("{}", "-".repeat(55));
+
+                for d in &drift {
+                    let name = match (d.before, d.after) {
+                        (None, _) => format!("+{}", d.name),
+                        (_, None) => format!("-{}", d.name),
+                        _ => d.name.clone(),
+                    };
+                    let show = |c: Option<usize>| c.map_or("-".to_string(), |c| c.to_string());
+                    println!(
+                        "{:<20} {:>12} {:>12} {:>+8}",
+                        name,
+                        show(d.before),
+                        show(d.after),
+                        d.delta()
+                    );
+                }
+
+                if drift.iter().any(|d| d.is_changed()) {
+                    return Ok(ExitCode::FAILURE);
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
 
             println!("\n=== Column Statistics ===\n");
             println!("{:<20} {:>12}", "Column", "Cardinality");
             println!("{}", "-".repeat(34));
 
             for stat in stats {
-                println!("{:<20} {:>12}", stat.name, stat.cardinality);
+                let cardinality = if capped.contains(&stat.name) {
+                    format!(">{} (capped)", stat.cardinality)
+                } else {
+                    stat.cardinality.to_string()
+                };
+                println!("{:<20} {:>12}", stat.name, cardinality);
+            }
+
+            if !combine.is_empty() {
+                println!("\n=== Combined Keys ===\n");
+                println!("{:<20} {:>12}", "Columns", "Cardinality");
+                println!("{}", "-".repeat(34));
+
+                for group in combine {
+                    let columns: Vec<String> =
+                        group.split(',').map(|c| c.trim().to_string()).collect();
+                    let stat =
+                        compute_combined_cardinality(&headers, &rows, &columns, options.clone())?;
+                    println!("{:<20} {:>12}", stat.name, stat.cardinality());
+                }
+            }
+
+            if !functional_dep.is_empty() {
+                println!("\n=== Functional Dependencies ===\n");
+                println!("{:<30} {:>10} {:>10}", "Columns", "Conflicts", "Reverse");
+                println!("{}", "-".repeat(52));
+
+                for pair in functional_dep {
+                    let Some((left, right)) = pair.split_once(',') else {
+                        anyhow::bail!("--functional-dep needs two columns, e.g. col1,col2");
+                    };
+                    let dep = functional_dependency(&headers, &rows, left.trim(), right.trim())?;
+                    println!(
+                        "{:<30} {:>10} {:>10}",
+                        format!("{},{}", dep.left, dep.right),
+                        dep.left_conflicts,
+                        dep.right_conflicts
+                    );
+                    println!("  {}", dep);
+                }
+            }
+
+            if pii {
+                let findings: Vec<PiiFinding> =
+                    compute_cardinality(&headers, &rows, options.clone())?
+                        .iter()
+                        .filter_map(|stats| detect_pii(stats, PII_SAMPLE))
+                        .collect();
+
+                println!("\n=== Personal Data ===\n");
+                if findings.is_empty() {
+                    println!("{OK}✓ No column looks like personal data{OK:#}");
+                } else {
+                    println!(
+                        "{:<20} {:<12} {:>12} {:>8}",
+                        "Column", "Looks like", "Matched", "Risk"
+                    );
+                    println!("{}", "-".repeat(55));
+                    for finding in &findings {
+                        println!(
+                            "{:<20} {:<12} {:>12} {:>8}",
+                            finding.column,
+                            finding.kind.to_string(),
+                            format!("{}/{}", finding.matched, finding.sampled),
+                            finding.risk.to_string()
+                        );
+                    }
+                }
+
+                if let Some(schema_path) = pii_schema {
+                    let mut rsf_schema = read_schema(&schema_path)?;
+                    for col in &mut rsf_schema.columns {
+                        let finding = findings.iter().find(|f| f.column == col.name);
+                        col.pii_risk = finding.map(|f| f.risk);
+                        col.tags.retain(|tag| tag != PII_TAG);
+                        if finding.is_some() {
+                            col.tags.push(PII_TAG.to_string());
+                        }
+                    }
+                    write_schema(&rsf_schema, &schema_path)?;
+                    info!("PII assessment written to: {}", schema_path.display());
+                }
+            }
+
+            if null_modes {
+                let shifts = compare_null_modes(&headers, &rows, options)?;
+                let moved: Vec<_> = shifts.iter().filter(|s| s.is_moved()).collect();
+
+                println!("\n=== Null Modes ===\n");
+                if moved.is_empty() {
+                    println!("{OK}✓ Column order is the same with nulls distinct or folded{OK:#}");
+                } else {
+                    println!(
+                        "{:<20} {:>18} {:>18}",
+                        "Column", "Distinct (rank)", "Folded (rank)"
+                    );
+                    println!("{}", "-".repeat(58));
+                    for s in moved {
+                        println!(
+                            "{:<20} {:>18} {:>18}",
+                            s.name,
+                            format!("{} ({})", s.distinct_cardinality, s.distinct_rank),
+                            format!("{} ({})", s.folded_cardinality, s.folded_rank)
+                        );
+                    }
+                }
             }
         }
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
 
-fn read_csv(input: &str) -> Result<(Vec<String>, Vec<Vec<String>>)> {
-    if input == "-" {
-        read_csv_reader(io::stdin())
-    } else {
-        read_csv_file(&PathBuf::from(input))
+/// Parse the command line, filling unset flags from the config file
+fn parse_cli(matches: ArgMatches) -> Result<Cli> {
+    let config_path = matches.get_one::<PathBuf>("config").cloned();
+    let no_config = matches.get_flag("no_config");
+    let (matches, from_file) = apply_config(
+        matches,
+        std::env::args_os().collect(),
+        config_path,
+        no_config,
+    )?;
+
+    let settings = effective_settings(&matches, &from_file);
+    if settings
+        .iter()
+        .any(|setting| setting.source > Source::Default)
+    {
+        verbose!("Options set for this run:");
+        for setting in settings.iter().filter(|s| s.source > Source::Default) {
+            verbose!("  {}", setting);
+        }
+    }
+
+    Ok(Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit()))
+}
+
+/// Parse `args` again with the config file's flags added, unless the file is
+/// disabled or none is found; returns the new matches and the added flags
+fn apply_config(
+    matches: ArgMatches,
+    mut args: Vec<OsString>,
+    config_path: Option<PathBuf>,
+    no_config: bool,
+) -> Result<(ArgMatches, Vec<String>)> {
+    if no_config {
+        return Ok((matches, Vec::new()));
+    }
+    let config_path = match config_path {
+        Some(path) => path,
+        None => match config::discover(&std::env::current_dir()?) {
+            Some(path) => path,
+            None => return Ok((matches, Vec::new())),
+        },
+    };
+
+    let config = config::load(&config_path)?;
+    verbose!("Config file: {}", config_path.display());
+    let extra_args = config::config_args(&Cli::command(), &matches, &config)?;
+    if extra_args.is_empty() {
+        return Ok((matches, extra_args));
     }
+
+    // Insert before any `--` so the flags are not taken as positionals
+    let end = args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(args.len());
+    args.splice(end..end, extra_args.iter().map(OsString::from));
+
+    Ok((Cli::command().get_matches_from(args), extra_args))
 }
 
-fn read_csv_file(path: &PathBuf) -> Result<(Vec<String>, Vec<Vec<String>>)> {
-    let file = File::open(path).with_context(|| format!("Failed to open file: {:?}", path))?;
-    read_csv_reader(BufReader::new(file))
+/// Every option of the invoked subcommand with its value and source
+fn effective_settings(matches: &ArgMatches, from_file: &[String]) -> Vec<Setting> {
+    let command = Cli::command();
+    matches
+        .subcommand()
+        .and_then(|(name, sub_matches)| {
+            let sub_command = command.find_subcommand(name)?;
+            Some(config::effective(sub_command, sub_matches, from_file))
+        })
+        .unwrap_or_default()
+}
+
+/// Reports to take from the value counts of the ranking pass
+#[derive(Debug, Clone, Copy, Default)]
+struct CountReports {
+    /// Stats of the ranked columns, for `--stats-out`
+    stats: bool,
+    /// Hash of each column's distinct values, for `--output-column-fingerprint`
+    fingerprint: bool,
+    /// Nulls of the ranked columns, for `--nulls-report`
+    nulls: bool,
 }
 
-fn read_csv_reader<R: io::Read>(reader: R) -> Result<(Vec<String>, Vec<Vec<String>>)> {
-    let mut csv_reader = Reader::from_reader(reader);
+/// The reports a [`CountReports`] asked for
+#[derive(Default)]
+struct Counted {
+    stats: Option<StatsReport>,
+    fingerprint: Option<serde_json::Map<String, serde_json::Value>>,
+    nulls: Option<NullReport>,
+}
 
-    let headers = csv_reader
-        .headers()?
+impl CountReports {
+    /// Take the reports from `stats`, the counts of `doc`'s ranking
+    fn take(
+        self,
+        doc: &RsfDocument,
+        stats: &[ColumnStats],
+        options: &RankingOptions,
+    ) -> Result<Counted> {
+        Ok(Counted {
+            stats: self
+                .stats
+                .then(|| ranked_stats_report(doc, stats, options))
+                .transpose()?,
+            fingerprint: self.fingerprint.then(|| column_fingerprint(doc, stats)),
+            nulls: self.nulls.then(|| ranked_null_report(doc, stats)),
+        })
+    }
+}
+
+/// Rank a document's columns and sort its rows canonically, returning the
+/// `reports` taken from the counts of the ranking pass
+fn rank_document(
+    doc: &mut RsfDocument,
+    options: RankingOptions,
+    overrides: BTreeMap<String, usize>,
+    profile: &mut Profile,
+    progress: &dyn ProgressSink,
+    reports: CountReports,
+) -> Result<Counted> {
+    let stats = profile.time("rank", || {
+        doc.rank_with_overrides(options.clone(), overrides, progress)
+    })?;
+    // Reported before sorting so the counts are freed early
+    let counted = reports.take(doc, &stats, &options)?;
+    drop(stats);
+    profile.time("sort", || doc.sort_with_progress(progress));
+    Ok(counted)
+}
+
+/// Thread count of the second read of `--determinism-check` when the
+/// first was on one thread
+const DETERMINISM_THREADS: usize = 4;
+
+/// The rows of a ranked document that are new or changed since the file at
+/// `previous_path`, with those removed or changed, keeping its schema
+fn delta_since(
+    doc: RsfDocument,
+    previous_path: &Path,
+    read_options: ReadOptions,
+    options: &RankingOptions,
+) -> Result<(RsfDocument, Vec<Vec<String>>)> {
+    let previous = read_csv(previous_path, read_options)?;
+    let (_, previous_rows) = align_columns(&previous.headers, previous.rows, &doc.schema().columns)
+        .with_context(|| format!("{:?} has other columns", previous_path))?;
+    let schema = doc.schema().clone();
+    let (headers, rows) = doc.into_parts();
+    let delta = row_delta(&rows, &previous_rows, options.clone());
+    let doc = RsfDocument::new(headers, delta.added).with_schema(schema);
+    Ok((doc, delta.removed))
+}
+
+/// Everything `rank` ranked its input with, to rank it all over again for
+/// `--determinism-check`
+struct DeterminismCheck<'a> {
+    input: &'a str,
+    /// The input, when it cannot be read a second time
+    bytes: Option<&'a [u8]>,
+    read_options: ReadOptions,
+    pipeline: &'a RankPipeline,
+    options: RankingOptions,
+    overrides: BTreeMap<String, usize>,
+    /// The previous output of `--output-delta`
+    previous: Option<&'a Path>,
+    data_format: DataFormat,
+}
+
+impl DeterminismCheck<'_> {
+    /// Read, prepare, rank and finish the input again and fail unless the
+    /// result is byte for byte `doc` with the schema `schema`
+    ///
+    /// The input is read on another number of threads, so a file large
+    /// enough to be split into chunks is split differently, and counted into
+    /// fresh hash maps, so output that depends on their iteration order
+    /// shows up as a difference.
+    fn run(self, doc: &RsfDocument, schema: &Schema) -> Result<()> {
+        let threads = match self.read_options.threads {
+            1 => DETERMINISM_THREADS,
+            _ => 1,
+        };
+        verbose!(
+            "Determinism check: reading the input on {} thread(s)",
+            threads
+        );
+        let read_options = self.read_options.threads(threads);
+        let csv = match self.bytes {
+            Some(bytes) => {
+                read_csv_reader(bytes, read_options).map_err(csv_error_in(self.input))?
+            }
+            None => read_csv_file(self.input, read_options)?,
+        };
+        let (mut rerun, prepared) = self.pipeline.prepare(csv)?;
+        rank_document(
+            &mut rerun,
+            self.options.clone(),
+            self.overrides,
+            &mut Profile::new(self.options.clone()),
+            &NoProgress,
+            CountReports::default(),
+        )?;
+        if let Some(previous) = self.previous {
+            (rerun, _) = delta_since(rerun, previous, read_options, &self.options)?;
+        }
+        let rerun_schema = self.pipeline.finish(&mut rerun, &prepared)?;
+
+        if serde_yaml::to_string(schema)? != serde_yaml::to_string(&rerun_schema)? {
+            anyhow::bail!(
+                "Determinism check failed: two rankings of the input gave different schemas"
+            );
+        }
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        for (doc, bytes) in [(doc, &mut first), (&rerun, &mut second)] {
+            write_rows_to_with_progress(
+                bytes,
+                self.data_format,
+                doc.headers(),
+                doc.rows(),
+                &NoProgress,
+            )?;
+        }
+        if let Some(offset) = first
+            .iter()
+            .zip(&second)
+            .position(|(a, b)| a != b)
+            .or((first.len() != second.len()).then(|| first.len().min(second.len())))
+        {
+            anyhow::bail!(
+                "Determinism check failed: two rankings of the input differ from byte {}",
+                offset
+            );
+        }
+
+        info!("Determinism check passed: two rankings are byte-identical");
+        Ok(())
+    }
+}
+
+/// Stats of a ranked document in its column order, from the counts in
+/// input order that ranked it
+///
+/// Nulls are told as `rsf stats` would with the same `--null-values`.
+fn ranked_stats_report(
+    doc: &RsfDocument,
+    stats: &[ColumnStats],
+    options: &RankingOptions,
+) -> Result<StatsReport> {
+    let options = stats_options(options.null_values.clone(), options.null_values_ignore_case)?;
+    let by_name: HashMap<&str, &ColumnStats> = stats
         .iter()
-        .map(|s| s.to_string())
+        .map(|stats| (stats.name.as_str(), stats))
         .collect();
+    let ranked = doc
+        .headers()
+        .iter()
+        .filter_map(|name| by_name.get(name.as_str()).copied());
+    Ok(StatsReport::from_stats(ranked, doc.rows().len(), &options))
+}
 
-    let rows: Result<Vec<Vec<String>>> = csv_reader
-        .records()
-        .map(|result| {
+/// Nulls of the ranked columns, as tracked while counting them
+fn ranked_null_report(doc: &RsfDocument, stats: &[ColumnStats]) -> NullReport {
+    let by_name: HashMap<&str, &ColumnStats> = stats
+        .iter()
+        .map(|stats| (stats.name.as_str(), stats))
+        .collect();
+    let ranked = doc
+        .headers()
+        .iter()
+        .filter_map(|name| by_name.get(name.as_str()).copied());
+    NullReport::from_stats(ranked, doc.rows().len())
+}
+
+/// Map each ranked column to the SHA-256 of its distinct values, in rank order
+fn column_fingerprint(
+    doc: &RsfDocument,
+    stats: &[ColumnStats],
+) -> serde_json::Map<String, serde_json::Value> {
+    let by_name: HashMap<&str, &ColumnStats> = stats
+        .iter()
+        .map(|stats| (stats.name.as_str(), stats))
+        .collect();
+    doc.headers()
+        .iter()
+        .filter_map(|name| {
+            let stats = by_name.get(name.as_str())?;
+            Some((name.clone(), stats.fingerprint().into()))
+        })
+        .collect()
+}
+
+/// The delimiter to read `input` with, sniffing it for `--delimiter auto`
+/// and printing what was sniffed for `--delimiter-detect-report`
+fn input_delimiter(
+    input: &str,
+    delimiter: Delimiter,
+    options: ReadOptions,
+    report: bool,
+) -> Result<u8> {
+    if delimiter != Delimiter::Auto && !report {
+        return Ok(options.delimiter);
+    }
+    if input == "-" {
+        anyhow::bail!(
+            "--delimiter auto and --delimiter-detect-report need an input file, not stdin"
+        );
+    }
+
+    let mut sample = Vec::new();
+    File::open(input)
+        .with_context(|| format!("Failed to open file: {:?}", input))?
+        .take(SNIFF_BYTES as u64)
+        .read_to_end(&mut sample)
+        .with_context(|| format!("Failed to read file: {:?}", input))?;
+    let sniffed = sniff_delimiter(&sample, options);
+    if report {
+        print_delimiter_report(&sniffed);
+    }
+
+    match delimiter {
+        Delimiter::Byte(byte) => Ok(byte),
+        Delimiter::Auto => {
+            verbose!("Delimiter: {}", delimiter_name(sniffed.chosen));
+            Ok(sniffed.chosen)
+        }
+    }
+}
+
+fn delimiter_name(delimiter: u8) -> String {
+    match delimiter {
+        b',' => "comma".into(),
+        b'\t' => "tab".into(),
+        b';' => "semicolon".into(),
+        b'|' => "pipe".into(),
+        other => format!("{:?}", char::from(other)),
+    }
+}
+
+/// Print `rank --delimiter-detect-report` to stderr
+fn print_delimiter_report(report: &DelimiterReport) {
+    eprintln!(
+        "\n=== Delimiter Detection ({} records sampled) ===\n",
+        report.records
+    );
+    eprintln!(
+        "{:<12} {:>12} {:>12}  Result",
+        "Delimiter", "Mean fields", "Variance"
+    );
+    eprintln!("{}", "-".repeat(46));
+    for candidate in &report.candidates {
+        let result = if candidate.delimiter == report.chosen {
+            "chosen"
+        } else {
+            "rejected"
+        };
+        eprintln!(
+            "{:<12} {:>12.2} {:>12.2}  {}",
+            delimiter_name(candidate.delimiter),
+            candidate.mean_fields,
+            candidate.variance,
             result
-                .map(|record| record.iter().map(|s| s.to_string()).collect())
-                .context("Failed to read CSV record")
+        );
+    }
+    eprintln!("\nChosen delimiter: {}", delimiter_name(report.chosen));
+}
+
+/// Print `stats --nulls` as a table or JSON
+fn print_null_report(report: &NullReport, format: ReportFormat) -> Result<()> {
+    if format == ReportFormat::Json {
+        println!("{}", serde_json::to_string_pretty(report)?);
+        return Ok(());
+    }
+
+    println!("\n=== Null Counts ({} rows) ===\n", report.row_count);
+    println!("{:<20} {:>12} {:>8}", "Column", "Nulls", "Percent");
+    println!("{}", "-".repeat(42));
+    for col in &report.columns {
+        println!("{:<20} {:>12} {:>7.1}%", col.name, col.nulls, col.percent);
+    }
+    Ok(())
+}
+
+/// Render stats the same way for `stats --format json` and `rank --stats-out`
+fn stats_json(report: &StatsReport) -> Result<String> {
+    Ok(serde_json::to_string_pretty(report)? + "\n")
+}
+
+/// Name of the cache file for `input`: its file name, to tell caches apart
+/// at a glance, then a hash of its canonical path, so that files of the same
+/// name in different directories have caches of their own
+fn cache_file_name(input: &Path) -> Result<String> {
+    let path = input
+        .canonicalize()
+        .with_context(|| format!("Failed to open file: {:?}", input))?;
+    let name = path
+        .file_name()
+        .map_or("input".into(), |name| name.to_string_lossy());
+    let hash = format!("{:x}", Sha256::digest(path.as_os_str().as_encoded_bytes()));
+    Ok(format!("{}-{}.rank-cache.yaml", name, &hash[..16]))
+}
+
+/// Rank and sort with the value counts cached in `cache_dir` for `input`,
+/// then update the cache
+///
+/// A missing, stale or unreadable cache only means every row is counted.
+fn rank_document_cached(
+    doc: &mut RsfDocument,
+    options: RankingOptions,
+    profile: &mut Profile,
+    progress: &dyn ProgressSink,
+    cache_dir: &Path,
+    input: &str,
+    reports: CountReports,
+) -> Result<Counted> {
+    let cache_path = cache_dir.join(cache_file_name(Path::new(input))?);
+    verbose!("Cache file: {}", cache_path.display());
+
+    let cache = match read_cache(&cache_path) {
+        Ok(cache) if cache.covers(doc.headers(), doc.rows(), &options) => {
+            info!(
+                "Cache: {} rows counted before, {} new",
+                cache.records,
+                doc.rows().len() - cache.records
+            );
+            Some(cache)
+        }
+        Ok(_) => {
+            info!("Cache: input changed, counting every row");
+            None
+        }
+        Err(RsfError::IoError { cause, .. }) if cause.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => {
+            warnings::warn(format_args!(
+                "ignoring cache: {:#}",
+                anyhow::Error::from(err)
+            ));
+            None
+        }
+    };
+
+    let cache = profile.time("rank", || doc.rank_cached(options.clone(), cache))?;
+    let counted = reports.take(doc, &cache.columns, &options)?;
+    profile.time("sort", || doc.sort_with_progress(progress));
+
+    std::fs::create_dir_all(cache_dir)
+        .with_context(|| format!("Failed to create cache directory: {:?}", cache_dir))?;
+    write_cache(&cache, &cache_path)?;
+    Ok(counted)
+}
+
+/// Print the ranking summary to stderr
+fn print_ranking_summary(ranked_columns: &[ColumnMeta]) {
+    let by_entropy = ranked_columns.iter().any(|col| col.entropy.is_some());
+
+    info!("\n{HEADING}=== RSF Ranking Complete ==={HEADING:#}");
+    info!(
+        "Columns ranked by {} (highest → lowest):\n",
+        if by_entropy { "entropy" } else { "cardinality" }
+    );
+    for (rank, col) in ranked_columns.iter().enumerate() {
+        match col.entropy {
+            Some(entropy) => info!(
+                "  {}. {} (entropy: {:.3}, cardinality: {})",
+                rank + 1,
+                col.name,
+                entropy,
+                col.cardinality
+            ),
+            None => info!(
+                "  {}. {} (cardinality: {}{})",
+                rank + 1,
+                col.name,
+                col.cardinality,
+                if col.cardinality_overridden {
+                    ", overridden"
+                } else {
+                    ""
+                }
+            ),
+        }
+    }
+    info!("\nRows sorted canonically by key columns.");
+}
+
+/// Width of the longest bar in a histogram
+const HIST_BAR_WIDTH: usize = 40;
+
+fn hist_bar(count: usize, max: usize) -> String {
+    "#".repeat((count * HIST_BAR_WIDTH).div_ceil(max.max(1)))
+}
+
+fn print_numeric_histogram(column: &str, hist: &NumericHistogram) {
+    let (Some(first), Some(last)) = (hist.bins.first(), hist.bins.last()) else {
+        return;
+    };
+    // Enough decimals to tell neighbouring edges apart
+    let width = (last.upper - first.lower) / hist.bins.len() as f64;
+    let decimals = if width > 0.0 {
+        (1 - width.log10().floor() as i32).max(0) as usize
+    } else {
+        0
+    };
+    let edges: Vec<String> = hist
+        .bins
+        .iter()
+        .enumerate()
+        .map(|(idx, bin)| {
+            let close = if idx + 1 == hist.bins.len() { ']' } else { ')' };
+            format!(
+                "[{:.*}, {:.*}{}",
+                decimals, bin.lower, decimals, bin.upper, close
+            )
         })
         .collect();
+    let max = hist.bins.iter().map(|bin| bin.count).max().unwrap_or(0);
+    let label_width = edges.iter().map(String::len).max().unwrap_or(0).max(5);
+
+    println!("\n=== Histogram: {} ===\n", column);
+    for (edge, bin) in edges.iter().zip(&hist.bins) {
+        println!(
+            "{:<label_width$} {:>8}  {}",
+            edge,
+            bin.count,
+            hist_bar(bin.count, max)
+        );
+    }
+    if hist.other > 0 {
+        println!("{:<label_width$} {:>8}", "other", hist.other);
+    }
+    if hist.empty > 0 {
+        println!("{:<label_width$} {:>8}", "empty", hist.empty);
+    }
+}
+
+fn print_categories(column: &str, categories: &Categories) {
+    let max = categories.top.first().map_or(0, |(_, count)| *count);
+    let label_width = categories
+        .top
+        .iter()
+        .map(|(value, _)| value.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(5, 30);
+
+    println!(
+        "\n=== Top values: {} ({} distinct) ===\n",
+        column, categories.distinct
+    );
+    for (value, count) in &categories.top {
+        let value = if value.trim().is_empty() {
+            "(empty)"
+        } else {
+            value
+        };
+        println!(
+            "{:<label_width$} {:>8}  {}",
+            value,
+            count,
+            hist_bar(*count, max)
+        );
+    }
+    if categories.rest > 0 {
+        println!("{:<label_width$} {:>8}", "(rest)", categories.rest);
+    }
+}
 
-    Ok((headers, rows?))
+/// Maximum number of NUL warnings printed before summarizing
+const MAX_NUL_WARNINGS: usize = 10;
+
+/// Read CSV from a file, or from stdin when `input` is `-`
+fn read_csv(input: impl AsRef<Path>, options: ReadOptions) -> Result<CsvInput> {
+    read_csv_with_progress(input, options, &NoProgress)
+}
+
+fn read_csv_with_progress(
+    input: impl AsRef<Path>,
+    options: ReadOptions,
+    progress: &dyn ProgressSink,
+) -> Result<CsvInput> {
+    let input = input.as_ref();
+    let csv = if input == Path::new("-") {
+        read_csv_reader_with_progress(io::stdin(), options, progress)
+    } else {
+        read_csv_file_with_progress(input, options, progress)
+    }?;
+
+    report_nul_bytes(&csv, options.strip_nul);
+    Ok(csv)
+}
+
+/// Schema sidecar of the data written to `output`
+fn schema_path_for(output: Option<&Path>) -> PathBuf {
+    output
+        .map(|p| PathBuf::from(format!("{}.schema.yaml", p.display())))
+        .unwrap_or_else(|| PathBuf::from("output.schema.yaml"))
 }
 
-fn ranking_options(nulls_distinct: bool) -> RankingOptions {
-    if nulls_distinct {
-        RankingOptions {
-            treat_empty_as_null: false,
-            include_nulls: true,
+/// Fail before any work if `output` is the input or would be overwritten
+fn check_output_paths(input: &str, output: Option<&Path>) -> Result<()> {
+    let Some(output) = output else {
+        return Ok(());
+    };
+    if input != "-" {
+        clobber::check_not_input(Path::new(input), output)?;
+    }
+    clobber::check(output)
+}
+
+/// Hidden file next to `input` that `rank --in-place` writes first
+fn in_place_temp_path(input: &Path) -> PathBuf {
+    let name = input.file_name().unwrap_or_default().to_string_lossy();
+    input.with_file_name(format!(".{}.rsf-tmp", name))
+}
+
+/// Move the ranked `temp` over `input`, first copying `input` to
+/// `input.bak` if `backup`; returns false, removing `temp`, if the two are
+/// already the same so neither the input nor its backup is touched
+fn replace_in_place(temp: &Path, input: &Path, backup: bool) -> Result<bool> {
+    if same_contents(temp, input)? {
+        std::fs::remove_file(temp).with_context(|| format!("Failed to remove file: {:?}", temp))?;
+        info!("{} is already canonical; left unchanged", input.display());
+        return Ok(false);
+    }
+
+    if backup {
+        let backup_path = PathBuf::from(format!("{}.bak", input.display()));
+        std::fs::copy(input, &backup_path)
+            .with_context(|| format!("Failed to write backup: {:?}", backup_path))?;
+        info!("Backup written to: {}", backup_path.display());
+    }
+    std::fs::rename(temp, input)
+        .with_context(|| format!("Failed to replace {:?} with {:?}", input, temp))?;
+    info!("Ranked in place: {}", input.display());
+    Ok(true)
+}
+
+/// Bytes of each file compared at a time by [`same_contents`]
+const COMPARE_CHUNK: usize = 64 * 1024;
+
+/// Whether two files hold the same bytes, read a chunk at a time
+fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+    let open =
+        |path: &Path| File::open(path).with_context(|| format!("Failed to read file: {:?}", path));
+    let (mut a_file, mut b_file) = (open(a)?, open(b)?);
+    if a_file.metadata()?.len() != b_file.metadata()?.len() {
+        return Ok(false);
+    }
+    let mut a_chunk = vec![0; COMPARE_CHUNK];
+    let mut b_chunk = vec![0; COMPARE_CHUNK];
+    loop {
+        let read = read_chunk(&mut a_file, &mut a_chunk)
+            .with_context(|| format!("Failed to read file: {:?}", a))?;
+        let b_read = read_chunk(&mut b_file, &mut b_chunk)
+            .with_context(|| format!("Failed to read file: {:?}", b))?;
+        if a_chunk[..read] != b_chunk[..b_read] {
+            return Ok(false);
+        }
+        if read == 0 {
+            return Ok(true);
         }
+    }
+}
+
+/// Fill `chunk` from `reader`, short only at the end of its input
+fn read_chunk(reader: &mut impl Read, chunk: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < chunk.len() {
+        match reader.read(&mut chunk[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/// Read the raw bytes of a file or stdin
+fn read_input_bytes(input: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    if input == "-" {
+        io::stdin().read_to_end(&mut bytes)?;
     } else {
-        RankingOptions {
-            treat_empty_as_null: true,
-            include_nulls: true,
+        File::open(input)
+            .with_context(|| format!("Failed to open file: {:?}", input))?
+            .read_to_end(&mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+/// Name `input` in the CSV errors of reading it; stdin stays unnamed
+fn csv_error_in(input: impl AsRef<Path>) -> impl Fn(RsfError) -> RsfError {
+    let input = input.as_ref().to_path_buf();
+    move |err| {
+        if input == Path::new("-") {
+            err
+        } else {
+            err.with_csv_path(&input)
+        }
+    }
+}
+
+/// Say what `rank` did to prepare its input, and warn about what it found
+fn report_prepared(
+    prepared: &Prepared,
+    fill_value: Option<&str>,
+    unicode_form: Option<NormalizationForm>,
+) {
+    for (old, new) in &prepared.renamed {
+        info!("Renamed column '{}' to '{}'", old, new);
+    }
+    for group in &prepared.case_collisions {
+        warnings::warn(format_args!(
+            "Columns '{}' differ only by case; --rename-case-collisions renames them",
+            group.join("', '")
+        ));
+    }
+    if let (Some(filled), Some(value)) = (prepared.filled, fill_value) {
+        info!("Filled {} missing cell(s) with {:?}", filled, value);
+    }
+    if let Some(collapsed) = prepared.collapsed {
+        info!(
+            "Collapsed {} row(s) equal to an earlier one once trimmed",
+            collapsed
+        );
+    }
+    for conflict in &prepared.normalization_conflicts {
+        warnings::warn(conflict);
+    }
+    if let (Some(changed), Some(form)) = (prepared.normalized, unicode_form) {
+        info!("Normalized {} value(s) to {:?}", changed, form);
+    }
+}
+
+/// Warn about fields that contained NUL bytes
+fn report_nul_bytes(csv: &CsvInput, stripped: bool) {
+    let count = csv.nul_fields.len();
+
+    if stripped {
+        if count > 0 {
+            info!("Stripped NUL bytes from {} field(s)", count);
         }
+        return;
+    }
+
+    for &(row_idx, col_idx) in csv.nul_fields.iter().take(MAX_NUL_WARNINGS) {
+        warnings::warn(format_args!(
+            "NUL byte in row {}, column '{}'",
+            row_idx + 1,
+            csv.headers.get(col_idx).map(String::as_str).unwrap_or("?")
+        ));
+    }
+    if count > MAX_NUL_WARNINGS {
+        warnings::warn(format_args!(
+            "{} more field(s) contain NUL bytes (use --strip-nul to remove them)",
+            count - MAX_NUL_WARNINGS
+        ));
     }
 }
 
+/// Options for `--nulls-distinct`, to be finished by the caller
+fn ranking_options(nulls_distinct: bool) -> RankingOptionsBuilder {
+    RankingOptions::builder()
+        .treat_empty_as_null(!nulls_distinct)
+        .include_nulls(true)
+}
+
+/// Options of `rsf stats`: nulls distinct, unless `null_values` fold them
+fn stats_options(null_values: Vec<String>, ignore_case: bool) -> Result<RankingOptions> {
+    Ok(ranking_options(null_values.is_empty())
+        .null_values(null_values)
+        .null_values_ignore_case(ignore_case)
+        .build()?)
+}
+
 fn write_csv(headers: &[String], rows: &[Vec<String>], output: Option<&Path>) -> Result<()> {
-    let writer: Box<dyn io::Write> = if let Some(path) = output {
-        Box::new(File::create(path)?)
+    write_rows_with_progress(headers, rows, output, DataFormat::Csv, &[], &NoProgress)
+}
+
+/// Format for `--output-format`, going by the extension of `output` for
+/// `auto`; stdout and paths without an extension get CSV
+fn data_format(format: OutputFormat, output: Option<&Path>) -> Result<DataFormat> {
+    match format {
+        OutputFormat::Csv => Ok(DataFormat::Csv),
+        OutputFormat::Tsv => Ok(DataFormat::Tsv),
+        OutputFormat::Jsonl => Ok(DataFormat::Jsonl),
+        #[cfg(feature = "parquet")]
+        OutputFormat::Parquet => Ok(DataFormat::Parquet),
+        OutputFormat::Auto => {
+            let Some(extension) = output.and_then(Path::extension) else {
+                return Ok(DataFormat::Csv);
+            };
+            let extension = extension.to_string_lossy();
+            DataFormat::from_extension(&extension).with_context(|| {
+                format!(
+                    "No output format for .{} files; pick one with --output-format",
+                    extension
+                )
+            })
+        }
+    }
+}
+
+/// The `#key: value` lines of `rank --emit-metadata-header` for a file
+/// with `headers` in rank order
+fn metadata_header(version: &str, ranked_at: &str, headers: &[String]) -> String {
+    let mut lines = format!(
+        "#rsf-version: {}\n#ranked-at: {}\n#column-count: {}\n",
+        version,
+        ranked_at,
+        headers.len()
+    );
+    if let Some(top) = headers.first() {
+        // A line break in the name would end the comment early
+        lines.push_str(&format!("#top-key: {}\n", top.replace(['\r', '\n'], " ")));
+    }
+    lines
+}
+
+/// The `#ranked-at` value among the comment lines and front matter at the
+/// top of `bytes`
+fn metadata_ranked_at(bytes: &[u8]) -> Option<String> {
+    bytes
+        .split(|&byte| byte == b'\n')
+        .take_while(|line| line.starts_with(b"#") || line.starts_with(b"---"))
+        .find_map(|line| line.strip_prefix(b"#ranked-at: "))
+        .map(|value| String::from_utf8_lossy(value.trim_ascii_end()).into_owned())
+}
+
+/// Write `preamble`, such as front matter, then the rows in `format`
+fn write_rows_with_progress(
+    headers: &[String],
+    rows: &[Vec<String>],
+    output: Option<&Path>,
+    format: DataFormat,
+    preamble: &[u8],
+    progress: &dyn ProgressSink,
+) -> Result<()> {
+    let mut writer = output_writer(output)?;
+    writer.write_all(preamble)?;
+
+    Ok(write_rows_to_with_progress(
+        writer, format, headers, rows, progress,
+    )?)
+}
+
+/// Buffer size for ranked output, so a pipe sees few large writes
+const OUTPUT_BUFFER: usize = 256 * 1024;
+
+/// A buffered writer to `output`, or to stdout locked for the whole run
+fn output_writer(output: Option<&Path>) -> Result<Box<dyn io::Write>> {
+    Ok(match output {
+        Some(path) => Box::new(BufWriter::with_capacity(
+            OUTPUT_BUFFER,
+            clobber::create(path)?,
+        )),
+        None => Box::new(BufWriter::with_capacity(OUTPUT_BUFFER, io::stdout().lock())),
+    })
+}
+
+/// Write CSV to `output` (or stdout) and TSV to `tsv_path` in one pass
+fn write_csv_and_tsv(
+    headers: &[String],
+    rows: &[Vec<String>],
+    output: Option<&Path>,
+    preamble: &[u8],
+    tsv_path: &Path,
+    progress: &dyn ProgressSink,
+) -> Result<()> {
+    let mut writer = output_writer(output)?;
+    writer.write_all(preamble)?;
+    let tsv = output_writer(Some(tsv_path))?;
+
+    Ok(write_csv_and_tsv_to_with_progress(
+        writer, tsv, headers, rows, progress,
+    )?)
+}
+
+/// Compare the output of ranking `input` with its `bytes` for `rank --check`,
+/// describing what would change when they differ
+fn check_canonical(
+    input: &str,
+    bytes: &[u8],
+    ranked_bytes: &[u8],
+    (headers, rows): (Vec<String>, Vec<Vec<String>>),
+    doc: &RsfDocument,
+    options: RankingOptions,
+) -> Result<ExitCode> {
+    if ranked_bytes == bytes {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    eprintln!("{} is not canonical RSF:", input);
+
+    // Options such as --concat-key and --emit-row-hashes change the columns
+    let mut sorted_before = headers.clone();
+    let mut sorted_after = doc.headers().to_vec();
+    sorted_before.sort();
+    sorted_after.sort();
+    if sorted_before != sorted_after {
+        eprintln!(
+            "  columns would change from {} to {}",
+            headers.join(","),
+            doc.headers().join(",")
+        );
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let ranked_columns = &doc.schema().columns;
+    let plan = plan_rank(&headers, &rows, ranked_columns, options)?;
+    let mut moved_columns = 0;
+    for col in plan.moved_columns() {
+        moved_columns += 1;
+        eprintln!(
+            "  column '{}' would move from position {} to {}",
+            col.name, col.from, col.to
+        );
+    }
+
+    if rows.len() != doc.rows().len() {
+        eprintln!("  {} row(s) would become {}", rows.len(), doc.rows().len());
+    }
+    let (_, reordered_rows) = reorder_data(&headers, rows, ranked_columns)?;
+    let moved_rows = reordered_rows
+        .iter()
+        .zip(doc.rows())
+        .filter(|(before, after)| before != after)
+        .count();
+    if moved_rows > 0 {
+        eprintln!("  {} row(s) would be reordered or changed", moved_rows);
+    }
+
+    if moved_columns == 0 && moved_rows == 0 && reordered_rows.len() == doc.rows().len() {
+        eprintln!("  formatting would change (quoting, line endings or ragged rows)");
+    }
+
+    Ok(ExitCode::FAILURE)
+}
+
+/// Print a rank plan to stdout
+fn print_plan(plan: &RankPlan, format: ReportFormat) -> Result<()> {
+    if format == ReportFormat::Json {
+        serde_json::to_writer_pretty(io::stdout(), plan)?;
+        println!();
+        return Ok(());
+    }
+
+    println!("\n=== RSF Rank Plan ===\n");
+    println!(
+        "{:<20} {:>6} {:>6} {:>12}",
+        "Column", "From", "To", "Cardinality"
+    );
+    println!("{}", "-".repeat(47));
+    for col in &plan.columns {
+        println!(
+            "{:<20} {:>6} {:>6} {:>12}",
+            col.name, col.from, col.to, col.cardinality
+        );
+    }
+
+    println!(
+        "\n{} of {} column(s) move.",
+        plan.moved_columns().count(),
+        plan.columns.len()
+    );
+    if plan.rows_sorted {
+        println!("Rows ({}) are already in canonical order.", plan.rows);
     } else {
-        Box::new(io::stdout())
-    };
+        println!(
+            "Rows ({}) need sorting: {} adjacent pair(s) out of order.",
+            plan.rows, plan.rows_out_of_order
+        );
+    }
+    Ok(())
+}
 
-    let mut csv_writer = Writer::from_writer(writer);
+fn print_estimate(estimate: &SizeEstimate, format: ReportFormat) -> Result<()> {
+    if format == ReportFormat::Json {
+        println!("{}", serde_json::to_string_pretty(estimate)?);
+        return Ok(());
+    }
 
-    csv_writer.write_record(headers)?;
+    let mib = |bytes: u64| format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0));
+    println!("\n=== RSF Size Estimate ===\n");
+    println!(
+        "Rows: {} ({} of cell text)",
+        estimate.rows,
+        mib(estimate.cell_bytes)
+    );
+    println!(
+        "Distinct values sketched from the first {} row(s):\n",
+        estimate.sampled_rows
+    );
+    println!("{:<20} {:>12} {:>10}", "Column", "Distinct", "Mean bytes");
+    println!("{}", "-".repeat(44));
+    for col in &estimate.columns {
+        println!(
+            "{:<20} {:>12} {:>10.1}",
+            col.name,
+            format!(
+                "~{}{}",
+                col.distinct,
+                if col.extrapolated { "*" } else { "" }
+            ),
+            col.mean_bytes
+        );
+    }
+    if estimate.columns.iter().any(|col| col.extrapolated) {
+        println!("\n* scaled up from the sample, where nearly every value was distinct");
+    }
 
-    for row in rows {
-        csv_writer.write_record(row)?;
+    println!(
+        "\nPeak memory in memory: ~{}",
+        mib(estimate.in_memory_bytes)
+    );
+    println!("Peak memory streaming: ~{}", mib(estimate.streaming_bytes));
+    let available = estimate.available_bytes.map(mib);
+    if let Some(available) = &available {
+        println!("Available memory:      {}", available);
     }
+    match (estimate.recommendation, available) {
+        (Recommendation::Streaming, Some(available)) => println!(
+            "\nRecommendation: streaming. Holding the rows needs more than half of the {} \
+             available; counting values alone needs ~{}.",
+            available,
+            mib(estimate.streaming_bytes)
+        ),
+        (_, Some(available)) => println!(
+            "\nRecommendation: in memory. Rows and value counts fit in half of the {} available.",
+            available
+        ),
+        (_, None) => println!(
+            "\nRecommendation: in memory, as available memory is unknown; compare the peak \
+             above with what the machine has free."
+        ),
+    }
+    Ok(())
+}
+
+/// Memory the system can give a new process, from `/proc/meminfo`; `None`
+/// where that is not available
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kib * 1024)
+}
 
-    csv_writer.flush()?;
+/// Write `{"new_col_0": "old_col_3", ...}` so ranked output can be put back
+/// into its original column order
+fn write_inverse_mapping(permutation: &[usize], path: &Path) -> Result<()> {
+    let mapping: serde_json::Map<String, serde_json::Value> = permutation
+        .iter()
+        .enumerate()
+        .map(|(new_idx, old_idx)| {
+            (
+                format!("new_col_{}", new_idx),
+                serde_json::Value::String(format!("old_col_{}", old_idx)),
+            )
+        })
+        .collect();
+
+    clobber::write(path, serde_json::to_string_pretty(&mapping)? + "\n")?;
     Ok(())
 }
 
-fn validate_rsf(csv_path: &PathBuf, schema_path: &PathBuf) -> Result<()> {
-    // Read schema
-    let schema_file = File::open(schema_path)
-        .with_context(|| format!("Failed to open schema: {:?}", schema_path))?;
-    let schema: Schema = serde_yaml::from_reader(schema_file)?;
+/// Write one `new,old` pair of 1-based column positions per line, sorted by
+/// the first number; `inverse` writes `old,new` pairs instead
+fn write_index_map(permutation: &[usize], path: &Path, inverse: bool) -> Result<()> {
+    let mut pairs: Vec<(usize, usize)> = permutation
+        .iter()
+        .enumerate()
+        .map(|(new_idx, &old_idx)| {
+            if inverse {
+                (old_idx + 1, new_idx + 1)
+            } else {
+                (new_idx + 1, old_idx + 1)
+            }
+        })
+        .collect();
+    pairs.sort_unstable();
 
-    // Read CSV
-    let (headers, rows) = read_csv_file(csv_path)?;
+    let text: String = pairs
+        .iter()
+        .map(|(from, to)| format!("{},{}\n", from, to))
+        .collect();
+    clobber::write(path, text)
+}
 
-    validate_column_order(&headers, &schema.columns).map_err(IntoAnyhow::into_anyhow)?;
+/// Validate a file against its schema, printing every finding to stderr
+///
+/// Returns whether the file is valid.
+fn validate_rsf(
+    csv: CsvInput,
+    csv_path: &Path,
+    schema_path: Option<&Path>,
+    leniency: Leniency,
+    row_count: Option<RowCountCheck>,
+    context: usize,
+    report_path: Option<&Path>,
+) -> Result<Validation> {
+    // No path means the schema embedded in the CSV, reported as a null path
+    let schema = match (schema_path, &csv.front_matter) {
+        (Some(path), _) => read_schema(path)?,
+        (None, Some(yaml)) => read_schema_reader(yaml.as_bytes())
+            .with_context(|| format!("Invalid schema embedded in {:?}", csv_path))?,
+        (None, None) => anyhow::bail!("{:?} has no embedded schema", csv_path),
+    };
+    let mut doc = RsfDocument::from(csv).with_schema(schema);
+    if let Some(band) = leniency.cardinality_band {
+        doc = doc.with_cardinality_band(band);
+    }
+    if leniency.any_key_order {
+        doc = doc.with_any_key_order();
+    }
 
-    // Validate ranks are sequential
-    for (idx, col_meta) in schema.columns.iter().enumerate() {
-        if col_meta.rank != idx + 1 {
-            anyhow::bail!(
-                "Column '{}' has invalid rank: expected {}, found {}",
-                col_meta.name,
-                idx + 1,
-                col_meta.rank
-            );
-        }
+    let report = doc.validation_report();
+    for error in report
+        .checks
+        .iter()
+        .filter_map(|outcome| outcome.error.as_ref())
+    {
+        eprintln!("{ERROR}✗{ERROR:#} {}", error);
+    }
+    // Reordered key columns differ from the schema without failing
+    let order_failed = report
+        .checks
+        .iter()
+        .any(|outcome| outcome.check == Check::ColumnOrder && !outcome.passed);
+    let divergence = order_failed
+        .then(|| header_divergence(doc.headers(), &doc.schema().columns, context))
+        .flatten();
+    if let Some(divergence) = &divergence {
+        print_header_divergence(divergence);
     }
+    let row_count_passed = row_count.as_ref().is_none_or(RowCountCheck::passed);
+    if let Some(check) = row_count.as_ref().filter(|check| !check.passed()) {
+        eprintln!("{ERROR}✗{ERROR:#} {}", check);
+    }
+    let valid = report.valid && row_count_passed;
+
+    if let Some(path) = report_path {
+        let json = serde_json::json!({
+            "input": csv_path,
+            "schema": schema_path,
+            "cardinality_band": leniency.cardinality_band,
+            "allow_reordered_key_columns": leniency.any_key_order,
+            "valid": valid,
+            "checks": report.checks,
+            "row_count": row_count.as_ref().map(|check| serde_json::json!({
+                "expected": check.expected,
+                "tolerance": check.tolerance,
+                "actual": check.actual,
+                "passed": check.passed(),
+            })),
+            "header_divergence": divergence.map(|divergence| serde_json::json!({
+                "position": divergence.position,
+                "columns": divergence.columns.iter().map(|(position, expected, found)| {
+                    serde_json::json!({ "position": position, "expected": expected, "found": found })
+                }).collect::<Vec<_>>(),
+            })),
+        });
+        clobber::write(path, serde_json::to_string_pretty(&json)? + "\n")?;
+        info!("Validation report written to: {}", path.display());
+    }
+
+    Ok(Validation {
+        structure: report.valid,
+        row_count: row_count_passed,
+        options: doc.schema().ranking_options(),
+    })
+}
+
+/// Outcome of validating one file
+struct Validation {
+    /// Every check against the schema passed
+    structure: bool,
+    /// The row count matched `--row-count-file`, or none was given
+    row_count: bool,
+    /// Options the schema was ranked with, to fix the file with
+    options: RankingOptions,
+}
 
-    let options = ranking_options(true);
-    validate_cardinality_order(&headers, &rows, &schema.columns, options)
-        .map_err(IntoAnyhow::into_anyhow)?;
+impl Validation {
+    fn valid(&self) -> bool {
+        self.structure && self.row_count
+    }
+}
 
-    validate_sorted(&rows).map_err(IntoAnyhow::into_anyhow)?;
+/// Rank and sort `csv` as `rank` does for `validate --fix`, writing it to
+/// `output` (stdout for `-`) with the new schema embedded when `csv` had one
+fn write_fixed(csv: CsvInput, options: RankingOptions, output: &Path) -> Result<()> {
+    let embedded = csv.front_matter.is_some();
+    let mut doc = RsfDocument::from(csv);
+    rank_document(
+        &mut doc,
+        options.clone(),
+        BTreeMap::new(),
+        &mut Profile::new(options),
+        &NoProgress,
+        CountReports::default(),
+    )?;
 
+    let mut preamble = Vec::new();
+    if embedded {
+        write_front_matter(&mut preamble, &serde_yaml::to_string(doc.schema())?)?;
+    }
+    let output = (output != Path::new("-")).then_some(output);
+    write_rows_with_progress(
+        doc.headers(),
+        doc.rows(),
+        output,
+        DataFormat::Csv,
+        &preamble,
+        &NoProgress,
+    )?;
+    if let Some(path) = output {
+        info!("Fixed file written to: {}", path.display());
+    }
     Ok(())
 }
+
+/// Print the schema's and the data's column names side by side, marking
+/// the first difference
+fn print_header_divergence(divergence: &HeaderDivergence) {
+    let show = |name: &Option<String>| name.clone().unwrap_or_else(|| "-".to_string());
+    eprintln!("\n  {:>4}  {:<24} Found", "Pos", "Expected");
+    for (position, expected, found) in &divergence.columns {
+        let marker = if *position == divergence.position {
+            '>'
+        } else {
+            ' '
+        };
+        let (expected_style, found_style) = if expected == found {
+            (Style::new(), Style::new())
+        } else {
+            (EXPECTED, FOUND)
+        };
+        eprintln!(
+            "{} {:>4}  {expected_style}{:<24}{expected_style:#} {found_style}{}{found_style:#}",
+            marker,
+            position,
+            show(expected),
+            show(found)
+        );
+    }
+    eprintln!();
+}
+
+/// What `validate` accepts besides an exact match with the schema
+#[derive(Debug, Clone, Copy)]
+struct Leniency {
+    /// From `--cardinality-band`
+    cardinality_band: Option<CardinalityBand>,
+    /// From `--allow-reordered-key-columns`
+    any_key_order: bool,
+}
+
+/// Row count a validated file must have, from `validate --row-count-file`
+struct RowCountCheck {
+    expected: usize,
+    tolerance: usize,
+    actual: usize,
+}
+
+impl RowCountCheck {
+    fn passed(&self) -> bool {
+        self.actual.abs_diff(self.expected) <= self.tolerance
+    }
+}
+
+impl std::fmt::Display for RowCountCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Expected {} rows", self.expected)?;
+        if self.tolerance > 0 {
+            write!(f, " (±{})", self.tolerance)?;
+        }
+        write!(f, ", found {}", self.actual)
+    }
+}
+
+/// Read the single integer in a row count file, ignoring surrounding whitespace
+fn read_row_count(path: &Path) -> Result<usize> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read row count file: {:?}", path))?;
+    let text = text.trim();
+    text.parse()
+        .with_context(|| format!("{:?} should hold a row count, found {:?}", path, text))
+}
@@ -0,0 +1,63 @@
+//! Read-only memory maps of local files, for `ReadOptions::mmap`
+
+use std::fs::File;
+use std::ops::Deref;
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+
+/// A file mapped read-only into memory, unmapped on drop
+///
+/// The file must not be truncated while it is mapped; reading past the new
+/// end raises `SIGBUS`. The map is private, so writes by other processes may
+/// or may not show through, as with any read racing a writer.
+pub(crate) struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Map all of a regular file; `None` for empty files, pipes and devices
+    /// or if the system refuses, for the caller to read it normally instead
+    pub(crate) fn map(file: &File) -> Option<Self> {
+        let metadata = file.metadata().ok()?;
+        let len = usize::try_from(metadata.len()).ok()?;
+        if !metadata.is_file() || len == 0 {
+            return None;
+        }
+
+        // SAFETY: a fresh read-only private mapping of an open descriptor,
+        // checked for failure before use
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return None;
+        }
+        Some(Self { ptr, len })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` maps `len` readable bytes until `drop`
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly the range mapped in `map`, once
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::cmp::Ordering;
+
+/// Parse a numeric cell
+///
+/// With `decimal_comma`, `.` is a thousands separator and `,` the decimal
+/// point (`1.234,56` is 1234.56); thousands groups must be three digits so
+/// that values like `1.2` are not silently misread.
+pub fn parse_number(value: &str, decimal_comma: bool) -> Option<f64> {
+    let value = value.trim();
+    if !decimal_comma {
+        return value.parse().ok();
+    }
+
+    let unsigned = value.strip_prefix(['-', '+']).unwrap_or(value);
+    let (integer, fraction) = match unsigned.split_once(',') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None),
+    };
+
+    let mut groups = integer.split('.');
+    let first = groups.next().unwrap_or("");
+    if first.is_empty() || first.len() > 3 && integer.contains('.') || !is_digits(first) {
+        return None;
+    }
+    if !groups.all(|group| group.len() == 3 && is_digits(group)) {
+        return None;
+    }
+    if let Some(fraction) = fraction {
+        if fraction.is_empty() || !is_digits(fraction) {
+            return None;
+        }
+    }
+
+    canonical_decimal(value)?.parse().ok()
+}
+
+/// Rewrite a decimal-comma number in `.`-decimal form without separators
+///
+/// Returns `None` when the value is not a decimal-comma number.
+pub fn canonical_decimal(value: &str) -> Option<String> {
+    let value = value.trim();
+    let canonical: String = value
+        .chars()
+        .filter(|&c| c != '.')
+        .map(|c| if c == ',' { '.' } else { c })
+        .collect();
+
+    if canonical.is_empty() || !canonical.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if !canonical
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+'))
+    {
+        return None;
+    }
+    Some(canonical)
+}
+
+/// Canonicalize a cell if it is a valid decimal-comma number
+pub fn normalize_decimal_comma(value: &str) -> Option<String> {
+    parse_number(value, true)?;
+    canonical_decimal(value)
+}
+
+/// Rewrite decimal-comma numbers as `.`-decimal in place
+pub fn canonicalize_decimals(rows: &mut [Vec<String>]) {
+    for value in rows.iter_mut().flatten() {
+        if let Some(canonical) = normalize_decimal_comma(value) {
+            *value = canonical;
+        }
+    }
+}
+
+/// Rewrite decimal-comma numbers as `.`-decimal in the columns flagged in
+/// `numeric`, leaving other columns as written
+pub fn canonicalize_decimal_columns(rows: &mut [Vec<String>], numeric: &[bool]) {
+    for row in rows {
+        for (value, _) in row.iter_mut().zip(numeric).filter(|(_, &n)| n) {
+            if let Some(canonical) = normalize_decimal_comma(value) {
+                *value = canonical;
+            }
+        }
+    }
+}
+
+/// Compare two cells
+///
+/// With `decimal_comma`, numeric cells compare by value and sort before all
+/// non-numeric cells, which keeps the ordering total on mixed columns.
+pub fn compare_cells(a: &str, b: &str, decimal_comma: bool) -> Ordering {
+    if !decimal_comma {
+        return a.cmp(b);
+    }
+
+    match (parse_number(a, true), parse_number(b, true)) {
+        // Fall back to the text so equal values in different notation stay ordered
+        (Some(x), Some(y)) => x.total_cmp(&y).then_with(|| a.cmp(b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+fn is_digits(value: &str) -> bool {
+    value.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decimal_comma() {
+        assert_eq!(parse_number("1.234,56", true), Some(1234.56));
+        assert_eq!(parse_number("-12,5", true), Some(-12.5));
+        assert_eq!(parse_number("1.234.567", true), Some(1234567.0));
+        assert_eq!(parse_number("42", true), Some(42.0));
+        assert_eq!(parse_number("1.2", true), None);
+        assert_eq!(parse_number("1234.567,8", true), None);
+        assert_eq!(parse_number("abc", true), None);
+        assert_eq!(parse_number("1.234,56", false), None);
+        assert_eq!(
+            normalize_decimal_comma("1.234,56"),
+            Some("1234.56".to_string())
+        );
+        assert_eq!(normalize_decimal_comma("v1.2"), None);
+    }
+
+    #[test]
+    fn test_compare_cells_decimal_comma() {
+        let mut values = vec!["999,5", "1.234,56", "12,0", "n/a"];
+        values.sort_by(|a, b| compare_cells(a, b, true));
+        assert_eq!(values, vec!["12,0", "999,5", "1.234,56", "n/a"]);
+
+        values.sort_by(|a, b| compare_cells(a, b, false));
+        assert_eq!(values, vec!["1.234,56", "12,0", "999,5", "n/a"]);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::ranking::ColumnStats;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+
+/// Distinct values checked per column
+pub const PII_SAMPLE: usize = 1000;
+
+/// Tag suggested for columns that look like personal data
+pub const PII_TAG: &str = "PII";
+
+/// Kind of personal data a column's values look like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PiiKind {
+    /// Card numbers that pass the Luhn check
+    CreditCard,
+    Email,
+    IpAddress,
+    Phone,
+    /// Two to four capitalized words; the weakest signal
+    Name,
+}
+
+impl PiiKind {
+    /// In order of precedence when a column matches several kinds equally
+    const ALL: [PiiKind; 5] = [
+        PiiKind::CreditCard,
+        PiiKind::Email,
+        PiiKind::IpAddress,
+        PiiKind::Phone,
+        PiiKind::Name,
+    ];
+
+    fn matches(self, value: &str) -> bool {
+        match self {
+            PiiKind::CreditCard => is_card_number(value),
+            PiiKind::Email => is_email(value),
+            PiiKind::IpAddress => value.parse::<IpAddr>().is_ok(),
+            PiiKind::Phone => is_phone(value),
+            PiiKind::Name => is_full_name(value),
+        }
+    }
+
+    /// Risk when nearly every value matches
+    fn max_risk(self) -> PiiRisk {
+        match self {
+            PiiKind::Name => PiiRisk::Medium,
+            _ => PiiRisk::High,
+        }
+    }
+}
+
+impl fmt::Display for PiiKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PiiKind::CreditCard => "credit card",
+            PiiKind::Email => "email",
+            PiiKind::IpAddress => "IP address",
+            PiiKind::Phone => "phone",
+            PiiKind::Name => "name",
+        })
+    }
+}
+
+/// How likely a column is to hold personal data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PiiRisk {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for PiiRisk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PiiRisk::Low => "low",
+            PiiRisk::Medium => "medium",
+            PiiRisk::High => "high",
+        })
+    }
+}
+
+/// A column whose values look like personal data
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct PiiFinding {
+    pub column: String,
+    pub kind: PiiKind,
+    /// Sampled values that look like `kind`
+    pub matched: usize,
+    /// Non-empty distinct values checked
+    pub sampled: usize,
+    pub risk: PiiRisk,
+}
+
+/// Check up to `sample` distinct values of a column against patterns for
+/// emails, phone numbers, card numbers, IP addresses and full names
+///
+/// Returns the kind most values match, rated high when at least 80% do,
+/// medium from 30% and low from 10%; names are never rated high. This
+/// catches obvious cases only and is no substitute for a proper scan.
+pub fn detect_pii(stats: &ColumnStats, sample: usize) -> Option<PiiFinding> {
+    let mut values: Vec<&str> = stats
+        .value_counts
+        .keys()
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .collect();
+    values.sort_unstable();
+    // Spread the sample over the sorted values for a stable result
+    let step = values.len().div_ceil(sample.max(1)).max(1);
+    let sampled: Vec<&str> = values.into_iter().step_by(step).collect();
+    if sampled.is_empty() {
+        return None;
+    }
+
+    let (kind, matched) = PiiKind::ALL
+        .iter()
+        .map(|&kind| (kind, sampled.iter().filter(|v| kind.matches(v)).count()))
+        .fold(
+            None,
+            |best: Option<(PiiKind, usize)>, candidate| match best {
+                Some(best) if best.1 >= candidate.1 => Some(best),
+                _ => Some(candidate),
+            },
+        )?;
+
+    let share = matched as f64 / sampled.len() as f64;
+    let risk = if share >= 0.8 {
+        kind.max_risk()
+    } else if share >= 0.3 {
+        PiiRisk::Medium.min(kind.max_risk())
+    } else if share >= 0.1 {
+        PiiRisk::Low
+    } else {
+        return None;
+    };
+
+    Some(PiiFinding {
+        column: stats.name.clone(),
+        kind,
+        matched,
+        sampled: sampled.len(),
+        risk,
+    })
+}
+
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    let label_ok = |label: &str| {
+        !label.is_empty()
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    };
+    !local.is_empty()
+        && !local.contains(char::is_whitespace)
+        && domain.contains('.')
+        && domain.split('.').all(label_ok)
+}
+
+fn is_phone(value: &str) -> bool {
+    let digits = value.bytes().filter(u8::is_ascii_digit).count();
+    let separated = value.starts_with('+') || value.contains([' ', '-', '(', ')']);
+    separated
+        && (9..=15).contains(&digits)
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || b" +-().".contains(&b))
+}
+
+fn is_card_number(value: &str) -> bool {
+    if !value
+        .bytes()
+        .all(|b| b.is_ascii_digit() || b == b' ' || b == b'-')
+    {
+        return false;
+    }
+    let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+    (13..=19).contains(&digits.len()) && luhn(&digits)
+}
+
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(idx, &d)| match (idx % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn is_full_name(value: &str) -> bool {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    (2..=4).contains(&words.len())
+        && words.iter().all(|word| {
+            let mut chars = word.chars();
+            chars.next().is_some_and(char::is_uppercase)
+                && chars.as_str().chars().any(char::is_lowercase)
+                && chars.all(|c| c.is_alphabetic() || matches!(c, '\'' | '-' | '.'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(values: &[&str]) -> ColumnStats {
+        let mut stats = ColumnStats::new("col");
+        for value in values {
+            stats.add_value(value);
+        }
+        stats
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert!(is_email("ana.lopez@example.co.uk"));
+        assert!(!is_email("ana@localhost"));
+        assert!(is_card_number("4111 1111 1111 1111"));
+        assert!(!is_card_number("4111 1111 1111 1112"));
+        assert!(is_phone("+44 20 7946 0958"));
+        assert!(!is_phone("2024-01-02"));
+        assert!(!is_phone("123456789"));
+        assert!(is_full_name("Ana María López"));
+        assert!(!is_full_name("ACME LTD"));
+        assert!(PiiKind::IpAddress.matches("192.168.0.1"));
+    }
+
+    #[test]
+    fn test_detect_pii() {
+        let finding = detect_pii(&stats(&["a@x.io", "b@y.org", "c@z.net", ""]), 10).unwrap();
+        assert_eq!(
+            (finding.kind, finding.matched, finding.sampled, finding.risk),
+            (PiiKind::Email, 3, 3, PiiRisk::High)
+        );
+
+        let finding = detect_pii(&stats(&["Ana Lopez", "Bo Chen", "n/a"]), 10).unwrap();
+        assert_eq!(
+            (finding.kind, finding.risk),
+            (PiiKind::Name, PiiRisk::Medium)
+        );
+
+        let finding = detect_pii(&stats(&["10.0.0.1", "x", "y", "z", "w"]), 10).unwrap();
+        assert_eq!(
+            (finding.kind, finding.risk),
+            (PiiKind::IpAddress, PiiRisk::Low)
+        );
+
+        assert_eq!(detect_pii(&stats(&["food", "travel", "12"]), 10), None);
+        assert_eq!(detect_pii(&stats(&["", " "]), 10), None);
+    }
+}
@@ -0,0 +1,461 @@
+//! The transforms `rsf rank` applies around ranking a document
+//!
+//! [`RankPipeline::prepare`] cleans the input before it is ranked: it fixes
+//! headers, takes the type row, fills and rewrites cells and adds a
+//! concatenated key. [`RankPipeline::finish`] then adds row hashes to the
+//! ranked document and returns the schema to write alongside it:
+//!
+//! ```
+//! use rsf::io::{read_csv_reader, ReadOptions};
+//! use rsf::pipeline::{ConcatKey, RankPipeline};
+//! use rsf::ranking::RankingOptions;
+//!
+//! let pipeline = RankPipeline::new()
+//!     .fill_missing(Some(String::new()))
+//!     .concat_key(Some(ConcatKey::new(["Account", "Month"], "Key")));
+//! let csv = read_csv_reader(
+//!     "Account,Month,Amount\na,1\nb,2,5\n".as_bytes(),
+//!     ReadOptions::default().flexible(true),
+//! )?;
+//! let (mut doc, prepared) = pipeline.prepare(csv)?;
+//! assert_eq!(prepared.filled, Some(1));
+//!
+//! doc.rank(RankingOptions::default())?;
+//! doc.sort();
+//! let schema = pipeline.finish(&mut doc, &prepared)?;
+//! assert_eq!(doc.headers()[0], "Key");
+//! assert_eq!(schema.columns.len(), 4);
+//! # Ok::<(), rsf::RsfError>(())
+//! ```
+
+use crate::document::RsfDocument;
+use crate::errors::{RsfError, RsfResult};
+use crate::io::CsvInput;
+use crate::numeric::{canonicalize_decimal_columns, canonicalize_decimals};
+use crate::ranking::{
+    case_collisions, check_declared_types, dedup_normalized, fill_missing, key_column_names,
+    parse_type_row, prepend_concat_key, rename_case_collisions, DataType, Schema,
+};
+use crate::unicode::{
+    normalization_conflicts, normalize_cells, NormalizationConflict, NormalizationForm,
+};
+
+/// A key column joining the cells of other columns, prepended to the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConcatKey {
+    pub columns: Vec<String>,
+    pub name: String,
+    /// Put between the joined cells; `|` by default
+    pub separator: String,
+    /// Remove the joined columns from the output
+    pub drop_columns: bool,
+}
+
+impl ConcatKey {
+    /// Join `columns` into a key column called `name`
+    pub fn new(columns: impl IntoIterator<Item = impl Into<String>>, name: &str) -> Self {
+        Self {
+            columns: columns.into_iter().map(Into::into).collect(),
+            name: name.to_string(),
+            separator: "|".to_string(),
+            drop_columns: false,
+        }
+    }
+
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    pub fn drop_columns(mut self, drop_columns: bool) -> Self {
+        self.drop_columns = drop_columns;
+        self
+    }
+}
+
+/// A column holding a hash of each row's key cells, appended after ranking
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RowHashes {
+    pub name: String,
+    /// The columns hashed; `None` for those the schema types as keys
+    pub key: Option<Vec<String>>,
+}
+
+impl RowHashes {
+    /// Hash the key columns the schema records into a column called `name`
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            key: None,
+        }
+    }
+
+    /// Hash these columns instead
+    pub fn key(mut self, columns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.key = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
+/// Transforms of a table before and after it is ranked, each off until set
+///
+/// They run in a fixed order whatever order they are set in; see
+/// [`prepare`](Self::prepare) and [`finish`](Self::finish).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RankPipeline {
+    strip_nul: bool,
+    rename_case_collisions: bool,
+    preserve_original_header_case: bool,
+    type_row: bool,
+    fill_missing: Option<String>,
+    dedup_normalized: bool,
+    decimal_comma: bool,
+    canonical_decimal: bool,
+    verify_unicode_normalization: bool,
+    normalize_unicode: Option<NormalizationForm>,
+    concat_key: Option<ConcatKey>,
+    row_hashes: Option<RowHashes>,
+    sort_stable: bool,
+}
+
+/// What [`RankPipeline::prepare`] found and did, for
+/// [`RankPipeline::finish`] and to report
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Prepared {
+    /// NUL bytes were stripped from some cells
+    pub nul_stripped: bool,
+    /// Columns renamed, from the name read to the new one
+    pub renamed: Vec<(String, String)>,
+    /// Groups of columns whose names differ only by case, left as they are
+    pub case_collisions: Vec<Vec<String>>,
+    /// The type of each column from the type row, by the column's name
+    /// before a concat key added or dropped columns
+    pub declared_types: Option<Vec<(String, DataType)>>,
+    /// Missing cells filled in
+    pub filled: Option<usize>,
+    /// Rows dropped as equal to an earlier one once trimmed
+    pub collapsed: Option<usize>,
+    /// Values that differ only in Unicode normalization, before normalizing
+    pub normalization_conflicts: Vec<NormalizationConflict>,
+    /// Cells rewritten in the Unicode normalization form
+    pub normalized: Option<usize>,
+}
+
+impl RankPipeline {
+    /// A pipeline that passes the table through unchanged
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove NUL bytes from the cells the reader found them in, as
+    /// [`ReadOptions::strip_nul`](crate::io::ReadOptions::strip_nul) does,
+    /// and record in the schema that they were
+    pub fn strip_nul(mut self, strip_nul: bool) -> Self {
+        self.strip_nul = strip_nul;
+        self
+    }
+
+    /// Rename columns whose names differ only by case with a numeric suffix
+    pub fn rename_case_collisions(mut self, rename: bool) -> Self {
+        self.rename_case_collisions = rename;
+        self
+    }
+
+    /// Record the name each renamed column was read with in the schema
+    pub fn preserve_original_header_case(mut self, preserve: bool) -> Self {
+        self.preserve_original_header_case = preserve;
+        self
+    }
+
+    /// Take the first row as the type of each column, checked against every
+    /// cell and recorded in the schema
+    pub fn type_row(mut self, type_row: bool) -> Self {
+        self.type_row = type_row;
+        self
+    }
+
+    /// Pad short rows with `value`
+    pub fn fill_missing(mut self, value: Option<String>) -> Self {
+        self.fill_missing = value;
+        self
+    }
+
+    /// Drop rows equal to an earlier one once each cell is trimmed
+    pub fn dedup_normalized(mut self, dedup: bool) -> Self {
+        self.dedup_normalized = dedup;
+        self
+    }
+
+    /// Numbers are written with a decimal comma, when checking the type row's
+    /// types and in the schema
+    pub fn decimal_comma(mut self, decimal_comma: bool) -> Self {
+        self.decimal_comma = decimal_comma;
+        self
+    }
+
+    /// Rewrite decimal-comma numbers as `.`-decimal: in the numeric columns of
+    /// the type row, or in every column without one
+    pub fn canonical_decimal(mut self, canonical: bool) -> Self {
+        self.canonical_decimal = canonical;
+        self
+    }
+
+    /// Look for values that differ only in Unicode normalization
+    pub fn verify_unicode_normalization(mut self, verify: bool) -> Self {
+        self.verify_unicode_normalization = verify;
+        self
+    }
+
+    /// Rewrite every cell in `form`
+    pub fn normalize_unicode(mut self, form: Option<NormalizationForm>) -> Self {
+        self.normalize_unicode = form;
+        self
+    }
+
+    /// Prepend a key column joining other columns
+    pub fn concat_key(mut self, key: Option<ConcatKey>) -> Self {
+        self.concat_key = key;
+        self
+    }
+
+    /// Append a hash of each row's key cells once ranked
+    pub fn row_hashes(mut self, row_hashes: Option<RowHashes>) -> Self {
+        self.row_hashes = row_hashes;
+        self
+    }
+
+    /// Record in the schema that rows equal in every column keep their input
+    /// order
+    pub fn sort_stable(mut self, sort_stable: bool) -> Self {
+        self.sort_stable = sort_stable;
+        self
+    }
+
+    /// Clean `csv` into a document ready to rank
+    ///
+    /// In order: strip NUL bytes, rename case collisions, take the type row,
+    /// fill missing cells, drop duplicate rows, canonicalize decimals, look
+    /// for and normalize Unicode spellings, prepend the concat key and check
+    /// the cells against the type row.
+    pub fn prepare(&self, mut csv: CsvInput) -> RsfResult<(RsfDocument, Prepared)> {
+        let mut prepared = Prepared::default();
+        if self.strip_nul {
+            for &(row, col) in &csv.nul_fields {
+                if let Some(value) = csv.rows.get_mut(row).and_then(|row| row.get_mut(col)) {
+                    value.retain(|c| c != '\0');
+                }
+            }
+            prepared.nul_stripped = !csv.nul_fields.is_empty();
+        }
+
+        if self.rename_case_collisions {
+            prepared.renamed = rename_case_collisions(&mut csv.headers);
+        } else {
+            prepared.case_collisions = case_collisions(&csv.headers);
+        }
+
+        let types = if self.type_row {
+            if csv.rows.is_empty() {
+                return Err(RsfError::schema_error(
+                    "The type row is missing: there is no row after the header".to_string(),
+                ));
+            }
+            let types_row = csv.rows.remove(0);
+            Some(parse_type_row(&csv.headers, &types_row)?)
+        } else {
+            None
+        };
+
+        if let Some(value) = &self.fill_missing {
+            prepared.filled = Some(fill_missing(&mut csv.rows, csv.headers.len(), value));
+        }
+        if self.dedup_normalized {
+            prepared.collapsed = Some(dedup_normalized(&mut csv.rows));
+        }
+        match (&types, self.canonical_decimal) {
+            (Some(types), true) => {
+                let numeric: Vec<bool> = types.iter().map(|t| t.is_numeric()).collect();
+                canonicalize_decimal_columns(&mut csv.rows, &numeric);
+            }
+            (None, true) => canonicalize_decimals(&mut csv.rows),
+            (_, false) => {}
+        }
+        if self.verify_unicode_normalization {
+            prepared.normalization_conflicts = normalization_conflicts(&csv.headers, &csv.rows);
+        }
+        if let Some(form) = self.normalize_unicode {
+            prepared.normalized = Some(normalize_cells(&mut csv.rows, form));
+        }
+
+        // Named before the concat key adds or drops columns
+        prepared.declared_types =
+            types.map(|types| csv.headers.iter().cloned().zip(types).collect());
+        if let Some(key) = &self.concat_key {
+            (csv.headers, csv.rows) = prepend_concat_key(
+                &csv.headers,
+                &csv.rows,
+                &key.columns,
+                &key.name,
+                &key.separator,
+                key.drop_columns,
+            )?;
+        }
+        if let Some(types) = &prepared.declared_types {
+            check_declared_types(&csv.headers, &csv.rows, types, self.number_decimal_comma())?;
+        }
+
+        Ok((RsfDocument::from(csv), prepared))
+    }
+
+    /// Record the declared types in the ranked `doc`, append its row hashes
+    /// and return the schema to write with it
+    ///
+    /// The row hash column is in the returned schema but not in the
+    /// document's, which describes the ranked columns only.
+    pub fn finish(&self, doc: &mut RsfDocument, prepared: &Prepared) -> RsfResult<Schema> {
+        if let Some(types) = &prepared.declared_types {
+            doc.declare_types(types);
+        }
+        let mut schema = doc.schema().clone();
+        if let Some(row_hashes) = &self.row_hashes {
+            let key = match &row_hashes.key {
+                Some(key) => key.clone(),
+                None => key_column_names(&schema.columns)
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            };
+            schema
+                .columns
+                .push(doc.append_row_hashes(&row_hashes.name, &key)?);
+        }
+
+        schema.nul_stripped = prepared.nul_stripped;
+        schema.sort_stable = self.sort_stable;
+        if self.preserve_original_header_case {
+            schema.record_original_names(&prepared.renamed);
+        }
+        // Canonicalized output is plain `.`-decimal and sorts without the
+        // decimal-comma parser
+        schema.decimal_comma = self.number_decimal_comma();
+        if let Some(options) = &mut schema.options {
+            options.decimal_comma = schema.decimal_comma;
+        }
+        Ok(schema)
+    }
+
+    /// Whether numbers still have a decimal comma once prepared
+    fn number_decimal_comma(&self) -> bool {
+        self.decimal_comma && !self.canonical_decimal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranking::RankingOptions;
+
+    fn csv(headers: &[&str], rows: &[&[&str]]) -> CsvInput {
+        CsvInput {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: rows
+                .iter()
+                .map(|row| row.iter().map(|v| v.to_string()).collect())
+                .collect(),
+            nul_fields: Vec::new(),
+            front_matter: None,
+        }
+    }
+
+    #[test]
+    fn test_unchanged_by_default() {
+        let input = csv(&["Id", "id"], &[&["1", " a"], &["1", "a"]]);
+        let (doc, prepared) = RankPipeline::new().prepare(input.clone()).unwrap();
+        assert_eq!(
+            (doc.headers(), doc.rows()),
+            (&input.headers[..], &input.rows[..])
+        );
+        assert_eq!(prepared.case_collisions, vec![vec!["Id", "id"]]);
+        assert_eq!(prepared.filled, None);
+    }
+
+    #[test]
+    fn test_prepare() {
+        let mut input = csv(
+            &["Id", "id", "Amt"],
+            &[
+                &["int", "str", "float"],
+                &["1", "x\0", "1,5"],
+                &["2", " y"],
+                &["2", "y ", ""],
+            ],
+        );
+        input.nul_fields = vec![(1, 1)];
+        let pipeline = RankPipeline::new()
+            .strip_nul(true)
+            .rename_case_collisions(true)
+            .type_row(true)
+            .fill_missing(Some(String::new()))
+            .dedup_normalized(true)
+            .decimal_comma(true)
+            .canonical_decimal(true)
+            .concat_key(Some(
+                ConcatKey::new(["Id", "id_2"], "Key")
+                    .separator("-")
+                    .drop_columns(true),
+            ));
+        let (doc, prepared) = pipeline.prepare(input).unwrap();
+
+        assert_eq!(doc.headers(), ["Key", "Amt"]);
+        assert_eq!(doc.rows(), [vec!["1-x", "1.5"], vec!["2- y", ""]]);
+        assert!(prepared.nul_stripped);
+        assert_eq!(prepared.renamed, [("id".to_string(), "id_2".to_string())]);
+        assert_eq!(prepared.filled, Some(1));
+        assert_eq!(prepared.collapsed, Some(1));
+        assert_eq!(
+            prepared.declared_types.unwrap()[2],
+            ("Amt".to_string(), DataType::Float)
+        );
+    }
+
+    #[test]
+    fn test_prepare_checks_the_type_row() {
+        let pipeline = RankPipeline::new().type_row(true);
+        assert!(pipeline.prepare(csv(&["Id"], &[])).is_err());
+        assert!(pipeline.prepare(csv(&["Id"], &[&["int"], &["x"]])).is_err());
+        assert!(pipeline.prepare(csv(&["Id"], &[&["int"], &["1"]])).is_ok());
+    }
+
+    #[test]
+    fn test_finish() {
+        let pipeline = RankPipeline::new()
+            .type_row(true)
+            .rename_case_collisions(true)
+            .preserve_original_header_case(true)
+            .row_hashes(Some(RowHashes::new("Hash").key(["Id"])))
+            .sort_stable(true);
+        let input = csv(&["Id", "id"], &[&["int", "str"], &["1", "a"], &["2", "a"]]);
+        let (mut doc, prepared) = pipeline.prepare(input).unwrap();
+        doc.rank(RankingOptions::default()).unwrap();
+        doc.sort();
+        let schema = pipeline.finish(&mut doc, &prepared).unwrap();
+
+        assert_eq!(doc.headers(), ["Id", "id_2", "Hash"]);
+        assert_eq!(doc.schema().columns.len(), 2);
+        assert_eq!(schema.columns.len(), 3);
+        assert_eq!(schema.columns[0].data_type, Some(DataType::Int));
+        assert_eq!(schema.columns[1].original_name.as_deref(), Some("id"));
+        assert!(schema.columns[2].is_unique);
+        assert!(schema.sort_stable);
+        assert!(!schema.nul_stripped);
+
+        // No column is typed as a key, so there is nothing to hash
+        let pipeline = RankPipeline::new().row_hashes(Some(RowHashes::new("Hash")));
+        let (mut doc, prepared) = pipeline.prepare(csv(&["Id"], &[&["1"]])).unwrap();
+        doc.rank(RankingOptions::default()).unwrap();
+        assert!(pipeline.finish(&mut doc, &prepared).is_err());
+    }
+}
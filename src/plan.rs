@@ -0,0 +1,125 @@
+use crate::errors::RsfResult;
+use crate::numeric::compare_cells;
+use crate::ranking::{column_permutation, ColumnMeta, RankingOptions};
+use serde::Serialize;
+
+/// Planned move of a single column
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ColumnMove {
+    pub name: String,
+    /// 1-based position in the input
+    pub from: usize,
+    /// 1-based position after ranking
+    pub to: usize,
+    pub cardinality: usize,
+}
+
+/// What a rank would do to a file, computed without writing anything
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct RankPlan {
+    pub columns: Vec<ColumnMove>,
+    /// Number of data rows
+    pub rows: usize,
+    /// Rows are already in canonical order after column reordering
+    pub rows_sorted: bool,
+    /// Estimate of rows that will move: adjacent pairs found out of order
+    pub rows_out_of_order: usize,
+}
+
+impl RankPlan {
+    /// Columns whose position changes
+    pub fn moved_columns(&self) -> impl Iterator<Item = &ColumnMove> {
+        self.columns.iter().filter(|col| col.from != col.to)
+    }
+}
+
+/// Build a rank plan from the input and its computed ranking
+///
+/// The sortedness check compares adjacent rows through the column
+/// permutation, so no rows are copied or sorted.
+pub fn plan_rank(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    ranked_columns: &[ColumnMeta],
+    options: RankingOptions,
+) -> RsfResult<RankPlan> {
+    let permutation = column_permutation(headers, ranked_columns)?;
+
+    let columns = ranked_columns
+        .iter()
+        .zip(permutation.iter())
+        .enumerate()
+        .map(|(new_idx, (col, &old_idx))| ColumnMove {
+            name: col.name.clone(),
+            from: old_idx + 1,
+            to: new_idx + 1,
+            cardinality: col.cardinality,
+        })
+        .collect();
+
+    let rows_out_of_order = rows
+        .windows(2)
+        .filter(|pair| compare_permuted(&pair[0], &pair[1], &permutation, &options).is_gt())
+        .count();
+
+    Ok(RankPlan {
+        columns,
+        rows: rows.len(),
+        rows_sorted: rows_out_of_order == 0,
+        rows_out_of_order,
+    })
+}
+
+/// Compare two rows in ranked column order
+fn compare_permuted(
+    a: &[String],
+    b: &[String],
+    permutation: &[usize],
+    options: &RankingOptions,
+) -> std::cmp::Ordering {
+    let empty = String::new();
+    for &idx in permutation {
+        let val_a = a.get(idx).unwrap_or(&empty);
+        let val_b = b.get(idx).unwrap_or(&empty);
+        match compare_cells(val_a, val_b, options.decimal_comma) {
+            std::cmp::Ordering::Equal => continue,
+            other => return other,
+        }
+    }
+    std::cmp::Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranking::rank_columns;
+
+    #[test]
+    fn test_plan_rank() {
+        let headers = vec!["Group".to_string(), "Id".to_string()];
+        let rows = vec![
+            vec!["a".to_string(), "3".to_string()],
+            vec!["a".to_string(), "1".to_string()],
+            vec!["b".to_string(), "2".to_string()],
+        ];
+
+        let ranked = rank_columns(&headers, &rows, Default::default()).unwrap();
+        let plan = plan_rank(&headers, &rows, &ranked, Default::default()).unwrap();
+
+        assert_eq!(
+            plan.columns[0],
+            ColumnMove {
+                name: "Id".to_string(),
+                from: 2,
+                to: 1,
+                cardinality: 3,
+            }
+        );
+        assert_eq!(plan.moved_columns().count(), 2);
+        assert_eq!(plan.rows, 3);
+        assert!(!plan.rows_sorted);
+        assert_eq!(plan.rows_out_of_order, 1);
+    }
+}
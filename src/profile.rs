@@ -0,0 +1,105 @@
+use crate::log::verbose;
+use anyhow::Result;
+use rsf::ranking::{ColumnMeta, RankingOptions};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Instant;
+
+/// Duration of a single pipeline phase
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub millis: f64,
+}
+
+/// Machine-readable summary of a rank run
+#[derive(Debug, Serialize)]
+pub struct Profile {
+    pub rows: usize,
+    pub columns: usize,
+    /// Sum of distinct-set sizes across all columns, the dominant memory cost
+    pub estimated_distinct_values: usize,
+    pub options: RankingOptions,
+    pub phases: Vec<PhaseTiming>,
+}
+
+impl Profile {
+    pub fn new(options: RankingOptions) -> Self {
+        Self {
+            rows: 0,
+            columns: 0,
+            estimated_distinct_values: 0,
+            options,
+            phases: Vec::new(),
+        }
+    }
+
+    /// Run `f` and record how long it took under `phase`
+    pub fn time<T>(&mut self, phase: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.phases.push(PhaseTiming {
+            phase: phase.to_string(),
+            millis: start.elapsed().as_secs_f64() * 1000.0,
+        });
+        result
+    }
+
+    /// Record row, column and distinct-value counts
+    pub fn record_counts(&mut self, rows: usize, ranked_columns: &[ColumnMeta]) {
+        self.rows = rows;
+        self.columns = ranked_columns.len();
+        self.estimated_distinct_values = ranked_columns.iter().map(|c| c.cardinality).sum();
+    }
+
+    /// Print the phase timings and counts with `--verbose`
+    pub fn print_timings(&self) {
+        for phase in &self.phases {
+            verbose!("Phase {}: {:.1} ms", phase.phase, phase.millis);
+        }
+        verbose!(
+            "{} row(s), {} column(s), ~{} distinct value(s)",
+            self.rows,
+            self.columns,
+            self.estimated_distinct_values
+        );
+    }
+
+    /// Write the profile as pretty-printed JSON
+    pub fn write(&self, path: &Path) -> Result<()> {
+        crate::clobber::write(path, serde_json::to_string_pretty(self)? + "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsf::ranking::rank_columns;
+
+    #[test]
+    fn test_profile_json_contains_phases_and_counts() {
+        let headers = vec!["A".to_string(), "B".to_string()];
+        let rows = vec![
+            vec!["1".to_string(), "x".to_string()],
+            vec!["2".to_string(), "x".to_string()],
+        ];
+
+        let mut profile = Profile::new(RankingOptions::default());
+        let ranked = profile
+            .time("cardinality", || {
+                rank_columns(&headers, &rows, RankingOptions::default())
+            })
+            .unwrap();
+        profile.time("sort", || ());
+        profile.record_counts(rows.len(), &ranked);
+
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["rows"], 2);
+        assert_eq!(json["columns"], 2);
+        assert_eq!(json["estimated_distinct_values"], 3);
+        assert_eq!(json["phases"][0]["phase"], "cardinality");
+        assert_eq!(json["phases"][1]["phase"], "sort");
+        assert!(json["phases"][0]["millis"].is_number());
+        assert!(json["options"]["treat_empty_as_null"].is_boolean());
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+
+/// Rows processed between two [`ProgressSink::on_rows`] reports
+pub const PROGRESS_INTERVAL: u64 = 10_000;
+
+/// Stage of a long operation, reported to a [`ProgressSink`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Phase {
+    Read,
+    Cardinality,
+    Sort,
+    Write,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Read => write!(f, "read"),
+            Phase::Cardinality => write!(f, "cardinality"),
+            Phase::Sort => write!(f, "sort"),
+            Phase::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// Receives progress reports from long operations, e.g. to drive a
+/// progress bar
+///
+/// Each phase starts with [`on_phase`](Self::on_phase), followed by
+/// [`on_rows`](Self::on_rows) every [`PROGRESS_INTERVAL`] rows and once more
+/// when the phase is done. Reading a file of known size also reports
+/// [`on_bytes`](Self::on_bytes) just before each `on_rows`. The methods do
+/// nothing by default.
+///
+/// Reports are made before results are stored, so a sink that panics
+/// unwinds out of the operation and leaves a document as it was. The
+/// exceptions are rows sorted in place by [`Phase::Sort`], which stay
+/// sorted, and output already written by [`Phase::Write`], which stays
+/// written.
+pub trait ProgressSink {
+    /// A phase is starting
+    fn on_phase(&self, phase: Phase) {
+        let _ = phase;
+    }
+
+    /// `processed` rows of the current phase are done, out of `total` when
+    /// it is known in advance
+    fn on_rows(&self, processed: u64, total: Option<u64>) {
+        let _ = (processed, total);
+    }
+
+    /// `processed` bytes of a `total`-byte input have been read, during
+    /// [`Phase::Read`]
+    fn on_bytes(&self, processed: u64, total: u64) {
+        let _ = (processed, total);
+    }
+}
+
+/// A sink that ignores every report
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {}
+
+/// Counts the rows of one phase, reporting every [`PROGRESS_INTERVAL`]
+pub(crate) struct RowCounter<'a> {
+    sink: &'a dyn ProgressSink,
+    processed: u64,
+    total: Option<u64>,
+    /// Size of the input, for byte reports
+    bytes: Option<u64>,
+}
+
+impl<'a> RowCounter<'a> {
+    /// Start `phase`, announcing it to `sink`
+    pub(crate) fn start(sink: &'a dyn ProgressSink, phase: Phase, total: Option<usize>) -> Self {
+        sink.on_phase(phase);
+        Self {
+            sink,
+            processed: 0,
+            total: total.map(|total| total as u64),
+            bytes: None,
+        }
+    }
+
+    /// Also report bytes read out of `bytes`, when the input's size is known
+    pub(crate) fn with_bytes(mut self, bytes: Option<u64>) -> Self {
+        self.bytes = bytes;
+        self
+    }
+
+    /// Like [`tick`](Self::tick), with `byte` bytes of the input read so far
+    pub(crate) fn tick_at(&mut self, byte: u64) {
+        self.processed += 1;
+        if self.processed.is_multiple_of(PROGRESS_INTERVAL) {
+            if let Some(total) = self.bytes {
+                self.sink.on_bytes(byte.min(total), total);
+            }
+            self.sink.on_rows(self.processed, self.total);
+        }
+    }
+
+    pub(crate) fn tick(&mut self) {
+        self.processed += 1;
+        if self.processed.is_multiple_of(PROGRESS_INTERVAL) {
+            self.sink.on_rows(self.processed, self.total);
+        }
+    }
+
+    /// Count `rows` at once, for work that cannot be reported row by row
+    pub(crate) fn finish_with(mut self, rows: usize) {
+        self.processed = rows as u64;
+        self.finish();
+    }
+
+    pub(crate) fn finish(self) {
+        if let Some(total) = self.bytes {
+            self.sink.on_bytes(total, total);
+        }
+        self.sink.on_rows(self.processed, self.total);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Recorder(RefCell<Vec<String>>);
+
+    impl ProgressSink for Recorder {
+        fn on_phase(&self, phase: Phase) {
+            self.0.borrow_mut().push(phase.to_string());
+        }
+
+        fn on_rows(&self, processed: u64, total: Option<u64>) {
+            self.0
+                .borrow_mut()
+                .push(format!("{}/{}", processed, total.unwrap_or(0)));
+        }
+
+        fn on_bytes(&self, processed: u64, total: u64) {
+            self.0
+                .borrow_mut()
+                .push(format!("{}B/{}B", processed, total));
+        }
+    }
+
+    #[test]
+    fn test_reports_are_bounded() {
+        let sink = Recorder::default();
+        let mut counter = RowCounter::start(&sink, Phase::Cardinality, Some(25_000));
+        for _ in 0..25_000 {
+            counter.tick();
+        }
+        counter.finish();
+
+        assert_eq!(
+            sink.0.into_inner(),
+            ["cardinality", "10000/25000", "20000/25000", "25000/25000"]
+        );
+    }
+
+    #[test]
+    fn test_bytes_are_reported_before_rows() {
+        let sink = Recorder::default();
+        let mut counter = RowCounter::start(&sink, Phase::Read, None).with_bytes(Some(900_000));
+        for row in 1..=15_000 {
+            counter.tick_at(row * 50);
+        }
+        counter.finish();
+
+        assert_eq!(
+            sink.0.into_inner(),
+            [
+                "read",
+                "500000B/900000B",
+                "10000/0",
+                "900000B/900000B",
+                "15000/0"
+            ]
+        );
+    }
+}
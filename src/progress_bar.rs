@@ -0,0 +1,142 @@
+use console::Term;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rsf::progress::{Phase, ProgressSink};
+use std::cell::{Cell, RefCell};
+
+/// Line of a phase with a total to fill the bar by
+const BAR: &str = "{prefix:<12} [{bar:30}] {percent:>3}% {msg}";
+
+/// Line of a phase without a total
+const SPINNER: &str = "{prefix:<12} {spinner} {msg}";
+
+/// `indicatif` progress bar on stderr, one line per phase
+///
+/// Reading a file fills the bar by bytes read and phases with a known total
+/// by rows; the rest, such as reading stdin, show a spinner and the row
+/// count. The line of the last phase is left in place when the bar is
+/// dropped.
+#[derive(Debug)]
+pub struct StderrProgress {
+    /// Where each phase's bar draws
+    target: fn() -> ProgressDrawTarget,
+    bar: RefCell<Option<ProgressBar>>,
+    /// The current phase reports bytes, which fill the bar instead of rows
+    by_bytes: Cell<bool>,
+}
+
+impl StderrProgress {
+    /// Draw on stderr even when it is not a terminal, as the caller has
+    /// already decided to show progress
+    pub fn new() -> Self {
+        Self::with_target(|| ProgressDrawTarget::term_like_with_hz(Box::new(Term::stderr()), 20))
+    }
+
+    fn with_target(target: fn() -> ProgressDrawTarget) -> Self {
+        Self {
+            target,
+            bar: RefCell::new(None),
+            by_bytes: Cell::new(false),
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish();
+        }
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template)
+        .expect("progress template is valid")
+        .progress_chars("# ")
+}
+
+/// Turn `bar` into one filled by `done` out of `total`
+fn fill(bar: &ProgressBar, done: u64, total: u64) {
+    if bar.length().is_none() {
+        bar.set_style(style(BAR));
+    }
+    bar.set_length(total);
+    bar.set_position(done.min(total));
+}
+
+impl ProgressSink for StderrProgress {
+    fn on_phase(&self, phase: Phase) {
+        self.finish();
+        self.by_bytes.set(false);
+        let bar = ProgressBar::with_draw_target(None, (self.target)())
+            .with_style(style(SPINNER))
+            .with_prefix(phase.to_string())
+            .with_message("0 rows");
+        bar.tick();
+        *self.bar.borrow_mut() = Some(bar);
+    }
+
+    fn on_rows(&self, processed: u64, total: Option<u64>) {
+        let bar = self.bar.borrow();
+        let Some(bar) = bar.as_ref() else {
+            return;
+        };
+        bar.set_message(format!("{} rows", processed));
+        match total {
+            _ if self.by_bytes.get() => {}
+            Some(total) => fill(bar, processed, total),
+            None => bar.tick(),
+        }
+    }
+
+    fn on_bytes(&self, processed: u64, total: u64) {
+        if let Some(bar) = self.bar.borrow().as_ref() {
+            self.by_bytes.set(true);
+            fill(bar, processed, total);
+        }
+    }
+}
+
+impl Drop for StderrProgress {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(progress: &StderrProgress) -> ProgressBar {
+        progress.bar.borrow().clone().unwrap()
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        let progress = StderrProgress::with_target(ProgressDrawTarget::hidden);
+
+        // Read by bytes: a quarter of the file holds 1,000 rows
+        progress.on_phase(Phase::Read);
+        let read = bar(&progress);
+        assert_eq!(read.prefix(), "read");
+        assert_eq!(read.length(), None);
+        progress.on_bytes(250, 1_000);
+        progress.on_rows(1_000, None);
+        assert_eq!((read.position(), read.length()), (250, Some(1_000)));
+        assert_eq!(read.message(), "1000 rows");
+
+        progress.on_phase(Phase::Sort);
+        assert!(read.is_finished());
+        let sort = bar(&progress);
+        progress.on_rows(50, Some(100));
+        assert_eq!((sort.position(), sort.length()), (50, Some(100)));
+        assert_eq!(sort.message(), "50 rows");
+
+        // Stdin has no size: a spinner and the row count
+        progress.on_phase(Phase::Read);
+        let stdin = bar(&progress);
+        progress.on_rows(20_000, None);
+        assert_eq!(stdin.length(), None);
+        assert_eq!(stdin.message(), "20000 rows");
+
+        drop(progress);
+        assert!(stdin.is_finished());
+    }
+}
@@ -0,0 +1,595 @@
+use crate::ranking::{
+    counted_value, ColumnMeta, ColumnStats, Interner, RankMetric, RankingOptions, TieBreak,
+};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+
+/// Incremental column ranking for data that arrives one record at a time
+///
+/// Only per-column value counts are kept, so the records themselves never
+/// need to be held in memory; a value counted in several columns is stored
+/// once. Once every record has been pushed,
+/// [`finish`](Self::finish) ranks the columns and returns a [`ColumnOrder`]
+/// for moving records into rank order; sorting the reordered records is left
+/// to the caller.
+///
+/// ```
+/// use rsf::{ranking::RankingOptions, RsfRanker};
+///
+/// let mut ranker = RsfRanker::new(RankingOptions::default()).with_headers(&["Category", "Id"]);
+/// ranker.push_record(&["food", "2"]);
+/// ranker.push_record(&["travel", "1"]);
+/// ranker.push_record(&["food", "3"]);
+///
+/// let (columns, order) = ranker.finish();
+/// assert_eq!(columns[0].name, "Id");
+/// assert_eq!(order.reorder(&["food", "2"]), ["2", "food"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RsfRanker {
+    options: RankingOptions,
+    stats: Vec<ColumnStats>,
+    /// The distinct values of every column in `stats`
+    interner: Interner,
+    records: usize,
+    overrides: BTreeMap<String, usize>,
+    max_distinct: Option<usize>,
+}
+
+impl RsfRanker {
+    /// Create a ranker with no columns; set them with
+    /// [`with_headers`](Self::with_headers)
+    pub fn new(options: RankingOptions) -> Self {
+        Self {
+            options,
+            stats: Vec::new(),
+            interner: Interner::new(),
+            records: 0,
+            overrides: BTreeMap::new(),
+            max_distinct: None,
+        }
+    }
+
+    /// Pick up counting where an earlier ranker left off, given its
+    /// statistics and the number of records it had seen
+    pub(crate) fn resume(options: RankingOptions, stats: Vec<ColumnStats>, records: usize) -> Self {
+        let mut interner = Interner::new();
+        interner.extend(&stats);
+        Self {
+            options,
+            stats,
+            interner,
+            records,
+            overrides: BTreeMap::new(),
+            max_distinct: None,
+        }
+    }
+
+    /// Name the columns, discarding anything pushed so far
+    pub fn with_headers(mut self, headers: &[impl AsRef<str>]) -> Self {
+        self.stats = headers
+            .iter()
+            .map(|name| ColumnStats::new(name.as_ref()))
+            .collect();
+        self.interner = Interner::new();
+        self.records = 0;
+        match self.max_distinct {
+            Some(max) => self.with_max_distinct(max),
+            None => self,
+        }
+    }
+
+    /// Stop counting new values of a column once it has `max` distinct ones;
+    /// see [`ColumnStats::with_max_distinct`]
+    pub fn with_max_distinct(mut self, max: usize) -> Self {
+        self.max_distinct = Some(max);
+        self.stats = self
+            .stats
+            .into_iter()
+            .map(|stat| stat.with_max_distinct(max))
+            .collect();
+        self
+    }
+
+    /// Rank the named columns by the given cardinalities instead of their
+    /// counted ones, e.g. ones known from a larger population than the
+    /// records pushed; other columns keep their counts
+    ///
+    /// Names that are not a column are ignored.
+    pub fn with_cardinality_overrides(mut self, overrides: BTreeMap<String, usize>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Count the cells of one record
+    ///
+    /// Cells beyond the last header are ignored, and missing trailing cells
+    /// are not counted as values, only as nulls.
+    pub fn push_record(&mut self, record: &[impl AsRef<str>]) {
+        for (idx, stat) in self.stats.iter_mut().enumerate() {
+            let Some(value) = record.get(idx) else {
+                stat.add_null();
+                continue;
+            };
+            let value = value.as_ref();
+            if self.options.is_null(value) {
+                stat.add_null();
+                if self.options.treat_empty_as_null {
+                    stat.add_null_as_written(value, self.options.include_nulls);
+                }
+            }
+            if let Some(value) = counted_value(value, &self.options) {
+                stat.add_interned(&value, &mut self.interner);
+            }
+        }
+        self.records += 1;
+    }
+
+    /// Number of records pushed so far
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Statistics of each column so far, in header order
+    pub fn stats(&self) -> &[ColumnStats] {
+        &self.stats
+    }
+
+    /// Give up the ranker, returning its statistics in header order
+    pub fn into_stats(self) -> Vec<ColumnStats> {
+        self.stats
+    }
+
+    /// Rank the columns from the records pushed so far
+    ///
+    /// Returns the columns in rank order and the mapping that moves a record's
+    /// cells into that order.
+    pub fn finish(self) -> (Vec<ColumnMeta>, ColumnOrder) {
+        self.ranking()
+    }
+
+    /// Like [`finish`](Self::finish), keeping the counts for
+    /// [`into_stats`](Self::into_stats) or more records
+    pub fn ranking(&self) -> (Vec<ColumnMeta>, ColumnOrder) {
+        let metric = self.options.metric;
+        // Indexed by original position, like the ranks before sorting
+        let hashes: Option<Vec<u64>> = (self.options.tiebreak == TieBreak::Hash)
+            .then(|| self.stats.iter().map(ColumnStats::content_hash).collect());
+        // Ties go by content hash and name if asked, and finally by original
+        // position (stable)
+        let tiebreak = |a: &ColumnMeta, b: &ColumnMeta| {
+            match &hashes {
+                Some(hashes) => hashes[a.rank]
+                    .cmp(&hashes[b.rank])
+                    .then_with(|| a.name.cmp(&b.name)),
+                None => Ordering::Equal,
+            }
+            .then(a.rank.cmp(&b.rank))
+        };
+        // Ranked by original position until sorted below
+        let mut columns = self.column_meta();
+
+        match metric {
+            // Sort by cardinality (descending), then by the tie-break
+            RankMetric::Cardinality => columns.sort_by(|a, b| {
+                b.cardinality
+                    .cmp(&a.cardinality)
+                    .then_with(|| tiebreak(a, b))
+            }),
+            // Sort by entropy (descending), then by the tie-break
+            RankMetric::Entropy => columns.sort_by(|a, b| {
+                b.entropy
+                    .unwrap_or(0.0)
+                    .total_cmp(&a.entropy.unwrap_or(0.0))
+                    .then_with(|| tiebreak(a, b))
+            }),
+        }
+
+        // Pinned columns go last in their listed order; the sort is stable
+        // and names not among the columns are ignored
+        let pins = &self.options.pin_last;
+        columns.sort_by_key(|col| pins.iter().position(|name| *name == col.name));
+
+        let order = ColumnOrder::from_sources(columns.iter().map(|col| Some(col.rank)).collect());
+
+        // Update ranks
+        for (new_rank, col) in columns.iter_mut().enumerate() {
+            col.rank = new_rank + 1;
+        }
+
+        (columns, order)
+    }
+
+    /// Column metadata for the records pushed so far, leaving the columns in
+    /// header order with ranks 1, 2, 3, ...
+    pub fn finish_in_source_order(self) -> Vec<ColumnMeta> {
+        let mut columns = self.column_meta();
+        for col in &mut columns {
+            col.rank += 1;
+        }
+        columns
+    }
+
+    /// Metadata in header order, with each column's 0-based position as its rank
+    fn column_meta(&self) -> Vec<ColumnMeta> {
+        let metric = self.options.metric;
+        let records = self.records;
+
+        self.stats
+            .iter()
+            .enumerate()
+            .map(|(idx, stat)| {
+                let mut meta = ColumnMeta::new(stat.name.clone(), idx, stat.cardinality());
+                if records > 0 {
+                    meta.cardinality_ratio = stat.cardinality() as f64 / records as f64;
+                    meta.is_unique = stat.is_unique(records);
+                }
+                if metric == RankMetric::Entropy {
+                    meta.entropy = Some(stat.entropy());
+                }
+                if let Some(&cardinality) = self.overrides.get(&stat.name) {
+                    meta.cardinality = cardinality;
+                    meta.cardinality_overridden = true;
+                }
+                meta
+            })
+            .collect()
+    }
+}
+
+/// Moves the cells of a record from input column order into rank order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnOrder {
+    /// Input position of each ranked column, `None` if the input lacks it
+    sources: Vec<Option<usize>>,
+    /// Swaps that permute a record padded to `width` cells into rank order,
+    /// or `None` if an input position feeds two ranked columns
+    swaps: Option<Vec<(usize, usize)>>,
+    /// Cells a record is padded to before swapping
+    width: usize,
+}
+
+impl ColumnOrder {
+    /// Map `headers` onto `ranked_columns`, e.g. the columns of a schema
+    ///
+    /// Ranked columns missing from `headers` come out as empty cells.
+    pub fn new(headers: &[impl AsRef<str>], ranked_columns: &[ColumnMeta]) -> Self {
+        Self::from_sources(
+            ranked_columns
+                .iter()
+                .map(|col| headers.iter().position(|h| h.as_ref() == col.name))
+                .collect(),
+        )
+    }
+
+    fn from_sources(sources: Vec<Option<usize>>) -> Self {
+        let width = sources
+            .iter()
+            .flatten()
+            .map(|idx| idx + 1)
+            .max()
+            .unwrap_or(0)
+            .max(sources.len());
+
+        let mut seen = HashSet::new();
+        let movable = sources.iter().flatten().all(|idx| seen.insert(*idx));
+        let swaps = movable.then(|| {
+            // Complete the mapping to a permutation of 0..width: ranked columns
+            // the input lacks, and positions past the last one, take the
+            // unused input positions, which are cleared or cut off afterwards
+            let mut unused = (0..width).filter(|idx| !seen.contains(idx));
+            let permutation: Vec<usize> = (0..width)
+                .map(|target| {
+                    sources
+                        .get(target)
+                        .copied()
+                        .flatten()
+                        .or_else(|| unused.next())
+                        .unwrap_or(target)
+                })
+                .collect();
+            cycle_swaps(&permutation)
+        });
+
+        Self {
+            sources,
+            swaps,
+            width,
+        }
+    }
+
+    /// The cells of `record` in rank order
+    pub fn reorder(&self, record: &[impl AsRef<str>]) -> Vec<String> {
+        self.sources
+            .iter()
+            .map(|source| {
+                source
+                    .and_then(|idx| record.get(idx))
+                    .map_or_else(String::new, |value| value.as_ref().to_string())
+            })
+            .collect()
+    }
+
+    /// Like [`reorder`](Self::reorder), moving the cells out of `record`
+    /// instead of copying them
+    pub fn reorder_owned(&self, mut record: Vec<String>) -> Vec<String> {
+        self.reorder_in_place(&mut record);
+        record
+    }
+
+    /// Put the cells of `record` in rank order by swapping them, reusing its
+    /// allocation
+    ///
+    /// Short records are padded with empty cells first, so no cell is lost;
+    /// cells of columns that are not ranked are dropped.
+    pub fn reorder_in_place(&self, record: &mut Vec<String>) {
+        // A cell feeding two columns (duplicate headers) must be copied
+        let Some(swaps) = &self.swaps else {
+            *record = self.reorder(record);
+            return;
+        };
+
+        if record.len() < self.width {
+            record.resize_with(self.width, String::new);
+        }
+        for &(a, b) in swaps {
+            record.swap(a, b);
+        }
+        record.truncate(self.sources.len());
+        for (value, source) in record.iter_mut().zip(&self.sources) {
+            if source.is_none() {
+                value.clear();
+            }
+        }
+    }
+}
+
+/// Swaps that rearrange a slice so position `i` holds what was at
+/// `permutation[i]`, following each cycle once
+fn cycle_swaps(permutation: &[usize]) -> Vec<(usize, usize)> {
+    let mut swaps = Vec::new();
+    let mut done = vec![false; permutation.len()];
+    for start in 0..permutation.len() {
+        if done[start] {
+            continue;
+        }
+        done[start] = true;
+        let mut idx = start;
+        while permutation[idx] != start {
+            swaps.push((idx, permutation[idx]));
+            idx = permutation[idx];
+            done[idx] = true;
+        }
+    }
+    swaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranking::{compute_cardinality, rank_columns, reorder_data};
+
+    fn table() -> (Vec<String>, Vec<Vec<String>>) {
+        let headers = ["Region", "Id", "Flag", "Note"].map(String::from).to_vec();
+        let rows = [
+            ["north", "1", "y", ""],
+            ["south", "2", "y", "late"],
+            ["north", "3", "n", ""],
+            ["east", "4", "y", " "],
+            ["north", "5", "y", "late"],
+        ]
+        .iter()
+        .map(|row| row.map(String::from).to_vec())
+        .collect();
+        (headers, rows)
+    }
+
+    #[test]
+    fn test_record_by_record_matches_batch() {
+        let (headers, rows) = table();
+        let options = [
+            RankingOptions::default(),
+            RankingOptions {
+                metric: RankMetric::Entropy,
+                ..Default::default()
+            },
+            RankingOptions {
+                treat_empty_as_null: true,
+                include_nulls: false,
+                ..Default::default()
+            },
+        ];
+
+        for options in options {
+            let mut ranker = RsfRanker::new(options.clone()).with_headers(&headers);
+            for row in &rows {
+                let record: Vec<&str> = row.iter().map(String::as_str).collect();
+                ranker.push_record(&record);
+            }
+            assert_eq!(ranker.records(), rows.len());
+
+            let stats = compute_cardinality(&headers, &rows, options.clone()).unwrap();
+            let counts: Vec<usize> = ranker.stats().iter().map(|s| s.cardinality()).collect();
+            assert_eq!(
+                counts,
+                stats.iter().map(|s| s.cardinality()).collect::<Vec<_>>()
+            );
+
+            let (columns, order) = ranker.finish();
+            let batch = rank_columns(&headers, &rows, options).unwrap();
+            assert_eq!(columns, batch);
+
+            let (_, batch_rows) = reorder_data(&headers, rows.clone(), &batch).unwrap();
+            let streamed: Vec<Vec<String>> = rows.iter().map(|row| order.reorder(row)).collect();
+            assert_eq!(streamed, batch_rows);
+        }
+    }
+
+    #[test]
+    fn test_hash_tiebreak_ignores_input_column_order() {
+        // Region and Flag tie at 2 distinct values; Copy has Flag's values
+        let columns = [
+            ("Id", ["1", "2", "3"]),
+            ("Region", ["north", "south", "north"]),
+            ("Flag", ["y", "n", "y"]),
+            ("Copy", ["n", "y", "y"]),
+        ];
+        let options = RankingOptions::builder()
+            .tiebreak(TieBreak::Hash)
+            .build()
+            .unwrap();
+        let ranked_names = |order: &[usize]| -> Vec<String> {
+            let headers: Vec<&str> = order.iter().map(|&i| columns[i].0).collect();
+            let rows: Vec<Vec<String>> = (0..3)
+                .map(|r| order.iter().map(|&i| columns[i].1[r].to_string()).collect())
+                .collect();
+            rank_columns(&headers, &rows, options.clone())
+                .unwrap()
+                .into_iter()
+                .map(|col| col.name)
+                .collect()
+        };
+
+        let expected = ranked_names(&[0, 1, 2, 3]);
+        assert_eq!(expected[0], "Id");
+        // Equal content falls back to the name
+        let copy = expected.iter().position(|name| name == "Copy").unwrap();
+        let flag = expected.iter().position(|name| name == "Flag").unwrap();
+        assert_eq!(flag, copy + 1);
+        for order in [[3, 2, 1, 0], [2, 0, 3, 1], [1, 3, 0, 2]] {
+            assert_eq!(ranked_names(&order), expected);
+        }
+
+        // By position, the same reordering changes the ranking
+        let by_position = |order: [usize; 4]| {
+            let headers: Vec<&str> = order.iter().map(|&i| columns[i].0).collect();
+            let rows: Vec<Vec<String>> = (0..3)
+                .map(|r| order.iter().map(|&i| columns[i].1[r].to_string()).collect())
+                .collect();
+            rank_columns(&headers, &rows, Default::default()).unwrap()[1]
+                .name
+                .clone()
+        };
+        assert_ne!(by_position([0, 1, 2, 3]), by_position([0, 2, 1, 3]));
+    }
+
+    #[test]
+    fn test_reorder_owned_matches_reorder() {
+        let ranked = [ColumnMeta::new("B", 1, 2), ColumnMeta::new("A", 2, 2)];
+        let record = vec!["a".to_string(), "b".to_string()];
+
+        let order = ColumnOrder::new(&["A", "B"], &ranked);
+        assert_eq!(order.reorder_owned(record.clone()), order.reorder(&record));
+        assert_eq!(order.reorder_owned(vec!["a".to_string()]), ["", "a"]);
+
+        // Both "A" columns read the first cell, so it has to be copied
+        let ranked = [ColumnMeta::new("A", 1, 2), ColumnMeta::new("A", 2, 2)];
+        let order = ColumnOrder::new(&["A", "A"], &ranked);
+        assert_eq!(order.reorder_owned(record.clone()), ["a", "a"]);
+        assert_eq!(order.reorder_owned(record.clone()), order.reorder(&record));
+    }
+
+    #[test]
+    fn test_reorder_in_place() {
+        let row = |cells: &[&str]| -> Vec<String> { cells.iter().map(|c| c.to_string()).collect() };
+        let ranked: Vec<ColumnMeta> = ["D", "Missing", "A", "C"]
+            .iter()
+            .map(|name| ColumnMeta::new(*name, 0, 0))
+            .collect();
+        // B is not ranked, so its cell is dropped
+        let order = ColumnOrder::new(&["A", "B", "C", "D"], &ranked);
+
+        for record in [
+            row(&["a", "b", "c", "d"]),
+            row(&["a", "b", "c", "d", "extra"]),
+            row(&["a", "b"]),
+            row(&[]),
+        ] {
+            let expected = order.reorder(&record);
+            let mut reordered = record;
+            // Room for the padding, so the buffer must not move
+            reordered.reserve(order.width.saturating_sub(reordered.len()));
+            let ptr = reordered.as_ptr();
+
+            order.reorder_in_place(&mut reordered);
+            assert_eq!(reordered, expected);
+            assert_eq!(reordered.as_ptr(), ptr);
+        }
+
+        let mut record = row(&["a", "b", "c", "d"]);
+        order.reorder_in_place(&mut record);
+        assert_eq!(record, ["d", "", "a", "c"]);
+    }
+
+    #[test]
+    fn test_no_records() {
+        let (columns, order) = RsfRanker::new(Default::default())
+            .with_headers(&["A", "B"])
+            .finish();
+
+        let ranks: Vec<usize> = columns.iter().map(|col| col.rank).collect();
+        assert_eq!(ranks, vec![1, 2]);
+        assert!(columns.iter().all(|col| !col.is_unique));
+        assert_eq!(order.reorder(&["a", "b"]), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_max_distinct() {
+        let mut ranker = RsfRanker::new(Default::default())
+            .with_max_distinct(3)
+            .with_headers(&["Id", "Kind"]);
+        for id in 0..10 {
+            ranker.push_record(&[id.to_string().as_str(), ["a", "b"][id % 2]]);
+        }
+
+        let stats = ranker.stats();
+        assert!(stats[0].is_capped());
+        assert_eq!(stats[0].cardinality(), 3);
+        // Values already kept are still counted
+        assert_eq!(stats[0].value_counts["0"], 1);
+        assert!(!stats[1].is_capped());
+        assert_eq!(stats[1].cardinality(), 2);
+        assert_eq!(stats[1].value_counts["a"], 5);
+    }
+
+    #[test]
+    fn test_values_are_interned_across_columns() {
+        let mut ranker = RsfRanker::new(Default::default()).with_headers(&["Id", "Parent"]);
+        ranker.push_record(&["1", ""]);
+        ranker.push_record(&["2", "1"]);
+
+        let stats = ranker.into_stats();
+        let (id, _) = stats[0].value_counts.get_key_value("1").unwrap();
+        let (parent, _) = stats[1].value_counts.get_key_value("1").unwrap();
+        assert!(std::sync::Arc::ptr_eq(id, parent));
+    }
+
+    #[test]
+    fn test_finish_in_source_order() {
+        let (headers, rows) = table();
+        let mut ranker = RsfRanker::new(Default::default()).with_headers(&headers);
+        for row in &rows {
+            ranker.push_record(row);
+        }
+
+        let columns = ranker.finish_in_source_order();
+        let names: Vec<&str> = columns.iter().map(|col| col.name.as_str()).collect();
+        let ranks: Vec<usize> = columns.iter().map(|col| col.rank).collect();
+        assert_eq!(names, headers);
+        assert_eq!(ranks, vec![1, 2, 3, 4]);
+        assert!(columns[1].is_unique);
+    }
+
+    #[test]
+    fn test_column_order_from_schema_columns() {
+        let ranked = vec![
+            ColumnMeta::new("B", 1, 2),
+            ColumnMeta::new("Missing", 2, 1),
+            ColumnMeta::new("A", 3, 1),
+        ];
+        let order = ColumnOrder::new(&["A", "B"], &ranked);
+
+        assert_eq!(order.reorder(&["a", "b"]), ["b", "", "a"]);
+        assert_eq!(order.reorder(&["a"]), ["", "", "a"]);
+    }
+}
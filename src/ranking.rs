@@ -1,339 +1,1987 @@
 use crate::errors::{RsfError, RsfResult};
+use crate::numeric::{compare_cells, parse_number};
+use crate::pii::PiiRisk;
+use crate::ranker::{ColumnOrder, RsfRanker};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io::Read;
+#[cfg(feature = "std-fs")]
+use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 /// Column type classification
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
+#[non_exhaustive]
 pub enum ColumnType {
     Key,
     Value,
 }
 
+/// Declared type of a column's values, as given by a type row
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum DataType {
+    /// Whole numbers
+    Int,
+    /// Any finite number
+    Float,
+    /// Calendar dates written as YYYY-MM-DD
+    Date,
+    /// Any text
+    Str,
+}
+
+impl DataType {
+    /// Parse a type token such as `int` or `str`, ignoring case
+    pub fn from_token(token: &str) -> Option<Self> {
+        match token.trim().to_ascii_lowercase().as_str() {
+            "int" | "integer" => Some(DataType::Int),
+            "float" | "number" => Some(DataType::Float),
+            "date" => Some(DataType::Date),
+            "str" | "string" | "text" => Some(DataType::Str),
+            _ => None,
+        }
+    }
+
+    /// Values are numbers, so decimal notation may be normalized
+    pub fn is_numeric(self) -> bool {
+        matches!(self, DataType::Int | DataType::Float)
+    }
+
+    /// Whether `value` is of this type; empty cells are nulls and always match
+    pub fn accepts(self, value: &str, decimal_comma: bool) -> bool {
+        let value = value.trim();
+        if value.is_empty() {
+            return true;
+        }
+        match self {
+            DataType::Int => {
+                let digits = value.strip_prefix(['-', '+']).unwrap_or(value);
+                !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+            }
+            DataType::Float => parse_number(value, decimal_comma).is_some_and(f64::is_finite),
+            DataType::Date => is_iso_date(value),
+            DataType::Str => true,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DataType::Int => "int",
+            DataType::Float => "float",
+            DataType::Date => "date",
+            DataType::Str => "str",
+        })
+    }
+}
+
+fn is_iso_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return false;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<u32> {
+        let part = &value[range];
+        part.bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| part.parse().ok())
+            .flatten()
+    };
+    let (Some(year), Some(month), Some(day)) = (number(0..4), number(5..7), number(8..10)) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return false,
+    };
+    (1..=days).contains(&day)
+}
+
+/// Read the declared type of each column from a type row
+///
+/// Fails on the first token that is not a known type, naming its column.
+pub fn parse_type_row(headers: &[String], row: &[String]) -> RsfResult<Vec<DataType>> {
+    headers
+        .iter()
+        .enumerate()
+        .map(|(idx, header)| {
+            let token = row.get(idx).map_or("", String::as_str);
+            DataType::from_token(token).ok_or_else(|| {
+                RsfError::schema_error(format!(
+                    "Unknown type '{}' for column '{}' in the type row (expected int, float, date or str)",
+                    token, header
+                ))
+            })
+        })
+        .collect()
+}
+
 /// Column metadata for schema
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ColumnMeta {
     pub name: String,
+    /// Header as written in the input, when rank renamed the column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_name: Option<String>,
+    /// 1-based position in the ranked output
     pub rank: usize,
+    /// Number of distinct values
     pub cardinality: usize,
+    /// Cardinality divided by the schema's row count; 0 when there are no rows
+    #[serde(default)]
+    pub cardinality_ratio: f64,
     #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
     pub col_type: Option<ColumnType>,
+    /// Type declared by the input's type row
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<DataType>,
+    /// Shannon entropy in bits, recorded when ranking by entropy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entropy: Option<f64>,
+    /// Every row has a distinct value (a perfect key)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_unique: bool,
+    /// The cardinality came from `rank --cardinalities` rather than the data;
+    /// validation orders by it but does not recount it
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub cardinality_overridden: bool,
+    /// How likely the values are personal data, from `stats --detect-pii`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pii_risk: Option<PiiRisk>,
+    /// Free-form labels such as `PII`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Rank added the column after the ranked ones; validation does not rank it
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub synthetic: bool,
+    /// What a synthetic column's values are derived from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synthetic_source: Option<SyntheticSource>,
+}
+
+impl ColumnMeta {
+    /// Create column metadata with no optional fields set
+    pub fn new(name: impl Into<String>, rank: usize, cardinality: usize) -> Self {
+        Self {
+            name: name.into(),
+            original_name: None,
+            rank,
+            cardinality,
+            cardinality_ratio: 0.0,
+            col_type: None,
+            data_type: None,
+            entropy: None,
+            is_unique: false,
+            cardinality_overridden: false,
+            pii_risk: None,
+            tags: Vec::new(),
+            synthetic: false,
+            synthetic_source: None,
+        }
+    }
+}
+
+/// What a synthetic column holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SyntheticSource {
+    /// SHA-256 of the row's ranked cells, from `rank --emit-row-hashes`
+    RowHash,
+}
+
+/// Metric used to order columns
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum RankMetric {
+    /// Number of distinct values
+    #[default]
+    Cardinality,
+    /// Shannon entropy of the value distribution
+    Entropy,
+}
+
+impl fmt::Display for RankMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RankMetric::Cardinality => write!(f, "cardinality"),
+            RankMetric::Entropy => write!(f, "entropy"),
+        }
+    }
+}
+
+/// How columns with the same cardinality or entropy are ordered
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum TieBreak {
+    /// Keep the order the columns had in the input
+    #[default]
+    Position,
+    /// Order by a stable hash of each column's distinct values, then by
+    /// name, so the ranking does not depend on the input column order
+    Hash,
+}
+
+impl fmt::Display for TieBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TieBreak::Position => write!(f, "position"),
+            TieBreak::Hash => write!(f, "hash"),
+        }
+    }
 }
 
 /// Schema representation
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Schema {
     pub version: String,
+    /// Number of data rows the columns were counted over
+    #[serde(default)]
+    pub row_count: usize,
     pub columns: Vec<ColumnMeta>,
+    /// Metric the columns were ranked by
+    #[serde(default)]
+    pub rank_by: RankMetric,
+    /// NUL bytes were removed from fields before ranking
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub nul_stripped: bool,
+    /// Numeric cells use `,` as the decimal point and sort by value
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub decimal_comma: bool,
+    /// Columns were left in their source order instead of being ranked
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub source_order: bool,
+    /// Asserts that ties were ordered by stable sorts: columns of equal rank
+    /// keep their input order and equal rows their relative order, so the
+    /// same input always gives the same bytes
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub sort_stable: bool,
+    /// Empty cells were left out of the cardinalities
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub nulls_excluded: bool,
+    /// A tab-separated copy of the data written alongside the CSV
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub also_available_as_tsv: Option<PathBuf>,
+    /// Files the data was split into, in row order; each holds the header
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<PathBuf>,
+    /// Options the data was ranked with; schemas written before they were
+    /// recorded fall back to `rank_by`, `decimal_comma` and `nulls_excluded`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<RankingOptions>,
+}
+
+impl Schema {
+    /// Create a schema for the given ranked columns
+    pub fn new(columns: Vec<ColumnMeta>) -> Self {
+        Self {
+            version: "0.1".to_string(),
+            row_count: 0,
+            columns,
+            rank_by: RankMetric::Cardinality,
+            nul_stripped: false,
+            decimal_comma: false,
+            source_order: false,
+            sort_stable: false,
+            nulls_excluded: false,
+            also_available_as_tsv: None,
+            chunks: Vec::new(),
+            options: None,
+        }
+    }
+
+    /// Options the schema's data was ranked with, for validation
+    pub fn ranking_options(&self) -> RankingOptions {
+        if let Some(options) = &self.options {
+            return RankingOptions {
+                cardinality_band: None,
+                ..options.clone()
+            };
+        }
+        RankingOptions {
+            treat_empty_as_null: self.nulls_excluded,
+            include_nulls: !self.nulls_excluded,
+            metric: self.rank_by,
+            decimal_comma: self.decimal_comma,
+            cardinality_band: None,
+            // Validation accepts tied columns in either order
+            tiebreak: TieBreak::Position,
+            null_values: Vec::new(),
+            null_values_ignore_case: false,
+            pin_last: Vec::new(),
+        }
+    }
+
+    /// Check the schema is consistent on its own: column names are unique
+    /// and ranks run 1, 2, 3, ...
+    pub fn validate_self(&self) -> RsfResult<()> {
+        if let Some(name) = duplicate_names(&self.columns).first() {
+            return Err(RsfError::schema_error(format!(
+                "Column '{}' appears more than once",
+                name
+            )));
+        }
+        validate_rank_sequence(&self.columns)
+    }
+
+    /// Record the input header of each column renamed from `(old, new)`, so
+    /// the exact spelling survives case-insensitive renaming
+    pub fn record_original_names(&mut self, renamed: &[(String, String)]) {
+        for col in &mut self.columns {
+            if let Some((old, _)) = renamed.iter().find(|(_, new)| *new == col.name) {
+                col.original_name = Some(old.clone());
+            }
+        }
+    }
+}
+
+/// Names used by more than one column, each listed once in schema order
+pub(crate) fn duplicate_names(columns: &[ColumnMeta]) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    for col in columns {
+        let name = col.name.as_str();
+        if !seen.insert(name) && !duplicates.contains(&name) {
+            duplicates.push(name);
+        }
+    }
+    duplicates
+}
+
+/// Fold case for comparing names the way case-insensitive systems do;
+/// uppercasing first also maps `ß` to `ss` like `SS`
+fn fold_case(name: &str) -> String {
+    name.to_uppercase().to_lowercase()
+}
+
+/// Spellings of names that differ only by case, such as `ID` and `id`, one
+/// group per name in order of first appearance
+///
+/// Case is compared with Unicode rules, not just ASCII. Exact duplicates are
+/// not listed twice, so a name that is only repeated is no collision.
+pub fn case_collisions(names: &[impl AsRef<str>]) -> Vec<Vec<String>> {
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    let mut by_folded: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        let name = name.as_ref();
+        let idx = *by_folded.entry(fold_case(name)).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        if !groups[idx].iter().any(|spelling| spelling == name) {
+            groups[idx].push(name.to_string());
+        }
+    }
+
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Rename headers that differ only by case from an earlier one by appending
+/// `_2`, `_3`, ... until no two are equal once case is folded
+///
+/// The first spelling of each name is kept, and so are exact duplicates of
+/// it. Returns `(old, new)` for each header renamed.
+pub fn rename_case_collisions(headers: &mut [String]) -> Vec<(String, String)> {
+    let mut first: HashMap<String, String> = HashMap::new();
+    for header in headers.iter() {
+        first
+            .entry(fold_case(header))
+            .or_insert_with(|| header.clone());
+    }
+    let mut taken: HashSet<String> = first.keys().cloned().collect();
+
+    let mut renamed = Vec::new();
+    for header in headers.iter_mut() {
+        if first[&fold_case(header)] == *header {
+            continue;
+        }
+        let new = (2..)
+            .map(|n| format!("{}_{}", header, n))
+            .find(|candidate| !taken.contains(&fold_case(candidate)))
+            .expect("unbounded suffixes");
+        taken.insert(fold_case(&new));
+        renamed.push((std::mem::replace(header, new.clone()), new));
+    }
+    renamed
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+/// One shared allocation per distinct string
+///
+/// [`RsfRanker`] interns the values of all its columns here, so a value that
+/// occurs in several columns, such as an id repeated as a foreign key, is
+/// stored once.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    values: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The shared copy of `value`, made on first use
+    pub fn intern(&mut self, value: &str) -> Arc<str> {
+        if let Some(shared) = self.values.get(value) {
+            return Arc::clone(shared);
+        }
+        let shared: Arc<str> = Arc::from(value);
+        self.values.insert(Arc::clone(&shared));
+        shared
+    }
+
+    /// Share the distinct values of `stats` with later ones
+    pub(crate) fn extend(&mut self, stats: &[ColumnStats]) {
+        for stat in stats {
+            self.values.extend(stat.value_counts.keys().cloned());
+        }
+    }
+
+    /// Number of distinct strings interned
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
 }
 
 /// Statistics for a single column
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct ColumnStats {
     pub name: String,
-    pub cardinality: usize,
-    pub distinct_values: HashSet<String>,
+    /// Occurrences of each distinct value; equal values counted through an
+    /// [`Interner`] share one allocation across columns
+    pub value_counts: HashMap<Arc<str>, usize>,
+    /// Null and missing cells, whether or not they are counted as values
+    nulls: usize,
+    /// Null cells as written, when the counting folds them into
+    /// [`NULL_VALUE`] or leaves them out of `value_counts`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    null_spellings: HashMap<Arc<str>, usize>,
+    /// Occurrences of [`NULL_VALUE`] in `value_counts` that are folded nulls
+    #[serde(default, skip_serializing_if = "is_zero")]
+    folded_nulls: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_distinct: Option<usize>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    capped: bool,
 }
 
 impl ColumnStats {
-    pub fn new(name: String) -> Self {
+    /// Create empty statistics for a column
+    pub fn new(name: impl Into<String>) -> Self {
         Self {
-            name,
-            cardinality: 0,
-            distinct_values: HashSet::new(),
+            name: name.into(),
+            value_counts: HashMap::new(),
+            nulls: 0,
+            null_spellings: HashMap::new(),
+            folded_nulls: 0,
+            max_distinct: None,
+            capped: false,
         }
     }
 
+    /// Keep at most `max` distinct values, bounding memory for columns with
+    /// very many; values first seen after that are dropped and only noted by
+    /// [`is_capped`](Self::is_capped)
+    pub fn with_max_distinct(mut self, max: usize) -> Self {
+        self.max_distinct = Some(max);
+        self
+    }
+
+    /// Count one occurrence of `value`
     pub fn add_value(&mut self, value: &str) {
-        self.distinct_values.insert(value.to_string());
-        self.cardinality = self.distinct_values.len();
+        self.add_with(value, |value| Arc::from(value));
+    }
+
+    /// Count one occurrence of `value`, storing a first occurrence as the
+    /// copy in `interner`
+    pub fn add_interned(&mut self, value: &str, interner: &mut Interner) {
+        self.add_with(value, |value| interner.intern(value));
+    }
+
+    fn add_with(&mut self, value: &str, share: impl FnOnce(&str) -> Arc<str>) {
+        let full = self
+            .max_distinct
+            .is_some_and(|max| self.value_counts.len() >= max);
+        match self.value_counts.get_mut(value) {
+            Some(count) => *count += 1,
+            None if full => self.capped = true,
+            None => {
+                self.value_counts.insert(share(value), 1);
+            }
+        }
+    }
+
+    /// Count one null or missing cell, in addition to any value it adds
+    pub fn add_null(&mut self) {
+        self.nulls += 1;
+    }
+
+    /// Keep a null cell as written when the counting does not: it is folded
+    /// into [`NULL_VALUE`] if `folded`, and otherwise left out
+    ///
+    /// [`counts_as_written`](Self::counts_as_written) then reports the cell
+    /// as written. This does not count the cell as null; see
+    /// [`add_null`](Self::add_null).
+    pub fn add_null_as_written(&mut self, value: &str, folded: bool) {
+        match self.null_spellings.get_mut(value) {
+            Some(count) => *count += 1,
+            None => {
+                self.null_spellings.insert(Arc::from(value), 1);
+            }
+        }
+        if folded {
+            self.folded_nulls += 1;
+        }
+    }
+
+    /// Null and missing cells seen so far, as counted by
+    /// [`add_null`](Self::add_null)
+    pub fn null_count(&self) -> usize {
+        self.nulls
     }
 
+    /// Occurrences of each distinct cell as written, nulls included,
+    /// whatever the null options of the counting
+    ///
+    /// With nulls distinct this is `value_counts`; otherwise the null cells
+    /// kept by [`add_null_as_written`](Self::add_null_as_written) take the
+    /// place of [`NULL_VALUE`]. Each value appears once, in arbitrary order.
+    pub fn counts_as_written(&self) -> impl Iterator<Item = (&str, usize)> {
+        let spelled_null = |value: &str| self.null_spellings.get(value).copied().unwrap_or(0);
+        let values = self.value_counts.iter().filter_map(move |(value, &count)| {
+            let count = match &**value {
+                NULL_VALUE => count.saturating_sub(self.folded_nulls) + spelled_null(NULL_VALUE),
+                _ => count,
+            };
+            (count > 0).then_some((&**value, count))
+        });
+        // A null written as NULL_VALUE was merged into its entry above
+        let nulls = self
+            .null_spellings
+            .iter()
+            .filter(|(value, _)| {
+                &***value != NULL_VALUE || !self.value_counts.contains_key(NULL_VALUE)
+            })
+            .map(|(value, &count)| (&**value, count));
+        values.chain(nulls)
+    }
+
+    /// Whether a distinct value was dropped for
+    /// [`with_max_distinct`](Self::with_max_distinct), making the cardinality
+    /// a lower bound
+    pub fn is_capped(&self) -> bool {
+        self.capped
+    }
+
+    /// Number of distinct values seen so far
     pub fn cardinality(&self) -> usize {
-        self.cardinality
+        self.value_counts.len()
+    }
+
+    /// Whether every one of `row_count` rows has a distinct value
+    pub fn is_unique(&self, row_count: usize) -> bool {
+        self.cardinality() == row_count
+    }
+
+    /// The `n` most frequent values with their counts, most frequent first
+    ///
+    /// Ties are broken by value so the result is deterministic.
+    pub fn top_values(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut values: Vec<(&str, usize)> = self
+            .value_counts
+            .iter()
+            .map(|(value, &count)| (&**value, count))
+            .collect();
+        values.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        values.truncate(n);
+        values
+    }
+
+    /// Distinct values seen so far, in arbitrary order
+    pub fn distinct_values(&self) -> impl Iterator<Item = &str> {
+        self.value_counts.keys().map(|value| &**value)
+    }
+
+    /// Shannon entropy of the value distribution, in bits
+    ///
+    /// Counts are summed in sorted order so the result does not depend on
+    /// hash iteration order.
+    pub fn entropy(&self) -> f64 {
+        let total: usize = self.value_counts.values().sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let mut counts: Vec<usize> = self.value_counts.values().copied().collect();
+        counts.sort_unstable();
+
+        let total = total as f64;
+        counts
+            .into_iter()
+            .map(|count| {
+                let p = count as f64 / total;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    /// SHA-256 of the sorted distinct values as lowercase hex; each value is
+    /// followed by `0xFF` as in [`content_hash`](Self::content_hash)
+    ///
+    /// Equal fingerprints mean the same set of values, whatever their counts.
+    pub fn fingerprint(&self) -> String {
+        let mut values: Vec<&str> = self.distinct_values().collect();
+        values.sort_unstable();
+
+        let mut hasher = Sha256::new();
+        for value in values {
+            hasher.update(value.as_bytes());
+            hasher.update([0xFF]);
+        }
+        format!("{:x}", hasher.finalize())
     }
 
-    pub fn distinct_values(&self) -> &HashSet<String> {
-        &self.distinct_values
+    /// Hash of the sorted distinct values, the same on every platform and
+    /// Rust version
+    ///
+    /// 64-bit FNV-1a; values are separated by `0xFF`, which never occurs in
+    /// UTF-8.
+    pub fn content_hash(&self) -> u64 {
+        let mut values: Vec<&str> = self.distinct_values().collect();
+        values.sort_unstable();
+
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in values
+            .iter()
+            .flat_map(|value| value.bytes().chain(std::iter::once(0xFF)))
+        {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        hash
     }
 }
 
 /// Options for ranking behavior
-#[derive(Debug, Clone, Copy)]
+///
+/// Built with [`RankingOptions::builder`]; the default is the builder with
+/// nothing set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
 pub struct RankingOptions {
     /// Treat empty strings as null
     pub treat_empty_as_null: bool,
-    /// Include nulls as a distinct value
+    /// Count null as a distinct value; without it cardinality counts only
+    /// real values. Has no effect unless `treat_empty_as_null` is set.
     pub include_nulls: bool,
+    /// Metric used to order columns
+    pub metric: RankMetric,
+    /// Read `1.234,56` as a number and sort numeric cells by value
+    pub decimal_comma: bool,
+    /// Accept cardinalities within this ratio of the schema instead of
+    /// requiring an exact match when validating
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cardinality_band: Option<CardinalityBand>,
+    /// How columns that tie on the metric are ordered
+    pub tiebreak: TieBreak,
+    /// Tokens such as `N/A` or `\N` that count as null, like an empty cell
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub null_values: Vec<String>,
+    /// Match `null_values` regardless of ASCII case
+    #[serde(skip_serializing_if = "is_false")]
+    pub null_values_ignore_case: bool,
+    /// Columns ranked last, in this order, whatever their cardinality
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pin_last: Vec<String>,
 }
 
 impl Default for RankingOptions {
     fn default() -> Self {
         Self {
             treat_empty_as_null: true,
-            include_nulls: false,
+            include_nulls: true,
+            metric: RankMetric::Cardinality,
+            decimal_comma: false,
+            cardinality_band: None,
+            tiebreak: TieBreak::Position,
+            null_values: Vec::new(),
+            null_values_ignore_case: false,
+            pin_last: Vec::new(),
         }
     }
 }
 
-/// Rank columns by cardinality
-pub fn rank_columns(
-    headers: &[String],
-    rows: &[Vec<String>],
+impl RankingOptions {
+    /// Start from the default options
+    pub fn builder() -> RankingOptionsBuilder {
+        RankingOptionsBuilder {
+            options: Self::default(),
+        }
+    }
+
+    /// Whether `value` is a null: empty, whitespace-only or, once trimmed,
+    /// one of the `null_values`
+    pub fn is_null(&self, value: &str) -> bool {
+        let value = value.trim();
+        value.is_empty()
+            || self.null_values.iter().any(|token| {
+                if self.null_values_ignore_case {
+                    token.eq_ignore_ascii_case(value)
+                } else {
+                    token == value
+                }
+            })
+    }
+}
+
+/// Builder for [`RankingOptions`]
+///
+/// ```
+/// use rsf::ranking::{RankMetric, RankingOptions};
+///
+/// let options = RankingOptions::builder()
+///     .metric(RankMetric::Entropy)
+///     .decimal_comma(true)
+///     .build()?;
+/// assert!(options.treat_empty_as_null);
+/// # Ok::<(), rsf::RsfError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct RankingOptionsBuilder {
     options: RankingOptions,
-) -> RsfResult<Vec<ColumnMeta>> {
-    if headers.is_empty() {
-        return Ok(Vec::new());
+}
+
+impl RankingOptionsBuilder {
+    /// Fold empty and whitespace-only cells into one null value
+    pub fn treat_empty_as_null(mut self, treat_empty_as_null: bool) -> Self {
+        self.options.treat_empty_as_null = treat_empty_as_null;
+        self
     }
 
-    if rows.is_empty() {
-        return Ok(headers
+    /// Count null as a distinct value
+    pub fn include_nulls(mut self, include_nulls: bool) -> Self {
+        self.options.include_nulls = include_nulls;
+        self
+    }
+
+    /// Metric used to order columns
+    pub fn metric(mut self, metric: RankMetric) -> Self {
+        self.options.metric = metric;
+        self
+    }
+
+    /// Read `1.234,56` as a number and sort numeric cells by value
+    pub fn decimal_comma(mut self, decimal_comma: bool) -> Self {
+        self.options.decimal_comma = decimal_comma;
+        self
+    }
+
+    /// Accept validated cardinalities within this ratio of the schema
+    pub fn cardinality_band(mut self, band: Option<CardinalityBand>) -> Self {
+        self.options.cardinality_band = band;
+        self
+    }
+
+    /// How columns that tie on the metric are ordered
+    pub fn tiebreak(mut self, tiebreak: TieBreak) -> Self {
+        self.options.tiebreak = tiebreak;
+        self
+    }
+
+    /// Count these tokens as null too, e.g. `N/A`, `-`, `\\N` or `null`
+    pub fn null_values(mut self, tokens: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.options.null_values = tokens.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Match the null tokens regardless of ASCII case
+    pub fn null_values_ignore_case(mut self, ignore_case: bool) -> Self {
+        self.options.null_values_ignore_case = ignore_case;
+        self
+    }
+
+    /// Rank these columns last, in this order, after the ranked remainder
+    pub fn pin_last(mut self, columns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.options.pin_last = columns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Check the settings fit together and return the options
+    pub fn build(self) -> RsfResult<RankingOptions> {
+        if let Some(band) = self.options.cardinality_band {
+            CardinalityBand::new(band.min, band.max).map_err(RsfError::invalid_options)?;
+        }
+        let pinned = &self.options.pin_last;
+        if let Some(name) = pinned
             .iter()
             .enumerate()
-            .map(|(idx, name)| ColumnMeta {
-                name: name.clone(),
-                rank: idx,
-                cardinality: 0,
-                col_type: None,
-            })
-            .collect());
+            .find_map(|(idx, name)| pinned[..idx].contains(name).then_some(name))
+        {
+            return Err(RsfError::invalid_options(format!(
+                "Column '{}' is pinned last more than once",
+                name
+            )));
+        }
+        Ok(self.options)
     }
+}
 
-    // Compute cardinality statistics
-    let stats = compute_cardinality(headers, rows, options)?;
+/// Accepted range of `actual / schema` cardinality ratios, written `MIN:MAX`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CardinalityBand {
+    pub min: f64,
+    pub max: f64,
+}
 
-    // Create initial column metadata
-    let mut columns: Vec<ColumnMeta> = stats
-        .into_iter()
-        .enumerate()
-        .map(|(idx, stat)| ColumnMeta {
-            name: stat.name,
-            rank: idx,
-            cardinality: stat.cardinality,
-            col_type: None,
-        })
-        .collect();
+impl CardinalityBand {
+    /// Create a band, rejecting negative or non-finite ratios and a minimum
+    /// above the maximum
+    pub fn new(min: f64, max: f64) -> Result<Self, String> {
+        for value in [min, max] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("'{}' is not a non-negative ratio", value));
+            }
+        }
+        if min > max {
+            return Err(format!("band minimum {} exceeds maximum {}", min, max));
+        }
+        Ok(Self { min, max })
+    }
 
-    // Sort by cardinality (descending), then by original position (stable)
-    columns.sort_by(|a, b| b.cardinality.cmp(&a.cardinality).then(a.rank.cmp(&b.rank)));
+    /// Whether `actual` is within the band around the schema's `expected`
+    ///
+    /// A schema cardinality of zero only accepts zero.
+    pub fn contains(&self, expected: usize, actual: usize) -> bool {
+        if expected == 0 {
+            return actual == 0;
+        }
+        let ratio = actual as f64 / expected as f64;
+        self.min <= ratio && ratio <= self.max
+    }
+}
 
-    // Update ranks
-    for (new_rank, col) in columns.iter_mut().enumerate() {
-        col.rank = new_rank + 1;
+impl std::str::FromStr for CardinalityBand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min, max) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected MIN:MAX, got '{}'", s))?;
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("'{}' is not a non-negative ratio", value))
+        };
+        Self::new(parse(min)?, parse(max)?)
     }
+}
 
+/// Rank columns by cardinality (or by entropy, per `options.metric`)
+pub fn rank_columns(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    options: RankingOptions,
+) -> RsfResult<Vec<ColumnMeta>> {
+    check_pinned_columns(headers, &options)?;
+    let (columns, _) = ranker_for(headers, rows, options).finish();
     Ok(columns)
 }
 
-/// Compute cardinality for each column
-fn compute_cardinality(
-    headers: &[String],
+/// Fail if a column pinned last is not among `headers`
+pub(crate) fn check_pinned_columns(
+    headers: &[impl AsRef<str>],
+    options: &RankingOptions,
+) -> RsfResult<()> {
+    match options
+        .pin_last
+        .iter()
+        .find(|name| !headers.iter().any(|h| h.as_ref() == name.as_str()))
+    {
+        Some(name) => Err(RsfError::schema_error(format!(
+            "Column '{}' is pinned last but is not in the data",
+            name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Compute value statistics for each column, in header order
+pub fn compute_cardinality(
+    headers: &[impl AsRef<str>],
     rows: &[Vec<String>],
     options: RankingOptions,
 ) -> RsfResult<Vec<ColumnStats>> {
-    if headers.is_empty() {
-        return Ok(Vec::new());
+    Ok(ranker_for(headers, rows, options).into_stats())
+}
+
+fn ranker_for(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    options: RankingOptions,
+) -> RsfRanker {
+    let mut ranker = RsfRanker::new(options).with_headers(headers);
+    for row in rows {
+        ranker.push_record(row);
     }
+    ranker
+}
 
-    // Initialize stats for each column
-    let mut stats: Vec<ColumnStats> = headers
-        .iter()
-        .map(|name| ColumnStats::new(name.clone()))
-        .collect();
+/// Compute the cardinality of the combined key formed by several columns
+///
+/// The cells of the named columns are joined per row into a single value, so
+/// the resulting cardinality is the number of distinct tuples.
+pub fn compute_combined_cardinality(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    columns: &[impl AsRef<str>],
+    options: RankingOptions,
+) -> RsfResult<ColumnStats> {
+    let indices = column_indices(headers, columns)?;
 
-    // Count distinct values per column
+    let names: Vec<&str> = columns.iter().map(AsRef::as_ref).collect();
+    let mut stat = ColumnStats::new(names.join(","));
     for row in rows {
-        // Handle rows with fewer columns than headers
-        for (i, value) in row.iter().enumerate().take(headers.len()) {
-            let val = normalize_value(value, options);
-            if let Some(stat) = stats.get_mut(i) {
-                stat.add_value(&val);
-            }
-        }
+        stat.add_value(&combine_key(row, &indices, &options));
     }
 
-    Ok(stats)
+    Ok(stat)
 }
 
-/// Normalize a value for cardinality counting
-fn normalize_value(value: &str, options: RankingOptions) -> String {
-    if options.treat_empty_as_null && value.trim().is_empty() {
-        if options.include_nulls {
-            "NULL".to_string()
-        } else {
-            "NULL".to_string()
-        }
-    } else {
-        value.to_string()
-    }
+/// Whether the values of one column determine those of another
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FunctionalDependency {
+    pub left: String,
+    pub right: String,
+    /// Values of `left` that occur with more than one value of `right`
+    pub left_conflicts: usize,
+    /// Values of `right` that occur with more than one value of `left`
+    pub right_conflicts: usize,
 }
 
-/// Reorder data according to ranked columns
-pub fn reorder_data(
-    headers: &[String],
-    rows: &[Vec<String>],
-    ranked_columns: &[ColumnMeta],
-) -> RsfResult<(Vec<String>, Vec<Vec<String>>)> {
-    if ranked_columns.is_empty() {
-        return Ok((Vec::new(), Vec::new()));
+impl FunctionalDependency {
+    /// Each value of `left` maps to exactly one value of `right`
+    pub fn left_determines_right(&self) -> bool {
+        self.left_conflicts == 0
     }
 
-    // Create mapping from old position to new position
-    let mut old_to_new: HashMap<usize, usize> = HashMap::new();
+    /// Each value of `right` maps to exactly one value of `left`
+    pub fn right_determines_left(&self) -> bool {
+        self.right_conflicts == 0
+    }
+}
 
-    for (new_idx, col) in ranked_columns.iter().enumerate() {
-        if let Some(old_idx) = headers.iter().position(|h| h == &col.name) {
-            old_to_new.insert(old_idx, new_idx);
+impl fmt::Display for FunctionalDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.left_determines_right(), self.right_determines_left()) {
+            (true, true) => write!(f, "{} <-> {}", self.left, self.right),
+            (true, false) => write!(f, "{} -> {}", self.left, self.right),
+            (false, true) => write!(f, "{} -> {}", self.right, self.left),
+            (false, false) => write!(f, "{} and {} are independent", self.left, self.right),
         }
     }
+}
 
-    // Reorder headers
-    let new_headers: Vec<String> = ranked_columns.iter().map(|col| col.name.clone()).collect();
-
-    // Reorder rows
-    let new_rows: Vec<Vec<String>> = rows
-        .iter()
-        .map(|row| {
-            let mut new_row = vec![String::new(); row.len()];
-            for (old_idx, value) in row.iter().enumerate() {
-                if let Some(&new_idx) = old_to_new.get(&old_idx) {
-                    new_row[new_idx] = value.clone();
-                }
+/// Check whether `left` functionally determines `right`, and the reverse
+///
+/// Cells are compared as written, so empty cells count as a value.
+pub fn functional_dependency(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    left: &str,
+    right: &str,
+) -> RsfResult<FunctionalDependency> {
+    let indices = column_indices(headers, &[left, right])?;
+    let conflicts = |from: usize, to: usize| {
+        let mut seen: HashMap<&str, &str> = HashMap::new();
+        let mut conflicting: HashSet<&str> = HashSet::new();
+        for row in rows {
+            let cell = |idx: usize| row.get(idx).map_or("", String::as_str);
+            let (key, value) = (cell(from), cell(to));
+            if *seen.entry(key).or_insert(value) != value {
+                conflicting.insert(key);
             }
-            new_row
-        })
-        .collect();
+        }
+        conflicting.len()
+    };
 
-    Ok((new_headers, new_rows))
+    Ok(FunctionalDependency {
+        left: left.to_string(),
+        right: right.to_string(),
+        left_conflicts: conflicts(indices[0], indices[1]),
+        right_conflicts: conflicts(indices[1], indices[0]),
+    })
 }
 
-/// Sort rows canonically by all columns in rank order
-pub fn sort_rows_canonical(rows: &[Vec<String>]) -> Vec<Vec<String>> {
-    if rows.is_empty() {
-        return Vec::new();
+/// Prepend a synthetic key column joining the cells of `columns` with
+/// `separator`
+///
+/// With `drop_components` the joined columns are removed from the output.
+/// Rank the result as usual; ties leave the key column leftmost.
+pub fn prepend_concat_key(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    columns: &[impl AsRef<str>],
+    key_name: &str,
+    separator: &str,
+    drop_components: bool,
+) -> RsfResult<(Vec<String>, Vec<Vec<String>>)> {
+    let indices = column_indices(headers, columns)?;
+    let keep = |idx: usize| !(drop_components && indices.contains(&idx));
+
+    let mut new_headers = vec![key_name.to_string()];
+    for (idx, header) in headers.iter().enumerate() {
+        if !keep(idx) {
+            continue;
+        }
+        if header.as_ref() == key_name {
+            return Err(RsfError::schema_error(format!(
+                "Key column '{}' already exists in data",
+                key_name
+            )));
+        }
+        new_headers.push(header.as_ref().to_string());
     }
 
-    let mut sorted = rows.to_vec();
+    let new_rows = rows
+        .iter()
+        .map(|row| {
+            let parts: Vec<&str> = indices
+                .iter()
+                .map(|&idx| row.get(idx).map_or("", String::as_str))
+                .collect();
+            let mut new_row = vec![parts.join(separator)];
+            new_row.extend(
+                row.iter()
+                    .enumerate()
+                    .filter(|&(idx, _)| keep(idx))
+                    .map(|(_, value)| value.clone()),
+            );
+            new_row
+        })
+        .collect();
 
-    // Sort lexicographically by all columns in order
-    sorted.sort_by(|a, b| {
-        for (val_a, val_b) in a.iter().zip(b.iter()) {
-            match val_a.cmp(val_b) {
-                std::cmp::Ordering::Equal => continue,
-                other => return other,
+    Ok((new_headers, new_rows))
+}
+
+/// Append a column holding the hex SHA-256 of each row's `key_columns`
+/// cells joined with `|`, returning its schema entry
+///
+/// A `|` or `\` inside a cell is escaped with `\`, so `("a|b", "c")` and
+/// `("a", "b|c")` hash differently. Editing a cell outside the key keeps the
+/// row's hash, which makes it a stable row identifier for change tracking.
+/// Fails if `key_columns` is empty or names a column not in `headers`.
+pub fn append_row_hashes(
+    headers: &mut Vec<String>,
+    rows: &mut [Vec<String>],
+    name: &str,
+    key_columns: &[impl AsRef<str>],
+) -> RsfResult<ColumnMeta> {
+    if headers.iter().any(|header| header == name) {
+        return Err(RsfError::schema_error(format!(
+            "Row hash column '{}' already exists in data",
+            name
+        )));
+    }
+    if key_columns.is_empty() {
+        return Err(RsfError::schema_error(format!(
+            "Row hash column '{}' needs at least one key column",
+            name
+        )));
+    }
+    let indices = column_indices(headers, key_columns)?;
+
+    let mut distinct = HashSet::new();
+    for row in rows.iter_mut() {
+        let key = join_key(
+            indices
+                .iter()
+                .map(|&idx| row.get(idx).map_or("", String::as_str)),
+        );
+        let hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+        distinct.insert(hash.clone());
+        row.push(hash);
+    }
+    headers.push(name.to_string());
+
+    let mut column = ColumnMeta::new(name, headers.len(), distinct.len());
+    if !rows.is_empty() {
+        column.cardinality_ratio = distinct.len() as f64 / rows.len() as f64;
+        column.is_unique = distinct.len() == rows.len();
+    }
+    column.synthetic = true;
+    column.synthetic_source = Some(SyntheticSource::RowHash);
+    Ok(column)
+}
+
+/// Names of the columns typed [`ColumnType::Key`], in schema order
+pub fn key_column_names(columns: &[ColumnMeta]) -> Vec<&str> {
+    columns
+        .iter()
+        .filter(|col| col.col_type == Some(ColumnType::Key))
+        .map(|col| col.name.as_str())
+        .collect()
+}
+
+/// Position in `headers` of each named column
+fn column_indices(
+    headers: &[impl AsRef<str>],
+    columns: &[impl AsRef<str>],
+) -> RsfResult<Vec<usize>> {
+    columns
+        .iter()
+        .map(|name| {
+            let name = name.as_ref();
+            headers
+                .iter()
+                .position(|h| h.as_ref() == name)
+                .ok_or_else(|| {
+                    RsfError::schema_error(format!("Column '{}' not found in data", name))
+                })
+        })
+        .collect()
+}
+
+/// Separator used when joining cells into a combined key
+const KEY_SEPARATOR: char = '|';
+
+/// Join the selected cells of a row, escaping the separator so that
+/// `("a|b", "c")` and `("a", "b|c")` stay distinct
+fn combine_key(row: &[String], indices: &[usize], options: &RankingOptions) -> String {
+    join_key(
+        indices
+            .iter()
+            .map(|&idx| normalize_value(row.get(idx).map(String::as_str).unwrap_or(""), options)),
+    )
+}
+
+/// Join `parts` with [`KEY_SEPARATOR`], escaping it and `\` inside a part
+fn join_key(parts: impl IntoIterator<Item = impl AsRef<str>>) -> String {
+    let mut key = String::new();
+    for (pos, part) in parts.into_iter().enumerate() {
+        if pos > 0 {
+            key.push(KEY_SEPARATOR);
+        }
+        for c in part.as_ref().chars() {
+            if c == KEY_SEPARATOR || c == '\\' {
+                key.push('\\');
             }
+            key.push(c);
         }
-        std::cmp::Ordering::Equal
-    });
+    }
+    key
+}
+
+/// The value that null cells count as when folded together
+pub const NULL_VALUE: &str = "NULL";
+
+/// Normalize a value for cardinality counting
+pub(crate) fn normalize_value(value: &str, options: &RankingOptions) -> String {
+    if options.treat_empty_as_null && options.is_null(value) {
+        NULL_VALUE.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// The value `value` adds to its column's counts, or `None` for a null
+/// that is left out of the cardinality
+pub(crate) fn counted_value(value: &str, options: &RankingOptions) -> Option<String> {
+    if options.treat_empty_as_null && !options.include_nulls && options.is_null(value) {
+        None
+    } else {
+        Some(normalize_value(value, options))
+    }
+}
+
+/// Trim surrounding whitespace from every cell, leaving whitespace-only
+/// cells empty
+pub fn trim_cells(rows: &mut [Vec<String>]) {
+    for cell in rows.iter_mut().flatten() {
+        let trimmed = cell.trim();
+        if trimmed.len() != cell.len() {
+            *cell = trimmed.to_string();
+        }
+    }
+}
+
+/// Drop rows that equal an earlier row once each cell is trimmed, keeping
+/// the first row as written; returns the number of rows dropped
+pub fn dedup_normalized(rows: &mut Vec<Vec<String>>) -> usize {
+    let mut seen = HashSet::new();
+    let keep: Vec<bool> = rows
+        .iter()
+        .map(|row| seen.insert(row.iter().map(|cell| cell.trim()).collect::<Vec<&str>>()))
+        .collect();
 
+    let before = rows.len();
+    let mut keep = keep.into_iter();
+    rows.retain(|_| keep.next().unwrap_or(true));
+    before - rows.len()
+}
+
+/// Pad rows shorter than `width` with `value`, returning the number of
+/// cells added
+///
+/// Some exporters drop trailing empty fields, leaving jagged rows.
+pub fn fill_missing(rows: &mut [Vec<String>], width: usize, value: &str) -> usize {
+    let mut filled = 0;
+    for row in rows.iter_mut().filter(|row| row.len() < width) {
+        filled += width - row.len();
+        row.resize(width, value.to_string());
+    }
+    filled
+}
+
+/// Reorder data according to ranked columns
+///
+/// Takes the rows so each can be permuted in place rather than rebuilt.
+pub fn reorder_data(
+    headers: &[impl AsRef<str>],
+    mut rows: Vec<Vec<String>>,
+    ranked_columns: &[ColumnMeta],
+) -> RsfResult<(Vec<String>, Vec<Vec<String>>)> {
+    if ranked_columns.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let order = ColumnOrder::new(headers, ranked_columns);
+    let new_headers: Vec<String> = ranked_columns.iter().map(|col| col.name.clone()).collect();
+    for row in &mut rows {
+        order.reorder_in_place(row);
+    }
+
+    Ok((new_headers, rows))
+}
+
+/// Reorder columns by name to match `reference`, e.g. the columns of another
+/// file's schema, leaving rows in their order
+///
+/// Unlike [`reorder_data`], every column in `reference` must be among
+/// `headers` and every header among `reference`.
+pub fn align_columns(
+    headers: &[impl AsRef<str>],
+    rows: Vec<Vec<String>>,
+    reference: &[ColumnMeta],
+) -> RsfResult<(Vec<String>, Vec<Vec<String>>)> {
+    let names: Vec<&str> = reference.iter().map(|col| col.name.as_str()).collect();
+    require_columns(headers, &names)?;
+    if let Some(extra) = headers.iter().find(|h| !names.contains(&h.as_ref())) {
+        return Err(RsfError::schema_error(format!(
+            "Column '{}' is not in the reference schema",
+            extra.as_ref()
+        )));
+    }
+
+    reorder_data(headers, rows, reference)
+}
+
+/// Original position of each ranked column
+///
+/// Entry `i` is the index in `headers` of the column ranked at position `i`.
+pub fn column_permutation(
+    headers: &[impl AsRef<str>],
+    ranked_columns: &[ColumnMeta],
+) -> RsfResult<Vec<usize>> {
+    ranked_columns
+        .iter()
+        .map(|col| {
+            headers
+                .iter()
+                .position(|h| h.as_ref() == col.name)
+                .ok_or_else(|| {
+                    RsfError::schema_error(format!("Column '{}' not found in data", col.name))
+                })
+        })
+        .collect()
+}
+
+/// The first `key_count` cells of canonically sorted rows, each distinct
+/// combination once
+///
+/// Sorting orders rows by their leading cells first, so equal keys are
+/// adjacent and the result stays in canonical order.
+pub fn distinct_keys(rows: &[Vec<String>], key_count: usize) -> Vec<Vec<String>> {
+    let mut keys: Vec<Vec<String>> = Vec::new();
+    for row in rows {
+        let key = &row[..key_count.min(row.len())];
+        if keys.last().is_none_or(|last| last.as_slice() != key) {
+            keys.push(key.to_vec());
+        }
+    }
+    keys
+}
+
+/// Sort rows canonically in place, like [`sort_rows_with_options`] without
+/// copying them
+pub fn sort_rows_in_place(rows: &mut [Vec<String>], options: RankingOptions) {
+    rows.sort_by(|a, b| compare_rows(a, b, &options));
+}
+
+/// Sort rows canonically by all columns in rank order
+pub fn sort_rows_canonical(rows: &[Vec<String>]) -> Vec<Vec<String>> {
+    sort_rows_with_options(rows, RankingOptions::default())
+}
+
+/// Sort rows canonically, comparing cells as `options` dictates
+pub fn sort_rows_with_options(rows: &[Vec<String>], options: RankingOptions) -> Vec<Vec<String>> {
+    if rows.is_empty() {
+        return Vec::new();
+    }
+
+    // Sort lexicographically by all columns in order
+    let mut sorted = rows.to_vec();
+    sort_rows_in_place(&mut sorted, options);
     sorted
 }
 
+/// Compare two rows cell by cell in column order
+pub fn compare_rows(a: &[String], b: &[String], options: &RankingOptions) -> std::cmp::Ordering {
+    for (val_a, val_b) in a.iter().zip(b.iter()) {
+        match compare_cells(val_a, val_b, options.decimal_comma) {
+            std::cmp::Ordering::Equal => continue,
+            other => return other,
+        }
+    }
+    std::cmp::Ordering::Equal
+}
+
 /// Write schema to file
-pub fn write_schema(columns: &[ColumnMeta], path: &PathBuf) -> RsfResult<()> {
-    let schema = Schema {
-        version: "0.1".to_string(),
-        columns: columns.to_vec(),
-    };
+#[cfg(feature = "std-fs")]
+pub fn write_schema(schema: &Schema, path: impl AsRef<Path>) -> RsfResult<()> {
+    let path = path.as_ref();
+    let file = std::fs::File::create(path).map_err(|e| RsfError::io_error(path, e))?;
+
+    serde_yaml::to_writer(file, schema).map_err(|e| RsfError::from(e).with_schema_path(path))?;
+
+    Ok(())
+}
+
+/// Read a schema file
+#[cfg(feature = "std-fs")]
+pub fn read_schema(path: impl AsRef<Path>) -> RsfResult<Schema> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path).map_err(|e| RsfError::io_error(path, e))?;
+
+    read_schema_reader(file).map_err(|e| e.with_schema_path(path))
+}
+
+/// Read a schema from any reader, such as the bytes of an uploaded file
+pub fn read_schema_reader<R: Read>(reader: R) -> RsfResult<Schema> {
+    Ok(serde_yaml::from_reader(reader)?)
+}
+
+/// Read a YAML map from column names to the cardinalities to rank them by
+#[cfg(feature = "std-fs")]
+pub fn read_cardinality_overrides(path: impl AsRef<Path>) -> RsfResult<BTreeMap<String, usize>> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path).map_err(|e| RsfError::io_error(path, e))?;
+
+    read_cardinality_overrides_reader(file).map_err(|e| e.with_schema_path(path))
+}
+
+/// Read cardinality overrides from any reader
+pub fn read_cardinality_overrides_reader<R: Read>(reader: R) -> RsfResult<BTreeMap<String, usize>> {
+    Ok(serde_yaml::from_reader(reader)?)
+}
+
+/// Check that every column in `required` is among `headers`
+pub fn require_columns(headers: &[impl AsRef<str>], required: &[impl AsRef<str>]) -> RsfResult<()> {
+    match required
+        .iter()
+        .find(|name| !headers.iter().any(|h| h.as_ref() == name.as_ref()))
+    {
+        Some(name) => Err(RsfError::missing_column(name.as_ref())),
+        None => Ok(()),
+    }
+}
+
+/// Validate column ordering matches schema
+pub fn validate_column_order(
+    headers: &[impl AsRef<str>],
+    schema_columns: &[ColumnMeta],
+) -> RsfResult<()> {
+    if schema_columns.is_empty() {
+        return Ok(());
+    }
+
+    if headers.len() != schema_columns.len() {
+        return Err(RsfError::schema_error(format!(
+            "Schema column count ({}) does not match CSV column count ({})",
+            schema_columns.len(),
+            headers.len()
+        )));
+    }
+
+    // Validate column order matches schema
+    for (idx, col_meta) in schema_columns.iter().enumerate() {
+        let found = headers[idx].as_ref();
+        if found != col_meta.name {
+            return Err(RsfError::column_order_error(
+                idx,
+                col_meta.name.clone(),
+                found.to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Like [`validate_column_order`], but the columns the schema types `key`
+/// may come in any order, as long as all of them come first
+///
+/// Key columns with equal cardinality are ranked in an arbitrary order, so
+/// this accepts data re-ranked with them swapped. The remaining columns must
+/// follow in the schema's order.
+pub fn validate_column_order_any_key_order(
+    headers: &[impl AsRef<str>],
+    schema_columns: &[ColumnMeta],
+) -> RsfResult<()> {
+    if schema_columns.is_empty() {
+        return Ok(());
+    }
+
+    if headers.len() != schema_columns.len() {
+        return Err(RsfError::schema_error(format!(
+            "Schema column count ({}) does not match CSV column count ({})",
+            schema_columns.len(),
+            headers.len()
+        )));
+    }
+
+    let (keys, values): (Vec<&ColumnMeta>, Vec<&ColumnMeta>) = schema_columns
+        .iter()
+        .partition(|col| col.col_type == Some(ColumnType::Key));
+    let (key_headers, value_headers) = headers.split_at(keys.len());
+
+    for (idx, found) in key_headers.iter().enumerate() {
+        let found = found.as_ref();
+        let is_key = keys.iter().any(|col| col.name == found);
+        let seen = key_headers[..idx].iter().any(|h| h.as_ref() == found);
+        if !is_key || seen {
+            let names: Vec<&str> = keys.iter().map(|col| col.name.as_str()).collect();
+            return Err(RsfError::schema_error(format!(
+                "Column '{}' at position {} is not one of the key columns ({}), which must come first",
+                found,
+                idx,
+                names.join(", ")
+            )));
+        }
+    }
+
+    for (idx, (found, col_meta)) in value_headers.iter().zip(&values).enumerate() {
+        let found = found.as_ref();
+        if found != col_meta.name {
+            return Err(RsfError::column_order_error(
+                keys.len() + idx,
+                col_meta.name.clone(),
+                found.to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// The schema's and the data's column names around the first position where
+/// they differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HeaderDivergence {
+    /// 0-based position of the first difference, as in
+    /// [`RsfError::ColumnOrderError`]
+    pub position: usize,
+    /// `(position, expected, found)` for each position shown; `None` past the
+    /// end of the schema or the header
+    pub columns: Vec<(usize, Option<String>, Option<String>)>,
+}
+
+/// Line up the schema's column names with `headers` from `context`
+/// positions before the first difference to `context` after it
+///
+/// `None` when the names agree, including in number.
+pub fn header_divergence(
+    headers: &[impl AsRef<str>],
+    schema_columns: &[ColumnMeta],
+    context: usize,
+) -> Option<HeaderDivergence> {
+    let expected = |idx: usize| schema_columns.get(idx).map(|col| col.name.clone());
+    let found = |idx: usize| headers.get(idx).map(|h| h.as_ref().to_string());
+
+    let len = headers.len().max(schema_columns.len());
+    let position = (0..len).find(|&idx| expected(idx) != found(idx))?;
+    let columns = (position.saturating_sub(context)..len.min(position + context + 1))
+        .map(|idx| (idx, expected(idx), found(idx)))
+        .collect();
+
+    Some(HeaderDivergence { position, columns })
+}
+
+/// Validate cardinality ordering
+pub fn validate_cardinality_order(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    schema_columns: &[ColumnMeta],
+    options: RankingOptions,
+) -> RsfResult<()> {
+    if schema_columns.is_empty() {
+        return Ok(());
+    }
+
+    let stats = check_cardinalities(headers, rows, schema_columns, options.clone())?;
+    let mut cardinalities: HashMap<&str, usize> = stats
+        .iter()
+        .map(|stat| (stat.name.as_str(), stat.cardinality()))
+        .collect();
+    // Overridden columns were ranked by their recorded cardinality
+    for col in schema_columns
+        .iter()
+        .filter(|col| col.cardinality_overridden)
+    {
+        if let Some(actual) = cardinalities.get_mut(col.name.as_str()) {
+            *actual = col.cardinality;
+        }
+    }
+
+    // Columns pinned last follow the ranked ones in the recorded order
+    let pinned: Vec<&str> = options
+        .pin_last
+        .iter()
+        .map(String::as_str)
+        .filter(|name| schema_columns.iter().any(|col| col.name == *name))
+        .collect();
+    let (schema_columns, tail) = schema_columns.split_at(schema_columns.len() - pinned.len());
+    if !tail
+        .iter()
+        .map(|col| col.name.as_str())
+        .eq(pinned.iter().copied())
+    {
+        return Err(RsfError::schema_error(format!(
+            "Columns pinned last ({}) are not the last columns, in that order",
+            pinned.join(", ")
+        )));
+    }
+
+    if options.metric == RankMetric::Entropy {
+        return validate_entropy_order(&stats, schema_columns);
+    }
+
+    // Validate that columns are ordered by descending cardinality
+    for window in schema_columns.windows(2) {
+        let curr = &window[0];
+        let next = &window[1];
+
+        let curr_actual = cardinalities.get(curr.name.as_str()).ok_or_else(|| {
+            RsfError::schema_error(format!("Column '{}' not found in data", curr.name))
+        })?;
+
+        let next_actual = cardinalities.get(next.name.as_str()).ok_or_else(|| {
+            RsfError::schema_error(format!("Column '{}' not found in data", next.name))
+        })?;
+
+        if curr_actual < next_actual {
+            return Err(RsfError::cardinality_error(
+                curr.name.clone(),
+                *next_actual,
+                *curr_actual,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate that each schema column's cardinality matches the data (or lies
+/// within `options.cardinality_band`), without checking column order
+pub fn validate_cardinalities(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    schema_columns: &[ColumnMeta],
+    options: RankingOptions,
+) -> RsfResult<()> {
+    check_cardinalities(headers, rows, schema_columns, options).map(|_| ())
+}
+
+/// Compare schema cardinalities with the data, returning the data's statistics
+fn check_cardinalities(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    schema_columns: &[ColumnMeta],
+    options: RankingOptions,
+) -> RsfResult<Vec<ColumnStats>> {
+    // Compute actual cardinality
+    let stats = compute_cardinality(headers, rows, options.clone())?;
+    let mut cardinalities = HashMap::with_capacity(stats.len());
+    for stat in stats.iter() {
+        cardinalities.insert(stat.name.clone(), stat.cardinality());
+    }
+
+    for col_meta in schema_columns.iter() {
+        let actual = cardinalities.get(&col_meta.name).ok_or_else(|| {
+            RsfError::schema_error(format!("Column '{}' not found in data", col_meta.name))
+        })?;
+
+        if col_meta.is_unique && *actual != rows.len() {
+            return Err(RsfError::uniqueness_violation(
+                col_meta.name.clone(),
+                rows.len(),
+                *actual,
+            ));
+        }
+        if col_meta.cardinality_overridden {
+            continue;
+        }
+
+        if let Some(band) = options.cardinality_band {
+            if !band.contains(col_meta.cardinality, *actual) {
+                return Err(RsfError::cardinality_error(
+                    col_meta.name.clone(),
+                    col_meta.cardinality,
+                    *actual,
+                ));
+            }
+        } else if *actual != col_meta.cardinality {
+            return Err(RsfError::schema_error(format!(
+                "Column '{}' cardinality mismatch: schema {}, actual {}",
+                col_meta.name, col_meta.cardinality, actual
+            )));
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Validate that columns are ordered by descending entropy
+fn validate_entropy_order(stats: &[ColumnStats], schema_columns: &[ColumnMeta]) -> RsfResult<()> {
+    let entropies: HashMap<&str, f64> = stats
+        .iter()
+        .map(|stat| (stat.name.as_str(), stat.entropy()))
+        .collect();
+
+    for window in schema_columns.windows(2) {
+        let curr = entropies[window[0].name.as_str()];
+        let next = entropies[window[1].name.as_str()];
+
+        if curr < next {
+            return Err(RsfError::schema_error(format!(
+                "Column '{}' has lower entropy ({:.4}) than the following column '{}' ({:.4})",
+                window[0].name, curr, window[1].name, next
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Highest cardinality expected of a column, written `COLUMN:MAX`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardinalityLimit {
+    pub column: String,
+    pub max: usize,
+}
+
+impl std::str::FromStr for CardinalityLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Split at the last colon so column names may contain one
+        let (column, max) = s
+            .rsplit_once(':')
+            .filter(|(column, _)| !column.is_empty())
+            .ok_or_else(|| format!("expected COLUMN:MAX, got '{}'", s))?;
+        let max = max
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' is not a cardinality", max))?;
+        Ok(Self {
+            column: column.to_string(),
+            max,
+        })
+    }
+}
+
+/// A column whose cardinality is above its [`CardinalityLimit`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LimitExceeded {
+    pub column: String,
+    pub max: usize,
+    pub cardinality: usize,
+    /// The three most frequent values with their counts
+    pub top_values: Vec<(String, usize)>,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Column '{}' has cardinality {}, above the limit of {}",
+            self.column, self.cardinality, self.max
+        )?;
+        let top: Vec<String> = self
+            .top_values
+            .iter()
+            .map(|(value, count)| format!("{:?} ({})", value, count))
+            .collect();
+        if !top.is_empty() {
+            write!(f, "; most frequent: {}", top.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Columns whose cardinality is above their limit, in the order of `limits`
+pub fn check_cardinality_limits(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    limits: &[CardinalityLimit],
+    options: RankingOptions,
+) -> RsfResult<Vec<LimitExceeded>> {
+    let columns: Vec<&str> = limits.iter().map(|limit| limit.column.as_str()).collect();
+    let indices = column_indices(headers, &columns)?;
+
+    let mut ranker = RsfRanker::new(options).with_headers(&columns);
+    for row in rows {
+        let cells: Vec<&str> = indices
+            .iter()
+            .map(|&idx| row.get(idx).map_or("", String::as_str))
+            .collect();
+        ranker.push_record(&cells);
+    }
+
+    Ok(limits
+        .iter()
+        .zip(ranker.stats())
+        .filter(|(limit, stat)| stat.cardinality() > limit.max)
+        .map(|(limit, stat)| LimitExceeded {
+            column: limit.column.clone(),
+            max: limit.max,
+            cardinality: stat.cardinality(),
+            top_values: stat
+                .top_values(3)
+                .into_iter()
+                .map(|(value, count)| (value.to_string(), count))
+                .collect(),
+        })
+        .collect())
+}
+
+/// A column's rank and cardinality with empty cells counted as distinct
+/// values and with them folded into one null
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NullModeShift {
+    pub name: String,
+    pub distinct_rank: usize,
+    pub distinct_cardinality: usize,
+    pub folded_rank: usize,
+    pub folded_cardinality: usize,
+}
+
+impl NullModeShift {
+    /// Whether the column ranks differently under the two null modes
+    pub fn is_moved(&self) -> bool {
+        self.distinct_rank != self.folded_rank
+    }
+}
 
-    let file = std::fs::File::create(path).map_err(|e| RsfError::io_error(path.clone(), e))?;
+/// Rank the columns with nulls distinct and with nulls folded, reporting
+/// each column under both, in nulls-distinct rank order
+///
+/// `options` supplies everything but `treat_empty_as_null`, which is
+/// overridden for each ranking.
+pub fn compare_null_modes(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+    options: RankingOptions,
+) -> RsfResult<Vec<NullModeShift>> {
+    let distinct = rank_columns(
+        headers,
+        rows,
+        RankingOptions {
+            treat_empty_as_null: false,
+            ..options.clone()
+        },
+    )?;
+    let folded = rank_columns(
+        headers,
+        rows,
+        RankingOptions {
+            treat_empty_as_null: true,
+            ..options
+        },
+    )?;
 
-    serde_yaml::to_writer(file, &schema).map_err(|e| RsfError::schema_error(e.to_string()))?;
+    distinct
+        .iter()
+        .map(|col| {
+            let other = folded
+                .iter()
+                .find(|other| other.name == col.name)
+                .ok_or_else(|| RsfError::unknown(format!("Column '{}' not ranked", col.name)))?;
+            Ok(NullModeShift {
+                name: col.name.clone(),
+                distinct_rank: col.rank,
+                distinct_cardinality: col.cardinality,
+                folded_rank: other.rank,
+                folded_cardinality: other.cardinality,
+            })
+        })
+        .collect()
+}
 
-    Ok(())
+/// Change in a column's cardinality between two snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CardinalityDrift {
+    pub name: String,
+    /// Cardinality in the first snapshot, `None` if the column was added
+    pub before: Option<usize>,
+    /// Cardinality in the second snapshot, `None` if the column was removed
+    pub after: Option<usize>,
 }
 
-/// Validate column ordering matches schema
-pub fn validate_column_order(headers: &[String], schema_columns: &[ColumnMeta]) -> RsfResult<()> {
-    if schema_columns.is_empty() {
-        return Ok(());
+impl CardinalityDrift {
+    /// Signed change in cardinality (missing columns count as zero)
+    pub fn delta(&self) -> i64 {
+        self.after.unwrap_or(0) as i64 - self.before.unwrap_or(0) as i64
     }
 
-    if headers.len() != schema_columns.len() {
-        return Err(RsfError::schema_error(format!(
-            "Schema column count ({}) does not match CSV column count ({})",
-            schema_columns.len(),
-            headers.len()
-        )));
+    /// Whether the column was added, removed or changed cardinality
+    pub fn is_changed(&self) -> bool {
+        self.before != self.after
     }
+}
 
-    // Validate column order matches schema
-    for (idx, col_meta) in schema_columns.iter().enumerate() {
-        if headers[idx] != col_meta.name {
-            return Err(RsfError::column_order_error(
-                idx,
-                col_meta.name.clone(),
-                headers[idx].clone(),
-            ));
+/// Compare column cardinalities of two snapshots
+///
+/// Results are sorted by absolute delta (largest first); ties keep the order
+/// of the first snapshot followed by columns only present in the second.
+pub fn compare_cardinality(before: &[ColumnMeta], after: &[ColumnMeta]) -> Vec<CardinalityDrift> {
+    let after_by_name: HashMap<&str, usize> = after
+        .iter()
+        .map(|col| (col.name.as_str(), col.cardinality))
+        .collect();
+    let before_names: HashSet<&str> = before.iter().map(|col| col.name.as_str()).collect();
+
+    let mut drift: Vec<CardinalityDrift> = before
+        .iter()
+        .map(|col| CardinalityDrift {
+            name: col.name.clone(),
+            before: Some(col.cardinality),
+            after: after_by_name.get(col.name.as_str()).copied(),
+        })
+        .chain(
+            after
+                .iter()
+                .filter(|col| !before_names.contains(col.name.as_str()))
+                .map(|col| CardinalityDrift {
+                    name: col.name.clone(),
+                    before: None,
+                    after: Some(col.cardinality),
+                }),
+        )
+        .collect();
+
+    drift.sort_by_key(|d| std::cmp::Reverse(d.delta().unsigned_abs()));
+    drift
+}
+
+/// Validate rows are canonically sorted
+pub fn validate_sorted(rows: &[Vec<String>], options: RankingOptions) -> RsfResult<()> {
+    // A stable sort leaves rows alone exactly when no row sorts before its
+    // predecessor
+    for (idx, pair) in rows.windows(2).enumerate() {
+        if compare_rows(&pair[0], &pair[1], &options) == std::cmp::Ordering::Greater {
+            return Err(RsfError::sort_error_at(idx + 2));
         }
     }
 
     Ok(())
 }
 
-/// Validate cardinality ordering
-pub fn validate_cardinality_order(
+/// Validate that every cell of a typed column holds a value of its type
+pub fn validate_types(
     headers: &[String],
     rows: &[Vec<String>],
     schema_columns: &[ColumnMeta],
-    options: RankingOptions,
+    decimal_comma: bool,
 ) -> RsfResult<()> {
-    if schema_columns.is_empty() {
-        return Ok(());
-    }
-
-    // Compute actual cardinality
-    let stats = compute_cardinality(headers, rows, options)?;
-    let mut cardinalities = HashMap::with_capacity(stats.len());
-    for stat in stats.iter() {
-        cardinalities.insert(stat.name.clone(), stat.cardinality);
-    }
-
-    for col_meta in schema_columns.iter() {
-        let actual = cardinalities.get(&col_meta.name).ok_or_else(|| {
-            RsfError::schema_error(format!("Column '{}' not found in data", col_meta.name))
-        })?;
-
-        if *actual != col_meta.cardinality {
-            return Err(RsfError::schema_error(format!(
-                "Column '{}' cardinality mismatch: schema {}, actual {}",
-                col_meta.name, col_meta.cardinality, actual
-            )));
-        }
-    }
-
-    // Validate that columns are ordered by descending cardinality
-    for window in schema_columns.windows(2) {
-        let curr = &window[0];
-        let next = &window[1];
-
-        let curr_actual = cardinalities.get(&curr.name).ok_or_else(|| {
-            RsfError::schema_error(format!("Column '{}' not found in data", curr.name))
-        })?;
-
-        let next_actual = cardinalities.get(&next.name).ok_or_else(|| {
-            RsfError::schema_error(format!("Column '{}' not found in data", next.name))
-        })?;
+    let declared: Vec<(String, DataType)> = schema_columns
+        .iter()
+        .filter_map(|col| Some((col.name.clone(), col.data_type?)))
+        .collect();
+    check_declared_types(headers, rows, &declared, decimal_comma)
+}
 
-        if curr_actual < next_actual {
-            return Err(RsfError::cardinality_error(
-                curr.name.clone(),
-                *next_actual,
-                *curr_actual,
-            ));
+/// Check every cell of the named columns against its declared type
+///
+/// Columns not in `headers` are skipped; the first mismatch is reported with
+/// its 1-based data row.
+pub fn check_declared_types(
+    headers: &[String],
+    rows: &[Vec<String>],
+    declared: &[(String, DataType)],
+    decimal_comma: bool,
+) -> RsfResult<()> {
+    for (name, data_type) in declared {
+        let Some(idx) = headers.iter().position(|h| h == name) else {
+            continue;
+        };
+        for (row_idx, row) in rows.iter().enumerate() {
+            let value = row.get(idx).map_or("", String::as_str);
+            if !data_type.accepts(value, decimal_comma) {
+                return Err(RsfError::type_error(name, *data_type, row_idx + 1, value));
+            }
         }
     }
 
     Ok(())
 }
 
-/// Validate rows are canonically sorted
-pub fn validate_sorted(rows: &[Vec<String>]) -> RsfResult<()> {
-    let sorted = sort_rows_canonical(rows);
-
-    if sorted != rows {
-        return Err(RsfError::sort_error());
+/// Validate that schema ranks run 1, 2, 3, ... in column order
+pub fn validate_rank_sequence(schema_columns: &[ColumnMeta]) -> RsfResult<()> {
+    for (idx, col_meta) in schema_columns.iter().enumerate() {
+        if col_meta.rank != idx + 1 {
+            return Err(RsfError::schema_error(format!(
+                "Column '{}' has invalid rank: expected {}, found {}",
+                col_meta.name,
+                idx + 1,
+                col_meta.rank
+            )));
+        }
     }
 
     Ok(())
@@ -379,6 +2027,41 @@ mod tests {
         assert_eq!(ranked[2].name, "B");
     }
 
+    #[test]
+    fn test_fill_missing() {
+        let mut rows = vec![
+            vec!["a".to_string()],
+            vec!["b".to_string(), "2".to_string(), "x".to_string()],
+            vec![],
+        ];
+        assert_eq!(fill_missing(&mut rows, 3, "NA"), 5);
+        assert_eq!(rows[0], ["a", "NA", "NA"]);
+        assert_eq!(rows[1], ["b", "2", "x"]);
+        assert_eq!(rows[2], ["NA", "NA", "NA"]);
+
+        assert_eq!(fill_missing(&mut rows, 3, "NA"), 0);
+    }
+
+    #[test]
+    fn test_dedup_normalized() {
+        let row = |cells: &[&str]| cells.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        let mut rows = vec![
+            row(&["a ", "1"]),
+            row(&["b", "2"]),
+            row(&["a", " 1"]),
+            row(&["a", "1"]),
+            row(&["a b", "1"]),
+        ];
+        assert_eq!(dedup_normalized(&mut rows), 2);
+        // The first-seen spelling is kept; inner whitespace still matters
+        assert_eq!(
+            rows,
+            [row(&["a ", "1"]), row(&["b", "2"]), row(&["a b", "1"])]
+        );
+
+        assert_eq!(dedup_normalized(&mut rows), 0);
+    }
+
     #[test]
     fn test_reorder_data() {
         let headers = vec!["A".to_string(), "B".to_string()];
@@ -387,28 +2070,103 @@ mod tests {
             vec!["2".to_string(), "y".to_string()],
         ];
 
-        let ranked = vec![
-            ColumnMeta {
-                name: "B".to_string(),
-                rank: 1,
-                cardinality: 2,
-                col_type: None,
-            },
-            ColumnMeta {
-                name: "A".to_string(),
-                rank: 2,
-                cardinality: 2,
-                col_type: None,
-            },
-        ];
+        let ranked = vec![ColumnMeta::new("B", 1, 2), ColumnMeta::new("A", 2, 2)];
 
-        let (new_headers, new_rows) = reorder_data(&headers, &rows, &ranked).unwrap();
+        let (new_headers, new_rows) = reorder_data(&headers, rows, &ranked).unwrap();
 
         assert_eq!(new_headers, vec!["B".to_string(), "A".to_string()]);
         assert_eq!(new_rows[0], vec!["x".to_string(), "1".to_string()]);
         assert_eq!(new_rows[1], vec!["y".to_string(), "2".to_string()]);
     }
 
+    #[test]
+    fn test_uniqueness_violation() {
+        let headers = vec!["Id".to_string(), "Group".to_string()];
+        let rows = vec![
+            vec!["1".to_string(), "a".to_string()],
+            vec!["2".to_string(), "a".to_string()],
+        ];
+
+        let ranked = rank_columns(&headers, &rows, Default::default()).unwrap();
+        assert!(ranked[0].is_unique);
+        assert!(!ranked[1].is_unique);
+
+        let rows = vec![
+            vec!["1".to_string(), "a".to_string()],
+            vec!["1".to_string(), "a".to_string()],
+        ];
+        let err =
+            validate_cardinality_order(&headers, &rows, &ranked, Default::default()).unwrap_err();
+        assert!(matches!(
+            err,
+            RsfError::UniquenessViolation {
+                expected_cardinality: 2,
+                actual_cardinality: 1,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_column_permutation() {
+        let headers = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let rows = vec![
+            vec!["1".to_string(), "x".to_string(), "alpha".to_string()],
+            vec!["2".to_string(), "x".to_string(), "beta".to_string()],
+            vec!["1".to_string(), "y".to_string(), "gamma".to_string()],
+        ];
+
+        let ranked = rank_columns(&headers, &rows, Default::default()).unwrap();
+        let permutation = column_permutation(&headers, &ranked).unwrap();
+        assert_eq!(permutation, vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn test_compare_cardinality() {
+        let meta = |name: &str, cardinality: usize| ColumnMeta::new(name, 0, cardinality);
+        let before = vec![meta("A", 10), meta("B", 5), meta("C", 3)];
+        let after = vec![meta("A", 12), meta("B", 5), meta("D", 7)];
+
+        let drift = compare_cardinality(&before, &after);
+        let names: Vec<&str> = drift.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["D", "C", "A", "B"]);
+        assert_eq!(drift[0].before, None);
+        assert_eq!(drift[0].delta(), 7);
+        assert_eq!(drift[1].after, None);
+        assert_eq!(drift[1].delta(), -3);
+        assert_eq!(drift[2].delta(), 2);
+        assert!(!drift[3].is_changed());
+    }
+
+    #[test]
+    fn test_rank_by_entropy_on_skewed_data() {
+        let headers = vec!["Skewed".to_string(), "Even".to_string()];
+        // Skewed: 5 distinct values, one dominant. Even: 4 values spread evenly.
+        let skewed = ["x", "x", "x", "x", "x", "x", "a", "b", "c", "d"];
+        let even = ["p", "q", "r", "s", "p", "q", "r", "s", "p", "q"];
+        let rows: Vec<Vec<String>> = skewed
+            .iter()
+            .zip(even.iter())
+            .map(|(a, b)| vec![a.to_string(), b.to_string()])
+            .collect();
+
+        let by_cardinality = rank_columns(&headers, &rows, RankingOptions::default()).unwrap();
+        assert_eq!(by_cardinality[0].name, "Skewed");
+        assert_eq!(by_cardinality[0].entropy, None);
+
+        let options = RankingOptions {
+            metric: RankMetric::Entropy,
+            ..Default::default()
+        };
+        let by_entropy = rank_columns(&headers, &rows, options.clone()).unwrap();
+        assert_eq!(by_entropy[0].name, "Even");
+        assert_eq!(by_entropy[1].name, "Skewed");
+        assert!(by_entropy[0].entropy.unwrap() > by_entropy[1].entropy.unwrap());
+
+        assert!(validate_cardinality_order(&headers, &rows, &by_entropy, options.clone()).is_ok());
+        assert!(validate_cardinality_order(&headers, &rows, &by_cardinality, options).is_err());
+    }
+
     #[test]
     fn test_sort_rows_canonical() {
         let rows = vec![
@@ -424,12 +2182,608 @@ mod tests {
         assert_eq!(sorted[2], vec!["c".to_string(), "3".to_string()]);
     }
 
+    #[test]
+    fn test_sort_rows_decimal_comma() {
+        let rows = vec![
+            vec!["999,5".to_string()],
+            vec!["1.234,56".to_string()],
+            vec!["12,0".to_string()],
+        ];
+        let options = RankingOptions {
+            decimal_comma: true,
+            ..Default::default()
+        };
+
+        let sorted = sort_rows_with_options(&rows, options.clone());
+        assert_eq!(sorted[0], vec!["12,0".to_string()]);
+        assert_eq!(sorted[1], vec!["999,5".to_string()]);
+        assert_eq!(sorted[2], vec!["1.234,56".to_string()]);
+
+        assert!(validate_sorted(&sorted, options).is_ok());
+        assert!(validate_sorted(&sorted, RankingOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_ranking_options_builder() {
+        let options = RankingOptions::builder().build().unwrap();
+        assert_eq!(options, RankingOptions::default());
+
+        let options = RankingOptions::builder()
+            .treat_empty_as_null(false)
+            .metric(RankMetric::Entropy)
+            .build()
+            .unwrap();
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(
+            serde_json::from_str::<RankingOptions>(&json).unwrap(),
+            options
+        );
+        // Missing keys take their default
+        let partial: RankingOptions = serde_json::from_str(r#"{"decimal_comma":true}"#).unwrap();
+        assert!(partial.treat_empty_as_null && partial.decimal_comma);
+
+        let band = CardinalityBand { min: 2.0, max: 1.0 };
+        let err = RankingOptions::builder()
+            .cardinality_band(Some(band))
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, RsfError::InvalidOptions { .. }));
+    }
+
+    #[test]
+    fn test_prepend_concat_key() {
+        let headers = vec![
+            "Month".to_string(),
+            "Account".to_string(),
+            "Amount".to_string(),
+        ];
+        let rows = vec![
+            vec!["01".to_string(), "A1".to_string(), "5".to_string()],
+            vec!["01".to_string(), "A2".to_string(), "5".to_string()],
+            vec!["02".to_string(), "A1".to_string(), "7".to_string()],
+        ];
+
+        let (headers, rows) =
+            prepend_concat_key(&headers, &rows, &["Account", "Month"], "id", "-", false).unwrap();
+        assert_eq!(headers, ["id", "Month", "Account", "Amount"]);
+        assert_eq!(rows[0], ["A1-01", "01", "A1", "5"]);
+
+        // The key ties with nothing else, so ranking keeps it leftmost
+        let ranked = rank_columns(&headers, &rows, Default::default()).unwrap();
+        let (headers, rows) = reorder_data(&headers, rows, &ranked).unwrap();
+        assert_eq!(headers[0], "id");
+        let keys: Vec<&str> = rows.iter().map(|row| row[0].as_str()).collect();
+        assert_eq!(keys, ["A1-01", "A2-01", "A1-02"]);
+
+        let (headers, rows) =
+            prepend_concat_key(&headers, &rows, &["Account", "Month"], "key", "|", true).unwrap();
+        assert_eq!(headers, ["key", "id", "Amount"]);
+        assert_eq!(rows[2], ["A1|02", "A1-02", "7"]);
+
+        assert!(prepend_concat_key(&headers, &rows, &["Nope"], "k", "|", false).is_err());
+        assert!(prepend_concat_key(&headers, &rows, &["Amount"], "id", "|", false).is_err());
+    }
+
+    #[test]
+    fn test_cardinality_band() {
+        let headers = vec!["Id".to_string()];
+        let rows: Vec<Vec<String>> = (0..10).map(|i| vec![i.to_string()]).collect();
+        let schema = vec![ColumnMeta::new("Id", 1, 8)];
+
+        assert!(validate_cardinality_order(&headers, &rows, &schema, Default::default()).is_err());
+
+        let options = RankingOptions {
+            cardinality_band: Some("0.5:1.5".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(validate_cardinality_order(&headers, &rows, &schema, options).is_ok());
+
+        let options = RankingOptions {
+            cardinality_band: Some("0.5:1.2".parse().unwrap()),
+            ..Default::default()
+        };
+        let err = validate_cardinality_order(&headers, &rows, &schema, options).unwrap_err();
+        assert!(err.to_string().contains("ratio 1.25"), "{}", err);
+
+        assert!("2:1".parse::<CardinalityBand>().is_err());
+        assert!("1".parse::<CardinalityBand>().is_err());
+    }
+
+    #[test]
+    fn test_top_values() {
+        let mut stat = ColumnStats::new("Status");
+        for value in ["open", "closed", "open", "stale", "closed", "open"] {
+            stat.add_value(value);
+        }
+
+        assert_eq!(stat.top_values(2), vec![("open", 3), ("closed", 2)]);
+        assert_eq!(stat.top_values(10).len(), 3);
+    }
+
+    #[test]
+    fn test_cardinality_limits() {
+        let limit: CardinalityLimit = "error:code:2".parse().unwrap();
+        assert_eq!(limit.column, "error:code");
+        assert_eq!(limit.max, 2);
+        assert!("error_code".parse::<CardinalityLimit>().is_err());
+        assert!(":3".parse::<CardinalityLimit>().is_err());
+        assert!("error_code:many".parse::<CardinalityLimit>().is_err());
+
+        let headers = ["id", "error_code"];
+        let rows: Vec<Vec<String>> = [
+            ("1", "E1"),
+            ("2", "E1"),
+            ("3", "E2"),
+            ("4", "disk full on /dev/sda1"),
+            ("5", "E1"),
+        ]
+        .iter()
+        .map(|(id, code)| vec![id.to_string(), code.to_string()])
+        .collect();
+
+        let limits = ["error_code:2".parse().unwrap(), "id:5".parse().unwrap()];
+        let exceeded =
+            check_cardinality_limits(&headers, &rows, &limits, Default::default()).unwrap();
+        assert_eq!(exceeded.len(), 1);
+        assert_eq!(exceeded[0].cardinality, 3);
+        assert_eq!(
+            exceeded[0].to_string(),
+            "Column 'error_code' has cardinality 3, above the limit of 2; \
+             most frequent: \"E1\" (3), \"E2\" (1), \"disk full on /dev/sda1\" (1)"
+        );
+
+        let missing = ["code:1".parse().unwrap()];
+        assert!(check_cardinality_limits(&headers, &rows, &missing, Default::default()).is_err());
+    }
+
+    #[test]
+    fn test_compare_null_modes() {
+        let headers = ["Code", "Note"];
+        // Note has three kinds of empty cell that only count apart when
+        // nulls are distinct
+        let rows: Vec<Vec<String>> = [("a", ""), ("b", " "), ("c", "  "), ("a", "x")]
+            .iter()
+            .map(|(code, note)| vec![code.to_string(), note.to_string()])
+            .collect();
+
+        let shifts = compare_null_modes(&headers, &rows, Default::default()).unwrap();
+        let names: Vec<&str> = shifts.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Note", "Code"]);
+        assert_eq!(shifts[0].distinct_cardinality, 4);
+        assert_eq!(shifts[0].folded_cardinality, 2);
+        assert_eq!(shifts[0].folded_rank, 2);
+        assert!(shifts.iter().all(NullModeShift::is_moved));
+
+        let shifts = compare_null_modes(&headers, &rows[..1], Default::default()).unwrap();
+        assert!(!shifts.iter().any(NullModeShift::is_moved));
+    }
+
+    #[test]
+    fn test_require_columns() {
+        let headers = ["Id", "Name"];
+        assert!(require_columns(&headers, &["Name", "Id"]).is_ok());
+
+        let err = require_columns(&headers, &["Id", "Email", "Phone"]).unwrap_err();
+        assert!(matches!(err, RsfError::MissingColumn { column } if column == "Email"));
+    }
+
+    #[test]
+    fn test_header_divergence() {
+        let schema: Vec<ColumnMeta> = ["A", "B", "C", "D", "E"]
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| ColumnMeta::new(*name, idx + 1, 1))
+            .collect();
+
+        assert_eq!(
+            header_divergence(&["A", "B", "C", "D", "E"], &schema, 1),
+            None
+        );
+
+        let divergence = header_divergence(&["A", "B", "D", "C", "E"], &schema, 1).unwrap();
+        assert_eq!(divergence.position, 2);
+        let name = |name: &str| Some(name.to_string());
+        assert_eq!(
+            divergence.columns,
+            [
+                (1, name("B"), name("B")),
+                (2, name("C"), name("D")),
+                (3, name("D"), name("C")),
+            ]
+        );
+
+        // A missing last column shows as nothing found
+        let divergence = header_divergence(&["A", "B", "C", "D"], &schema, 2).unwrap();
+        assert_eq!(divergence.position, 4);
+        assert_eq!(divergence.columns.len(), 3);
+        assert_eq!(divergence.columns[2], (4, name("E"), None));
+    }
+
+    #[test]
+    fn test_align_columns() {
+        let reference: Vec<ColumnMeta> = ["Id", "Name", "Region"]
+            .iter()
+            .map(|name| ColumnMeta::new(*name, 0, 0))
+            .collect();
+        let rows = vec![
+            vec!["south".to_string(), "2".to_string(), "Bo".to_string()],
+            vec!["north".to_string(), "1".to_string(), "Al".to_string()],
+        ];
+
+        let (headers, aligned) =
+            align_columns(&["Region", "Id", "Name"], rows.clone(), &reference).unwrap();
+        assert_eq!(headers, ["Id", "Name", "Region"]);
+        assert_eq!(aligned, [["2", "Bo", "south"], ["1", "Al", "north"]]);
+
+        let err = align_columns(&["Region", "Id"], rows.clone(), &reference).unwrap_err();
+        assert!(matches!(err, RsfError::MissingColumn { column } if column == "Name"));
+
+        let err = align_columns(&["Region", "Id", "Name", "Note"], rows, &reference).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Schema error: Column 'Note' is not in the reference schema"
+        );
+    }
+
+    #[test]
+    fn test_distinct_keys() {
+        let rows: Vec<Vec<String>> = [
+            ["1", "a", "x"],
+            ["1", "a", "y"],
+            ["1", "b", "x"],
+            ["2", "a", "x"],
+        ]
+        .iter()
+        .map(|row| row.map(String::from).to_vec())
+        .collect();
+
+        assert_eq!(distinct_keys(&rows, 1), [["1"], ["2"]]);
+        assert_eq!(
+            distinct_keys(&rows, 2),
+            [["1", "a"], ["1", "b"], ["2", "a"]]
+        );
+        assert_eq!(distinct_keys(&rows, 5).len(), 4);
+    }
+
+    #[test]
+    fn test_functional_dependency() {
+        let headers = ["Account", "Vendor", "Month"];
+        let rows: Vec<Vec<String>> = [
+            ["a1", "acme", "jan"],
+            ["a2", "acme", "jan"],
+            ["a1", "acme", "feb"],
+            ["a3", "zeta", "feb"],
+        ]
+        .iter()
+        .map(|row| row.map(String::from).to_vec())
+        .collect();
+
+        // Each account has one vendor, but acme has several accounts
+        let dep = functional_dependency(&headers, &rows, "Account", "Vendor").unwrap();
+        assert!(dep.left_determines_right());
+        assert_eq!(dep.right_conflicts, 1);
+        assert_eq!(dep.to_string(), "Account -> Vendor");
+
+        let dep = functional_dependency(&headers, &rows, "Month", "Vendor").unwrap();
+        assert_eq!((dep.left_conflicts, dep.right_conflicts), (1, 1));
+        assert_eq!(dep.to_string(), "Month and Vendor are independent");
+
+        let dep = functional_dependency(&headers, &rows, "Vendor", "Account").unwrap();
+        assert_eq!(dep.to_string(), "Account -> Vendor");
+
+        assert!(functional_dependency(&headers, &rows, "Account", "Missing").is_err());
+    }
+
+    #[test]
+    fn test_type_row() {
+        let headers: Vec<String> = ["Id", "Amount", "Day", "Note"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        let row = |cells: &[&str]| -> Vec<String> { cells.iter().map(|c| c.to_string()).collect() };
+
+        let types = parse_type_row(&headers, &row(&["int", "Float", "date", "str"])).unwrap();
+        assert_eq!(
+            types,
+            [
+                DataType::Int,
+                DataType::Float,
+                DataType::Date,
+                DataType::Str
+            ]
+        );
+
+        let err = parse_type_row(&headers, &row(&["int", "money", "date", "str"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Schema error: Unknown type 'money' for column 'Amount' in the type row \
+             (expected int, float, date or str)"
+        );
+
+        let declared: Vec<(String, DataType)> = headers.iter().cloned().zip(types).collect();
+        let rows = vec![
+            row(&["1", "2.5", "2024-02-29", "x"]),
+            row(&["-7", "", "2024-01-31", "12"]),
+        ];
+        assert!(check_declared_types(&headers, &rows, &declared, false).is_ok());
+
+        let rows = vec![rows[0].clone(), row(&["3", "1", "2023-02-29", "y"])];
+        let err = check_declared_types(&headers, &rows, &declared, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Column 'Day' is declared date but row 2 holds '2023-02-29'"
+        );
+        assert!(!DataType::Int.accepts("1.0", false));
+        assert!(DataType::Float.accepts("1.234,5", true));
+    }
+
+    #[test]
+    fn test_exclude_nulls_from_cardinality() {
+        let headers = ["Note"];
+        let rows: Vec<Vec<String>> = ["late", "", "early", " ", "late"]
+            .iter()
+            .map(|cell| vec![cell.to_string()])
+            .collect();
+
+        let with_nulls = compute_cardinality(&headers, &rows, Default::default()).unwrap();
+        assert_eq!(with_nulls[0].cardinality(), 3);
+
+        let options = RankingOptions::builder()
+            .include_nulls(false)
+            .build()
+            .unwrap();
+        let without_nulls = compute_cardinality(&headers, &rows, options).unwrap();
+        assert_eq!(without_nulls[0].cardinality(), 2);
+
+        // Without null folding every empty cell is a real value
+        let options = RankingOptions::builder()
+            .treat_empty_as_null(false)
+            .include_nulls(false)
+            .build()
+            .unwrap();
+        let unfolded = compute_cardinality(&headers, &rows, options).unwrap();
+        assert_eq!(unfolded[0].cardinality(), 4);
+    }
+
+    #[test]
+    fn test_null_values() {
+        let headers = ["Score"];
+        let rows: Vec<Vec<String>> = ["7", "N/A", "", "n/a", "7", " N/A "]
+            .iter()
+            .map(|cell| vec![cell.to_string()])
+            .collect();
+
+        // N/A and the empty cell are the same null value
+        let options = RankingOptions::builder()
+            .null_values(["N/A", "\\N"])
+            .build()
+            .unwrap();
+        let stats = compute_cardinality(&headers, &rows, options.clone()).unwrap();
+        assert_eq!(stats[0].cardinality(), 3);
+        assert!(options.is_null(" N/A ") && !options.is_null("n/a"));
+
+        let options = RankingOptions::builder()
+            .null_values(["N/A"])
+            .null_values_ignore_case(true)
+            .include_nulls(false)
+            .build()
+            .unwrap();
+        let stats = compute_cardinality(&headers, &rows, options.clone()).unwrap();
+        assert_eq!(stats[0].cardinality(), 1);
+
+        // Recorded in the schema, so validation counts the same way
+        let yaml = serde_yaml::to_string(&options).unwrap();
+        let reread: RankingOptions = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(reread, options);
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let names = [
+            "ID", "Straße", "id", "Name", "STRASSE", "Id", "ID", "Äpfel", "äpfel",
+        ];
+        assert_eq!(
+            case_collisions(&names),
+            [
+                vec!["ID", "id", "Id"],
+                vec!["Straße", "STRASSE"],
+                vec!["Äpfel", "äpfel"]
+            ]
+        );
+        assert!(case_collisions(&["a", "a", "b"]).is_empty());
+
+        let mut headers: Vec<String> = ["ID", "id", "id_2", "ID", "Id"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        let renamed = rename_case_collisions(&mut headers);
+        assert_eq!(headers, ["ID", "id_3", "id_2", "ID", "Id_4"]);
+        assert_eq!(renamed.len(), 2);
+        assert!(case_collisions(&headers).is_empty());
+    }
+
+    #[test]
+    fn test_append_row_hashes() {
+        let rows = || -> Vec<Vec<String>> {
+            [["1", "x"], ["2", "x"], ["1", "y"]]
+                .iter()
+                .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+                .collect()
+        };
+        let mut headers = vec!["Id".to_string(), "Kind".to_string()];
+        let mut hashed = rows();
+        let column = append_row_hashes(&mut headers, &mut hashed, "row_hash", &["Id"]).unwrap();
+
+        assert_eq!(headers, ["Id", "Kind", "row_hash"]);
+        assert_eq!(
+            hashed[0][2],
+            format!("{:x}", Sha256::digest("1".as_bytes()))
+        );
+        // Editing Kind, which is not part of the key, keeps the hash
+        assert_eq!(hashed[0][2], hashed[2][2]);
+        assert_ne!(hashed[0][2], hashed[1][2]);
+        assert_eq!((column.rank, column.cardinality), (3, 2));
+        assert_eq!(column.synthetic_source, Some(SyntheticSource::RowHash));
+        let yaml = serde_yaml::to_string(&column).unwrap();
+        assert!(
+            yaml.contains("synthetic: true\nsynthetic_source: row_hash\n"),
+            "{}",
+            yaml
+        );
+
+        // The same rows hash the same on every run
+        let mut again = rows();
+        append_row_hashes(
+            &mut vec!["Id".into(), "Kind".into()],
+            &mut again,
+            "row_hash",
+            &["Id"],
+        )
+        .unwrap();
+        assert_eq!(again, hashed);
+
+        let mut headers = vec!["Id".to_string(), "Kind".to_string()];
+        assert!(append_row_hashes(&mut headers.clone(), &mut rows(), "Kind", &["Id"]).is_err());
+        assert!(append_row_hashes(&mut headers.clone(), &mut rows(), "h", &[] as &[&str]).is_err());
+        assert!(append_row_hashes(&mut headers, &mut rows(), "h", &["Missing"]).is_err());
+    }
+
+    #[test]
+    fn test_row_hash_escapes_the_separator() {
+        let mut rows: Vec<Vec<String>> = [["a|b", "c"], ["a", "b|c"]]
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect();
+        let mut headers = vec!["A".to_string(), "B".to_string()];
+        let column = append_row_hashes(&mut headers, &mut rows, "row_hash", &["A", "B"]).unwrap();
+
+        assert_ne!(rows[0][2], rows[1][2]);
+        assert_eq!(column.cardinality, 2);
+        assert_eq!(
+            rows[0][2],
+            format!("{:x}", Sha256::digest("a\\|b|c".as_bytes()))
+        );
+    }
+
+    #[test]
+    fn test_original_names_keep_case() {
+        let mut headers: Vec<String> = ["ID", "Straße", "id", "STRASSE"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        let renamed = rename_case_collisions(&mut headers);
+        let columns = headers
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| ColumnMeta::new(name, idx + 1, 1))
+            .collect();
+        let mut schema = Schema::new(columns);
+        schema.record_original_names(&renamed);
+
+        let original: Vec<(&str, Option<&str>)> = schema
+            .columns
+            .iter()
+            .map(|col| (col.name.as_str(), col.original_name.as_deref()))
+            .collect();
+        assert_eq!(
+            original,
+            [
+                ("ID", None),
+                ("Straße", None),
+                ("id_2", Some("id")),
+                ("STRASSE_2", Some("STRASSE"))
+            ]
+        );
+        let yaml = serde_yaml::to_string(&schema).unwrap();
+        assert!(yaml.contains("original_name: STRASSE\n"), "{}", yaml);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let headers = ["A", "B", "C"];
+        let rows: Vec<Vec<String>> = [["x", "y", "x"], ["y", "x", "z"], ["x", "x", "y"]]
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect();
+        let stats = compute_cardinality(&headers, &rows, RankingOptions::default()).unwrap();
+
+        // Same values with different counts, in a different order
+        assert_eq!(stats[0].fingerprint(), stats[1].fingerprint());
+        assert_ne!(stats[0].fingerprint(), stats[2].fingerprint());
+        assert_eq!(stats[0].fingerprint().len(), 64);
+        assert_eq!(
+            ColumnStats::new("Empty").fingerprint(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_pin_last() {
+        let headers = ["Note", "Id", "Kind"];
+        let rows: Vec<Vec<String>> = [["a", "1", "x"], ["b", "2", "x"], ["c", "3", "y"]]
+            .iter()
+            .map(|row| row.map(String::from).to_vec())
+            .collect();
+        let options = RankingOptions::builder()
+            .pin_last(["Note"])
+            .build()
+            .unwrap();
+
+        let ranked = rank_columns(&headers, &rows, options.clone()).unwrap();
+        let names: Vec<&str> = ranked.iter().map(|col| col.name.as_str()).collect();
+        // Id ties with Note on cardinality but Note goes last anyway
+        assert_eq!(names, ["Id", "Kind", "Note"]);
+        assert!(validate_cardinality_order(&headers, &rows, &ranked, options.clone()).is_ok());
+
+        // Without the pin the recorded order no longer validates
+        assert!(validate_cardinality_order(&headers, &rows, &ranked, Default::default()).is_err());
+        let unpinned = rank_columns(&headers, &rows, Default::default()).unwrap();
+        assert!(validate_cardinality_order(&headers, &rows, &unpinned, options).is_err());
+
+        let missing = RankingOptions::builder()
+            .pin_last(["Blob"])
+            .build()
+            .unwrap();
+        assert!(rank_columns(&headers, &rows, missing).is_err());
+        assert!(RankingOptions::builder()
+            .pin_last(["Note", "Note"])
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_cardinality_ratio() {
+        let headers = ["A", "B"];
+        let rows = vec![
+            vec!["1".to_string(), "x".to_string()],
+            vec!["2".to_string(), "x".to_string()],
+            vec!["3".to_string(), "x".to_string()],
+            vec!["4".to_string(), "y".to_string()],
+        ];
+
+        let ranked = rank_columns(&headers, &rows, Default::default()).unwrap();
+        assert_eq!(ranked[0].cardinality_ratio, 1.0);
+        assert_eq!(ranked[1].cardinality_ratio, 0.5);
+
+        let ranked = rank_columns(&headers, &[], Default::default()).unwrap();
+        assert_eq!(ranked[0].cardinality_ratio, 0.0);
+
+        // Schemas written before the fields existed still load
+        let schema: Schema = serde_yaml::from_str(
+            "version: '0.1'\ncolumns:\n- name: A\n  rank: 1\n  cardinality: 4\n",
+        )
+        .unwrap();
+        assert_eq!(schema.row_count, 0);
+        assert_eq!(schema.columns[0].cardinality_ratio, 0.0);
+    }
+
     #[test]
     fn test_empty_input() {
-        let ranked = rank_columns(&[], &[], Default::default()).unwrap();
+        let no_headers: [&str; 0] = [];
+        let ranked = rank_columns(&no_headers, &[], Default::default()).unwrap();
         assert!(ranked.is_empty());
 
-        let (new_headers, new_rows) = reorder_data(&[], &[], &[]).unwrap();
+        let (new_headers, new_rows) = reorder_data(&no_headers, Vec::new(), &[]).unwrap();
         assert!(new_headers.is_empty());
         assert!(new_rows.is_empty());
 
@@ -448,8 +2802,51 @@ mod tests {
         let ranked = rank_columns(&headers, &rows, Default::default()).unwrap();
         assert_eq!(ranked.len(), 2);
 
-        let (new_headers, new_rows) = reorder_data(&headers, &rows, &ranked).unwrap();
+        let (new_headers, new_rows) = reorder_data(&headers, rows, &ranked).unwrap();
         assert_eq!(new_headers.len(), 2);
         assert_eq!(new_rows.len(), 2);
     }
+
+    #[test]
+    fn test_combined_cardinality() {
+        let headers = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let rows = vec![
+            vec!["1".to_string(), "x".to_string(), "a|b".to_string()],
+            vec!["1".to_string(), "y".to_string(), "a".to_string()],
+            vec!["2".to_string(), "x".to_string(), "a".to_string()],
+            vec!["1".to_string(), "x".to_string(), "a|b".to_string()],
+        ];
+
+        let combined = compute_combined_cardinality(
+            &headers,
+            &rows,
+            &["A".to_string(), "B".to_string()],
+            Default::default(),
+        )
+        .unwrap();
+        assert_eq!(combined.name, "A,B");
+        assert_eq!(combined.cardinality(), 3);
+
+        // Escaping keeps ("a|b", "x") distinct from ("a", "b|x")
+        let rows = vec![
+            vec!["a|b".to_string(), "x".to_string(), String::new()],
+            vec!["a".to_string(), "b|x".to_string(), String::new()],
+        ];
+        let combined = compute_combined_cardinality(
+            &headers,
+            &rows,
+            &["A".to_string(), "B".to_string()],
+            Default::default(),
+        )
+        .unwrap();
+        assert_eq!(combined.cardinality(), 2);
+
+        assert!(compute_combined_cardinality(
+            &headers,
+            &rows,
+            &["Z".to_string()],
+            Default::default()
+        )
+        .is_err());
+    }
 }
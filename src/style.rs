@@ -0,0 +1,19 @@
+//! Styles for terminal output
+//!
+//! Printed through `anstream`, which drops them when the stream is not a
+//! terminal, `NO_COLOR` is set or `--color never` is given.
+
+use anstyle::{AnsiColor, Style};
+
+/// ✓ and other success lines
+pub const OK: Style = AnsiColor::Green.on_default().bold();
+/// ✗ and `Error:`
+pub const ERROR: Style = AnsiColor::Red.on_default().bold();
+/// `Warning:`
+pub const WARNING: Style = AnsiColor::Yellow.on_default().bold();
+/// What a check expected, such as a schema's column name
+pub const EXPECTED: Style = AnsiColor::Green.on_default();
+/// What a check found instead
+pub const FOUND: Style = AnsiColor::Red.on_default();
+/// Report titles
+pub const HEADING: Style = Style::new().bold();
@@ -0,0 +1,492 @@
+use crate::ranking::{ColumnStats, DataType, RankingOptions};
+use serde::Serialize;
+
+/// Column statistics of a table, as written by `rsf stats --format json`
+/// and `rsf rank --stats-out`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct StatsReport {
+    pub row_count: usize,
+    pub columns: Vec<ColumnSummary>,
+}
+
+/// Statistics of one column
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct ColumnSummary {
+    pub name: String,
+    pub cardinality: usize,
+    /// Null and missing cells, as [`RankingOptions::is_null`] tells
+    pub nulls: usize,
+    /// Narrowest type every non-null value fits, `None` if all are null
+    #[serde(rename = "type")]
+    pub data_type: Option<DataType>,
+    /// Shortest non-null value, in characters
+    pub min_length: Option<usize>,
+    /// Longest non-null value, in characters
+    pub max_length: Option<usize>,
+}
+
+impl StatsReport {
+    /// Summarize columns counted over `row_count` rows, keeping their order
+    ///
+    /// Nulls are told and counted by `options`, whatever the null options
+    /// the columns were counted with.
+    pub fn from_stats<'a>(
+        stats: impl IntoIterator<Item = &'a ColumnStats>,
+        row_count: usize,
+        options: &RankingOptions,
+    ) -> Self {
+        Self {
+            row_count,
+            columns: stats
+                .into_iter()
+                .map(|stats| ColumnSummary::from_stats(stats, row_count, options))
+                .collect(),
+        }
+    }
+}
+
+impl ColumnSummary {
+    /// Summarize a column counted over `row_count` rows; rows too short to
+    /// reach the column count as nulls
+    ///
+    /// With `treat_empty_as_null`, the null cells count as one value in the
+    /// cardinality, or as none without `include_nulls`.
+    pub fn from_stats(stats: &ColumnStats, row_count: usize, options: &RankingOptions) -> Self {
+        let mut counted = 0;
+        let mut null_values = 0;
+        let mut nulls = 0;
+        let mut values: Vec<&str> = Vec::new();
+        for (value, count) in stats.counts_as_written() {
+            counted += count;
+            if options.is_null(value) {
+                null_values += 1;
+                nulls += count;
+            } else {
+                values.push(value);
+            }
+        }
+        nulls += row_count.saturating_sub(counted);
+        let cardinality = if options.treat_empty_as_null {
+            values.len() + usize::from(options.include_nulls && null_values > 0)
+        } else {
+            values.len() + null_values
+        };
+
+        let data_type = (!values.is_empty()).then(|| {
+            [DataType::Int, DataType::Float, DataType::Date]
+                .into_iter()
+                .find(|data_type| values.iter().all(|v| data_type.accepts(v, false)))
+                .unwrap_or(DataType::Str)
+        });
+        let lengths = values.iter().map(|value| value.chars().count());
+
+        Self {
+            name: stats.name.clone(),
+            cardinality,
+            nulls,
+            data_type,
+            min_length: lengths.clone().min(),
+            max_length: lengths.max(),
+        }
+    }
+}
+
+/// Column profile for a data catalog, as written by
+/// `rsf stats --top-values-per-column`
+///
+/// Empty, whitespace-only and missing cells count towards `missing_rate`
+/// only; they are not values.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct CatalogColumn {
+    pub name: String,
+    /// Distinct non-empty values
+    pub cardinality: usize,
+    /// Empty cells as a fraction of the rows; 0 when there are no rows
+    pub missing_rate: f64,
+    /// Distinct values as a fraction of the non-empty cells; 0 when all are empty
+    pub unique_ratio: f64,
+    /// Most frequent values first, ties by value
+    pub top_values: Vec<TopValue>,
+    /// The first distinct values in sorted order
+    pub sample_values: Vec<String>,
+}
+
+/// One of the most frequent values of a column
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct TopValue {
+    pub value: String,
+    pub count: usize,
+    /// `count` as a fraction of the rows
+    pub frequency: f64,
+}
+
+impl CatalogColumn {
+    /// Profile a column counted over `row_count` rows, listing up to `top`
+    /// top and sample values; rows too short to reach the column count as
+    /// missing
+    pub fn from_stats(stats: &ColumnStats, row_count: usize, top: usize) -> Self {
+        let fraction = |count: usize, of: usize| {
+            if of == 0 {
+                0.0
+            } else {
+                count as f64 / of as f64
+            }
+        };
+
+        let mut values: Vec<(&str, usize)> = stats
+            .value_counts
+            .iter()
+            .filter(|(value, _)| !value.trim().is_empty())
+            .map(|(value, &count)| (&**value, count))
+            .collect();
+        let present: usize = values.iter().map(|(_, count)| count).sum();
+
+        values.sort_unstable();
+        let sample_values = values
+            .iter()
+            .take(top)
+            .map(|(value, _)| value.to_string())
+            .collect();
+        values.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        let top_values = values
+            .iter()
+            .take(top)
+            .map(|&(value, count)| TopValue {
+                value: value.to_string(),
+                count,
+                frequency: fraction(count, row_count),
+            })
+            .collect();
+
+        Self {
+            name: stats.name.clone(),
+            cardinality: values.len(),
+            missing_rate: fraction(row_count.saturating_sub(present), row_count),
+            unique_ratio: fraction(values.len(), present),
+            top_values,
+            sample_values,
+        }
+    }
+}
+
+/// Empty cells per column, as written by `rsf stats --nulls` and
+/// `rsf rank --nulls-report`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct NullReport {
+    pub row_count: usize,
+    /// Most nulls first; ties keep header order
+    pub columns: Vec<ColumnNulls>,
+}
+
+/// Empty cells of one column
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct ColumnNulls {
+    pub name: String,
+    /// Empty, whitespace-only and missing cells
+    pub nulls: usize,
+    /// Nulls as a percentage of the rows; 0 when there are no rows
+    pub percent: f64,
+    /// Nulls as a fraction of the rows; 0 when there are no rows
+    pub fraction: f64,
+}
+
+impl NullReport {
+    /// Report the nulls of columns counted over `row_count` rows, as
+    /// tracked by [`ColumnStats::null_count`]
+    pub fn from_stats<'a>(
+        stats: impl IntoIterator<Item = &'a ColumnStats>,
+        row_count: usize,
+    ) -> Self {
+        Self::new(
+            stats
+                .into_iter()
+                .map(|stats| (stats.name.clone(), stats.null_count())),
+            row_count,
+        )
+    }
+
+    fn new(nulls: impl IntoIterator<Item = (String, usize)>, rows: usize) -> Self {
+        let fraction = |nulls: usize, scale: f64| {
+            if rows == 0 {
+                0.0
+            } else {
+                nulls as f64 * scale / rows as f64
+            }
+        };
+        let mut columns: Vec<ColumnNulls> = nulls
+            .into_iter()
+            .map(|(name, nulls)| ColumnNulls {
+                name,
+                nulls,
+                percent: fraction(nulls, 100.0),
+                fraction: fraction(nulls, 1.0),
+            })
+            .collect();
+        columns.sort_by_key(|col| std::cmp::Reverse(col.nulls));
+
+        Self {
+            row_count: rows,
+            columns,
+        }
+    }
+}
+
+/// Counts empty cells one record at a time, keeping a single counter per
+/// column rather than the values
+///
+/// ```
+/// use rsf::summary::NullCounter;
+///
+/// let mut counter = NullCounter::new(&["Id", "Note"]);
+/// counter.push_record(&["1", ""]);
+/// counter.push_record(&["2", "x"]);
+///
+/// let report = counter.finish();
+/// assert_eq!(report.columns[0].name, "Note");
+/// assert_eq!(report.columns[0].percent, 50.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NullCounter {
+    names: Vec<String>,
+    nulls: Vec<usize>,
+    rows: usize,
+    options: RankingOptions,
+}
+
+impl NullCounter {
+    pub fn new(headers: &[impl AsRef<str>]) -> Self {
+        Self {
+            names: headers.iter().map(|h| h.as_ref().to_string()).collect(),
+            nulls: vec![0; headers.len()],
+            rows: 0,
+            options: RankingOptions::default(),
+        }
+    }
+
+    /// Also count the `null_values` of `options` as nulls
+    pub fn with_options(mut self, options: RankingOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Count the empty cells of one record; cells missing from a short
+    /// record are nulls and cells beyond the last header are ignored
+    pub fn push_record(&mut self, record: &[impl AsRef<str>]) {
+        for (idx, nulls) in self.nulls.iter_mut().enumerate() {
+            if record
+                .get(idx)
+                .is_none_or(|cell| self.options.is_null(cell.as_ref()))
+            {
+                *nulls += 1;
+            }
+        }
+        self.rows += 1;
+    }
+
+    pub fn finish(self) -> NullReport {
+        NullReport::new(self.names.into_iter().zip(self.nulls), self.rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(name: &str, values: &[&str]) -> ColumnStats {
+        let mut stats = ColumnStats::new(name);
+        for value in values {
+            stats.add_value(value);
+        }
+        stats
+    }
+
+    #[test]
+    fn test_column_summary() {
+        let report = StatsReport::from_stats(
+            &[
+                stats("Id", &["1", "22", "-3", ""]),
+                stats("Amount", &["1.5", "2", " ", "4"]),
+                stats("Day", &["2024-01-02", "2024-02-29"]),
+                stats("Note", &["", "", "", ""]),
+                stats("Vendor", &["acme", "12", "Zeta Ltd", "acme"]),
+            ],
+            4,
+            &RankingOptions::default(),
+        );
+
+        let types: Vec<(&str, Option<DataType>, usize)> = report
+            .columns
+            .iter()
+            .map(|col| (col.name.as_str(), col.data_type, col.nulls))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("Id", Some(DataType::Int), 1),
+                ("Amount", Some(DataType::Float), 1),
+                ("Day", Some(DataType::Date), 2),
+                ("Note", None, 4),
+                ("Vendor", Some(DataType::Str), 0),
+            ]
+        );
+
+        let vendor = &report.columns[4];
+        assert_eq!(
+            (vendor.cardinality, vendor.min_length, vendor.max_length),
+            (3, Some(2), Some(8))
+        );
+        assert_eq!(report.columns[3].min_length, None);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["columns"][0]["type"], "int");
+        assert!(json["columns"][3]["type"].is_null());
+    }
+
+    #[test]
+    fn test_column_summary_in_every_null_mode() {
+        use crate::ranker::RsfRanker;
+
+        let headers = ["Id", "Note"];
+        let rows: [&[&str]; 5] = [
+            &["1", ""],
+            &["2", "NULL"],
+            &["3", " "],
+            &["4", "n/a"],
+            &["5"],
+        ];
+        // Counted with `counting`, reported with `options`
+        let report = |counting: &RankingOptions, options: &RankingOptions| {
+            let mut ranker = RsfRanker::new(counting.clone()).with_headers(&headers);
+            for row in rows {
+                ranker.push_record(row);
+            }
+            StatsReport::from_stats(ranker.stats(), rows.len(), options)
+        };
+        let folded = |include_nulls: bool| {
+            RankingOptions::builder()
+                .treat_empty_as_null(true)
+                .include_nulls(include_nulls)
+                .null_values(["n/a"])
+                .build()
+                .unwrap()
+        };
+
+        let distinct = RankingOptions::builder()
+            .treat_empty_as_null(false)
+            .build()
+            .unwrap();
+        let as_written = report(&distinct, &distinct);
+        let note = &as_written.columns[1];
+        assert_eq!((note.cardinality, note.nulls), (4, 3));
+        for include_nulls in [true, false] {
+            assert_eq!(report(&folded(include_nulls), &distinct), as_written);
+        }
+
+        // n/a is a null too; the nulls count as one value, or as none
+        for counting in [&distinct, &folded(true), &folded(false)] {
+            let note = &report(counting, &folded(true)).columns[1];
+            assert_eq!((note.cardinality, note.nulls), (2, 4));
+            let note = &report(counting, &folded(false)).columns[1];
+            assert_eq!((note.cardinality, note.nulls), (1, 4));
+        }
+    }
+
+    #[test]
+    fn test_catalog_column() {
+        let column = CatalogColumn::from_stats(&stats("Kind", &["b", "a", "b", " ", "c"]), 6, 2);
+
+        assert_eq!(column.cardinality, 3);
+        // One blank cell and one short row out of six
+        assert!((column.missing_rate - 2.0 / 6.0).abs() < 1e-9);
+        assert_eq!(column.unique_ratio, 0.75);
+        let top: Vec<(&str, usize)> = column
+            .top_values
+            .iter()
+            .map(|top| (top.value.as_str(), top.count))
+            .collect();
+        assert_eq!(top, [("b", 2), ("a", 1)]);
+        assert!((column.top_values[0].frequency - 2.0 / 6.0).abs() < 1e-9);
+        assert_eq!(column.sample_values, ["a", "b"]);
+
+        let empty = CatalogColumn::from_stats(&stats("Note", &[]), 0, 10);
+        assert_eq!((empty.missing_rate, empty.unique_ratio), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_null_counts() {
+        let csv = "Id,Note,Email,Tag\n1,,a@x.io,t\n2, ,,t\n3,hi,,t\n4,,,\n";
+        let mut records = crate::io::csv_records(csv.as_bytes(), Default::default()).unwrap();
+        let mut counter = NullCounter::new(records.headers());
+        for record in records.by_ref() {
+            counter.push_record(&record.unwrap());
+        }
+        let report = counter.finish();
+
+        assert_eq!(report.row_count, 4);
+        let counts: Vec<(&str, usize, f64)> = report
+            .columns
+            .iter()
+            .map(|col| (col.name.as_str(), col.nulls, col.percent))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("Note", 3, 75.0),
+                ("Email", 3, 75.0),
+                ("Tag", 1, 25.0),
+                ("Id", 0, 0.0),
+            ]
+        );
+
+        // Missing trailing cells count as nulls
+        let mut counter = NullCounter::new(&["A", "B"]);
+        counter.push_record(&["x"]);
+        assert_eq!(counter.finish().columns[0].name, "B");
+
+        let options = RankingOptions::builder()
+            .null_values(["-"])
+            .build()
+            .unwrap();
+        let mut counter = NullCounter::new(&["A"]).with_options(options);
+        for record in [["-"], [""], ["x"]] {
+            counter.push_record(&record);
+        }
+        assert_eq!(counter.finish().columns[0].nulls, 2);
+    }
+
+    #[test]
+    fn test_null_report_from_ranking_counts() {
+        let options = RankingOptions::builder()
+            .treat_empty_as_null(true)
+            .null_values(["n/a"])
+            .build()
+            .unwrap();
+        let headers = ["Id", "Note", "Tag"];
+        let rows: Vec<Vec<String>> = [vec!["1", "", "x"], vec!["2", "n/a"], vec!["3", "y", " "]]
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect();
+        let stats = crate::ranking::compute_cardinality(&headers, &rows, options).unwrap();
+
+        let report = NullReport::from_stats(&stats, rows.len());
+        let nulls: Vec<(&str, usize, f64)> = report
+            .columns
+            .iter()
+            .map(|col| (col.name.as_str(), col.nulls, col.fraction))
+            .collect();
+        assert_eq!(
+            nulls,
+            [
+                ("Note", 2, 2.0 / 3.0),
+                ("Tag", 2, 2.0 / 3.0),
+                ("Id", 0, 0.0)
+            ]
+        );
+    }
+}
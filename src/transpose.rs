@@ -0,0 +1,129 @@
+use crate::errors::{RsfError, RsfResult};
+
+/// Header of the label column holding the original column names
+pub const LABEL_COLUMN: &str = "column";
+
+/// Prefix of the headers holding the original rows
+pub const VALUE_PREFIX: &str = "value_";
+
+/// Turn columns into rows
+///
+/// The result has a `column,value_1,value_2,...` header; each row holds an
+/// original column name followed by that column's cells in row order.
+pub fn transpose(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut new_headers = Vec::with_capacity(rows.len() + 1);
+    new_headers.push(LABEL_COLUMN.to_string());
+    new_headers.extend((1..=rows.len()).map(|n| format!("{}{}", VALUE_PREFIX, n)));
+
+    let new_rows = headers
+        .iter()
+        .enumerate()
+        .map(|(col, name)| {
+            let mut new_row = Vec::with_capacity(rows.len() + 1);
+            new_row.push(name.as_ref().to_string());
+            // Short rows contribute empty cells
+            new_row.extend(
+                rows.iter()
+                    .map(|row| row.get(col).cloned().unwrap_or_default()),
+            );
+            new_row
+        })
+        .collect();
+
+    (new_headers, new_rows)
+}
+
+/// Invert [`transpose`]
+///
+/// Columns are located by header name rather than position, so a transposed
+/// file that has since been ranked (and had its columns reordered) still
+/// restores the original rows in `value_N` order. Column order follows the
+/// transposed file's row order, which ranking sorts canonically.
+pub fn untranspose(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+) -> RsfResult<(Vec<String>, Vec<Vec<String>>)> {
+    let label_idx = headers
+        .iter()
+        .position(|h| h.as_ref() == LABEL_COLUMN)
+        .ok_or_else(|| {
+            RsfError::schema_error(format!("Transposed file has no '{}' column", LABEL_COLUMN))
+        })?;
+
+    // Map each value_N header back to its original row number
+    let mut value_columns: Vec<(usize, usize)> = Vec::with_capacity(headers.len());
+    for (idx, header) in headers.iter().enumerate() {
+        if idx == label_idx {
+            continue;
+        }
+        let header = header.as_ref();
+        let number = header
+            .strip_prefix(VALUE_PREFIX)
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or_else(|| {
+                RsfError::schema_error(format!("Unexpected column '{}' in transposed file", header))
+            })?;
+        value_columns.push((number, idx));
+    }
+    value_columns.sort();
+
+    let new_headers: Vec<String> = rows
+        .iter()
+        .map(|row| row.get(label_idx).cloned().unwrap_or_default())
+        .collect();
+
+    let new_rows = value_columns
+        .iter()
+        .map(|&(_, idx)| {
+            rows.iter()
+                .map(|row| row.get(idx).cloned().unwrap_or_default())
+                .collect()
+        })
+        .collect();
+
+    Ok((new_headers, new_rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_transpose_round_trip() {
+        let headers = strings(&["name", "column", "port"]);
+        let rows = vec![
+            strings(&["db", "x", "5432"]),
+            strings(&["cache", "y", "6379"]),
+        ];
+
+        let (t_headers, t_rows) = transpose(&headers, &rows);
+        assert_eq!(t_headers, strings(&["column", "value_1", "value_2"]));
+        assert_eq!(t_rows[0], strings(&["name", "db", "cache"]));
+        assert_eq!(t_rows[1], strings(&["column", "x", "y"]));
+
+        let (back_headers, back_rows) = untranspose(&t_headers, &t_rows).unwrap();
+        assert_eq!(back_headers, headers);
+        assert_eq!(back_rows, rows);
+    }
+
+    #[test]
+    fn test_untranspose_reordered_columns() {
+        // As if the transposed file had been ranked
+        let headers = strings(&["value_2", "column", "value_1"]);
+        let rows = vec![strings(&["cache", "name", "db"])];
+
+        let (back_headers, back_rows) = untranspose(&headers, &rows).unwrap();
+        assert_eq!(back_headers, strings(&["name"]));
+        assert_eq!(back_rows, vec![strings(&["db"]), strings(&["cache"])]);
+
+        assert!(untranspose(&strings(&["value_1"]), &[]).is_err());
+        assert!(untranspose(&strings(&["column", "other"]), &[]).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Unicode normalization of cell values
+//!
+//! `"café"` written with a precomposed `é` (NFC) and with `e` followed by a
+//! combining acute accent (NFD) render the same but are different strings,
+//! so they count as two distinct values. [`normalization_conflicts`] finds
+//! such values and [`normalize_cells`] rewrites every cell to one form.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use unicode_normalization::UnicodeNormalization;
+
+/// A Unicode normalization form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition
+    Nfc,
+    /// Canonical decomposition
+    Nfd,
+    /// Compatibility composition; also folds e.g. `ﬁ` into `fi`
+    Nfkc,
+    /// Compatibility decomposition
+    Nfkd,
+}
+
+impl NormalizationForm {
+    /// `value` in this form
+    pub fn normalize(self, value: &str) -> String {
+        match self {
+            NormalizationForm::Nfc => value.nfc().collect(),
+            NormalizationForm::Nfd => value.nfd().collect(),
+            NormalizationForm::Nfkc => value.nfkc().collect(),
+            NormalizationForm::Nfkd => value.nfkd().collect(),
+        }
+    }
+}
+
+impl fmt::Display for NormalizationForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NormalizationForm::Nfc => "NFC",
+            NormalizationForm::Nfd => "NFD",
+            NormalizationForm::Nfkc => "NFKC",
+            NormalizationForm::Nfkd => "NFKD",
+        })
+    }
+}
+
+/// Rewrite every cell in `form`, returning the number of cells changed
+///
+/// ASCII cells are the same in every form and are skipped.
+pub fn normalize_cells(rows: &mut [Vec<String>], form: NormalizationForm) -> usize {
+    let mut changed = 0;
+    for value in rows.iter_mut().flatten().filter(|value| !value.is_ascii()) {
+        let normalized = form.normalize(value);
+        if normalized != *value {
+            *value = normalized;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Values in one column that differ only in Unicode normalization
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NormalizationConflict {
+    pub column: String,
+    /// The distinct spellings, in the order they first appear
+    pub values: Vec<String>,
+}
+
+impl fmt::Display for NormalizationConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values: Vec<String> = self.values.iter().map(|v| escape_non_ascii(v)).collect();
+        write!(
+            f,
+            "Column '{}' has values that differ only in Unicode normalization: {}",
+            self.column,
+            values.join(", ")
+        )
+    }
+}
+
+/// Quote `value`, spelling out non-ASCII characters as `\u{..}` so the
+/// spellings of a [`NormalizationConflict`] can be told apart
+fn escape_non_ascii(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        if c.is_ascii() {
+            escaped.extend(c.escape_debug());
+        } else {
+            escaped.extend(c.escape_unicode());
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Groups of distinct values that are canonically equivalent (equal once
+/// NFC-normalized), column by column and in order of first appearance
+pub fn normalization_conflicts(
+    headers: &[impl AsRef<str>],
+    rows: &[Vec<String>],
+) -> Vec<NormalizationConflict> {
+    let mut conflicts = Vec::new();
+    for (idx, header) in headers.iter().enumerate() {
+        // NFC form -> position in `groups`
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        let mut groups: Vec<Vec<&str>> = Vec::new();
+        for value in rows.iter().filter_map(|row| row.get(idx)) {
+            if value.is_ascii() {
+                continue;
+            }
+            let group = *seen
+                .entry(NormalizationForm::Nfc.normalize(value))
+                .or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+            if !groups[group].contains(&value.as_str()) {
+                groups[group].push(value);
+            }
+        }
+        conflicts.extend(
+            groups
+                .into_iter()
+                .filter(|values| values.len() > 1)
+                .map(|values| NormalizationConflict {
+                    column: header.as_ref().to_string(),
+                    values: values.into_iter().map(str::to_string).collect(),
+                }),
+        );
+    }
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NFC: &str = "caf\u{e9}";
+    const NFD: &str = "cafe\u{301}";
+
+    fn rows(values: &[&str]) -> Vec<Vec<String>> {
+        values
+            .iter()
+            .map(|value| vec![value.to_string(), "x".to_string()])
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize_cells() {
+        let mut table = rows(&[NFC, NFD, "plain"]);
+        assert_eq!(normalize_cells(&mut table, NormalizationForm::Nfc), 1);
+        assert_eq!(table, rows(&[NFC, NFC, "plain"]));
+
+        assert_eq!(normalize_cells(&mut table, NormalizationForm::Nfd), 2);
+        assert_eq!(table, rows(&[NFD, NFD, "plain"]));
+
+        let mut ligature = rows(&["\u{fb01}le"]);
+        assert_eq!(normalize_cells(&mut ligature, NormalizationForm::Nfc), 0);
+        assert_eq!(normalize_cells(&mut ligature, NormalizationForm::Nfkc), 1);
+        assert_eq!(ligature, rows(&["file"]));
+    }
+
+    #[test]
+    fn test_normalization_conflicts() {
+        let table = rows(&[NFD, "tea", NFC, NFD, "na\u{ef}ve"]);
+        let conflicts = normalization_conflicts(&["drink", "other"], &table);
+        assert_eq!(
+            conflicts,
+            vec![NormalizationConflict {
+                column: "drink".to_string(),
+                values: vec![NFD.to_string(), NFC.to_string()],
+            }]
+        );
+        assert_eq!(
+            conflicts[0].to_string(),
+            "Column 'drink' has values that differ only in Unicode normalization: \
+             \"cafe\\u{301}\", \"caf\\u{e9}\""
+        );
+
+        let mut table = table;
+        normalize_cells(&mut table, NormalizationForm::Nfc);
+        assert!(normalization_conflicts(&["drink", "other"], &table).is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+//! Warnings printed during a run, counted for `--fail-on-warning`
+
+use crate::log::info;
+use crate::style::WARNING;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Print `message` as a warning on stderr, unless `--quiet`, and count it
+pub fn warn(message: impl fmt::Display) {
+    count();
+    info!("{WARNING}Warning:{WARNING:#} {}", message);
+}
+
+/// Count a warning printed in its own format, such as a schema lint
+pub fn count() {
+    COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Warnings counted since the last call, which starts the count afresh for
+/// the next `--watch` run
+pub fn take() -> usize {
+    COUNT.swap(0, Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_resets_the_count() {
+        take();
+        warn("first");
+        count();
+        assert_eq!(take(), 2);
+        assert_eq!(take(), 0);
+    }
+}
@@ -0,0 +1,101 @@
+//! `wasm-bindgen` API for validating RSF in the browser
+//!
+//! Built with `wasm-pack build --no-default-features --features wasm`, the
+//! package exports [`validate`], which takes file contents as `Uint8Array`s:
+//!
+//! ```js
+//! import init, { validate } from "./pkg/rsf.js";
+//!
+//! await init();
+//! for (const finding of validate(csvBytes, schemaBytes)) {
+//!     console.log(finding.check, finding.message);
+//! }
+//! ```
+//!
+//! The tests run in a JS engine with `wasm-pack test --node
+//! --no-default-features --features wasm`.
+
+use crate::document::{validate_bytes, Check, Finding};
+use wasm_bindgen::prelude::*;
+
+/// A failed validation check
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct ValidationFinding {
+    check: Check,
+    message: String,
+}
+
+#[wasm_bindgen]
+impl ValidationFinding {
+    /// The check that failed, such as `"sorted"`
+    #[wasm_bindgen(getter)]
+    pub fn check(&self) -> String {
+        match self.check {
+            Check::ColumnOrder => "column_order",
+            Check::RankSequence => "rank_sequence",
+            Check::Cardinality => "cardinality",
+            Check::Sorted => "sorted",
+            Check::Types => "types",
+        }
+        .to_string()
+    }
+
+    /// What the check verifies, in a few words
+    #[wasm_bindgen(getter)]
+    pub fn description(&self) -> String {
+        self.check.description().to_string()
+    }
+
+    /// Why the check failed
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+}
+
+impl From<Finding> for ValidationFinding {
+    fn from(finding: Finding) -> Self {
+        Self {
+            check: finding.check,
+            message: finding.to_string(),
+        }
+    }
+}
+
+/// Validate CSV against a schema, both given as file contents
+///
+/// Returns the failed checks, an empty array if the CSV is valid RSF, and
+/// throws an `Error` if either input cannot be parsed.
+#[wasm_bindgen]
+pub fn validate(csv: &[u8], schema: &[u8]) -> Result<Vec<ValidationFinding>, JsError> {
+    Ok(validate_bytes(csv, schema)?
+        .into_iter()
+        .map(ValidationFinding::from)
+        .collect())
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    const SCHEMA: &[u8] = b"version: '0.1'\ncolumns:\n- name: Id\n  rank: 1\n  cardinality: 2\n\
+        - name: Kind\n  rank: 2\n  cardinality: 1\n";
+
+    #[wasm_bindgen_test]
+    fn test_validate() {
+        assert!(validate(b"Id,Kind\n1,x\n2,x\n", SCHEMA).unwrap().is_empty());
+
+        let findings = validate(b"Id,Kind\n2,x\n1,x\n", SCHEMA).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].check(), "sorted");
+        assert_eq!(findings[0].description(), "Rows are in canonical order");
+        assert!(!findings[0].message().is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_validate_unreadable_schema() {
+        assert!(validate(b"Id,Kind\n", b"columns: 3").is_err());
+    }
+}
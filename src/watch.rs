@@ -0,0 +1,138 @@
+use crate::changelog::format_utc;
+use crate::log::info;
+use crate::warnings;
+use anyhow::{Context, Result};
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, DebouncedEventKind, Debouncer};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How long a change must hold still before re-ranking, so that a burst of
+/// writes triggers a single run
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Run `rank`, then run it again each time `path` changes, until the process
+/// is interrupted
+///
+/// Returns only if the file cannot be watched.
+pub fn watch(path: &Path, mut rank: impl FnMut()) -> Result<()> {
+    let watcher = FileWatcher::new(path, DEBOUNCE)
+        .with_context(|| format!("Cannot watch {}", path.display()))?;
+    rank();
+    info!("Watching {} for changes (Ctrl-C to stop)", path.display());
+
+    while watcher.wait(None) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        info!(
+            "[{}] {} changed, re-ranking",
+            format_utc(now),
+            path.display()
+        );
+        rank();
+    }
+    anyhow::bail!("Stopped watching {}", path.display())
+}
+
+/// Debounced change events for one file
+///
+/// The file's directory is watched rather than the file itself, so that an
+/// editor saving by writing a new file and renaming it over the old one is
+/// still seen.
+struct FileWatcher {
+    path: PathBuf,
+    events: Receiver<DebounceEventResult>,
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl FileWatcher {
+    fn new(path: &Path, debounce: Duration) -> Result<Self> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let dir = dir.canonicalize()?;
+        let name = path.file_name().context("Not a file")?;
+
+        let (tx, events) = mpsc::channel();
+        let mut debouncer = new_debouncer(debounce, tx)?;
+        debouncer
+            .watcher()
+            .watch(&dir, RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            path: dir.join(name),
+            events,
+            _debouncer: debouncer,
+        })
+    }
+
+    /// Wait until the file changes and then stays the same for the debounce
+    /// time
+    ///
+    /// Returns false if `timeout` passes first or the watcher stops.
+    fn wait(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let events = match deadline {
+                Some(deadline) => self
+                    .events
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .ok(),
+                None => self.events.recv().ok(),
+            };
+            match events {
+                None => return false,
+                Some(Ok(events)) => {
+                    // A file still being written to reports `AnyContinuous`
+                    // until it settles
+                    if events
+                        .iter()
+                        .any(|e| e.path == self.path && e.kind == DebouncedEventKind::Any)
+                    {
+                        return true;
+                    }
+                }
+                Some(Err(err)) => {
+                    warnings::warn(format_args!("Watching {}: {}", self.path.display(), err))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, thread};
+
+    #[test]
+    fn test_modifying_the_file_triggers_a_rerank() {
+        let dir = std::env::temp_dir().join(format!("rsf-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("input.csv");
+        fs::write(&path, "A\n1\n").unwrap();
+
+        let watcher = FileWatcher::new(&path, Duration::from_millis(50)).unwrap();
+        let timeout = Some(Duration::from_secs(5));
+        assert!(!watcher.wait(Some(Duration::from_millis(200))));
+
+        // Other files in the directory are ignored
+        fs::write(dir.join("other.csv"), "B\n").unwrap();
+        assert!(!watcher.wait(Some(Duration::from_millis(300))));
+
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                fs::write(&path, "A\n1\n2\n").unwrap();
+            })
+        };
+        let changed = watcher.wait(timeout);
+        writer.join().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(changed);
+    }
+}
@@ -0,0 +1,1040 @@
+//! Runs the `rsf` binary for behavior that shows only in its exit status
+
+#![cfg(feature = "cli")]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A file in a fresh directory for this test, holding `contents`
+fn input(test: &str, contents: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rsf-cli-{}-{}", test, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("input.csv");
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Run `rsf` in the directory of `input`, away from the repository's rsf.toml,
+/// with color off unless `--color always` is passed
+fn rsf(input: &Path, args: &[&str]) -> Output {
+    rsf_with_env(input, &[], args)
+}
+
+/// Run `rsf` like [`rsf`] with only the `RSF_` variables in `vars` set
+fn rsf_with_env(input: &Path, vars: &[(&str, &str)], args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rsf"));
+    for (name, _) in std::env::vars_os() {
+        if name.to_string_lossy().starts_with("RSF_") {
+            command.env_remove(name);
+        }
+    }
+    command
+        .current_dir(input.parent().unwrap())
+        .env("NO_COLOR", "1")
+        .env_remove("CLICOLOR_FORCE")
+        .envs(vars.iter().copied())
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_fail_on_warning() {
+    let path = input("fail-on-warning", "Id,Kind\n1,a\n2,b\n3,a\n");
+    let rank = [
+        "rank",
+        "input.csv",
+        "--no-progress",
+        "--warn-if-cardinality-exceeds",
+        "Id:2",
+    ];
+
+    let output = rsf(&path, &rank);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Warning: "), "{}", stderr);
+
+    let output = rsf(&path, &[&rank[..], &["--fail-on-warning"]].concat());
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("1 warning(s) printed with --fail-on-warning"),
+        "{}",
+        stderr
+    );
+
+    // Without warnings the flag changes nothing
+    let output = rsf(
+        &path,
+        &["rank", "input.csv", "--no-progress", "--fail-on-warning"],
+    );
+    assert!(output.status.success());
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_row_count_file() {
+    let path = input("row-count-file", "Id,Kind\n1,a\n2,b\n3,a\n");
+    let dir = path.parent().unwrap();
+    let output = rsf(
+        &path,
+        &[
+            "rank",
+            "input.csv",
+            "-o",
+            "out.rsf",
+            "--schema",
+            "--no-progress",
+        ],
+    );
+    assert!(output.status.success());
+
+    let validate = |expected: &str, tolerance: &str| {
+        std::fs::write(dir.join("expected.txt"), expected).unwrap();
+        rsf(
+            &path,
+            &[
+                "validate",
+                "out.rsf",
+                "--schema",
+                "out.rsf.schema.yaml",
+                "--row-count-file",
+                "expected.txt",
+                "--row-count-tolerance",
+                tolerance,
+            ],
+        )
+    };
+    assert!(validate("3\n", "0").status.success());
+    let output = validate("4\n", "0");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Expected 4 rows, found 3"), "{}", stderr);
+    assert!(validate("4\n", "1").status.success());
+    assert!(!validate("three\n", "0").status.success());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_determinism_check_passes() {
+    let path = input(
+        "determinism-check",
+        "Kind,Id,Note\nb,3,\na,1,x\nb,2,\na,4,y\n",
+    );
+    for args in [
+        &["--rank-by", "cardinality", "--tiebreak", "position"],
+        &["--rank-by", "entropy", "--tiebreak", "hash"][..],
+        &[
+            "--threads",
+            "2",
+            "--concat-key",
+            "Kind,Id",
+            "--emit-row-hashes",
+            "Hash",
+            "--row-hash-key",
+            "Id",
+            "--normalize-unicode",
+            "nfc",
+        ],
+    ] {
+        let output = rsf(
+            &path,
+            &[
+                &["rank", "input.csv", "--no-progress", "--determinism-check"],
+                args,
+            ]
+            .concat(),
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{}", stderr);
+        assert!(stderr.contains("Determinism check passed"), "{}", stderr);
+    }
+
+    // Stdin is read once, so the second run parses the same bytes again
+    let output = Command::new(env!("CARGO_BIN_EXE_rsf"))
+        .current_dir(path.parent().unwrap())
+        .args(["rank", "-", "--no-progress", "--determinism-check"])
+        .stdin(std::fs::File::open(&path).unwrap())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("Determinism check passed"), "{}", stderr);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_broken_pipe_exits_quietly() {
+    // Far more output than a pipe buffers, so writing outlives the reader
+    let rows: String = (0..200_000).map(|i| format!("{},{}\n", i, i % 7)).collect();
+    let path = input("broken-pipe", &format!("Id,Kind\n{}", rows));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rsf"))
+        .current_dir(path.parent().unwrap())
+        .args(["rank", "input.csv", "--no-progress"])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut first = String::new();
+    std::io::BufRead::read_line(
+        &mut std::io::BufReader::new(child.stdout.take().unwrap()),
+        &mut first,
+    )
+    .unwrap();
+    assert_eq!(first, "Id,Kind\n");
+
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(!stderr.contains("Error"), "{}", stderr);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_output_delta_validates() {
+    let path = input("output-delta", "Id,Kind,Amt\n1,a,10\n2,b,20\n3,a,30\n");
+    let dir = path.parent().unwrap();
+    let rank = |args: &[&str]| rsf(&path, &[&["rank", "--no-progress"], args].concat());
+    assert!(rank(&["input.csv", "-o", "v1.rsf"]).status.success());
+
+    std::fs::write(dir.join("v2.csv"), "Id,Kind,Amt\n1,a,10\n2,b,25\n4,b,40\n").unwrap();
+    let output = rank(&[
+        "v2.csv",
+        "-o",
+        "delta.rsf",
+        "--schema",
+        "--output-delta",
+        "v1.rsf",
+        "--deleted-output",
+        "deleted.csv",
+    ]);
+    assert!(output.status.success());
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+    assert_eq!(read("delta.rsf"), "Id,Amt,Kind\n2,25,b\n4,40,b\n");
+    assert_eq!(read("deleted.csv"), "Id,Amt,Kind\n2,20,b\n3,30,a\n");
+
+    let output = rsf(
+        &path,
+        &["validate", "delta.rsf", "--schema", "delta.rsf.schema.yaml"],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_validate_tap() {
+    let path = input("validate-tap", "Id,Kind\n1,a\n2,b\n3,a\n");
+    let dir = path.parent().unwrap();
+    let rank = rsf(&path, &["rank", "input.csv", "-o", "good.rsf", "--schema"]);
+    assert!(rank.status.success());
+    std::fs::write(dir.join("unsorted.rsf"), "Id,Kind\n3,a\n1,a\n2,b\n").unwrap();
+
+    let output = rsf(
+        &path,
+        &[
+            "validate",
+            "good.rsf",
+            "unsorted.rsf",
+            "missing.rsf",
+            "--schema",
+            "good.rsf.schema.yaml",
+            "--format",
+            "tap",
+        ],
+    );
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines().filter(|line| !line.starts_with('#'));
+    assert_eq!(lines.next(), Some("1..3"));
+    let statuses: Vec<(bool, &str)> = lines
+        .map(|line| match line.strip_prefix("not ") {
+            Some(rest) => (false, rest),
+            None => (true, line),
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            (true, "ok 1 - good.rsf"),
+            (false, "ok 2 - unsorted.rsf"),
+            (false, "ok 3 - missing.rsf"),
+        ]
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_config_show() {
+    let path = input("config-show", "Id\n1\n");
+    let dir = path.parent().unwrap();
+    let nested = dir.join("nested");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::write(
+        dir.join(".rsf.toml"),
+        "threads = 2\n[rank]\ntiebreak = \"hash\"\n",
+    )
+    .unwrap();
+
+    let show = |args: &[&str]| {
+        let output = rsf(
+            &nested.join("input.csv"),
+            &[&["config", "show"], args].concat(),
+        );
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let lines = show(&["rank", "input.csv", "--threads", "3"]);
+    assert!(
+        lines.contains("threads = \"3\"  # command line\n"),
+        "{}",
+        lines
+    );
+    assert!(
+        lines.contains("tiebreak = \"hash\"  # config file\n"),
+        "{}",
+        lines
+    );
+    assert!(
+        lines.contains("rank-by = \"cardinality\"  # default\n"),
+        "{}",
+        lines
+    );
+
+    let lines = show(&["rank", "input.csv", "--no-config"]);
+    assert!(
+        lines.contains("tiebreak = \"position\"  # default\n"),
+        "{}",
+        lines
+    );
+    assert!(lines.contains("threads = \"1\"  # default\n"), "{}", lines);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_max_distinct_per_column() {
+    let path = input("max-distinct", "Id,Kind\n1,a\n2,b\n3,a\n4,b\n5,a\n");
+
+    let output = rsf(
+        &path,
+        &["stats", "input.csv", "--max-distinct-per-column", "3"],
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let row = |name: &str| {
+        stdout
+            .lines()
+            .find(|line| line.starts_with(name))
+            .map(|line| line[name.len()..].trim().to_string())
+    };
+    assert_eq!(row("Id").as_deref(), Some(">3 (capped)"));
+    assert_eq!(row("Kind").as_deref(), Some("2"));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_quiet_and_verbose() {
+    let path = input("quiet-verbose", "Id,Kind\n1,a\n2,b\n3,a\n");
+    let rank = ["rank", "input.csv", "-o", "out.rsf", "--schema", "--force"];
+
+    let output = rsf(&path, &[&rank[..], &["--quiet"]].concat());
+    assert!(output.status.success());
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = rsf(&path, &[&rank[..], &["-v"]].concat());
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Ranking options: "), "{}", stderr);
+    assert!(stderr.contains("Read 3 row(s) x 2 column(s)"), "{}", stderr);
+    assert!(stderr.contains("Phase rank: "), "{}", stderr);
+
+    // Errors still print when quiet
+    let output = rsf(&path, &["validate", "missing.rsf", "-q"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error: "));
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_color_flag() {
+    let path = input("color", "Id,Kind\n1,a\n2,b\n");
+    let rank = ["rank", "input.csv", "-o", "out.rsf", "--schema"];
+    assert!(rsf(&path, &rank).status.success());
+    std::fs::write(path.with_file_name("swapped.rsf"), "Kind,Id\na,1\nb,2\n").unwrap();
+    let validate = ["validate", "swapped.rsf", "--schema", "out.rsf.schema.yaml"];
+
+    let output = rsf(&path, &validate);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("✗ Column order mismatch"), "{}", stderr);
+    assert!(!stderr.contains('\x1b'), "{}", stderr);
+
+    // --color always wins over NO_COLOR
+    let output = rsf(&path, &[&validate[..], &["--color", "always"]].concat());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("\x1b[31mKind"), "{}", stderr);
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_env_overrides() {
+    let path = input("env", "Id,Kind\n1,NA\n2,b\n3,a\n");
+    let rank = ["rank", "input.csv", "--no-progress"];
+
+    let output = rsf_with_env(&path, &[("RSF_QUIET", "yes")], &rank);
+    assert!(output.status.success());
+    assert!(
+        output.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // A flag on the command line wins over the variable
+    let output = rsf_with_env(&path, &[("RSF_QUIET", "1")], &[&rank[..], &["-v"]].concat());
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Ranking options: "), "{}", stderr);
+
+    // The variable wins over the config file
+    std::fs::write(
+        path.with_file_name("rsf.toml"),
+        "[rank]\ntiebreak = \"hash\"\n",
+    )
+    .unwrap();
+    let output = rsf_with_env(
+        &path,
+        &[("RSF_TIE_BREAK", "position")],
+        &["config", "show", "rank", "input.csv"],
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("tiebreak = \"position\"  # environment"),
+        "{}",
+        stdout
+    );
+
+    // A bad value names the variable it came from
+    let output = rsf_with_env(&path, &[("RSF_TIE_BREAK", "bogus")], &rank);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("invalid value 'bogus'"), "{}", stderr);
+    assert!(
+        stderr.contains("environment variable RSF_TIE_BREAK"),
+        "{}",
+        stderr
+    );
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_in_place() {
+    let path = input("in-place", "Kind,Id\nx,2\nx,1\n");
+    let backup = path.with_file_name("input.csv.bak");
+    let rank = [
+        "rank",
+        "input.csv",
+        "--no-progress",
+        "--in-place",
+        "--schema",
+    ];
+
+    assert!(rsf(&path, &rank).status.success());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "Id,Kind\n1,x\n2,x\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(&backup).unwrap(),
+        "Kind,Id\nx,2\nx,1\n"
+    );
+    assert!(path.with_file_name("input.csv.schema.yaml").exists());
+    assert!(!path.with_file_name(".input.csv.rsf-tmp").exists());
+
+    // A canonical input is not rewritten, so no backup is made
+    std::fs::remove_file(&backup).unwrap();
+    let output = rsf(&path, &rank);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("already canonical"), "{}", stderr);
+    assert!(!backup.exists());
+
+    std::fs::write(&path, "Kind,Id\nx,2\nx,1\n").unwrap();
+    assert!(rsf(&path, &[&rank[..], &["--no-backup"]].concat())
+        .status
+        .success());
+    assert!(!backup.exists());
+
+    // Files longer than the chunks they are compared in
+    let canonical: String = std::iter::once("Id,Kind\n".to_string())
+        .chain((0..20_000).map(|id| format!("{:05},x\n", id)))
+        .collect();
+    std::fs::write(&path, &canonical).unwrap();
+    assert!(rsf(&path, &rank).status.success());
+    assert!(!backup.exists());
+    let swapped = canonical.replace("19998,x\n19999,x\n", "19999,x\n19998,x\n");
+    std::fs::write(&path, &swapped).unwrap();
+    assert!(rsf(&path, &rank).status.success());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), canonical);
+    assert_eq!(std::fs::read_to_string(&backup).unwrap(), swapped);
+
+    let output = rsf(&path, &["rank", "-", "--in-place"]);
+    assert!(!output.status.success());
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_refuses_to_clobber() {
+    let path = input("clobber", "Kind,Id\nx,2\nx,1\n");
+    let dir = path.parent().unwrap();
+    let rank = ["rank", "input.csv", "--no-progress", "-o", "out.rsf"];
+
+    assert!(rsf(&path, &rank).status.success());
+    std::fs::write(dir.join("out.rsf"), "mine\n").unwrap();
+    let output = rsf(&path, &rank);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("\"out.rsf\""), "{}", stderr);
+    assert!(stderr.contains("--force"), "{}", stderr);
+    assert_eq!(
+        std::fs::read_to_string(dir.join("out.rsf")).unwrap(),
+        "mine\n"
+    );
+
+    // The default schema path is checked before any output is written
+    std::fs::write(dir.join("output.schema.yaml"), "mine\n").unwrap();
+    let output = rsf(&path, &["rank", "input.csv", "--schema"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+
+    assert!(rsf(&path, &[&rank[..], &["--force"]].concat())
+        .status
+        .success());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("out.rsf")).unwrap(),
+        "Id,Kind\n1,x\n2,x\n"
+    );
+
+    // The input under another name is refused even with --force
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&path, dir.join("link.csv")).unwrap();
+        for output in ["link.csv", "./input.csv"] {
+            let output = rsf(&path, &["rank", "input.csv", "-o", output, "--force"]);
+            assert!(!output.status.success());
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains("is the input file"), "{}", stderr);
+        }
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "Kind,Id\nx,2\nx,1\n"
+        );
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_delimiter_detect_report() {
+    let path = input("delimiter", "id;note\n2;a, b\n1;c\n");
+    let output = rsf(
+        &path,
+        &[
+            "rank",
+            "input.csv",
+            "-o",
+            "out.rsf",
+            "--no-progress",
+            "--delimiter",
+            "auto",
+            "--delimiter-detect-report",
+        ],
+    );
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Chosen delimiter: semicolon"), "{}", stderr);
+    assert!(
+        stderr
+            .lines()
+            .any(|line| line.starts_with("comma") && line.ends_with("rejected")),
+        "{}",
+        stderr
+    );
+    assert_eq!(
+        std::fs::read_to_string(path.with_file_name("out.rsf")).unwrap(),
+        "id,note\n1,c\n2,\"a, b\"\n"
+    );
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_metadata_header() {
+    let path = input("metadata-header", "Kind,Id\nx,2\nx,1\n");
+    let rank = [
+        "rank",
+        "input.csv",
+        "-o",
+        "out.csv",
+        "--schema",
+        "--no-progress",
+        "--emit-metadata-header",
+    ];
+    assert!(rsf(&path, &rank).status.success());
+
+    let out = std::fs::read_to_string(path.with_file_name("out.csv")).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "#rsf-version: 0.1");
+    assert!(lines[1].starts_with("#ranked-at: 20"), "{}", out);
+    assert_eq!(
+        lines[2..],
+        ["#column-count: 2", "#top-key: Id", "Id,Kind", "1,x", "2,x"]
+    );
+
+    let validate = ["validate", "out.csv", "--schema", "out.csv.schema.yaml"];
+    assert!(!rsf(&path, &validate).status.success());
+    assert!(
+        rsf(&path, &[&validate[..], &["--comment-char", "#"]].concat())
+            .status
+            .success()
+    );
+
+    // Rank's own output is canonical, whenever its header was written
+    let check = [
+        "rank",
+        "out.csv",
+        "--emit-metadata-header",
+        "--comment",
+        "#",
+        "--check",
+    ];
+    let output = rsf(&path, &check);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    std::fs::write(
+        path.with_file_name("out.csv"),
+        out.replace("1,x\n2,x", "2,x\n1,x"),
+    )
+    .unwrap();
+    assert!(!rsf(&path, &check).status.success());
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_validate_fix() {
+    let path = input("validate-fix", "Kind,Id\nx,2\nx,1\n");
+    assert!(
+        rsf(&path, &["rank", "input.csv", "-o", "out.rsf", "--schema"])
+            .status
+            .success()
+    );
+    let dir = path.parent().unwrap();
+    std::fs::copy(
+        dir.join("out.rsf.schema.yaml"),
+        dir.join("input.schema.yaml"),
+    )
+    .unwrap();
+
+    // A valid file is left alone
+    let output = rsf(
+        &path,
+        &["validate", "--fix", "out.rsf", "-s", "out.rsf.schema.yaml"],
+    );
+    assert!(output.status.success());
+    assert!(!dir.join("out.fixed.csv").exists());
+
+    // `--fix` takes no value without `=`, so the input is not swallowed
+    let output = rsf(&path, &["validate", "--fix", "input.csv"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("input.fixed.csv")).unwrap(),
+        "Id,Kind\n1,x\n2,x\n"
+    );
+
+    let output = rsf(&path, &["validate", "input.csv", "--fix=-"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Id,Kind\n1,x\n2,x\n"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_csv_errors_name_the_line() {
+    let path = input("csv-errors", "A,B\n1,2\n3,\"x\n4,5\n");
+    let unbalanced = "input.csv:3:3: unterminated quoted field starting at byte 10\n  | 3,\"x\n";
+    for args in [
+        &["rank", "input.csv", "-o", "out.csv"][..],
+        &["validate", "input.csv"],
+        &["stats", "input.csv"],
+        &["stats", "input.csv", "--nulls"],
+    ] {
+        let output = rsf(&path, args);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(unbalanced), "{:?}: {}", args, stderr);
+    }
+
+    // An embedded NUL is read, with a warning naming the field
+    std::fs::write(&path, "A,B\nx\0,y\nz,w\n").unwrap();
+    let output = rsf(&path, &["rank", "input.csv", "-o", "nul.csv"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("Warning: NUL byte in row 1, column 'A'\n"),
+        "{}",
+        stderr
+    );
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_inverse_mapping() {
+    let path = input("inverse-mapping", "Kind,Id\nx,1\nx,2\n");
+    let args = ["rank", "input.csv", "-o", "out.csv"];
+    let output = rsf(
+        &path,
+        &[&args[..], &["--emit-inverse-mapping", "map.json"]].concat(),
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(path.with_file_name("map.json")).unwrap(),
+        "{\n  \"new_col_0\": \"old_col_1\",\n  \"new_col_1\": \"old_col_0\"\n}\n"
+    );
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_check_runs_the_rank_pipeline() {
+    let path = input("check", "Kind,Id\nx,1\nx,2\n");
+    let output = rsf(&path, &["rank", "input.csv", "--check"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("column 'Id' would move from position 2 to 1"),
+        "{}",
+        stderr
+    );
+
+    assert!(rsf(&path, &["rank", "input.csv", "-o", "out.csv"])
+        .status
+        .success());
+    assert!(rsf(&path, &["rank", "out.csv", "--check"]).status.success());
+
+    // Transforms of the rows are checked too
+    let output = rsf(
+        &path,
+        &[
+            "rank",
+            "out.csv",
+            "--check",
+            "--emit-row-hashes",
+            "Hash",
+            "--row-hash-key",
+            "Id",
+        ],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("columns would change"), "{}", stderr);
+
+    std::fs::write(&path, "Id,Kind\n1,x\n1,x \n2\n").unwrap();
+    for args in [
+        &["--dedup-normalized", "--fill-missing", "y"][..],
+        &["--fill-missing", "y"],
+    ] {
+        let output = rsf(
+            &path,
+            &[&["rank", "input.csv", "--check"][..], args].concat(),
+        );
+        assert!(!output.status.success(), "{:?}", args);
+    }
+    let args = [
+        "rank",
+        "input.csv",
+        "-o",
+        "filled.csv",
+        "--dedup-normalized",
+    ];
+    assert!(rsf(&path, &[&args[..], &["--fill-missing", "y"]].concat())
+        .status
+        .success());
+    let check = ["rank", "filled.csv", "--check", "--dedup-normalized"];
+    assert!(rsf(&path, &[&check[..], &["--fill-missing", "y"]].concat())
+        .status
+        .success());
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_rows_per_chunk_is_chunk_rows() {
+    let path = input("rows-per-chunk", "Id\n3\n1\n2\n");
+    let args = ["rank", "input.csv", "-o", "parts", "--schema"];
+    let output = rsf(&path, &[&args[..], &["--rows-per-chunk", "2"]].concat());
+    assert!(output.status.success());
+
+    let dir = path.parent().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.join("parts/part-0001.csv")).unwrap(),
+        "Id\n1\n2\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("parts/part-0002.csv")).unwrap(),
+        "Id\n3\n"
+    );
+    let schema = std::fs::read_to_string(dir.join("parts.schema.yaml")).unwrap();
+    assert!(
+        schema.contains("chunks:\n- parts/part-0001.csv\n- parts/part-0002.csv\n"),
+        "{}",
+        schema
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_parquet_output_needs_the_feature() {
+    let path = input("parquet", "Id\n2\n1\n");
+    let output = rsf(&path, &["rank", "input.csv", "-o", "out.parquet"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if cfg!(feature = "parquet") {
+        assert!(output.status.success(), "{}", stderr);
+        let bytes = std::fs::read(path.with_file_name("out.parquet")).unwrap();
+        assert!(bytes.starts_with(b"PAR1") && bytes.ends_with(b"PAR1"));
+    } else {
+        assert!(!output.status.success());
+        assert!(
+            stderr.contains("No output format for .parquet files"),
+            "{}",
+            stderr
+        );
+    }
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_unicode_normalization() {
+    // "café" precomposed, then with a combining accent
+    let path = input(
+        "unicode-normalization",
+        "Kind,Drink\na,caf\u{e9}\nb,cafe\u{301}\na,tea\n",
+    );
+    let dir = path.parent().unwrap();
+
+    let output = rsf(
+        &path,
+        &[
+            "rank",
+            "input.csv",
+            "-o",
+            "raw.csv",
+            "--verify-utf8-normalization",
+        ],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains(
+            "Column 'Drink' has values that differ only in Unicode normalization: \
+             \"caf\\u{e9}\", \"cafe\\u{301}\""
+        ),
+        "{}",
+        stderr
+    );
+    let raw = std::fs::read_to_string(dir.join("raw.csv")).unwrap();
+    assert!(raw.starts_with("Drink,Kind\n"), "{}", raw);
+
+    let output = rsf(
+        &path,
+        &[
+            "rank",
+            "input.csv",
+            "-o",
+            "nfc.csv",
+            "--normalize-unicode",
+            "nfc",
+        ],
+    );
+    assert!(output.status.success());
+    let nfc = std::fs::read_to_string(dir.join("nfc.csv")).unwrap();
+    assert_eq!(nfc, "Kind,Drink\na,caf\u{e9}\na,tea\nb,caf\u{e9}\n");
+
+    let output = rsf(
+        &path,
+        &[
+            "rank",
+            "input.csv",
+            "-o",
+            "nfd.csv",
+            "--normalize-unicode",
+            "nfd",
+        ],
+    );
+    assert!(output.status.success());
+    let nfd = std::fs::read_to_string(dir.join("nfd.csv")).unwrap();
+    assert_eq!(nfd, "Kind,Drink\na,cafe\u{301}\na,tea\nb,cafe\u{301}\n");
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_row_hashes_cover_the_key() {
+    let path = input("row-hashes", "Id,Kind\n1,x\n2,y\n");
+    let dir = path.parent().unwrap();
+    let hashes = |output: &str| -> Vec<String> {
+        let args = [
+            "rank",
+            "input.csv",
+            "-o",
+            output,
+            "--emit-row-hashes",
+            "Hash",
+            "--row-hash-key",
+            "Id",
+        ];
+        assert!(rsf(&path, &args).status.success());
+        std::fs::read_to_string(dir.join(output))
+            .unwrap()
+            .lines()
+            .map(|line| line.rsplit(',').next().unwrap().to_string())
+            .collect()
+    };
+
+    let before = hashes("before.csv");
+    assert_eq!(before[0], "Hash");
+    // Kind is not part of the key
+    std::fs::write(&path, "Id,Kind\n1,z\n2,y\n").unwrap();
+    assert_eq!(hashes("after.csv"), before);
+
+    let output = rsf(
+        &path,
+        &[
+            "rank",
+            "input.csv",
+            "-o",
+            "out.csv",
+            "--emit-row-hashes",
+            "Hash",
+        ],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--row-hash-key"), "{}", stderr);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_stats_out_matches_stats() {
+    let path = input(
+        "stats-out",
+        "Id,Note,Kind\n1,,a\n2, ,b\n3,NULL,a\n4,x,\n5,x,a\n",
+    );
+    let dir = path.parent().unwrap();
+    // Options of rank, and of stats for the same nulls
+    let null_modes: [(&[&str], &[&str]); 4] = [
+        (&[], &[]),
+        (&["--nulls-distinct=false"], &[]),
+        (&["--exclude-nulls-from-cardinality"], &[]),
+        (&["--null-values", "NULL"], &["--null-values", "NULL"]),
+    ];
+    for (nulls, stats_nulls) in null_modes {
+        let rank = [
+            &[
+                "rank",
+                "input.csv",
+                "--no-progress",
+                "--force",
+                "-o",
+                "out.csv",
+            ][..],
+            &["--stats-out", "stats.json"],
+            nulls,
+        ]
+        .concat();
+        assert!(rsf(&path, &rank).status.success());
+
+        let stats = [&["stats", "out.csv", "--format", "json"][..], stats_nulls].concat();
+        let output = rsf(&path, &stats);
+        assert!(output.status.success());
+        let stats_out: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join("stats.json")).unwrap()).unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(stats_out, stats, "{:?}", nulls);
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_cache_per_input_path() {
+    let path = input("cache-per-path", "");
+    let dir = path.parent().unwrap();
+    for (sub, contents) in [("a", "Id,Kind\n1,x\n2,y\n"), ("b", "Id,Kind\n7,z\n")] {
+        std::fs::create_dir_all(dir.join(sub)).unwrap();
+        std::fs::write(dir.join(sub).join("input.csv"), contents).unwrap();
+    }
+    let rank = |input: &str| {
+        let output = rsf(
+            &path,
+            &[
+                "rank",
+                input,
+                "--no-progress",
+                "--force",
+                "-o",
+                "out.csv",
+                "--cache-dir",
+                "cache",
+            ],
+        );
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+
+    rank("a/input.csv");
+    rank("b/input.csv");
+    let caches = std::fs::read_dir(dir.join("cache")).unwrap().count();
+    assert_eq!(caches, 2);
+
+    // b's run left a's cache alone
+    std::fs::write(dir.join("a/input.csv"), "Id,Kind\n1,x\n2,y\n3,x\n").unwrap();
+    let stderr = rank("a/input.csv");
+    assert!(
+        stderr.contains("Cache: 2 rows counted before, 1 new"),
+        "{}",
+        stderr
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,76 @@
+//! Builds tests/ffi/harness.c against a static build of the library and
+//! runs it
+
+#![cfg(all(feature = "ffi", unix))]
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Target triple of the host, which the harness is built for
+fn host_triple() -> String {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(rustc).arg("-vV").output().unwrap();
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .expect("rustc -vV reports the host")
+        .to_string()
+}
+
+/// Build `librsf.a` with the `ffi` feature in its own target directory, so
+/// that builds with other features cannot replace it
+fn build_static_lib(root: &Path, target_dir: &Path) -> PathBuf {
+    let status = Command::new(env!("CARGO"))
+        .current_dir(root)
+        .args(["rustc", "--quiet", "--lib", "--no-default-features"])
+        .args([
+            "--features",
+            "ffi",
+            "--crate-type",
+            "staticlib",
+            "--target-dir",
+        ])
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "building the static library failed");
+    target_dir.join("debug").join("librsf.a")
+}
+
+#[test]
+fn test_c_harness() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let library = build_static_lib(root, &out_dir);
+
+    let host = host_triple();
+    let compiler = cc::Build::new()
+        .cargo_metadata(false)
+        .target(&host)
+        .host(&host)
+        .opt_level(0)
+        .out_dir(&out_dir)
+        .get_compiler();
+    let harness = out_dir.join("harness");
+    let status = compiler
+        .to_command()
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(root.join("tests/ffi/harness.c"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&harness)
+        .status()
+        .unwrap();
+    assert!(status.success(), "compiling the C harness failed");
+
+    let output = Command::new(&harness).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(output.stdout, b"ok\n");
+}
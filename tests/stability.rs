@@ -0,0 +1,74 @@
+//! Ranks the same data twice and checks the output matches byte for byte,
+//! the property `rank --sort-stability-guarantee` records in the schema
+
+use rsf::ranking::{RankMetric, RankingOptions, TieBreak};
+use rsf::RsfDocument;
+
+/// Columns B, C and D tie on cardinality and rows 1 and 3 are equal, so only
+/// stable sorts give one answer
+const TIED: &str = "A,B,C,D\n3,x,p,1\n1,y,q,2\n3,x,p,1\n2,x,q,2\n1,y,p,1\n";
+
+fn rank(csv: &str, options: RankingOptions) -> Vec<u8> {
+    let mut doc = RsfDocument::from_reader(csv.as_bytes()).unwrap();
+    doc.rank(options).unwrap();
+    doc.sort();
+    let mut out = Vec::new();
+    doc.write_to(&mut out).unwrap();
+    out
+}
+
+fn assert_stable(csv: &str, options: RankingOptions) {
+    let first = rank(csv, options.clone());
+    for _ in 0..10 {
+        assert_eq!(rank(csv, options.clone()), first);
+    }
+}
+
+#[test]
+fn test_two_passes_match_byte_for_byte() {
+    assert_stable(TIED, RankingOptions::default());
+    assert_stable(
+        TIED,
+        RankingOptions::builder()
+            .metric(RankMetric::Entropy)
+            .build()
+            .unwrap(),
+    );
+    assert_stable(
+        TIED,
+        RankingOptions::builder()
+            .tiebreak(TieBreak::Hash)
+            .build()
+            .unwrap(),
+    );
+    assert_stable(
+        "A,B\n,x\n1,\n,\n1,x\n",
+        RankingOptions::builder()
+            .treat_empty_as_null(true)
+            .build()
+            .unwrap(),
+    );
+}
+
+#[test]
+fn test_tied_columns_keep_input_order() {
+    let out = String::from_utf8(rank(TIED, RankingOptions::default())).unwrap();
+    assert!(out.starts_with("A,B,C,D\n"), "{}", out);
+
+    let out = String::from_utf8(rank(
+        "D,C,B,A\n1,p,x,3\n2,q,y,1\n",
+        RankingOptions::default(),
+    ))
+    .unwrap();
+    assert!(out.starts_with("D,C,B,A\n"), "{}", out);
+}
+
+#[test]
+fn test_ranked_output_ranks_to_itself() {
+    let once = rank(TIED, RankingOptions::default());
+    let twice = rank(
+        std::str::from_utf8(&once).unwrap(),
+        RankingOptions::default(),
+    );
+    assert_eq!(twice, once);
+}